@@ -0,0 +1,19 @@
+-- ============================================================
+-- Pending Events Table
+-- ============================================================
+-- Detected or chat-sourced events waiting for an operator to approve, edit or reject.
+-- payload uses the detection schema shape: {"type": "kill", "data": {...}}
+CREATE TABLE pending_events (
+    pending_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    confidence REAL,
+    payload TEXT NOT NULL,
+    occurred_at TIMESTAMP,
+    received_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK(status IN ('pending', 'approved', 'rejected')),
+    reviewed_at TIMESTAMP,
+    review_note TEXT
+);
+
+CREATE INDEX idx_pending_events_status ON pending_events(status);
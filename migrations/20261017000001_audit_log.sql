@@ -0,0 +1,17 @@
+-- ============================================================
+-- Audit Log Table
+-- ============================================================
+-- One row per correction made through the API, before/after are JSON snapshots
+-- of the row (after is NULL for deletes)
+CREATE TABLE audit_log (
+    audit_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    table_name TEXT NOT NULL,
+    row_id INTEGER NOT NULL,
+    action TEXT NOT NULL CHECK(action IN ('update', 'delete')),
+    before_json TEXT,
+    after_json TEXT,
+    source TEXT NOT NULL,
+    changed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_audit_log_row ON audit_log(table_name, row_id);
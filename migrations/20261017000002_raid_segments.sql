@@ -0,0 +1,9 @@
+-- ============================================================
+-- Raid Segments
+-- ============================================================
+-- A map transfer continues the raid on a new row. Every later segment points at
+-- the first one of its chain, which has no parent. segment_order counts up from 0.
+ALTER TABLE raids ADD COLUMN parent_raid_id INTEGER REFERENCES raids(raid_id) ON DELETE SET NULL;
+ALTER TABLE raids ADD COLUMN segment_order INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX idx_raids_parent_raid_id ON raids(parent_raid_id);
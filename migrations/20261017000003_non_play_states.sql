@@ -0,0 +1,33 @@
+-- ============================================================
+-- Non-play States
+-- ============================================================
+-- Time a raid spends in one of these doesn't count towards its active duration,
+-- e.g. a disconnect that drags the raid out. Overridden by NON_PLAY_STATES at startup.
+CREATE TABLE non_play_states (
+    state TEXT PRIMARY KEY NOT NULL
+);
+
+INSERT INTO non_play_states (state) VALUES ('reconnecting'), ('error'), ('paused');
+
+-- Non-play time per raid segment. A span runs until the raid's next transition or
+-- its end, so a raid still sitting in one has nothing counted for it yet.
+CREATE VIEW raid_non_play_time AS
+SELECT
+    spans.raid_id,
+    spans.chain_id,
+    CAST(ROUND(SUM((julianday(spans.until) - julianday(spans.transitioned_at)) * 86400000)) AS INTEGER) AS non_play_ms
+FROM (
+    SELECT
+        t.raid_id,
+        COALESCE(r.parent_raid_id, r.raid_id) AS chain_id,
+        t.to_state,
+        t.transitioned_at,
+        COALESCE(LEAD(t.transitioned_at) OVER (
+            PARTITION BY t.raid_id ORDER BY julianday(t.transitioned_at), t.transition_id
+        ), r.ended_at) AS until
+    FROM raid_state_transitions t
+    JOIN raids r ON r.raid_id = t.raid_id
+) spans
+WHERE spans.until IS NOT NULL
+  AND spans.to_state IN (SELECT state FROM non_play_states)
+GROUP BY spans.raid_id;
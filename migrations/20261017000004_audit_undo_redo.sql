@@ -0,0 +1,23 @@
+-- ============================================================
+-- Audit Log: undo / redo
+-- ============================================================
+-- Undo and redo are logged as well. A row they put back has no before_json, one
+-- they take away has no after_json. SQLite can't alter a CHECK so the table is rebuilt.
+CREATE TABLE audit_log_new (
+    audit_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    table_name TEXT NOT NULL,
+    row_id INTEGER NOT NULL,
+    action TEXT NOT NULL CHECK(action IN ('update', 'delete', 'undo', 'redo')),
+    before_json TEXT,
+    after_json TEXT,
+    source TEXT NOT NULL,
+    changed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO audit_log_new (audit_id, table_name, row_id, action, before_json, after_json, source, changed_at)
+SELECT audit_id, table_name, row_id, action, before_json, after_json, source, changed_at FROM audit_log;
+
+DROP TABLE audit_log;
+ALTER TABLE audit_log_new RENAME TO audit_log;
+
+CREATE INDEX idx_audit_log_row ON audit_log(table_name, row_id);
@@ -1,10 +1,62 @@
-use serde::{Deserialize, Serialize};
-use crate::models::{CharacterType, GameMode, SessionType};
+use serde::{Deserialize, Deserializer, Serialize};
+use time::{OffsetDateTime, UtcOffset};
+use time::format_description::well_known::Rfc3339;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use crate::api::error::AppError;
+use crate::db::{Cursor, HistoryFilter, Page, StatsFilter};
+use crate::detection::schema::DetectionKind;
+use crate::models::{
+    AuditEntry, CharacterType, GameMode, Kill, PendingEvent, Raid, RaidState, RaidStateTransition, RaidSummary,
+    RaidUnit, ReviewStatus, SessionSummary, SessionType, SortOrder, StatsGroup, TrendMetric, TrendUnit,
+};
+
+// Request timestamps are optional RFC3339 strings, None means "now". They're stored as
+// text and ordered by it, so every one is moved to UTC first.
+pub fn parse_timestamp(field: &str, value: Option<&str>) -> Result<Option<OffsetDateTime>, AppError> {
+    value.map(|v| OffsetDateTime::parse(v, &Rfc3339)
+        .map(|ts| ts.to_offset(UtcOffset::UTC))
+        .map_err(|e| AppError::BadRequest(format!("Invalid {} '{}': {}", field, v, e))))
+        .transpose()
+}
+
+pub fn format_timestamp(ts: OffsetDateTime) -> String {
+    ts.format(&Rfc3339).unwrap_or_else(|_| ts.to_string())
+}
+
+// Leeway for clients whose clock runs a little ahead of ours
+const CLOCK_SKEW: time::Duration = time::Duration::minutes(1);
+
+// Backdating is the point of caller timestamps, forward-dating is always a mistake
+pub fn check_not_future(field: &str, ts: OffsetDateTime) -> Result<(), AppError> {
+    if ts > OffsetDateTime::now_utc() + CLOCK_SKEW {
+        return Err(AppError::ValidationError(format!(
+            "{} {} is in the future", field, format_timestamp(ts)
+        )));
+    }
+    Ok(())
+}
+
+// `what` names the neighbouring record, e.g. "the session started"
+pub fn check_not_before(field: &str, ts: OffsetDateTime, bound: OffsetDateTime, what: &str) -> Result<(), AppError> {
+    if ts < bound {
+        return Err(AppError::ValidationError(format!(
+            "{} {} is before {} ({})", field, format_timestamp(ts), what, format_timestamp(bound)
+        )));
+    }
+    Ok(())
+}
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreateSessionRequest {
     pub session_type: SessionType,
     pub notes: Option<String>,
+    pub started_at: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EndSessionRequest {
+    pub ended_at: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -12,12 +64,26 @@ pub struct CreateRaidRequest {
     pub map_name: String,
     pub character_type: CharacterType,
     pub game_mode: GameMode,
+    pub started_at: Option<String>,
+}
+
+// Later bound, e.g. a raid start can't move past its first transition
+pub fn check_not_after(field: &str, ts: OffsetDateTime, bound: OffsetDateTime, what: &str) -> Result<(), AppError> {
+    if ts > bound {
+        return Err(AppError::ValidationError(format!(
+            "{} {} is after {} ({})", field, format_timestamp(ts), what, format_timestamp(bound)
+        )));
+    }
+    Ok(())
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct StateTransitionRequest {
     pub to_state: String,
     pub transitioned_at: Option<String>,
+    // Escape hatch: log the transition even if it is not in the RaidState graph
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -25,6 +91,9 @@ pub struct EndRaidRequest {
     pub final_state: String,
     pub extract_location: Option<String>,
     pub ended_at: Option<String>,
+    // Escape hatch: end the raid even if the final transition is not in the RaidState graph
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -38,12 +107,605 @@ pub struct RaidResponse {
     pub game_mode: GameMode,
     pub current_state: String,
     pub extract_location: Option<String>,
+    // Set on map transfer segments, points at the first segment of the chain
+    #[serde(default)]
+    pub parent_raid_id: Option<i64>,
+    #[serde(default)]
+    pub segment_order: i64,
+}
+
+impl From<&Raid> for RaidResponse {
+    fn from(raid: &Raid) -> Self {
+        Self {
+            raid_id: raid.raid_id,
+            session_id: raid.session_id,
+            started_at: format_timestamp(raid.started_at),
+            ended_at: raid.ended_at.map(format_timestamp),
+            map_name: raid.map_name.clone(),
+            character_type: raid.character_type.clone(),
+            game_mode: raid.game_mode.clone(),
+            current_state: raid.current_state.to_string(),
+            extract_location: raid.extract_location.clone(),
+            parent_raid_id: raid.parent_raid_id,
+            segment_order: raid.segment_order,
+        }
+    }
+}
+
+// Map transfer out of the current raid, the raid carries on as a new segment
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TransferRaidRequest {
+    pub map_name: String,
+    pub transferred_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RaidSegmentResponse {
+    #[serde(flatten)]
+    pub raid: RaidResponse,
+    pub kill_count: i64,
+    pub duration_seconds: Option<i64>,
+}
+
+// A raid and its map transfers. The chain's outcome is wherever the last segment ended up.
+#[derive(Debug, Serialize)]
+pub struct RaidChainResponse {
+    pub chain_id: i64,
+    pub outcome: String,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub duration_seconds: Option<i64>,
+    pub kill_count: i64,
+    pub maps: Vec<String>,
+    pub segments: Vec<RaidSegmentResponse>,
+}
+
+// For patch fields that can be cleared: left out is None, an explicit null is Some(None)
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// Corrections, only the fields present are changed
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct UpdateRaidRequest {
+    pub map_name: Option<String>,
+    pub character_type: Option<CharacterType>,
+    pub game_mode: Option<GameMode>,
+    // null clears it
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub extract_location: Option<Option<String>>,
+    pub started_at: Option<String>,
+    pub ended_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CurrentRaidResponse {
+    #[serde(flatten)]
+    pub raid: RaidResponse,
+    pub kill_count: i64,
+    pub time_in_state_seconds: i64,
+}
+
+// Only the fields present are changed
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct UpdateTransitionRequest {
+    pub to_state: Option<String>,
+    pub transitioned_at: Option<String>,
+    // Escape hatch: keep the edit even if it leaves an illegal move in the chain
+    #[serde(default)]
+    pub force: bool,
+}
+
+// Same escape hatch for removing a transition
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct DeleteTransitionQuery {
+    #[serde(default)]
+    pub force: bool,
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TransitionResponse {
+    pub transition_id: i64,
+    pub raid_id: i64,
+    pub from_state: Option<String>,
+    pub to_state: String,
+    pub transitioned_at: String,
+}
+
+impl From<&RaidStateTransition> for TransitionResponse {
+    fn from(t: &RaidStateTransition) -> Self {
+        Self {
+            transition_id: t.transition_id,
+            raid_id: t.raid_id,
+            from_state: t.from_state.as_ref().map(RaidState::to_string),
+            to_state: t.to_state.to_string(),
+            transitioned_at: format_timestamp(t.transitioned_at),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CreateKillRequest {
+    pub enemy_type: String,
+    pub weapon_used: Option<String>,
+    pub headshot: Option<bool>,
+    pub killed_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BatchKillRequest {
+    pub kills: Vec<CreateKillRequest>,
+}
+
+// Only the fields present are changed
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UpdateKillRequest {
+    pub enemy_type: Option<String>,
+    // null clears these
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub weapon_used: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub headshot: Option<Option<bool>>,
+    pub killed_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct KillResponse {
+    pub kill_id: i64,
+    pub raid_id: i64,
+    pub killed_at: String,
+    pub enemy_type: String,
+    pub weapon_used: Option<String>,
+    pub headshot: Option<bool>,
+}
+
+impl From<&Kill> for KillResponse {
+    fn from(kill: &Kill) -> Self {
+        Self {
+            kill_id: kill.kill_id,
+            raid_id: kill.raid_id,
+            killed_at: format_timestamp(kill.killed_at),
+            enemy_type: kill.enemy_type.clone(),
+            weapon_used: kill.weapon_used.clone(),
+            headshot: kill.headshot,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct StatsQuery {
+    pub game_mode: Option<GameMode>,
+}
+
+// Narrows the stats, ?unit=chains counts a raid and its map transfers once
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct StatsFilterQuery {
+    pub game_mode: Option<GameMode>,
+    pub character_type: Option<CharacterType>,
+    pub unit: Option<RaidUnit>,
+}
+
+pub const DEFAULT_TREND_COUNT: usize = 10;
+pub const DEFAULT_TREND_WINDOW: usize = 3;
+pub const MAX_TREND_COUNT: usize = 500;
+
+// ?over=raids&count=20&window=5, the sparkline also takes &metric=kd_ratio&rolling=true
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TrendQuery {
+    pub over: Option<TrendUnit>,
+    pub count: Option<usize>,
+    pub window: Option<usize>,
+    pub game_mode: Option<GameMode>,
+    pub character_type: Option<CharacterType>,
+    pub unit: Option<RaidUnit>,
+    pub metric: Option<TrendMetric>,
+    #[serde(default)]
+    pub rolling: bool,
+}
+
+impl TrendQuery {
+    // (count, window)
+    pub fn sizes(&self) -> Result<(usize, usize), AppError> {
+        let count = self.count.unwrap_or(DEFAULT_TREND_COUNT);
+        let window = self.window.unwrap_or(DEFAULT_TREND_WINDOW);
+
+        if !(1..=MAX_TREND_COUNT).contains(&count) {
+            return Err(AppError::ValidationError(format!(
+                "count must be between 1 and {}, got {}", MAX_TREND_COUNT, count
+            )));
+        }
+        if !(1..=MAX_TREND_COUNT).contains(&window) {
+            return Err(AppError::ValidationError(format!(
+                "window must be between 1 and {}, got {}", MAX_TREND_COUNT, window
+            )));
+        }
+        Ok((count, window))
+    }
+}
+
+// ?by=map&game_mode=pvp
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GroupedStatsQuery {
+    pub by: StatsGroup,
+    pub game_mode: Option<GameMode>,
+}
+
+// The stats engine, e.g. ?by=game_mode,weekday&sessions=3,4&from=2026-01-01T00:00:00Z&map=customs.
+// Lists are comma separated, without ?by= there is one overall row.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct StatsBreakdownQuery {
+    pub by: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub sessions: Option<String>,
+    pub game_mode: Option<GameMode>,
+    pub character_type: Option<CharacterType>,
+    pub map: Option<String>,
+    pub session_type: Option<SessionType>,
+    pub unit: Option<RaidUnit>,
+}
+
+fn comma_list(value: Option<&str>) -> impl Iterator<Item = &str> {
+    value.unwrap_or_default().split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+impl StatsBreakdownQuery {
+    // In the order given, repeats dropped
+    pub fn groups(&self) -> Result<Vec<StatsGroup>, AppError> {
+        let mut groups = Vec::new();
+        for name in comma_list(self.by.as_deref()) {
+            let group = StatsGroup::parse(name).ok_or_else(|| AppError::ValidationError(format!(
+                "by takes {}, got '{}'", StatsGroup::ALL.map(|g| g.as_str()).join(", "), name
+            )))?;
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+        Ok(groups)
+    }
+
+    pub fn filter(&self) -> Result<StatsFilter, AppError> {
+        let from = parse_timestamp("from", self.from.as_deref())?;
+        let to = parse_timestamp("to", self.to.as_deref())?;
+        if let (Some(from), Some(to)) = (from, to) {
+            check_not_after("from", from, to, "to")?;
+        }
+
+        let session_ids = comma_list(self.sessions.as_deref())
+            .map(|id| id.parse().map_err(|_| AppError::BadRequest(format!("Invalid session id '{}'", id))))
+            .collect::<Result<Vec<i64>, _>>()?;
+
+        Ok(StatsFilter {
+            session_ids,
+            game_mode: self.game_mode.clone(),
+            character_type: self.character_type.clone(),
+            map_name: self.map.clone(),
+            session_type: self.session_type.clone(),
+            from,
+            to,
+            unit: self.unit.unwrap_or_default(),
+        })
+    }
+}
+
+// Queue and deploy times, one overall row unless ?by= is given
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct WaitTimesQuery {
+    pub by: Option<StatsGroup>,
+    pub game_mode: Option<GameMode>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EventsQuery {
+    pub last_event_id: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+// Who made a correction, goes into the audit log. Defaults to "api".
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EditQuery {
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct AuditQuery {
+    pub table: Option<String>,
+    pub row_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditEntryResponse {
+    pub audit_id: i64,
+    pub table: String,
+    pub row_id: i64,
+    pub action: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub source: String,
+    pub changed_at: String,
+}
+
+impl From<&AuditEntry> for AuditEntryResponse {
+    fn from(entry: &AuditEntry) -> Self {
+        let parse = |json: &Option<String>| json.as_deref()
+            .map(|j| serde_json::from_str(j).unwrap_or_else(|_| serde_json::Value::String(j.to_string())));
+
+        Self {
+            audit_id: entry.audit_id,
+            table: entry.table_name.clone(),
+            row_id: entry.row_id,
+            action: entry.action.clone(),
+            before: parse(&entry.before_json),
+            after: parse(&entry.after_json),
+            source: entry.source.clone(),
+            changed_at: format_timestamp(entry.changed_at),
+        }
+    }
+}
+
+// Defaults to the running session, or the last one if none is running.
+// source goes into the audit log like EditQuery's.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HistoryQuery {
+    pub session_id: Option<i64>,
+    pub source: Option<String>,
+}
+
+// One event for the review queue, `event` is in the detection schema shape:
+// {"type": "kill", "data": {"enemy_type": "pmc", "weapon": "M4A1"}}
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SubmitEventRequest {
+    pub source: String,
+    pub confidence: Option<f64>,
+    pub occurred_at: Option<String>,
+    pub event: DetectionKind,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SubmitBatchRequest {
+    pub source: String,
+    pub events: Vec<SubmitBatchEvent>,
+    // The batch was already checked offline, queue it and approve it in one go
+    #[serde(default)]
+    pub approve: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SubmitBatchEvent {
+    pub confidence: Option<f64>,
+    pub occurred_at: Option<String>,
+    pub event: DetectionKind,
+}
+
+// Only the fields present are changed
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EditPendingEventRequest {
+    pub event: Option<DetectionKind>,
+    pub occurred_at: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct RejectPendingEventRequest {
+    pub note: Option<String>,
+}
+
+// No ids means every pending event, optionally only from one source
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BulkApproveRequest {
+    pub ids: Option<Vec<i64>>,
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ReviewQuery {
+    pub status: Option<ReviewStatus>,
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingEventResponse {
+    pub pending_id: i64,
+    pub source: String,
+    pub event_type: String,
+    pub confidence: Option<f64>,
+    pub event: serde_json::Value,
+    pub occurred_at: Option<String>,
+    pub received_at: String,
+    pub status: ReviewStatus,
+    pub reviewed_at: Option<String>,
+    pub review_note: Option<String>,
+}
+
+impl From<&PendingEvent> for PendingEventResponse {
+    fn from(event: &PendingEvent) -> Self {
+        Self {
+            pending_id: event.pending_id,
+            source: event.source.clone(),
+            event_type: event.event_type.clone(),
+            confidence: event.confidence,
+            // Stored by us so it should always parse, fall back to the raw text just in case
+            event: serde_json::from_str(&event.payload)
+                .unwrap_or_else(|_| serde_json::Value::String(event.payload.clone())),
+            occurred_at: event.occurred_at.map(format_timestamp),
+            received_at: format_timestamp(event.received_at),
+            status: event.status.clone(),
+            reviewed_at: event.reviewed_at.map(format_timestamp),
+            review_note: event.review_note.clone(),
+        }
+    }
+}
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+// Filters, sort and paging for GET /api/raids and /api/sessions, e.g.
+// ?map=customs&game_mode=pvp&outcome=died&sort=kills&order=desc&limit=20
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HistoryListQuery<S> {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub map: Option<String>,
+    pub character_type: Option<CharacterType>,
+    pub game_mode: Option<GameMode>,
+    pub session_type: Option<SessionType>,
+    pub outcome: Option<String>,
+    pub has_kills: Option<bool>,
+    pub sort: Option<S>,
+    pub order: Option<SortOrder>,
+    pub limit: Option<i64>,
+    // next_cursor from the previous page
+    pub cursor: Option<String>,
+}
+
+impl<S> HistoryListQuery<S> {
+    pub fn filter(&self) -> Result<HistoryFilter, AppError> {
+        let from = parse_timestamp("from", self.from.as_deref())?;
+        let to = parse_timestamp("to", self.to.as_deref())?;
+        if let (Some(from), Some(to)) = (from, to) {
+            check_not_after("from", from, to, "to")?;
+        }
+
+        let outcome = self.outcome.as_deref().map(RaidState::parse);
+        if let Some(outcome) = &outcome && !outcome.is_terminal() {
+            return Err(AppError::ValidationError(format!(
+                "outcome must be survived, died or mia, got '{}'", outcome
+            )));
+        }
+
+        Ok(HistoryFilter {
+            from,
+            to,
+            map_name: self.map.clone(),
+            character_type: self.character_type.clone(),
+            game_mode: self.game_mode.clone(),
+            session_type: self.session_type.clone(),
+            outcome: outcome.map(String::from),
+            has_kills: self.has_kills,
+        })
+    }
+
+    pub fn limit(&self) -> Result<i64, AppError> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(AppError::ValidationError(format!(
+                "limit must be between 1 and {}, got {}", MAX_PAGE_SIZE, limit
+            )));
+        }
+        Ok(limit)
+    }
+
+    pub fn cursor(&self) -> Result<Option<Cursor>, AppError> {
+        self.cursor.as_deref().map(decode_cursor).transpose()
+    }
+}
+
+// Cursors are opaque to clients, "<sort key>:<id>" in url-safe base64
+pub fn encode_cursor(cursor: Cursor) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", cursor.key, cursor.id))
+}
+
+pub fn decode_cursor(value: &str) -> Result<Cursor, AppError> {
+    let invalid = || AppError::BadRequest(format!("Invalid cursor '{}'", value));
+
+    let decoded = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let (key, id) = decoded.split_once(':').ok_or_else(invalid)?;
+
+    Ok(Cursor {
+        key: key.parse().map_err(|_| invalid())?,
+        id: id.parse().map_err(|_| invalid())?,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct PageResponse<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> PageResponse<T> {
+    pub fn from_page<R>(page: &Page<R>) -> Self
+    where
+        T: for<'a> From<&'a R>,
+    {
+        Self {
+            items: page.items.iter().map(T::from).collect(),
+            next_cursor: page.next.map(encode_cursor),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RaidSummaryResponse {
+    #[serde(flatten)]
+    pub raid: RaidResponse,
+    pub session_type: Option<SessionType>,
+    pub kill_count: i64,
+    pub duration_seconds: Option<i64>,
+    pub active_duration_seconds: Option<i64>,
+}
+
+impl From<&RaidSummary> for RaidSummaryResponse {
+    fn from(summary: &RaidSummary) -> Self {
+        Self {
+            raid: RaidResponse::from(&summary.raid),
+            session_type: summary.session_type.clone(),
+            kill_count: summary.kill_count,
+            duration_seconds: summary.duration_seconds,
+            active_duration_seconds: summary.active_duration_seconds,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionSummaryResponse {
+    pub session_id: i64,
+    pub session_type: Option<SessionType>,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub notes: Option<String>,
+    pub raid_count: i64,
+    pub kill_count: i64,
+    pub duration_seconds: Option<i64>,
+}
+
+impl From<&SessionSummary> for SessionSummaryResponse {
+    fn from(summary: &SessionSummary) -> Self {
+        let s = &summary.session;
+        Self {
+            session_id: s.session_id,
+            session_type: s.session_type.clone(),
+            started_at: format_timestamp(s.started_at),
+            ended_at: s.ended_at.map(format_timestamp),
+            notes: s.notes.clone(),
+            raid_count: summary.raid_count,
+            kill_count: summary.kill_count,
+            duration_seconds: summary.duration_seconds,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_timestamp_moves_to_utc() {
+        let ts = parse_timestamp("at", Some("2026-01-15T20:05:00+02:00")).unwrap().unwrap();
+        assert_eq!(ts.offset(), UtcOffset::UTC);
+        assert_eq!(format_timestamp(ts), "2026-01-15T18:05:00Z");
+    }
+
     #[test]
     fn test_create_session_request_deserialization() {
         let json = r#"{"session_type": "stream", "notes": "Test session"}"#;
@@ -79,6 +741,16 @@ mod tests {
 
         assert_eq!(req.to_state, "queue");
         assert_eq!(req.transitioned_at, Some("2026-02-13T12:00:00Z".into()));
+        assert!(!req.force);
+    }
+
+    #[test]
+    fn test_state_transition_request_force() {
+        let json = r#"{"to_state": "looting_body", "force": true}"#;
+        let req: StateTransitionRequest = serde_json::from_str(json).unwrap();
+
+        assert_eq!(req.to_state, "looting_body");
+        assert!(req.force);
     }
 
     #[test]
@@ -103,6 +775,8 @@ mod tests {
             game_mode: GameMode::PVP,
             current_state: "in_raid".to_string(),
             extract_location: None,
+            parent_raid_id: None,
+            segment_order: 0,
         };
 
         let json = serde_json::to_string(&resp).unwrap();
@@ -112,4 +786,64 @@ mod tests {
         assert!(json.contains(r#""map_name":"Interchange""#));
         assert!(json.contains(r#"character_type":"scav""#));
     }
+
+    #[test]
+    fn test_current_raid_response_flattens_raid() {
+        let resp = CurrentRaidResponse {
+            raid: RaidResponse {
+                raid_id: 7,
+                session_id: 1,
+                started_at: "2026-02-13T10:00:00Z".to_string(),
+                ended_at: None,
+                map_name: "Woods".to_string(),
+                character_type: CharacterType::PMC,
+                game_mode: GameMode::PVE,
+                current_state: "raid_active".to_string(),
+                extract_location: None,
+                parent_raid_id: None,
+                segment_order: 0,
+            },
+            kill_count: 3,
+            time_in_state_seconds: 90,
+        };
+
+        let json = serde_json::to_value(&resp).unwrap();
+
+        assert_eq!(json["raid_id"], 7);
+        assert_eq!(json["kill_count"], 3);
+        assert_eq!(json["time_in_state_seconds"], 90);
+    }
+
+    #[test]
+    fn test_batch_kill_request_deserialization() {
+        let json = r#"{"kills": [
+            {"enemy_type": "scav"},
+            {"enemy_type": "pmc", "weapon_used": "M4A1", "headshot": true, "killed_at": "2026-02-13T12:00:00Z"}
+        ]}"#;
+        let req: BatchKillRequest = serde_json::from_str(json).unwrap();
+
+        assert_eq!(req.kills.len(), 2);
+        assert_eq!(req.kills[0].weapon_used, None);
+        assert_eq!(req.kills[1].headshot, Some(true));
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("ended_at", None).unwrap(), None);
+
+        let ts = parse_timestamp("ended_at", Some("2026-02-13T12:00:00Z")).unwrap().unwrap();
+        assert_eq!(format_timestamp(ts), "2026-02-13T12:00:00Z");
+
+        let err = parse_timestamp("ended_at", Some("yesterday")).unwrap_err();
+        assert_eq!(err.status_code(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor { key: 2461331.123456789, id: 42 };
+        assert_eq!(decode_cursor(&encode_cursor(cursor)).unwrap(), cursor);
+
+        let err = decode_cursor("bm9wZQ").unwrap_err();
+        assert_eq!(err.status_code(), http::StatusCode::BAD_REQUEST);
+    }
 }
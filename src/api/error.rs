@@ -1,3 +1,4 @@
+#[derive(Debug)]
 pub enum AppError { 
     DatabaseError(sqlx::Error),
     NotFound(String),
@@ -35,6 +36,47 @@ impl AppError {
     }
 }
 
+impl From<crate::models::InvalidTransition> for AppError {
+    fn from(err: crate::models::InvalidTransition) -> Self {
+        AppError::Conflict(err.to_string())
+    }
+}
+
+impl From<crate::db::TransitionError> for AppError {
+    fn from(err: crate::db::TransitionError) -> Self {
+        use crate::db::TransitionError;
+
+        match err {
+            TransitionError::Invalid(e) => e.into(),
+            TransitionError::Database(e) => AppError::DatabaseError(e),
+        }
+    }
+}
+
+impl From<crate::import::ImportError> for AppError {
+    fn from(err: crate::import::ImportError) -> Self {
+        use crate::import::ImportError;
+
+        match err {
+            ImportError::Invalid(_) => AppError::ValidationError(err.to_string()),
+            ImportError::Overlap(msg) => AppError::Conflict(msg),
+            ImportError::Database(e) => AppError::DatabaseError(e),
+        }
+    }
+}
+
+impl From<crate::history::HistoryError> for AppError {
+    fn from(err: crate::history::HistoryError) -> Self {
+        use crate::history::HistoryError;
+
+        match err {
+            HistoryError::Empty(msg) => AppError::NotFound(msg),
+            HistoryError::Stale(msg) => AppError::Conflict(msg),
+            HistoryError::Database(e) => AppError::DatabaseError(e),
+        }
+    }
+}
+
 impl axum::response::IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
         let status = self.status_code();
@@ -84,6 +126,18 @@ mod tests {
         assert_eq!(body["type"], "validation_error");
     }
 
+    #[test]
+    fn test_invalid_transition_is_conflict() {
+        use crate::models::RaidState;
+
+        let err: AppError = RaidState::Died.check_transition(&RaidState::RaidActive)
+            .unwrap_err()
+            .into();
+
+        assert_eq!(err.status_code(), http::StatusCode::CONFLICT);
+        assert_eq!(err.json_body()["type"], "conflict");
+    }
+
     #[test]
     fn test_into_response() {
         use axum::response::IntoResponse;
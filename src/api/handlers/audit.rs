@@ -0,0 +1,107 @@
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::Serialize;
+use sqlx::SqliteConnection;
+use crate::api::dto::{AuditEntryResponse, AuditQuery, EditQuery, KillResponse, RaidResponse, TransitionResponse};
+use crate::api::{error::AppError, state::AppState};
+use crate::db;
+use crate::history::Action;
+use crate::models::{Kill, NewAuditEntry, Raid, RaidStateTransition};
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("responses serialize")
+}
+
+// Snapshots use the API response shapes so the log reads the same as the endpoints.
+// No `after` means the row was deleted. Goes on the same connection as the edit so
+// the two commit together.
+pub async fn record<T: Serialize>(
+    conn: &mut SqliteConnection,
+    query: &EditQuery,
+    table: &str,
+    row_id: i64,
+    before: &T,
+    after: Option<&T>,
+) -> Result<(), AppError> {
+    let entry = NewAuditEntry {
+        table_name: table.to_string(),
+        row_id,
+        action: if after.is_some() { "update" } else { "delete" }.to_string(),
+        before_json: Some(to_json(before)),
+        after_json: after.map(to_json),
+        source: query.source.as_deref().unwrap_or("api").to_string(),
+    };
+
+    db::add_audit_entry(conn, &entry).await.map_err(AppError::DatabaseError)?;
+    Ok(())
+}
+
+// The rows an undo or redo of `action` changes, for History to log with it. Sessions
+// aren't audited, and a raid that was ended only shows its terminal transition.
+pub fn history_entries(action: &Action, undo: bool, source: &str) -> Vec<NewAuditEntry> {
+    // (table, row id, before, after) as the action itself did it
+    let mut changes: Vec<(&str, i64, Option<String>, Option<String>)> = Vec::new();
+    let raid = |r: &Raid| to_json(&RaidResponse::from(r));
+    let kill = |k: &Kill| to_json(&KillResponse::from(k));
+    let transition = |t: &RaidStateTransition| to_json(&TransitionResponse::from(t));
+
+    match action {
+        Action::SessionStarted(_) | Action::SessionEnded { .. } => {}
+        Action::RaidCreated(r) => changes.push(("raids", r.raid_id, None, Some(raid(r)))),
+        Action::Transitioned(t) => changes.push(("raid_state_transitions", t.transition_id, None, Some(transition(t)))),
+        Action::RaidEnded { transition: t, .. } => {
+            if let Some(t) = t {
+                changes.push(("raid_state_transitions", t.transition_id, None, Some(transition(t))));
+            }
+        }
+        Action::RaidTransferred { transition: t, segment, segment_transition, .. } => {
+            changes.push(("raid_state_transitions", t.transition_id, None, Some(transition(t))));
+            changes.push(("raids", segment.raid_id, None, Some(raid(segment))));
+            changes.push(("raid_state_transitions", segment_transition.transition_id, None,
+                Some(transition(segment_transition))));
+        }
+        Action::KillsAdded(kills) => {
+            changes.extend(kills.iter().map(|k| ("kills", k.kill_id, None, Some(kill(k)))));
+        }
+        Action::KillUpdated { before, after } => {
+            changes.push(("kills", after.kill_id, Some(kill(before)), Some(kill(after))));
+        }
+        Action::KillDeleted(k) => changes.push(("kills", k.kill_id, Some(kill(k)), None)),
+        Action::RaidUpdated { before, after } => {
+            changes.push(("raids", after.raid_id, Some(raid(before)), Some(raid(after))));
+        }
+        // Its transitions and kills go and come back with it, like the delete itself
+        Action::RaidDeleted { raid: r, .. } => changes.push(("raids", r.raid_id, Some(raid(r)), None)),
+        Action::TransitionUpdated { before, after } => {
+            changes.push(("raid_state_transitions", after.transition_id, Some(transition(before)),
+                Some(transition(after))));
+        }
+        Action::TransitionDeleted(t) => {
+            changes.push(("raid_state_transitions", t.transition_id, Some(transition(t)), None));
+        }
+    }
+
+    changes.into_iter()
+        .map(|(table, row_id, before, after)| {
+            let (before_json, after_json) = if undo { (after, before) } else { (before, after) };
+            NewAuditEntry {
+                table_name: table.to_string(),
+                row_id,
+                action: if undo { "undo" } else { "redo" }.to_string(),
+                before_json,
+                after_json,
+                source: source.to_string(),
+            }
+        })
+        .collect()
+}
+
+pub async fn get_audit_log(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntryResponse>>, AppError> {
+    let entries = db::get_audit_entries(&state.pool, query.table.as_deref(), query.row_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(entries.iter().map(AuditEntryResponse::from).collect()))
+}
@@ -0,0 +1,60 @@
+use axum::{extract::State, Json};
+use crate::api::state::AppState;
+
+fn status_json(state: &AppState) -> serde_json::Value {
+    serde_json::json!({
+        "paused": state.detection.is_paused(),
+        "connected": state.detection.is_connected(),
+    })
+}
+
+pub async fn get_detection_status(State(state): State<AppState>) -> Json<serde_json::Value> {
+    Json(status_json(&state))
+}
+
+// Manual override: detections keep arriving but nothing is written until resumed
+pub async fn pause_detection(State(state): State<AppState>) -> Json<serde_json::Value> {
+    state.detection.set_paused(true);
+    Json(status_json(&state))
+}
+
+pub async fn resume_detection(State(state): State<AppState>) -> Json<serde_json::Value> {
+    state.detection.set_paused(false);
+    Json(status_json(&state))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use http::StatusCode;
+    use tower::ServiceExt;
+    use crate::api::{state::AppState, routes::api_router};
+    use crate::db::tests::setup_test_db;
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let pool = setup_test_db().await.unwrap();
+        let state = AppState::new(pool);
+        let app = api_router().with_state(state.clone());
+
+        let response = app.clone()
+            .oneshot(Request::post("/api/detection/pause").body(Body::empty()).unwrap())
+            .await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(state.detection.is_paused());
+
+        let response = app.clone()
+            .oneshot(Request::post("/api/detection/resume").body(Body::empty()).unwrap())
+            .await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!state.detection.is_paused());
+
+        let response = app
+            .oneshot(Request::get("/api/detection").body(Body::empty()).unwrap())
+            .await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["paused"], false);
+        assert_eq!(json["connected"], false);
+    }
+}
@@ -0,0 +1,254 @@
+use std::convert::Infallible;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::Response;
+use http::HeaderMap;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::{Stream, StreamExt};
+use tracing::warn;
+
+use crate::api::dto::EventsQuery;
+use crate::api::handlers::raid::load_current_raid;
+use crate::api::handlers::session::session_json;
+use crate::api::{error::AppError, state::AppState};
+use crate::db;
+use crate::events::{EventEnvelope, Subscription};
+
+// Sent on connect (or when a resume id is too old) so a client never has to
+// poll the REST API to find out where things stand
+async fn snapshot_json(state: &AppState) -> Result<serde_json::Value, AppError> {
+    let last_event_id = state.events.last_event_id();
+
+    let session = db::get_active_session(&state.pool)
+        .await.map_err(AppError::DatabaseError)?;
+    let raid = load_current_raid(&state.pool).await?;
+
+    Ok(serde_json::json!({
+        "id": last_event_id,
+        "type": "snapshot",
+        "session": session.as_ref().map(session_json),
+        "raid": raid,
+    }))
+}
+
+fn sse_event(envelope: &EventEnvelope) -> Option<Event> {
+    Event::default()
+        .id(envelope.id.to_string())
+        .event(envelope.event.kind())
+        .json_data(envelope)
+        .inspect_err(|e| warn!("Failed to encode event {}: {}", envelope.id, e))
+        .ok()
+}
+
+// SSE clients send Last-Event-ID on reconnect, the query param is for manual use
+fn resume_id(headers: &HeaderMap, query: &EventsQuery) -> Option<u64> {
+    headers.get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .or(query.last_event_id)
+}
+
+pub async fn sse_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    // Subscribe before building the snapshot so nothing published in between is lost
+    let sub = state.events.subscribe(resume_id(&headers, &query));
+
+    let mut initial = Vec::new();
+    if !sub.complete {
+        let snapshot = snapshot_json(&state).await?;
+        initial.push(Event::default()
+            .id(snapshot["id"].to_string())
+            .event("snapshot")
+            .data(snapshot.to_string()));
+    }
+    initial.extend(sub.replay.iter().filter_map(sse_event));
+
+    let live = BroadcastStream::new(sub.receiver).filter_map(|msg| match msg {
+        Ok(envelope) => sse_event(&envelope),
+        Err(BroadcastStreamRecvError::Lagged(n)) => {
+            warn!("SSE client lagged, skipped {} events", n);
+            None
+        }
+    });
+
+    let stream = tokio_stream::iter(initial).chain(live).map(Ok);
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+pub async fn ws_events(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Response, AppError> {
+    let sub = state.events.subscribe(query.last_event_id);
+
+    let snapshot = if sub.complete {
+        None
+    } else {
+        Some(snapshot_json(&state).await?)
+    };
+
+    Ok(ws.on_upgrade(move |socket| stream_ws(socket, sub, snapshot)))
+}
+
+async fn stream_ws(mut socket: WebSocket, sub: Subscription, snapshot: Option<serde_json::Value>) {
+    let initial = snapshot.into_iter()
+        .chain(sub.replay.iter().filter_map(|e| serde_json::to_value(e).ok()));
+
+    for msg in initial {
+        if socket.send(Message::Text(msg.to_string().into())).await.is_err() {
+            return;
+        }
+    }
+
+    let mut receiver = sub.receiver;
+
+    loop {
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Ok(envelope) => {
+                    let Ok(text) = serde_json::to_string(&envelope) else { continue };
+                    if socket.send(Message::Text(text.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(n)) => warn!("WebSocket client lagged, skipped {} events", n),
+                Err(RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // The stream is one-way, anything the client sends is ignored
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use futures_util::StreamExt;
+    use http::StatusCode;
+    use tower::ServiceExt;
+    use crate::api::{state::AppState, routes::api_router};
+    use crate::db::{self, tests::setup_test_db};
+    use crate::events::LiveEvent;
+    use crate::models::SessionType;
+
+    // Reads SSE chunks until one contains `needle`
+    async fn read_until(stream: &mut axum::body::BodyDataStream, needle: &str) -> String {
+        let mut seen = String::new();
+        while !seen.contains(needle) {
+            let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), stream.next())
+                .await.expect("timed out waiting for SSE data")
+                .expect("stream ended").unwrap();
+            seen.push_str(&String::from_utf8_lossy(&chunk));
+        }
+        seen
+    }
+
+    #[tokio::test]
+    async fn test_sse_snapshot_then_live_events() {
+        let pool = setup_test_db().await.expect("setup db");
+        let session_id = db::create_session(&pool, SessionType::Stream, None, None).await.unwrap();
+        let state = AppState::new(pool);
+        let app = api_router().with_state(state.clone());
+
+        let response = app
+            .oneshot(Request::get("/api/events").body(Body::empty()).unwrap())
+            .await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut stream = response.into_body().into_data_stream();
+        let snapshot = read_until(&mut stream, "event: snapshot").await;
+        assert!(snapshot.contains(&format!(r#""session_id":{}"#, session_id)));
+
+        // A mutation through the API publishes on the same bus
+        let app = api_router().with_state(state.clone());
+        app.oneshot(
+            Request::post("/api/raid")
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"map_name": "Customs", "character_type": "pmc", "game_mode": "pve"}"#))
+                .unwrap(),
+        ).await.unwrap();
+
+        let event = read_until(&mut stream, "event: raid_created").await;
+        assert!(event.contains(r#""map_name":"Customs""#));
+        assert!(event.contains("id: 1"));
+    }
+
+    #[tokio::test]
+    async fn test_sse_resume_from_last_event_id() {
+        let pool = setup_test_db().await.expect("setup db");
+        let state = AppState::new(pool);
+
+        for session_id in 1..=3 {
+            state.events.publish(LiveEvent::SessionEnded {
+                session_id,
+                ended_at: time::OffsetDateTime::now_utc(),
+            });
+        }
+
+        let app = api_router().with_state(state.clone());
+        let response = app
+            .oneshot(Request::get("/api/events")
+                .header("Last-Event-ID", "1")
+                .body(Body::empty()).unwrap())
+            .await.unwrap();
+
+        let mut stream = response.into_body().into_data_stream();
+        let replay = read_until(&mut stream, "id: 3").await;
+
+        assert!(!replay.contains("snapshot"));
+        assert!(!replay.contains("id: 1\n"));
+        assert!(replay.contains("id: 2"));
+    }
+
+    #[tokio::test]
+    async fn test_websocket_stream() {
+        use tokio_tungstenite::tungstenite::Message;
+
+        let pool = setup_test_db().await.expect("setup db");
+        let state = AppState::new(pool);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = api_router().with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/api/events/ws", addr))
+            .await.expect("connect");
+
+        let next_json = async |ws: &mut tokio_tungstenite::WebSocketStream<_>| -> serde_json::Value {
+            loop {
+                let msg = tokio::time::timeout(std::time::Duration::from_secs(5), ws.next())
+                    .await.expect("timed out").expect("closed").unwrap();
+                if let Message::Text(text) = msg {
+                    return serde_json::from_str(&text).unwrap();
+                }
+            }
+        };
+
+        let snapshot = next_json(&mut ws).await;
+        assert_eq!(snapshot["type"], "snapshot");
+        assert!(snapshot["session"].is_null());
+
+        state.events.publish(LiveEvent::SessionEnded {
+            session_id: 9,
+            ended_at: time::OffsetDateTime::UNIX_EPOCH,
+        });
+
+        let event = next_json(&mut ws).await;
+        assert_eq!(event["type"], "session_ended");
+        assert_eq!(event["session_id"], 9);
+        assert_eq!(event["id"], 1);
+    }
+}
@@ -0,0 +1,213 @@
+use axum::extract::{Query, State};
+use axum::Json;
+use crate::api::dto::HistoryQuery;
+use crate::api::handlers::audit;
+use crate::api::{error::AppError, state::AppState};
+use crate::db;
+use crate::events::LiveEvent;
+use crate::history::Action;
+use crate::models::RaidState;
+
+async fn target_session(state: &AppState, query: &HistoryQuery) -> Result<i64, AppError> {
+    if let Some(session_id) = query.session_id {
+        return Ok(session_id);
+    }
+
+    // get_all_sessions is newest first, which is the running one if there is one
+    let sessions = db::get_all_sessions(&state.pool)
+        .await.map_err(AppError::DatabaseError)?;
+    sessions.first()
+        .map(|s| s.session_id)
+        .ok_or_else(|| AppError::NotFound("No sessions to undo in".into()))
+}
+
+// Where the touched raid is now, so overlays can catch up
+async fn raid_state(state: &AppState, action: &Action) -> Result<Option<RaidState>, AppError> {
+    let Some(raid_id) = action.raid_id() else {
+        return Ok(None);
+    };
+
+    let raid = db::get_raid_by_id(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+    Ok(raid.map(|r| r.state()))
+}
+
+async fn history_json(state: &AppState, session_id: i64, key: &str, action: &Action, raid_state: Option<RaidState>)
+    -> serde_json::Value
+{
+    let (undo_depth, redo_depth) = state.history.depth(session_id).await;
+
+    serde_json::json!({
+        key: action.name(),
+        "session_id": session_id,
+        "raid_id": action.raid_id(),
+        "raid_state": raid_state,
+        "undo_depth": undo_depth,
+        "redo_depth": redo_depth,
+    })
+}
+
+pub async fn undo(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let session_id = target_session(&state, &query).await?;
+    let source = query.source.as_deref().unwrap_or("api");
+    let action = state.history.undo(&state.pool, session_id, |action| audit::history_entries(action, true, source))
+        .await?;
+    let raid_state = raid_state(&state, &action).await?;
+
+    state.events.publish(LiveEvent::ActionUndone {
+        session_id,
+        action: action.name().to_string(),
+        raid_id: action.raid_id(),
+        raid_state: raid_state.clone(),
+    });
+
+    Ok(Json(history_json(&state, session_id, "undone", &action, raid_state).await))
+}
+
+pub async fn redo(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let session_id = target_session(&state, &query).await?;
+    let source = query.source.as_deref().unwrap_or("api");
+    let action = state.history.redo(&state.pool, session_id, |action| audit::history_entries(action, false, source))
+        .await?;
+    let raid_state = raid_state(&state, &action).await?;
+
+    state.events.publish(LiveEvent::ActionRedone {
+        session_id,
+        action: action.name().to_string(),
+        raid_id: action.raid_id(),
+        raid_state: raid_state.clone(),
+    });
+
+    Ok(Json(history_json(&state, session_id, "redone", &action, raid_state).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use http::StatusCode;
+    use tower::ServiceExt;
+    use crate::api::{state::AppState, routes::api_router};
+    use crate::db;
+    use crate::db::tests::setup_test_db;
+
+    async fn post(app: axum::Router, uri: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::post(uri);
+        if !body.is_empty() {
+            request = request.header("content-type", "application/json");
+        }
+        let response = app
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await.unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_undo_mispressed_death() {
+        let pool = setup_test_db().await.unwrap();
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        post(app.clone(), "/api/session", r#"{"session_type": "stream"}"#).await;
+        let (_, json) = post(app.clone(), "/api/raid",
+            r#"{"map_name": "Customs", "character_type": "pmc", "game_mode": "pve"}"#).await;
+        let raid_id = json["raid_id"].as_i64().unwrap();
+        for state in ["pre_raid_setup", "queuing", "deploying_committed", "raid_active"] {
+            post(app.clone(), "/api/raid/transition", &format!(r#"{{"to_state": "{}"}}"#, state)).await;
+        }
+        let (status, _) = post(app.clone(), "/api/raid/end", r#"{"final_state": "died"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, json) = post(app.clone(), "/api/undo", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["undone"], "raid_ended");
+        assert_eq!(json["raid_state"], "raid_active");
+        assert_eq!(json["redo_depth"], 1);
+
+        let raid = db::get_active_raid(&pool).await.unwrap().expect("raid reopened");
+        assert_eq!(raid.raid_id, raid_id);
+        assert!(!db::get_raid_transitions(&pool, raid_id).await.unwrap().iter().any(|t| t.to_state == "died"));
+
+        // Now the right button
+        let (status, _) = post(app.clone(), "/api/raid/end", r#"{"final_state": "survived", "extract_location": "ZB-1011"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        let raid = db::get_raid_by_id(&pool, raid_id).await.unwrap().unwrap();
+        assert_eq!(raid.current_state, "survived");
+        assert_eq!(raid.extract_location.as_deref(), Some("ZB-1011"));
+
+        // The new end replaced what was undone
+        let (status, _) = post(app, "/api/redo", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_undo_redo_transition_and_kill() {
+        let pool = setup_test_db().await.unwrap();
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        post(app.clone(), "/api/session", r#"{"session_type": "stream"}"#).await;
+        let (_, json) = post(app.clone(), "/api/raid",
+            r#"{"map_name": "Woods", "character_type": "pmc", "game_mode": "pvp"}"#).await;
+        let raid_id = json["raid_id"].as_i64().unwrap();
+        post(app.clone(), "/api/raid/transition", r#"{"to_state": "pre_raid_setup"}"#).await;
+        post(app.clone(), &format!("/api/raid/{}/kills", raid_id), r#"{"enemy_type": "scav"}"#).await;
+
+        let (_, json) = post(app.clone(), "/api/undo", "").await;
+        assert_eq!(json["undone"], "kills_added");
+        assert!(db::get_kills_for_raid(&pool, raid_id).await.unwrap().is_empty());
+
+        let (_, json) = post(app.clone(), "/api/undo", "").await;
+        assert_eq!(json["undone"], "state_transitioned");
+        assert_eq!(json["raid_state"], "stash_management");
+        assert!(db::get_raid_transitions(&pool, raid_id).await.unwrap().is_empty());
+
+        let (_, json) = post(app.clone(), "/api/redo", "").await;
+        assert_eq!(json["redone"], "state_transitioned");
+        let (_, json) = post(app.clone(), "/api/redo", "").await;
+        assert_eq!(json["redone"], "kills_added");
+
+        let raid = db::get_raid_by_id(&pool, raid_id).await.unwrap().unwrap();
+        assert_eq!(raid.current_state, "pre_raid_setup");
+        assert_eq!(db::get_kills_for_raid(&pool, raid_id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_undo_and_redo_are_audited() {
+        let pool = setup_test_db().await.unwrap();
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        post(app.clone(), "/api/session", r#"{"session_type": "stream"}"#).await;
+        let (_, json) = post(app.clone(), "/api/raid",
+            r#"{"map_name": "Woods", "character_type": "pmc", "game_mode": "pvp"}"#).await;
+        let raid_id = json["raid_id"].as_i64().unwrap();
+        post(app.clone(), &format!("/api/raid/{}/kills", raid_id), r#"{"enemy_type": "scav"}"#).await;
+        let kill_id = db::get_kills_for_raid(&pool, raid_id).await.unwrap()[0].kill_id;
+
+        post(app.clone(), "/api/undo?source=streamdeck", "").await;
+        post(app.clone(), "/api/redo", "").await;
+
+        // Newest first: the redo puts the kill back, the undo took it away
+        let entries = db::get_audit_entries(&pool, Some("kills"), Some(kill_id)).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].action.as_str(), entries[0].source.as_str()), ("redo", "api"));
+        assert!(entries[0].before_json.is_none() && entries[0].after_json.is_some());
+        assert_eq!((entries[1].action.as_str(), entries[1].source.as_str()), ("undo", "streamdeck"));
+        assert!(entries[1].before_json.is_some() && entries[1].after_json.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_undo_with_no_history() {
+        let pool = setup_test_db().await.unwrap();
+        let app = api_router().with_state(AppState::new(pool));
+
+        let (status, _) = post(app, "/api/undo", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,122 @@
+use axum::extract::{Query, State};
+use axum::Json;
+use http::StatusCode;
+use time::UtcOffset;
+use crate::api::dto::ImportQuery;
+use crate::api::{error::AppError, state::AppState};
+use crate::events::LiveEvent;
+use crate::import::{self, ImportReport, SessionImport};
+
+// Backfill a whole recorded session with its original timestamps.
+// ?dry_run=true validates and reports without writing anything.
+pub async fn import_session(
+    State(state): State<AppState>,
+    Query(query): Query<ImportQuery>,
+    Json(doc): Json<SessionImport>,
+) -> Result<(StatusCode, Json<ImportReport>), AppError> {
+    let report = import::import_session(&state.pool, &doc, query.dry_run).await?;
+
+    // Only once it's committed, a dry run has nothing to tell
+    if let Some(session_id) = report.session_id {
+        state.events.publish(LiveEvent::SessionImported {
+            session_id,
+            raid_count: report.raids.len(),
+            // Same UTC times the rows were written with
+            started_at: doc.session.started_at.to_offset(UtcOffset::UTC),
+            ended_at: doc.session.ended_at.map(|ts| ts.to_offset(UtcOffset::UTC)),
+        });
+    }
+
+    let status = if report.dry_run { StatusCode::OK } else { StatusCode::CREATED };
+    Ok((status, Json(report)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use http::StatusCode;
+    use tower::ServiceExt;
+    use crate::api::{state::AppState, routes::api_router};
+    use crate::db;
+    use crate::db::tests::setup_test_db;
+
+    const BODY: &str = r#"{
+        "session": {"session_type": "stream", "started_at": "2026-01-15T18:00:00Z", "ended_at": "2026-01-15T19:00:00Z"},
+        "events": [
+            {"type": "raid_start", "timestamp": "2026-01-15T18:05:00Z", "map_name": "Factory", "character_type": "pmc", "game_mode": "pvp"},
+            {"type": "transition", "timestamp": "2026-01-15T18:06:00Z", "to_state": "pre_raid_setup"},
+            {"type": "transition", "timestamp": "2026-01-15T18:07:00Z", "to_state": "queuing"},
+            {"type": "transition", "timestamp": "2026-01-15T18:08:00Z", "to_state": "deploying_committed"},
+            {"type": "transition", "timestamp": "2026-01-15T18:09:00Z", "to_state": "raid_active"},
+            {"type": "kill", "timestamp": "2026-01-15T18:12:00Z", "enemy_type": "pmc"},
+            {"type": "raid_end", "timestamp": "2026-01-15T18:20:00Z", "final_state": "died"}
+        ]
+    }"#;
+
+    async fn post(pool: &sqlx::SqlitePool, uri: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let app = api_router().with_state(AppState::new(pool.clone()));
+        let response = app
+            .oneshot(Request::post(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())).unwrap())
+            .await.unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_import_session_dry_run_then_real() {
+        let pool = setup_test_db().await.unwrap();
+
+        let (status, json) = post(&pool, "/api/import/session?dry_run=true", BODY).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["dry_run"], true);
+        assert_eq!(json["session_id"], serde_json::Value::Null);
+        assert_eq!(json["raids"][0]["final_state"], "died");
+        assert!(db::get_all_sessions(&pool).await.unwrap().is_empty());
+
+        let (status, json) = post(&pool, "/api/import/session", BODY).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(json["total_kills"], 1);
+        assert!(json["raids"][0]["raid_id"].is_i64());
+
+        // Importing the same recording twice overlaps
+        let (status, _) = post(&pool, "/api/import/session", BODY).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_import_is_published() {
+        let pool = setup_test_db().await.unwrap();
+        let state = AppState::new(pool.clone());
+        let mut subscription = state.events.subscribe(None);
+        let app = api_router().with_state(state);
+
+        let response = app
+            .oneshot(Request::post("/api/import/session")
+                .header("content-type", "application/json")
+                .body(Body::from(BODY)).unwrap())
+            .await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let envelope = subscription.receiver.try_recv().expect("import event");
+        assert_eq!(envelope.event.kind(), "session_imported");
+        let session = db::get_all_sessions(&pool).await.unwrap().pop().unwrap();
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["session_id"], session.session_id);
+        assert_eq!(json["raid_count"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_import_session_invalid_is_422() {
+        let pool = setup_test_db().await.unwrap();
+        let body = BODY.replace("2026-01-15T18:12:00Z", "2026-01-15T17:00:00Z");
+
+        let (status, json) = post(&pool, "/api/import/session", &body).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(json["error"].as_str().unwrap().contains("events[5]"), "{}", json);
+        assert!(db::get_all_sessions(&pool).await.unwrap().is_empty());
+    }
+}
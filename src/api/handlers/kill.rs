@@ -0,0 +1,446 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use http::StatusCode;
+use time::OffsetDateTime;
+use crate::api::dto::{
+    BatchKillRequest, CreateKillRequest, EditQuery, KillResponse, UpdateKillRequest, check_not_after,
+    check_not_before, check_not_future, parse_timestamp,
+};
+use crate::api::handlers::audit;
+use crate::api::{error::AppError, state::AppState};
+use crate::db;
+use crate::events::LiveEvent;
+use crate::history::Action;
+use crate::models::{NewKill, Raid};
+
+// A kill has to fall inside the raid it belongs to
+fn validate_kill_time(raid: &Raid, killed_at: OffsetDateTime) -> Result<(), AppError> {
+    check_not_future("killed_at", killed_at)?;
+    check_not_before("killed_at", killed_at, raid.started_at, "the raid started")?;
+
+    if let Some(ended_at) = raid.ended_at {
+        check_not_after("killed_at", killed_at, ended_at, "the raid ended")?;
+    }
+
+    Ok(())
+}
+
+// Kills entered without a time on an ended raid are stamped at the raid end
+fn to_new_kill(raid: &Raid, req: CreateKillRequest) -> Result<NewKill, AppError> {
+    if req.enemy_type.trim().is_empty() {
+        return Err(AppError::ValidationError("enemy_type cannot be empty".into()));
+    }
+
+    let killed_at = parse_timestamp("killed_at", req.killed_at.as_deref())?
+        .or(raid.ended_at)
+        .unwrap_or_else(OffsetDateTime::now_utc);
+
+    validate_kill_time(raid, killed_at)?;
+
+    Ok(NewKill {
+        enemy_type: req.enemy_type,
+        weapon_used: req.weapon_used,
+        headshot: req.headshot,
+        killed_at: Some(killed_at),
+    })
+}
+
+fn publish_kill(state: &AppState, raid_id: i64, kill_id: i64, kill: NewKill) {
+    state.events.publish(LiveEvent::KillAdded {
+        kill_id,
+        raid_id,
+        enemy_type: kill.enemy_type,
+        weapon_used: kill.weapon_used,
+        headshot: kill.headshot,
+        killed_at: kill.killed_at.unwrap_or_else(OffsetDateTime::now_utc),
+    });
+}
+
+async fn find_raid(state: &AppState, raid_id: i64) -> Result<Raid, AppError> {
+    db::get_raid_by_id(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Raid {} not found", raid_id)))
+}
+
+// Reads the rows back so undo/redo can put them back with the same ids
+async fn record_added(state: &AppState, raid: &Raid, kill_ids: &[i64]) -> Result<(), AppError> {
+    let mut kills = Vec::with_capacity(kill_ids.len());
+    for &kill_id in kill_ids {
+        if let Some(kill) = db::get_kill_by_id(&state.pool, kill_id).await.map_err(AppError::DatabaseError)? {
+            kills.push(kill);
+        }
+    }
+
+    state.history.record(raid.session_id, Action::KillsAdded(kills)).await;
+    Ok(())
+}
+
+pub async fn add_kill(
+    State(state): State<AppState>,
+    Path(raid_id): Path<i64>,
+    Json(req): Json<CreateKillRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let raid = find_raid(&state, raid_id).await?;
+    let kill = to_new_kill(&raid, req)?;
+
+    let kill_id = db::add_kill(
+        &state.pool,
+        raid.raid_id,
+        &kill.enemy_type,
+        kill.weapon_used.clone(),
+        kill.headshot,
+        kill.killed_at,
+    ).await.map_err(AppError::DatabaseError)?;
+
+    record_added(&state, &raid, &[kill_id]).await?;
+    publish_kill(&state, raid.raid_id, kill_id, kill);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({ "kill_id": kill_id })),
+    ))
+}
+
+pub async fn add_kills_batch_current(
+    State(state): State<AppState>,
+    Json(req): Json<BatchKillRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let raid = db::get_active_raid(&state.pool)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound("No active raid to add kills to".into()))?;
+
+    if req.kills.is_empty() {
+        return Err(AppError::ValidationError("kills cannot be empty".into()));
+    }
+
+    // Validate everything up front so a bad entry rejects the whole list
+    let kills = req.kills.into_iter()
+        .map(|k| to_new_kill(&raid, k))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let kill_ids = db::add_kills(&state.pool, raid.raid_id, &kills)
+        .await.map_err(AppError::DatabaseError)?;
+    record_added(&state, &raid, &kill_ids).await?;
+
+    for (kill_id, kill) in kill_ids.iter().zip(kills) {
+        publish_kill(&state, raid.raid_id, *kill_id, kill);
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "raid_id": raid.raid_id,
+            "kill_ids": kill_ids,
+        })),
+    ))
+}
+
+pub async fn get_kills(
+    State(state): State<AppState>,
+    Path(raid_id): Path<i64>,
+) -> Result<Json<Vec<KillResponse>>, AppError> {
+    let raid = find_raid(&state, raid_id).await?;
+
+    let kills = db::get_kills_for_raid(&state.pool, raid.raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(kills.iter().map(KillResponse::from).collect()))
+}
+
+pub async fn update_kill(
+    State(state): State<AppState>,
+    Path(kill_id): Path<i64>,
+    Query(query): Query<EditQuery>,
+    Json(req): Json<UpdateKillRequest>,
+) -> Result<Json<KillResponse>, AppError> {
+    let mut kill = db::get_kill_by_id(&state.pool, kill_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Kill {} not found", kill_id)))?;
+    let before = kill.clone();
+    let raid = find_raid(&state, kill.raid_id).await?;
+
+    if let Some(enemy_type) = req.enemy_type {
+        if enemy_type.trim().is_empty() {
+            return Err(AppError::ValidationError("enemy_type cannot be empty".into()));
+        }
+        kill.enemy_type = enemy_type;
+    }
+
+    if let Some(weapon_used) = req.weapon_used {
+        kill.weapon_used = weapon_used;
+    }
+
+    if let Some(headshot) = req.headshot {
+        kill.headshot = headshot;
+    }
+
+    if let Some(killed_at) = parse_timestamp("killed_at", req.killed_at.as_deref())? {
+        validate_kill_time(&raid, killed_at)?;
+        kill.killed_at = killed_at;
+    }
+
+    let mut tx = state.pool.begin().await.map_err(AppError::DatabaseError)?;
+    db::update_kill(&mut *tx, &kill)
+        .await.map_err(AppError::DatabaseError)?;
+    audit::record(&mut tx, &query, "kills", kill_id,
+        &KillResponse::from(&before), Some(&KillResponse::from(&kill))).await?;
+    tx.commit().await.map_err(AppError::DatabaseError)?;
+    state.history.record(raid.session_id, Action::KillUpdated { before, after: kill.clone() }).await;
+    state.events.publish(LiveEvent::RaidCorrected { raid_id: raid.raid_id, raid_state: Some(raid.state()) });
+
+    Ok(Json(KillResponse::from(&kill)))
+}
+
+pub async fn delete_kill(
+    State(state): State<AppState>,
+    Path(kill_id): Path<i64>,
+    Query(query): Query<EditQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let kill = db::get_kill_by_id(&state.pool, kill_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Kill {} not found", kill_id)))?;
+    let raid = find_raid(&state, kill.raid_id).await?;
+
+    let mut tx = state.pool.begin().await.map_err(AppError::DatabaseError)?;
+    let deleted = db::delete_kill(&mut *tx, kill_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    if !deleted {
+        return Err(AppError::NotFound(format!("Kill {} not found", kill_id)));
+    }
+    audit::record(&mut tx, &query, "kills", kill_id, &KillResponse::from(&kill), None).await?;
+    tx.commit().await.map_err(AppError::DatabaseError)?;
+    state.history.record(raid.session_id, Action::KillDeleted(kill)).await;
+    state.events.publish(LiveEvent::RaidCorrected { raid_id: raid.raid_id, raid_state: Some(raid.state()) });
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "kill_id": kill_id,
+        "message": "kill deleted"
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use http::StatusCode;
+    use time::OffsetDateTime;
+    use tower::ServiceExt;
+    use crate::api::{state::AppState, routes::api_router};
+    use crate::db::{self, tests::setup_test_db};
+    use crate::models::{CharacterType, GameMode, SessionType};
+
+    async fn send(pool: &sqlx::SqlitePool, method: &str, uri: &str, body: Option<&str>) -> (StatusCode, serde_json::Value) {
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or_else(Body::empty))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    async fn setup_raid(pool: &sqlx::SqlitePool, started_at: OffsetDateTime) -> i64 {
+        let session_id = db::create_session(pool, SessionType::Stream, None, Some(started_at))
+            .await.expect("session");
+        db::create_raid(pool, session_id, "Customs", CharacterType::PMC, GameMode::PVE, Some(started_at))
+            .await.expect("raid")
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_kills() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_raid(&pool, OffsetDateTime::now_utc() - time::Duration::minutes(10)).await;
+
+        let (status, json) = send(&pool, "POST", &format!("/api/raid/{}/kills", raid_id),
+            Some(r#"{"enemy_type": "scav", "weapon_used": "AK-74M", "headshot": true}"#)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(json["kill_id"].as_i64().is_some());
+
+        let (status, json) = send(&pool, "GET", &format!("/api/raid/{}/kills", raid_id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["enemy_type"], "scav");
+        assert_eq!(json[0]["weapon_used"], "AK-74M");
+        assert_eq!(json[0]["headshot"], true);
+    }
+
+    #[tokio::test]
+    async fn test_add_kill_unknown_raid() {
+        let pool = setup_test_db().await.expect("setup db");
+
+        let (status, _) = send(&pool, "POST", "/api/raid/999/kills",
+            Some(r#"{"enemy_type": "scav"}"#)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(&pool, "GET", "/api/raid/999/kills", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_add_kill_outside_raid_window() {
+        let pool = setup_test_db().await.expect("setup db");
+        let started_at = OffsetDateTime::parse("2026-02-13T12:00:00Z", &time::format_description::well_known::Rfc3339).unwrap();
+        let raid_id = setup_raid(&pool, started_at).await;
+        db::end_raid(&pool, raid_id, Some(started_at + time::Duration::minutes(30)), None)
+            .await.expect("end raid");
+
+        let (status, json) = send(&pool, "POST", &format!("/api/raid/{}/kills", raid_id),
+            Some(r#"{"enemy_type": "scav", "killed_at": "2026-02-13T11:59:00Z"}"#)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(json["error"], "killed_at 2026-02-13T11:59:00Z is before the raid started (2026-02-13T12:00:00Z)");
+
+        let (status, json) = send(&pool, "POST", &format!("/api/raid/{}/kills", raid_id),
+            Some(r#"{"enemy_type": "scav", "killed_at": "2026-02-13T12:31:00Z"}"#)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(json["error"], "killed_at 2026-02-13T12:31:00Z is after the raid ended (2026-02-13T12:30:00Z)");
+
+        // No timestamp on an ended raid is stamped at the raid end
+        let (status, _) = send(&pool, "POST", &format!("/api/raid/{}/kills", raid_id),
+            Some(r#"{"enemy_type": "pmc"}"#)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let kills = db::get_kills_for_raid(&pool, raid_id).await.expect("kills");
+        assert_eq!(kills.len(), 1);
+        assert_eq!(kills[0].killed_at, started_at + time::Duration::minutes(30));
+    }
+
+    #[tokio::test]
+    async fn test_batch_kills_current_raid() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_raid(&pool, OffsetDateTime::now_utc() - time::Duration::minutes(10)).await;
+
+        let (status, json) = send(&pool, "POST", "/api/raid/current/kills/batch",
+            Some(r#"{"kills": [{"enemy_type": "scav"}, {"enemy_type": "pmc"}, {"enemy_type": "boss"}]}"#)).await;
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(json["raid_id"], raid_id);
+        assert_eq!(json["kill_ids"].as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_batch_kills_rejects_whole_list() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_raid(&pool, OffsetDateTime::now_utc() - time::Duration::minutes(10)).await;
+
+        let (status, _) = send(&pool, "POST", "/api/raid/current/kills/batch",
+            Some(r#"{"kills": [{"enemy_type": "scav"}, {"enemy_type": "pmc", "killed_at": "2001-01-01T00:00:00Z"}]}"#)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let kills = db::get_kills_for_raid(&pool, raid_id).await.expect("kills");
+        assert!(kills.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batch_kills_no_active_raid() {
+        let pool = setup_test_db().await.expect("setup db");
+
+        let (status, _) = send(&pool, "POST", "/api/raid/current/kills/batch",
+            Some(r#"{"kills": [{"enemy_type": "scav"}]}"#)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_update_kill() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_raid(&pool, OffsetDateTime::now_utc() - time::Duration::minutes(10)).await;
+        let kill_id = db::add_kill(&pool, raid_id, "scav", Some("SKS".into()), Some(false), None)
+            .await.expect("kill");
+
+        let (status, json) = send(&pool, "PATCH", &format!("/api/kills/{}", kill_id),
+            Some(r#"{"enemy_type": "pmc", "headshot": true}"#)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["enemy_type"], "pmc");
+        assert_eq!(json["headshot"], true);
+        assert_eq!(json["weapon_used"], "SKS");
+
+        let (status, _) = send(&pool, "PATCH", &format!("/api/kills/{}", kill_id),
+            Some(r#"{"killed_at": "2001-01-01T00:00:00Z"}"#)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        // null clears the weapon, leaving it out keeps it
+        let (status, json) = send(&pool, "PATCH", &format!("/api/kills/{}", kill_id),
+            Some(r#"{"weapon_used": null}"#)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(json["weapon_used"].is_null());
+        assert_eq!(json["enemy_type"], "pmc");
+        assert_eq!(json["headshot"], true);
+
+        let (status, json) = send(&pool, "PATCH", &format!("/api/kills/{}", kill_id),
+            Some(r#"{"headshot": null}"#)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(json["headshot"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_kill_corrections_are_audited() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_raid(&pool, OffsetDateTime::now_utc() - time::Duration::minutes(10)).await;
+        let kill_id = db::add_kill(&pool, raid_id, "scav", None, None, None).await.expect("kill");
+
+        send(&pool, "PATCH", &format!("/api/kills/{}?source=streamdeck", kill_id),
+            Some(r#"{"enemy_type": "pmc"}"#)).await;
+        send(&pool, "DELETE", &format!("/api/kills/{}", kill_id), None).await;
+
+        let entries = db::get_audit_entries(&pool, Some("kills"), Some(kill_id)).await.expect("audit");
+        assert_eq!(entries.len(), 2);
+
+        // Newest first
+        assert_eq!(entries[0].action, "delete");
+        assert_eq!(entries[0].source, "api");
+        assert!(entries[0].after_json.is_none());
+
+        assert_eq!(entries[1].action, "update");
+        assert_eq!(entries[1].source, "streamdeck");
+        let before: serde_json::Value = serde_json::from_str(entries[1].before_json.as_deref().unwrap()).unwrap();
+        let after: serde_json::Value = serde_json::from_str(entries[1].after_json.as_deref().unwrap()).unwrap();
+        assert_eq!(before["enemy_type"], "scav");
+        assert_eq!(after["enemy_type"], "pmc");
+    }
+
+    #[tokio::test]
+    async fn test_delete_kill() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_raid(&pool, OffsetDateTime::now_utc() - time::Duration::minutes(10)).await;
+        let kill_id = db::add_kill(&pool, raid_id, "scav", None, None, None).await.expect("kill");
+
+        let (status, _) = send(&pool, "DELETE", &format!("/api/kills/{}", kill_id), None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(&pool, "DELETE", &format!("/api/kills/{}", kill_id), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_kill_corrections_are_published() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_raid(&pool, OffsetDateTime::now_utc() - time::Duration::minutes(10)).await;
+        let kill_id = db::add_kill(&pool, raid_id, "scav", None, None, None).await.expect("kill");
+
+        let state = AppState::new(pool.clone());
+        let mut subscription = state.events.subscribe(None);
+        let app = api_router().with_state(state);
+
+        for (method, body) in [("PATCH", r#"{"enemy_type": "pmc"}"#), ("DELETE", "")] {
+            let response = app.clone()
+                .oneshot(Request::builder().method(method).uri(format!("/api/kills/{}", kill_id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body)).unwrap())
+                .await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let envelope = subscription.receiver.try_recv().expect("correction event");
+            assert_eq!(envelope.event.kind(), "raid_corrected");
+            let json = serde_json::to_value(&envelope).unwrap();
+            assert_eq!(json["raid_id"], raid_id);
+        }
+    }
+}
@@ -1,3 +1,12 @@
+pub mod audit;
+pub mod detection;
+pub mod events;
 pub mod health;
+pub mod history;
+pub mod import;
+pub mod kill;
 pub mod raid;
+pub mod review;
 pub mod session;
+pub mod stats;
+pub mod transition;
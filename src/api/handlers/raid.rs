@@ -1,8 +1,18 @@
-use axum::{extract::State, Json};
+use axum::extract::{Path, Query, State};
+use axum::Json;
 use http::StatusCode;
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
 use crate::api::{state::AppState, dto::CreateRaidRequest, error::AppError};
-use crate::api::dto;
+use crate::api::dto::{
+    CurrentRaidResponse, EditQuery, EndRaidRequest, HistoryListQuery, PageResponse, RaidChainResponse, RaidResponse,
+    RaidSegmentResponse, RaidSummaryResponse, StateTransitionRequest, TransferRaidRequest, UpdateRaidRequest, check_not_after, check_not_before, check_not_future, format_timestamp, parse_timestamp,
+};
+use crate::api::handlers::audit;
 use crate::db;
+use crate::events::LiveEvent;
+use crate::history::Action;
+use crate::models::{Raid, RaidSort, RaidState, RaidStateTransition};
 
 pub async fn create_raid(
     State(state): State<AppState>,
@@ -22,15 +32,40 @@ pub async fn create_raid(
         return Err(AppError::Conflict("Raid already in progress".into()));
     }
 
+    let started_at = parse_timestamp("started_at", req.started_at.as_deref())?
+        .unwrap_or_else(OffsetDateTime::now_utc);
+    check_not_future("started_at", started_at)?;
+    check_not_before("started_at", started_at, session.started_at, "the session started")?;
+
+    let raids = db::get_raids_for_session(&state.pool, session.session_id)
+        .await.map_err(AppError::DatabaseError)?;
+    if let Some(previous) = raids.last() {
+        let previous_end = previous.ended_at.unwrap_or(previous.started_at);
+        check_not_before("started_at", started_at, previous_end, "the previous raid ended")?;
+    }
+
     let raid_id = db::create_raid(
         &state.pool,
         session.session_id,
         &req.map_name,
-        req.character_type,
-        req.game_mode,
-        None, //started_at defaults to now
+        req.character_type.clone(),
+        req.game_mode.clone(),
+        Some(started_at),
     ).await.map_err(AppError::DatabaseError)?;
 
+    if let Some(raid) = db::get_raid_by_id(&state.pool, raid_id).await.map_err(AppError::DatabaseError)? {
+        state.history.record(session.session_id, Action::RaidCreated(raid)).await;
+    }
+
+    state.events.publish(LiveEvent::RaidCreated {
+        raid_id,
+        session_id: session.session_id,
+        map_name: req.map_name,
+        character_type: req.character_type,
+        game_mode: req.game_mode,
+        started_at,
+    });
+
     Ok((
         StatusCode::CREATED,
         Json(serde_json::json!({"raid_id": raid_id})),
@@ -38,12 +73,452 @@ pub async fn create_raid(
 
 }
 
+pub async fn list_raids(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryListQuery<RaidSort>>,
+) -> Result<Json<PageResponse<RaidSummaryResponse>>, AppError> {
+    let page = db::list_raids(
+        &state.pool,
+        &query.filter()?,
+        query.sort.unwrap_or_default(),
+        query.order.unwrap_or_default(),
+        query.cursor()?,
+        query.limit()?,
+    ).await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(PageResponse::from_page(&page)))
+}
+
+// Shared with the live event snapshot
+pub async fn load_current_raid(pool: &SqlitePool) -> Result<Option<CurrentRaidResponse>, AppError> {
+    let Some(raid) = db::get_active_raid(pool)
+        .await.map_err(AppError::DatabaseError)? else {
+        return Ok(None);
+    };
+
+    let kills = db::get_kills_for_raid(pool, raid.raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    let transitions = db::get_raid_transitions(pool, raid.raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    // Before the first transition the raid has been in its initial state since it started
+    let state_entered_at = transitions.last()
+        .map(|t| t.transitioned_at)
+        .unwrap_or(raid.started_at);
+    let time_in_state = OffsetDateTime::now_utc() - state_entered_at;
+
+    Ok(Some(CurrentRaidResponse {
+        raid: RaidResponse::from(&raid),
+        kill_count: kills.len() as i64,
+        time_in_state_seconds: time_in_state.whole_seconds().max(0),
+    }))
+}
+
+pub async fn get_current_raid(
+    State(state): State<AppState>,
+) -> Result<Json<CurrentRaidResponse>, AppError> {
+    load_current_raid(&state.pool).await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound("No active raid".into()))
+}
+
+// Transitions and the raid end have to come after everything already logged for the raid
+async fn check_raid_time(
+    state: &AppState,
+    raid: &Raid,
+    field: &str,
+    ts: OffsetDateTime,
+    include_kills: bool,
+) -> Result<(), AppError> {
+    check_not_future(field, ts)?;
+    check_not_before(field, ts, raid.started_at, "the raid started")?;
+
+    let transitions = db::get_raid_transitions(&state.pool, raid.raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+    if let Some(last) = transitions.last() {
+        check_not_before(field, ts, last.transitioned_at, "the last state transition")?;
+    }
+
+    if include_kills {
+        let kills = db::get_kills_for_raid(&state.pool, raid.raid_id)
+            .await.map_err(AppError::DatabaseError)?;
+        if let Some(last_kill) = kills.iter().map(|k| k.killed_at).max() {
+            check_not_before(field, ts, last_kill, "the last kill")?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn transition_raid(
+    State(state): State<AppState>,
+    Json(req): Json<StateTransitionRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let raid = db::get_active_raid(&state.pool)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound("No active raid to transition".into()))?;
+
+    let from_state = raid.state();
+    let to_state = RaidState::parse(&req.to_state);
+
+    let ts = parse_timestamp("transitioned_at", req.transitioned_at.as_deref())?
+        .unwrap_or_else(OffsetDateTime::now_utc);
+    check_raid_time(&state, &raid, "transitioned_at", ts, false).await?;
+
+    // The graph is checked by the db against the state at write time
+    let transition_id = if req.force {
+        db::log_forced_transition(&state.pool, raid.raid_id, to_state.as_str(), Some(ts))
+            .await.map_err(AppError::DatabaseError)?
+    } else {
+        db::log_state_transition(&state.pool, raid.raid_id, to_state.as_str(), Some(ts)).await?
+    };
+
+    state.history.record(raid.session_id, Action::Transitioned(RaidStateTransition {
+        transition_id,
+        raid_id: raid.raid_id,
+        from_state: Some(raid.current_state.clone()),
+        to_state: to_state.clone(),
+        transitioned_at: ts,
+    })).await;
+
+    state.events.publish(LiveEvent::StateTransitioned {
+        raid_id: raid.raid_id,
+        from_state: from_state.clone(),
+        to_state: to_state.clone(),
+        transitioned_at: ts,
+    });
+
+    Ok(Json(serde_json::json!({
+        "raid_id": raid.raid_id,
+        "from_state": from_state,
+        "to_state": to_state,
+        "transitioned_at": format_timestamp(ts),
+    })))
+}
+
+pub async fn end_current_raid(
+    State(state): State<AppState>,
+    Json(req): Json<EndRaidRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let final_state = RaidState::parse(&req.final_state);
+
+    if !final_state.is_terminal() {
+        return Err(AppError::ValidationError(format!(
+            "final_state must be survived, died or mia, got '{}'", final_state
+        )));
+    }
+
+    let raid = db::get_active_raid(&state.pool)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound("No active raid to end".into()))?;
+
+    let ended_at = parse_timestamp("ended_at", req.ended_at.as_deref())?
+        .unwrap_or_else(OffsetDateTime::now_utc);
+    check_raid_time(&state, &raid, "ended_at", ended_at, true).await?;
+
+    // The outcome lives in current_state, stats count "survived"/"died" from there.
+    // If it was already logged via /transition there is nothing more to record.
+    // Both writes go in one transaction, a raid must not be left dead but still running.
+    let mut tx = state.pool.begin().await.map_err(AppError::DatabaseError)?;
+    let mut transition = None;
+    if raid.state() != final_state {
+        let transition_id = if req.force {
+            db::log_forced_transition(&mut *tx, raid.raid_id, final_state.as_str(), Some(ended_at))
+                .await.map_err(AppError::DatabaseError)?
+        } else {
+            db::log_state_transition(&mut *tx, raid.raid_id, final_state.as_str(), Some(ended_at)).await?
+        };
+        transition = Some(RaidStateTransition {
+            transition_id,
+            raid_id: raid.raid_id,
+            from_state: Some(raid.current_state.clone()),
+            to_state: final_state.clone(),
+            transitioned_at: ended_at,
+        });
+    }
+
+    db::end_raid(&mut *tx, raid.raid_id, Some(ended_at), req.extract_location.clone())
+        .await.map_err(AppError::DatabaseError)?;
+    tx.commit().await.map_err(AppError::DatabaseError)?;
+
+    if transition.is_some() {
+        state.events.publish(LiveEvent::StateTransitioned {
+            raid_id: raid.raid_id,
+            from_state: raid.state(),
+            to_state: final_state.clone(),
+            transitioned_at: ended_at,
+        });
+    }
+
+    // Ending and its terminal transition undo as one step
+    state.history.record(raid.session_id, Action::RaidEnded {
+        raid_id: raid.raid_id,
+        ended_at,
+        extract_location: req.extract_location.clone(),
+        transition,
+    }).await;
+
+    state.events.publish(LiveEvent::RaidEnded {
+        raid_id: raid.raid_id,
+        final_state: final_state.clone(),
+        extract_location: req.extract_location,
+        ended_at,
+    });
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "raid_id": raid.raid_id,
+        "final_state": final_state,
+        "ended_at": format_timestamp(ended_at),
+        "message": "raid ended"
+    })))
+}
+
+// Map transfer: the current segment ends in `transfer` and the raid carries on as a new
+// segment on the next map, back at deploying_committed with its own timer
+pub async fn transfer_raid(
+    State(state): State<AppState>,
+    Json(req): Json<TransferRaidRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    if req.map_name.trim().is_empty() {
+        return Err(AppError::ValidationError("map_name cannot be empty".into()));
+    }
+
+    let raid = db::get_active_raid(&state.pool)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound("No active raid to transfer".into()))?;
+
+    let ts = parse_timestamp("transferred_at", req.transferred_at.as_deref())?
+        .unwrap_or_else(OffsetDateTime::now_utc);
+    check_raid_time(&state, &raid, "transferred_at", ts, true).await?;
+
+    let db::RaidTransfer { transition_id, segment, segment_transition_id } =
+        db::transfer_raid(&state.pool, &raid, &req.map_name, ts).await?;
+    let segment_id = segment.raid_id;
+
+    state.history.record(raid.session_id, Action::RaidTransferred {
+        raid_id: raid.raid_id,
+        ended_at: ts,
+        transition: RaidStateTransition {
+            transition_id,
+            raid_id: raid.raid_id,
+            from_state: Some(raid.current_state.clone()),
+            to_state: RaidState::Transfer,
+            transitioned_at: ts,
+        },
+        segment: segment.clone(),
+        segment_transition: RaidStateTransition {
+            transition_id: segment_transition_id,
+            raid_id: segment_id,
+            from_state: Some(segment.current_state.clone()),
+            to_state: RaidState::DeployingCommitted,
+            transitioned_at: ts,
+        },
+    }).await;
+
+    state.events.publish(LiveEvent::StateTransitioned {
+        raid_id: raid.raid_id,
+        from_state: raid.state(),
+        to_state: RaidState::Transfer,
+        transitioned_at: ts,
+    });
+    state.events.publish(LiveEvent::RaidTransferred {
+        from_raid_id: raid.raid_id,
+        raid_id: segment_id,
+        chain_id: segment.chain_id(),
+        segment_order: segment.segment_order,
+        map_name: segment.map_name.clone(),
+        transferred_at: ts,
+    });
+    state.events.publish(LiveEvent::StateTransitioned {
+        raid_id: segment_id,
+        from_state: RaidState::Transfer,
+        to_state: RaidState::DeployingCommitted,
+        transitioned_at: ts,
+    });
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "raid_id": segment_id,
+            "from_raid_id": raid.raid_id,
+            "chain_id": segment.chain_id(),
+            "segment_order": segment.segment_order,
+            "map_name": segment.map_name,
+            "transferred_at": format_timestamp(ts),
+        })),
+    ))
+}
+
+// Any segment's id works, the chain is looked up from its first segment
+pub async fn get_raid_chain(
+    State(state): State<AppState>,
+    Path(raid_id): Path<i64>,
+) -> Result<Json<RaidChainResponse>, AppError> {
+    let raid = find_raid(&state, raid_id).await?;
+    let chain = db::get_raid_chain(&state.pool, raid.chain_id())
+        .await.map_err(AppError::DatabaseError)?;
+    let (Some(first), Some(last)) = (chain.first(), chain.last()) else {
+        return Err(AppError::NotFound(format!("Raid {} not found", raid_id)));
+    };
+
+    let mut segments = Vec::with_capacity(chain.len());
+    for segment in &chain {
+        let kills = db::get_kills_for_raid(&state.pool, segment.raid_id)
+            .await.map_err(AppError::DatabaseError)?;
+        segments.push(RaidSegmentResponse {
+            raid: RaidResponse::from(segment),
+            kill_count: kills.len() as i64,
+            duration_seconds: segment.ended_at.map(|end| (end - segment.started_at).whole_seconds()),
+        });
+    }
+
+    Ok(Json(RaidChainResponse {
+        chain_id: first.raid_id,
+        outcome: last.current_state.to_string(),
+        started_at: format_timestamp(first.started_at),
+        ended_at: last.ended_at.map(format_timestamp),
+        duration_seconds: last.ended_at.map(|end| (end - first.started_at).whole_seconds()),
+        kill_count: segments.iter().map(|s| s.kill_count).sum(),
+        maps: chain.iter().map(|s| s.map_name.clone()).collect(),
+        segments,
+    }))
+}
+
+async fn find_raid(state: &AppState, raid_id: i64) -> Result<Raid, AppError> {
+    db::get_raid_by_id(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Raid {} not found", raid_id)))
+}
+
+// Times have to keep enclosing everything that happened in the raid
+async fn check_raid_bounds(state: &AppState, raid: &Raid) -> Result<(), AppError> {
+    check_not_future("started_at", raid.started_at)?;
+
+    if let Some(session) = db::get_session_by_id(&state.pool, raid.session_id)
+        .await.map_err(AppError::DatabaseError)?
+    {
+        check_not_before("started_at", raid.started_at, session.started_at, "the session started")?;
+    }
+
+    let transitions = db::get_raid_transitions(&state.pool, raid.raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+    let kills = db::get_kills_for_raid(&state.pool, raid.raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+    let times = transitions.iter().map(|t| t.transitioned_at).chain(kills.iter().map(|k| k.killed_at));
+    let (first, last) = (times.clone().min(), times.max());
+
+    if let Some(first) = first {
+        check_not_after("started_at", raid.started_at, first, "the raid's first transition or kill")?;
+    }
+
+    if let Some(ended_at) = raid.ended_at {
+        check_not_future("ended_at", ended_at)?;
+        check_not_before("ended_at", ended_at, raid.started_at, "the raid started")?;
+        if let Some(last) = last {
+            check_not_before("ended_at", ended_at, last, "the raid's last transition or kill")?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn update_raid(
+    State(state): State<AppState>,
+    Path(raid_id): Path<i64>,
+    Query(query): Query<EditQuery>,
+    Json(req): Json<UpdateRaidRequest>,
+) -> Result<Json<RaidResponse>, AppError> {
+    let before = find_raid(&state, raid_id).await?;
+    let mut raid = before.clone();
+
+    if let Some(map_name) = req.map_name {
+        if map_name.trim().is_empty() {
+            return Err(AppError::ValidationError("map_name cannot be empty".into()));
+        }
+        raid.map_name = map_name;
+    }
+    if let Some(character_type) = req.character_type {
+        raid.character_type = character_type;
+    }
+    if let Some(game_mode) = req.game_mode {
+        raid.game_mode = game_mode;
+    }
+    if let Some(extract_location) = req.extract_location {
+        raid.extract_location = extract_location;
+    }
+    if let Some(started_at) = parse_timestamp("started_at", req.started_at.as_deref())? {
+        raid.started_at = started_at;
+    }
+    if let Some(ended_at) = parse_timestamp("ended_at", req.ended_at.as_deref())? {
+        if raid.ended_at.is_none() {
+            return Err(AppError::Conflict(format!(
+                "Raid {} is still running, end it with /api/raid/end", raid_id
+            )));
+        }
+        raid.ended_at = Some(ended_at);
+    }
+
+    check_raid_bounds(&state, &raid).await?;
+
+    let mut tx = state.pool.begin().await.map_err(AppError::DatabaseError)?;
+    db::update_raid(&mut *tx, &raid)
+        .await.map_err(AppError::DatabaseError)?;
+    audit::record(&mut tx, &query, "raids", raid_id,
+        &RaidResponse::from(&before), Some(&RaidResponse::from(&raid))).await?;
+    tx.commit().await.map_err(AppError::DatabaseError)?;
+    state.history.record(raid.session_id, Action::RaidUpdated { before, after: raid.clone() }).await;
+
+    state.events.publish(LiveEvent::RaidCorrected { raid_id, raid_state: Some(raid.state()) });
+
+    Ok(Json(RaidResponse::from(&raid)))
+}
+
+// Removes the raid along with its transitions and kills
+pub async fn delete_raid(
+    State(state): State<AppState>,
+    Path(raid_id): Path<i64>,
+    Query(query): Query<EditQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let raid = find_raid(&state, raid_id).await?;
+    // Its segments would be left pointing at nothing, and undoing the delete can't relink them
+    let segments = db::get_raid_chain(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+    if segments.len() > 1 {
+        return Err(AppError::Conflict(format!(
+            "Raid {} starts a transfer chain, delete its later segments first", raid_id
+        )));
+    }
+    let transitions = db::get_raid_transitions(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+    let kills = db::get_kills_for_raid(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    let mut tx = state.pool.begin().await.map_err(AppError::DatabaseError)?;
+    db::delete_raid(&mut *tx, raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+    audit::record(&mut tx, &query, "raids", raid_id, &RaidResponse::from(&raid), None).await?;
+    tx.commit().await.map_err(AppError::DatabaseError)?;
+
+    let (transition_count, kill_count) = (transitions.len(), kills.len());
+    state.history.record(raid.session_id, Action::RaidDeleted { raid, transitions, kills }).await;
+    state.events.publish(LiveEvent::RaidCorrected { raid_id, raid_state: None });
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "raid_id": raid_id,
+        "transitions_deleted": transition_count,
+        "kills_deleted": kill_count,
+        "message": "raid deleted"
+    })))
+}
+
 #[cfg(test)]
 mod tests {
     use axum::{body::Body, http::Request};
     use http::StatusCode;
     use tower::ServiceExt;
-    use crate::api;
     use crate::api::{state::AppState, routes::api_router};
     use crate::db::{self, tests::setup_test_db};
     use crate::models::SessionType;
@@ -127,4 +602,483 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::CONFLICT);
     }
+
+    async fn setup_active_raid(pool: &sqlx::SqlitePool) -> i64 {
+        let session_id = db::create_session(pool, SessionType::Stream, None, None).await.expect("session");
+        db::create_raid(pool, session_id, "Customs",
+            crate::models::CharacterType::PMC,
+            crate::models::GameMode::PVE,
+            None)
+        .await.expect("raid")
+    }
+
+    async fn post_json(app: axum::Router, uri: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .oneshot(
+                Request::post(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            ).await.unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_get_current_raid_returns_404_when_none() {
+        let pool = setup_test_db().await.expect("setup db");
+        let app = api_router().with_state(AppState::new(pool));
+
+        let response = app
+            .oneshot(Request::get("/api/raid/current").body(Body::empty()).unwrap())
+            .await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_current_raid_includes_kills() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+
+        db::add_kill(&pool, raid_id, "scav", None, None, None).await.expect("kill");
+        db::add_kill(&pool, raid_id, "pmc", None, Some(true), None).await.expect("kill");
+
+        let app = api_router().with_state(AppState::new(pool));
+
+        let response = app
+            .oneshot(Request::get("/api/raid/current").body(Body::empty()).unwrap())
+            .await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(json["raid_id"], raid_id);
+        assert_eq!(json["map_name"], "Customs");
+        assert_eq!(json["current_state"], "stash_management");
+        assert_eq!(json["kill_count"], 2);
+        assert!(json["time_in_state_seconds"].as_i64().unwrap() >= 0);
+    }
+
+    #[tokio::test]
+    async fn test_transition_raid_success() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let (status, json) = post_json(app, "/api/raid/transition",
+            r#"{"to_state": "pre_raid_setup"}"#).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["from_state"], "stash_management");
+        assert_eq!(json["to_state"], "pre_raid_setup");
+
+        let transitions = db::get_raid_transitions(&pool, raid_id).await.expect("transitions");
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].to_state, "pre_raid_setup");
+    }
+
+    #[tokio::test]
+    async fn test_transition_raid_illegal_is_conflict() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let (status, json) = post_json(app, "/api/raid/transition",
+            r#"{"to_state": "raid_active"}"#).await;
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(json["type"], "conflict");
+
+        let transitions = db::get_raid_transitions(&pool, raid_id).await.expect("transitions");
+        assert!(transitions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_transition_raid_typo_needs_force() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+
+        let app = api_router().with_state(AppState::new(pool.clone()));
+        let (status, _) = post_json(app, "/api/raid/transition",
+            r#"{"to_state": "queing"}"#).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let app = api_router().with_state(AppState::new(pool.clone()));
+        let (status, _) = post_json(app, "/api/raid/transition",
+            r#"{"to_state": "looting_body", "force": true}"#).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let raid = db::get_active_raid(&pool).await.expect("raid").expect("active");
+        assert_eq!(raid.raid_id, raid_id);
+        assert_eq!(raid.current_state, "looting_body");
+    }
+
+    #[tokio::test]
+    async fn test_transition_raid_bad_timestamp() {
+        let pool = setup_test_db().await.expect("setup db");
+        setup_active_raid(&pool).await;
+        let app = api_router().with_state(AppState::new(pool));
+
+        let (status, _) = post_json(app, "/api/raid/transition",
+            r#"{"to_state": "pre_raid_setup", "transitioned_at": "not a time"}"#).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_end_raid_records_final_state() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+
+        for s in ["pre_raid_setup", "queuing", "deploying_committed", "raid_active"] {
+            db::log_state_transition(&pool, raid_id, s, None).await.expect("transition");
+        }
+
+        let app = api_router().with_state(AppState::new(pool.clone()));
+        let (status, json) = post_json(app, "/api/raid/end",
+            r#"{"final_state": "survived", "extract_location": "Crossroads"}"#).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["final_state"], "survived");
+
+        assert!(db::get_active_raid(&pool).await.expect("query").is_none());
+
+        let transitions = db::get_raid_transitions(&pool, raid_id).await.expect("transitions");
+        assert_eq!(transitions.last().unwrap().to_state, "survived");
+
+        let raids = db::get_all_raids(&pool).await.expect("raids");
+        assert_eq!(raids[0].current_state, "survived");
+        assert_eq!(raids[0].extract_location, Some("Crossroads".into()));
+        assert!(raids[0].ended_at.is_some());
+
+        let stats = crate::stats::calculate_session_stats(&pool, raids[0].session_id).await.expect("stats");
+        assert_eq!(stats.survived_raids, 1);
+    }
+
+    #[tokio::test]
+    async fn test_end_raid_already_in_final_state() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+
+        for s in ["pre_raid_setup", "queuing", "deploying_committed", "raid_active", "died"] {
+            db::log_state_transition(&pool, raid_id, s, None).await.expect("transition");
+        }
+
+        let app = api_router().with_state(AppState::new(pool.clone()));
+        let (status, _) = post_json(app, "/api/raid/end", r#"{"final_state": "died"}"#).await;
+
+        assert_eq!(status, StatusCode::OK);
+
+        // "died" should not be logged twice
+        let transitions = db::get_raid_transitions(&pool, raid_id).await.expect("transitions");
+        assert_eq!(transitions.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_end_raid_rejects_non_terminal_state() {
+        let pool = setup_test_db().await.expect("setup db");
+        setup_active_raid(&pool).await;
+        let app = api_router().with_state(AppState::new(pool));
+
+        let (status, json) = post_json(app, "/api/raid/end", r#"{"final_state": "raid_active"}"#).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(json["type"], "validation_error");
+    }
+
+    #[tokio::test]
+    async fn test_end_raid_returns_404_when_none() {
+        let pool = setup_test_db().await.expect("setup db");
+        let app = api_router().with_state(AppState::new(pool));
+
+        let (status, _) = post_json(app, "/api/raid/end", r#"{"final_state": "died"}"#).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_create_raid_backdated() {
+        let pool = setup_test_db().await.expect("setup db");
+        let session_start = time::OffsetDateTime::now_utc() - time::Duration::hours(2);
+        db::create_session(&pool, SessionType::Stream, None, Some(session_start)).await.expect("session");
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let before_session = crate::api::dto::format_timestamp(session_start - time::Duration::minutes(5));
+        let (status, json) = post_json(app.clone(), "/api/raid", &format!(
+            r#"{{"map_name": "Customs", "character_type": "pmc", "game_mode": "pve", "started_at": "{}"}}"#, before_session
+        )).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(json["error"].as_str().unwrap().contains("before the session started"));
+
+        let started_at = session_start + time::Duration::minutes(10);
+        let (status, _) = post_json(app, "/api/raid", &format!(
+            r#"{{"map_name": "Customs", "character_type": "pmc", "game_mode": "pve", "started_at": "{}"}}"#,
+            crate::api::dto::format_timestamp(started_at)
+        )).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let raid = db::get_active_raid(&pool).await.expect("query").expect("raid");
+        assert_eq!(raid.started_at, started_at);
+    }
+
+    #[tokio::test]
+    async fn test_transition_and_end_must_follow_raid_history() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let now = time::OffsetDateTime::now_utc();
+        db::add_kill(&pool, raid_id, "scav", None, None, Some(now)).await.expect("kill");
+        let app = api_router().with_state(AppState::new(pool));
+
+        let an_hour_ago = crate::api::dto::format_timestamp(now - time::Duration::hours(1));
+        let (status, json) = post_json(app.clone(), "/api/raid/transition", &format!(
+            r#"{{"to_state": "pre_raid_setup", "transitioned_at": "{}"}}"#, an_hour_ago
+        )).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(json["error"].as_str().unwrap().contains("before the raid started"));
+
+        let tomorrow = crate::api::dto::format_timestamp(now + time::Duration::days(1));
+        let (status, json) = post_json(app.clone(), "/api/raid/transition", &format!(
+            r#"{{"to_state": "pre_raid_setup", "transitioned_at": "{}"}}"#, tomorrow
+        )).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(json["error"].as_str().unwrap().contains("in the future"));
+
+        // The kill was logged at `now`, the raid can't end before it
+        let just_before_kill = crate::api::dto::format_timestamp(now - time::Duration::milliseconds(10));
+        let (status, json) = post_json(app, "/api/raid/end", &format!(
+            r#"{{"final_state": "died", "ended_at": "{}", "force": true}}"#, just_before_kill
+        )).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(json["error"].as_str().unwrap().contains("before the last kill"));
+    }
+
+    async fn send(app: axum::Router, method: &str, uri: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if !body.is_empty() {
+            request = request.header("content-type", "application/json");
+        }
+        let response = app
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await.unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_update_raid_details() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let (status, json) = send(app.clone(), "PATCH", &format!("/api/raid/{}", raid_id),
+            r#"{"map_name": "Ground Zero", "game_mode": "pvp"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["map_name"], "Ground Zero");
+        assert_eq!(json["game_mode"], "pvp");
+        assert_eq!(json["character_type"], "pmc");
+
+        // Still running, ending goes through /api/raid/end
+        let ended_at = crate::api::dto::format_timestamp(time::OffsetDateTime::now_utc());
+        let (status, _) = send(app.clone(), "PATCH", &format!("/api/raid/{}", raid_id),
+            &format!(r#"{{"ended_at": "{}"}}"#, ended_at)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let entries = db::get_audit_entries(&pool, Some("raids"), Some(raid_id)).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].before_json.as_deref().unwrap().contains("Customs"));
+        assert!(entries[0].after_json.as_deref().unwrap().contains("Ground Zero"));
+
+        let (status, _) = send(app.clone(), "PATCH", "/api/raid/999", r#"{"map_name": "Woods"}"#).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // A wrong extract can be cleared with null
+        let (_, json) = send(app.clone(), "PATCH", &format!("/api/raid/{}", raid_id),
+            r#"{"extract_location": "Tunnel"}"#).await;
+        assert_eq!(json["extract_location"], "Tunnel");
+        let (status, json) = send(app, "PATCH", &format!("/api/raid/{}", raid_id),
+            r#"{"extract_location": null}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(json["extract_location"].is_null());
+        assert_eq!(json["map_name"], "Ground Zero");
+    }
+
+    #[tokio::test]
+    async fn test_raid_start_cannot_pass_its_kills() {
+        let pool = setup_test_db().await.expect("setup db");
+        let start = time::OffsetDateTime::now_utc() - time::Duration::hours(1);
+        let session_id = db::create_session(&pool, SessionType::Stream, None, Some(start)).await.unwrap();
+        let raid_id = db::create_raid(&pool, session_id, "Customs",
+            crate::models::CharacterType::PMC, crate::models::GameMode::PVE, Some(start)).await.unwrap();
+        db::add_kill(&pool, raid_id, "scav", None, None, Some(start + time::Duration::minutes(10))).await.unwrap();
+        let app = api_router().with_state(AppState::new(pool));
+
+        let late = crate::api::dto::format_timestamp(start + time::Duration::minutes(20));
+        let (status, json) = send(app, "PATCH", &format!("/api/raid/{}", raid_id),
+            &format!(r#"{{"started_at": "{}"}}"#, late)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(json["error"].as_str().unwrap().contains("first transition or kill"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_raid_and_undo() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+        db::log_state_transition(&pool, raid_id, "pre_raid_setup", None).await.unwrap();
+        db::add_kill(&pool, raid_id, "scav", None, None, None).await.unwrap();
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let (status, json) = send(app.clone(), "DELETE", &format!("/api/raid/{}", raid_id), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["transitions_deleted"], 1);
+        assert_eq!(json["kills_deleted"], 1);
+        assert!(db::get_raid_by_id(&pool, raid_id).await.unwrap().is_none());
+        assert!(db::get_kills_for_raid(&pool, raid_id).await.unwrap().is_empty());
+
+        let (status, json) = send(app, "POST", "/api/undo", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["undone"], "raid_deleted");
+
+        let raid = db::get_raid_by_id(&pool, raid_id).await.unwrap().expect("raid restored");
+        assert_eq!(raid.current_state, "pre_raid_setup");
+        assert_eq!(db::get_raid_transitions(&pool, raid_id).await.unwrap().len(), 1);
+        assert_eq!(db::get_kills_for_raid(&pool, raid_id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_links_segments_into_a_chain() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+        db::log_forced_transition(&pool, raid_id, "raid_active", None).await.unwrap();
+        db::add_kill(&pool, raid_id, "scav", None, None, None).await.unwrap();
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let (status, json) = send(app.clone(), "POST", "/api/raid/transfer", r#"{"map_name": "Labs"}"#).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", json);
+        assert_eq!(json["from_raid_id"], raid_id);
+        assert_eq!(json["chain_id"], raid_id);
+        assert_eq!(json["segment_order"], 1);
+        let segment_id = json["raid_id"].as_i64().unwrap();
+
+        let first = db::get_raid_by_id(&pool, raid_id).await.unwrap().unwrap();
+        assert_eq!(first.current_state, "transfer");
+        assert!(first.ended_at.is_some());
+        let segment = db::get_active_raid(&pool).await.unwrap().expect("segment is the active raid");
+        assert_eq!(segment.raid_id, segment_id);
+        assert_eq!(segment.parent_raid_id, Some(raid_id));
+        assert_eq!(segment.current_state, "deploying_committed");
+        assert_eq!(segment.game_mode, first.game_mode);
+
+        db::log_state_transition(&pool, segment_id, "raid_active", None).await.unwrap();
+        db::add_kill(&pool, segment_id, "pmc", None, None, None).await.unwrap();
+        let (status, _) = send(app.clone(), "POST", "/api/raid/end", r#"{"final_state": "survived"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Either segment finds the chain, the outcome comes from the last one
+        let (status, json) = send(app.clone(), "GET", &format!("/api/raid/{}/chain", raid_id), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["chain_id"], raid_id);
+        assert_eq!(json["outcome"], "survived");
+        assert_eq!(json["kill_count"], 2);
+        assert_eq!(json["maps"], serde_json::json!(["Customs", "Labs"]));
+        assert_eq!(json["segments"][1]["raid_id"], segment_id);
+        assert_eq!(json["segments"][1]["kill_count"], 1);
+        let (_, same) = send(app.clone(), "GET", &format!("/api/raid/{}/chain", segment_id), "").await;
+        assert_eq!(same["chain_id"], raid_id);
+
+        // The head can't go while the chain hangs off it
+        let (status, _) = send(app.clone(), "DELETE", &format!("/api/raid/{}", raid_id), "").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(db::get_raid_by_id(&pool, raid_id).await.unwrap().is_some());
+
+        // The transfer is neither a survival nor a death, only the chain's end counts
+        let (_, json) = send(app.clone(), "GET", "/api/stats/all-time", "").await;
+        assert_eq!(json["total_raids"], 1);
+        assert_eq!(json["survived_raids"], 1);
+        assert_eq!(json["kd_ratio"], 2.0);
+        let (_, json) = send(app.clone(), "GET", "/api/stats/all-time?unit=chains", "").await;
+        assert_eq!(json["total_raids"], 1);
+        assert_eq!(json["survived_raids"], 1);
+        assert_eq!(json["total_kills"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_needs_a_raid_in_progress() {
+        let pool = setup_test_db().await.expect("setup db");
+        let raid_id = setup_active_raid(&pool).await;
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        // Still in the stash, there is no map to leave
+        let (status, _) = send(app.clone(), "POST", "/api/raid/transfer", r#"{"map_name": "Labs"}"#).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        db::log_forced_transition(&pool, raid_id, "raid_active", None).await.unwrap();
+        let (status, _) = send(app.clone(), "POST", "/api/raid/transfer", r#"{"map_name": " "}"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        // Undo puts the raid back on the first map
+        let (status, _) = send(app.clone(), "POST", "/api/raid/transfer", r#"{"map_name": "Labs"}"#).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, json) = send(app.clone(), "POST", "/api/undo", "").await;
+        assert_eq!(status, StatusCode::OK, "{}", json);
+        assert_eq!(json["undone"], "raid_transferred");
+        let active = db::get_active_raid(&pool).await.unwrap().unwrap();
+        assert_eq!(active.raid_id, raid_id);
+        assert_eq!(active.current_state, "raid_active");
+        assert_eq!(db::get_raid_chain(&pool, raid_id).await.unwrap().len(), 1);
+
+        let (status, _) = send(app.clone(), "POST", "/api/redo", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let active = db::get_active_raid(&pool).await.unwrap().unwrap();
+        assert_eq!(active.parent_raid_id, Some(raid_id));
+        assert_eq!(active.current_state, "deploying_committed");
+    }
+
+    #[tokio::test]
+    async fn test_list_raids_with_filters_and_cursor() {
+        let pool = setup_test_db().await.expect("setup db");
+        let ids = db::tests::seed_history(&pool).await.unwrap();
+        let app = api_router().with_state(AppState::new(pool));
+
+        let (status, json) = send(app.clone(), "GET", "/api/raids?map=customs&game_mode=pvp&sort=kills&limit=1", "").await;
+        assert_eq!(status, StatusCode::OK, "{}", json);
+        assert_eq!(json["items"][0]["raid_id"], ids[3]);
+        assert_eq!(json["items"][0]["kill_count"], 3);
+        assert_eq!(json["items"][0]["session_type"], "practice");
+
+        let cursor = json["next_cursor"].as_str().unwrap();
+        let (_, json) = send(app.clone(), "GET",
+            &format!("/api/raids?map=customs&game_mode=pvp&sort=kills&limit=1&cursor={}", cursor), "").await;
+        assert_eq!(json["items"][0]["raid_id"], ids[0]);
+        assert_eq!(json["items"][0]["duration_seconds"], 21 * 60);
+        assert_eq!(json["items"][0]["active_duration_seconds"], 21 * 60);
+        assert!(json["next_cursor"].is_null());
+
+        let (status, json) = send(app.clone(), "GET", "/api/raids?outcome=survived&has_kills=true", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["items"].as_array().unwrap().len(), 1);
+        assert_eq!(json["items"][0]["map_name"], "Woods");
+
+        let (status, _) = send(app.clone(), "GET", "/api/raids?outcome=raid_active", "").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = send(app.clone(), "GET", "/api/raids?limit=0", "").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = send(app.clone(), "GET", "/api/raids?cursor=nonsense", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Sessions only, rejected by the query extractor
+        let response = app
+            .oneshot(Request::get("/api/raids?sort=raids").body(Body::empty()).unwrap())
+            .await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
 }
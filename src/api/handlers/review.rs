@@ -0,0 +1,352 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use http::StatusCode;
+use time::OffsetDateTime;
+use crate::api::dto::{
+    BulkApproveRequest, EditPendingEventRequest, EditQuery, PendingEventResponse, RejectPendingEventRequest,
+    ReviewQuery, SubmitBatchRequest, SubmitEventRequest, check_not_future, parse_timestamp,
+};
+use crate::api::handlers::audit;
+use crate::api::{error::AppError, state::AppState};
+use crate::db;
+use crate::models::{PendingEvent, ReviewStatus};
+use crate::review::{self, Approval, ApprovalResult};
+
+fn validate_submission(source: &str, confidence: Option<f64>, occurred_at: Option<&str>)
+    -> Result<Option<OffsetDateTime>, AppError>
+{
+    if source.trim().is_empty() {
+        return Err(AppError::ValidationError("source cannot be empty".into()));
+    }
+    if let Some(confidence) = confidence && !(0.0..=1.0).contains(&confidence) {
+        return Err(AppError::ValidationError(format!("confidence {} is outside 0..1", confidence)));
+    }
+
+    let occurred_at = parse_timestamp("occurred_at", occurred_at)?;
+    if let Some(ts) = occurred_at {
+        check_not_future("occurred_at", ts)?;
+    }
+    Ok(occurred_at)
+}
+
+async fn find_pending(state: &AppState, pending_id: i64) -> Result<PendingEvent, AppError> {
+    let event = db::get_pending_event(&state.pool, pending_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Pending event {} not found", pending_id)))?;
+
+    if event.status != ReviewStatus::Pending {
+        return Err(AppError::Conflict(format!(
+            "Pending event {} was already {}", pending_id, event.status.as_str()
+        )));
+    }
+    Ok(event)
+}
+
+// The status changed between find_pending and the update
+fn reviewed_meanwhile(pending_id: i64) -> AppError {
+    AppError::Conflict(format!("Pending event {} was reviewed while this request was in flight", pending_id))
+}
+
+async fn pending_response(state: &AppState, pending_id: i64) -> Result<Json<PendingEventResponse>, AppError> {
+    let event = db::get_pending_event(&state.pool, pending_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Pending event {} not found", pending_id)))?;
+
+    Ok(Json(PendingEventResponse::from(&event)))
+}
+
+pub async fn list_pending_events(
+    State(state): State<AppState>,
+    Query(query): Query<ReviewQuery>,
+) -> Result<Json<Vec<PendingEventResponse>>, AppError> {
+    let events = db::get_pending_events(&state.pool, query.status, query.source.as_deref())
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(events.iter().map(PendingEventResponse::from).collect()))
+}
+
+pub async fn submit_event(
+    State(state): State<AppState>,
+    Json(req): Json<SubmitEventRequest>,
+) -> Result<(StatusCode, Json<PendingEventResponse>), AppError> {
+    let occurred_at = validate_submission(&req.source, req.confidence, req.occurred_at.as_deref())?;
+
+    let pending_id = review::submit(&state.pool, &state.events, &req.source, req.confidence, &req.event, occurred_at)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok((StatusCode::CREATED, pending_response(&state, pending_id).await?))
+}
+
+// Queues a whole batch, validating everything up front so a bad entry doesn't leave half of it queued.
+// With approve set the batch is applied straight away and the per-event results come back.
+pub async fn submit_batch(
+    State(state): State<AppState>,
+    Json(req): Json<SubmitBatchRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let mut occurred = Vec::with_capacity(req.events.len());
+    for (i, entry) in req.events.iter().enumerate() {
+        let occurred_at = validate_submission(&req.source, entry.confidence, entry.occurred_at.as_deref())
+            .map_err(|e| match e {
+                AppError::ValidationError(msg) => AppError::ValidationError(format!("events[{}]: {}", i, msg)),
+                AppError::BadRequest(msg) => AppError::BadRequest(format!("events[{}]: {}", i, msg)),
+                other => other,
+            })?;
+        occurred.push(occurred_at);
+    }
+
+    let mut ids = Vec::with_capacity(req.events.len());
+    for (entry, occurred_at) in req.events.iter().zip(occurred) {
+        let pending_id = review::submit(&state.pool, &state.events, &req.source, entry.confidence, &entry.event, occurred_at)
+            .await.map_err(AppError::DatabaseError)?;
+        ids.push(pending_id);
+    }
+
+    let approvals = if req.approve {
+        Some(review::approve(&state.pool, &state.events, &ids).await.map_err(AppError::DatabaseError)?)
+    } else {
+        None
+    };
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({
+        "pending_ids": ids,
+        "approvals": approvals,
+    }))))
+}
+
+pub async fn edit_pending_event(
+    State(state): State<AppState>,
+    Path(pending_id): Path<i64>,
+    Query(query): Query<EditQuery>,
+    Json(req): Json<EditPendingEventRequest>,
+) -> Result<Json<PendingEventResponse>, AppError> {
+    let before = find_pending(&state, pending_id).await?;
+    let mut event = before.clone();
+
+    if let Some(kind) = req.event {
+        event.event_type = kind.name().to_string();
+        event.payload = serde_json::to_string(&kind).expect("detection events serialize");
+    }
+    if let Some(ts) = parse_timestamp("occurred_at", req.occurred_at.as_deref())? {
+        check_not_future("occurred_at", ts)?;
+        event.occurred_at = Some(ts);
+    }
+
+    let mut tx = state.pool.begin().await.map_err(AppError::DatabaseError)?;
+    if !db::update_pending_event(&mut *tx, &event).await.map_err(AppError::DatabaseError)? {
+        return Err(reviewed_meanwhile(pending_id));
+    }
+    audit::record(&mut tx, &query, "pending_events", pending_id,
+        &PendingEventResponse::from(&before), Some(&PendingEventResponse::from(&event))).await?;
+    tx.commit().await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(PendingEventResponse::from(&event)))
+}
+
+pub async fn approve_pending_event(
+    State(state): State<AppState>,
+    Path(pending_id): Path<i64>,
+) -> Result<Json<PendingEventResponse>, AppError> {
+    find_pending(&state, pending_id).await?;
+
+    let approvals = review::approve(&state.pool, &state.events, &[pending_id])
+        .await.map_err(AppError::DatabaseError)?;
+
+    match &approvals[0].result {
+        ApprovalResult::Approved => pending_response(&state, pending_id).await,
+        ApprovalResult::NotApplied { reason } => Err(AppError::Conflict(reason.clone())),
+        ApprovalResult::AlreadyReviewed { status } => Err(AppError::Conflict(format!(
+            "Pending event {} was already {}", pending_id, status.as_str()
+        ))),
+        ApprovalResult::NotFound => Err(AppError::NotFound(format!("Pending event {} not found", pending_id))),
+    }
+}
+
+pub async fn reject_pending_event(
+    State(state): State<AppState>,
+    Path(pending_id): Path<i64>,
+    req: Option<Json<RejectPendingEventRequest>>,
+) -> Result<Json<PendingEventResponse>, AppError> {
+    find_pending(&state, pending_id).await?;
+    let req = req.map(|Json(r)| r).unwrap_or_default();
+
+    if !db::set_review_status(&state.pool, pending_id, ReviewStatus::Rejected, req.note)
+        .await.map_err(AppError::DatabaseError)?
+    {
+        return Err(reviewed_meanwhile(pending_id));
+    }
+
+    pending_response(&state, pending_id).await
+}
+
+// Approves the listed events, or everything still pending (from one source if given), oldest first
+pub async fn bulk_approve(
+    State(state): State<AppState>,
+    Json(req): Json<BulkApproveRequest>,
+) -> Result<Json<Vec<Approval>>, AppError> {
+    let ids = match req.ids {
+        Some(ids) => ids,
+        None => db::get_pending_events(&state.pool, Some(ReviewStatus::Pending), req.source.as_deref())
+            .await.map_err(AppError::DatabaseError)?
+            .iter()
+            .map(|e| e.pending_id)
+            .collect(),
+    };
+
+    let approvals = review::approve(&state.pool, &state.events, &ids)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(approvals))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use http::StatusCode;
+    use tower::ServiceExt;
+    use crate::api::{state::AppState, routes::api_router};
+    use crate::db;
+    use crate::db::tests::setup_test_db;
+    use crate::models::SessionType;
+
+    async fn send(app: axum::Router, method: &str, uri: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if !body.is_empty() {
+            request = request.header("content-type", "application/json");
+        }
+        let response = app
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await.unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_submit_edit_approve() {
+        let pool = setup_test_db().await.unwrap();
+        let session_id = db::create_session(&pool, SessionType::Stream, None, None).await.unwrap();
+        db::create_raid(&pool, session_id, "Customs",
+            crate::models::CharacterType::PMC, crate::models::GameMode::PVE, None).await.unwrap();
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let (status, json) = send(app.clone(), "POST", "/api/review", r#"{
+            "source": "chat", "confidence": 0.6,
+            "event": {"type": "kill", "data": {"enemy_type": "scav", "weapon": "AK-74"}}
+        }"#).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(json["status"], "pending");
+        assert_eq!(json["event_type"], "kill");
+        let pending_id = json["pending_id"].as_i64().unwrap();
+
+        // Chat got it wrong, it was a PMC
+        let (status, json) = send(app.clone(), "PATCH", &format!("/api/review/{}", pending_id), r#"{
+            "event": {"type": "kill", "data": {"enemy_type": "pmc", "weapon": "AK-74", "headshot": true}}
+        }"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["event"]["data"]["enemy_type"], "pmc");
+        let edits = db::get_audit_entries(&pool, Some("pending_events"), Some(pending_id)).await.unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].action, "update");
+        assert!(edits[0].before_json.as_deref().unwrap().contains("scav"));
+
+        let raid = db::get_active_raid(&pool).await.unwrap().unwrap();
+        assert!(db::get_kills_for_raid(&pool, raid.raid_id).await.unwrap().is_empty());
+
+        let (status, json) = send(app.clone(), "POST", &format!("/api/review/{}/approve", pending_id), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["status"], "approved");
+
+        let kills = db::get_kills_for_raid(&pool, raid.raid_id).await.unwrap();
+        assert_eq!(kills.len(), 1);
+        assert_eq!(kills[0].enemy_type, "pmc");
+        assert_eq!(kills[0].headshot, Some(true));
+
+        // Reviewed events are read only
+        let (status, _) = send(app, "POST", &format!("/api/review/{}/reject", pending_id), "").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_reject_and_list() {
+        let pool = setup_test_db().await.unwrap();
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let (_, json) = send(app.clone(), "POST", "/api/review", r#"{
+            "source": "detection", "event": {"type": "state_change", "data": {"state": "queuing"}}
+        }"#).await;
+        let pending_id = json["pending_id"].as_i64().unwrap();
+
+        let (status, json) = send(app.clone(), "POST", &format!("/api/review/{}/reject", pending_id),
+            r#"{"note": "was the loading screen"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["status"], "rejected");
+        assert_eq!(json["review_note"], "was the loading screen");
+
+        let (_, json) = send(app.clone(), "GET", "/api/review?status=pending", "").await;
+        assert_eq!(json.as_array().unwrap().len(), 0);
+        let (_, json) = send(app, "GET", "/api/review?status=rejected", "").await;
+        assert_eq!(json.as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reviewed_batch_import_with_approve() {
+        let pool = setup_test_db().await.unwrap();
+        db::create_session(&pool, SessionType::Stream, None, None).await.unwrap();
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let (status, json) = send(app.clone(), "POST", "/api/review/batch", r#"{
+            "source": "batch", "approve": true,
+            "events": [
+                {"event": {"type": "raid_info", "data": {"map": "Lighthouse", "game_mode": "pvp"}}},
+                {"event": {"type": "state_change", "data": {"state": "pre_raid_setup"}}},
+                {"event": {"type": "kill", "data": {"enemy_type": "raider"}}}
+            ]
+        }"#).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let approvals = json["approvals"].as_array().unwrap();
+        assert_eq!(approvals.len(), 3);
+        assert!(approvals.iter().all(|a| a["result"] == "approved"), "{}", json);
+
+        let raid = db::get_active_raid(&pool).await.unwrap().unwrap();
+        assert_eq!(raid.map_name, "Lighthouse");
+        assert_eq!(raid.current_state, "pre_raid_setup");
+        assert_eq!(db::get_kills_for_raid(&pool, raid.raid_id).await.unwrap().len(), 1);
+
+        // A bad entry rejects the whole batch before anything is queued
+        let (status, json) = send(app, "POST", "/api/review/batch", r#"{
+            "source": "batch",
+            "events": [
+                {"event": {"type": "kill", "data": {"enemy_type": "scav"}}},
+                {"confidence": 3.0, "event": {"type": "kill", "data": {"enemy_type": "scav"}}}
+            ]
+        }"#).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(json["error"].as_str().unwrap().starts_with("events[1]"));
+        assert_eq!(db::get_pending_events(&pool, None, None).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_approve_pending_from_source() {
+        let pool = setup_test_db().await.unwrap();
+        let session_id = db::create_session(&pool, SessionType::Stream, None, None).await.unwrap();
+        let raid_id = db::create_raid(&pool, session_id, "Woods",
+            crate::models::CharacterType::PMC, crate::models::GameMode::PVE, None).await.unwrap();
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        for source in ["chat", "chat", "detection"] {
+            send(app.clone(), "POST", "/api/review", &format!(
+                r#"{{"source": "{}", "event": {{"type": "kill", "data": {{"enemy_type": "scav"}}}}}}"#, source
+            )).await;
+        }
+
+        let (status, json) = send(app, "POST", "/api/review/approve", r#"{"source": "chat"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json.as_array().unwrap().len(), 2);
+
+        assert_eq!(db::get_kills_for_raid(&pool, raid_id).await.unwrap().len(), 2);
+        let still_pending = db::get_pending_events(&pool, Some(crate::models::ReviewStatus::Pending), None).await.unwrap();
+        assert_eq!(still_pending.len(), 1);
+        assert_eq!(still_pending[0].source, "detection");
+    }
+}
@@ -1,27 +1,86 @@
-use axum::extract::State;
+use axum::extract::{Query, State};
 use axum::Json;
 use http::StatusCode;
 use crate::api::state::AppState;
-use crate::api::dto::CreateSessionRequest;
+use crate::api::dto::{
+    CreateSessionRequest, EndSessionRequest, HistoryListQuery, PageResponse, SessionSummaryResponse, check_not_before, check_not_future, format_timestamp,
+    parse_timestamp,
+};
 use crate::db;
+use crate::events::LiveEvent;
+use crate::history::Action;
+use crate::models::{SessionSort, StreamSession};
+use time::OffsetDateTime;
 
 pub async fn create_session(
     State(state): State<AppState>,
     Json(req): Json<CreateSessionRequest>,
 ) -> Result<(StatusCode, Json<serde_json::Value>), crate::api::error::AppError> {
+    let started_at = parse_timestamp("started_at", req.started_at.as_deref())?
+        .unwrap_or_else(OffsetDateTime::now_utc);
+    check_not_future("started_at", started_at)?;
+
+    // A backdated start can't reach back into the previous session
+    let sessions = db::get_all_sessions(&state.pool)
+        .await.map_err(crate::api::error::AppError::DatabaseError)?;
+    if let Some(previous) = sessions.first() {
+        match previous.ended_at {
+            Some(ended_at) => check_not_before("started_at", started_at, ended_at, "the previous session ended")?,
+            None => check_not_before("started_at", started_at, previous.started_at, "the running session started")?,
+        }
+    }
+
     let session_id = db::create_session(
         &state.pool, 
-        req.session_type, 
+        req.session_type.clone(), 
         req.notes,
-        None,
+        Some(started_at),
     ).await.map_err(crate::api::error::AppError::DatabaseError)?;
 
+    if let Some(session) = db::get_session_by_id(&state.pool, session_id)
+        .await.map_err(crate::api::error::AppError::DatabaseError)?
+    {
+        state.history.record(session_id, Action::SessionStarted(session)).await;
+    }
+
+    state.events.publish(LiveEvent::SessionStarted {
+        session_id,
+        session_type: req.session_type,
+        started_at,
+    });
+
     Ok((
         StatusCode::CREATED,
         Json(serde_json::json!({ "session_id": session_id })),
     ))
 }
 
+pub fn session_json(s: &StreamSession) -> serde_json::Value {
+    serde_json::json!({
+        "session_id": s.session_id,
+        "session_type": s.session_type,
+        "started_at": s.started_at.to_string(),
+        "ended_at": s.ended_at.map(|t| t.to_string()),
+        "notes": s.notes,
+    })
+}
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryListQuery<SessionSort>>,
+) -> Result<Json<PageResponse<SessionSummaryResponse>>, crate::api::error::AppError> {
+    let page = db::list_sessions(
+        &state.pool,
+        &query.filter()?,
+        query.sort.unwrap_or_default(),
+        query.order.unwrap_or_default(),
+        query.cursor()?,
+        query.limit()?,
+    ).await.map_err(crate::api::error::AppError::DatabaseError)?;
+
+    Ok(Json(PageResponse::from_page(&page)))
+}
+
 pub async fn get_current_session(
     State(state): State<AppState>
 ) -> Result<Json<serde_json::Value>, crate::api::error::AppError> {
@@ -30,32 +89,49 @@ pub async fn get_current_session(
         .map_err(crate::api::error::AppError::DatabaseError)?;
     
     match session {
-        Some(s) => Ok(Json(serde_json::json!({
-            "session_id": s.session_id,
-            "session_type": s.session_type,
-            "started_at": s.started_at.to_string(),
-            "ended_at": s.ended_at.map(|t| t.to_string()),
-            "notes": s.notes,
-        }))),
+        Some(s) => Ok(Json(session_json(&s))),
         None => Err(crate::api::error::AppError::NotFound(
                 "No active session".into()
         )),
     }
 }
 
+// The body is optional, a bare POST ends the session now
 pub async fn end_current_session(
-    State(state): State<AppState>
+    State(state): State<AppState>,
+    req: Option<Json<EndSessionRequest>>,
 ) -> Result<Json<serde_json::Value>, crate::api::error::AppError> {
+    let req = req.map(|Json(req)| req).unwrap_or_default();
     let session = db::get_active_session(&state.pool)
         .await.map_err(crate::api::error::AppError::DatabaseError)?;
 
     match session {
         Some(s) => {
-            db::end_session(&state.pool, s.session_id)
+            let ended_at = parse_timestamp("ended_at", req.ended_at.as_deref())?
+                .unwrap_or_else(OffsetDateTime::now_utc);
+            check_not_future("ended_at", ended_at)?;
+            check_not_before("ended_at", ended_at, s.started_at, "the session started")?;
+
+            // Nothing in the session may happen after it ends
+            let raids = db::get_raids_for_session(&state.pool, s.session_id)
+                .await.map_err(crate::api::error::AppError::DatabaseError)?;
+            if let Some(last_raid) = raids.last() {
+                let last_activity = last_raid.ended_at.unwrap_or(last_raid.started_at);
+                check_not_before("ended_at", ended_at, last_activity, "the session's last raid")?;
+            }
+
+            db::end_session(&state.pool, s.session_id, Some(ended_at))
                 .await.map_err(crate::api::error::AppError::DatabaseError)?;
+            state.history.record(s.session_id, Action::SessionEnded { session_id: s.session_id, ended_at }).await;
+
+            state.events.publish(LiveEvent::SessionEnded {
+                session_id: s.session_id,
+                ended_at,
+            });
             Ok(Json(serde_json::json!({
                 "status": "success",
                 "session_id": s.session_id,
+                "ended_at": format_timestamp(ended_at),
                 "message": "session ended"
             })))
         },
@@ -68,9 +144,9 @@ mod tests {
     use axum::{body::Body, http::Request};
     use http::StatusCode;
     use tower::ServiceExt;
-    use crate::api;
     use crate::api::state::AppState;
     use crate::api::routes::api_router;
+    use crate::api::dto::format_timestamp;
     use crate::db::tests::setup_test_db;
     use crate::db;
 
@@ -144,7 +220,7 @@ mod tests {
         let pool = setup_test_db().await.expect("setup db");
 
         //create a session directly via db layer
-        let session_id = db::create_session(
+        let _session_id = db::create_session(
             &pool,
             crate::models::SessionType::Stream,
             Some("Test Session to end".into()),
@@ -177,4 +253,111 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
+
+    async fn send(app: axum::Router, uri: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .oneshot(
+                Request::post(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            ).await.unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_backdated_session_start_and_end() {
+        let pool = setup_test_db().await.expect("setup db");
+        let app = api_router().with_state(AppState::new(pool.clone()));
+        let started_at = time::OffsetDateTime::now_utc() - time::Duration::hours(3);
+        let ended_at = started_at + time::Duration::hours(2);
+
+        let (status, json) = send(app.clone(), "/api/session", &format!(
+            r#"{{"session_type": "stream", "started_at": "{}"}}"#, format_timestamp(started_at)
+        )).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let session_id = json["session_id"].as_i64().unwrap();
+
+        // Ending before it started is rejected
+        let (status, _) = send(app.clone(), "/api/session/end", &format!(
+            r#"{{"ended_at": "{}"}}"#, format_timestamp(started_at - time::Duration::minutes(1))
+        )).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, json) = send(app, "/api/session/end", &format!(
+            r#"{{"ended_at": "{}"}}"#, format_timestamp(ended_at)
+        )).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["ended_at"], format_timestamp(ended_at));
+
+        let session = db::get_session_by_id(&pool, session_id).await.unwrap().unwrap();
+        assert_eq!(session.started_at, started_at);
+        assert_eq!(session.ended_at, Some(ended_at));
+    }
+
+    #[tokio::test]
+    async fn test_session_cannot_end_before_last_raid() {
+        let pool = setup_test_db().await.expect("setup db");
+        let started_at = time::OffsetDateTime::now_utc() - time::Duration::hours(3);
+        let session_id = db::create_session(&pool, crate::models::SessionType::Stream, None, Some(started_at))
+            .await.expect("session");
+        let raid_id = db::create_raid(&pool, session_id, "Customs",
+            crate::models::CharacterType::PMC, crate::models::GameMode::PVE,
+            Some(started_at + time::Duration::hours(1))).await.expect("raid");
+        db::end_raid(&pool, raid_id, Some(started_at + time::Duration::hours(2)), None).await.expect("end raid");
+        let app = api_router().with_state(AppState::new(pool));
+
+        let (status, json) = send(app, "/api/session/end", &format!(
+            r#"{{"ended_at": "{}"}}"#, format_timestamp(started_at + time::Duration::minutes(90))
+        )).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(json["error"].as_str().unwrap().contains("last raid"));
+    }
+
+    #[tokio::test]
+    async fn test_session_cannot_start_inside_previous_one() {
+        let pool = setup_test_db().await.expect("setup db");
+        let started_at = time::OffsetDateTime::now_utc() - time::Duration::hours(3);
+        let session_id = db::create_session(&pool, crate::models::SessionType::Stream, None, Some(started_at))
+            .await.expect("session");
+        db::end_session(&pool, session_id, Some(started_at + time::Duration::hours(2))).await.expect("end");
+        let app = api_router().with_state(AppState::new(pool));
+
+        let (status, json) = send(app, "/api/session", &format!(
+            r#"{{"session_type": "stream", "started_at": "{}"}}"#, format_timestamp(started_at + time::Duration::hours(1))
+        )).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(json["error"].as_str().unwrap().contains("previous session ended"));
+    }
+
+    #[tokio::test]
+    async fn test_list_sessions_embeds_counts() {
+        let pool = setup_test_db().await.expect("setup db");
+        db::tests::seed_history(&pool).await.expect("seed");
+        let app = api_router().with_state(AppState::new(pool));
+
+        let response = app.clone()
+            .oneshot(Request::get("/api/sessions?sort=raids&order=desc").body(Body::empty()).unwrap())
+            .await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let items = json["items"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["session_type"], "stream");
+        assert_eq!(items[0]["raid_count"], 3);
+        assert_eq!(items[0]["kill_count"], 3);
+        assert!(items[0]["duration_seconds"].is_null());
+        assert!(json["next_cursor"].is_null());
+
+        let response = app
+            .oneshot(Request::get("/api/sessions?from=2026-02-13T12:00:00Z&to=2026-02-12T12:00:00Z")
+                .body(Body::empty()).unwrap())
+            .await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
 }
@@ -0,0 +1,693 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use crate::api::dto::{GroupedStatsQuery, StatsBreakdownQuery, StatsFilterQuery, StatsQuery, TrendQuery, WaitTimesQuery};
+use crate::api::{error::AppError, state::AppState};
+use crate::db;
+use crate::models::RaidUnit;
+use crate::stats::{
+    self, BetweenRaidsTime, FirstRaidDelay, GroupedStats, MapStats, ModeStats, SessionComparison, SessionStats,
+    GroupedWaitTimes, Record, SessionTimeBreakdown, StateTime, Streaks, Trend, WaitComparison, WaitTimes,
+    WeaponBreakdown,
+};
+
+// Session routes take either a numeric id or "current" for the active session
+pub async fn resolve_session_id(state: &AppState, key: &str) -> Result<i64, AppError> {
+    let session = if key == "current" {
+        db::get_active_session(&state.pool)
+            .await.map_err(AppError::DatabaseError)?
+            .ok_or_else(|| AppError::NotFound("No active session".into()))?
+    } else {
+        let session_id: i64 = key.parse()
+            .map_err(|_| AppError::BadRequest(format!("Invalid session id '{}'", key)))?;
+
+        db::get_session_by_id(&state.pool, session_id)
+            .await.map_err(AppError::DatabaseError)?
+            .ok_or_else(|| AppError::NotFound(format!("Session {} not found", session_id)))?
+    };
+
+    Ok(session.session_id)
+}
+
+pub async fn get_session_stats(
+    State(state): State<AppState>,
+    Path(session): Path<String>,
+    Query(query): Query<StatsFilterQuery>,
+) -> Result<Json<SessionStats>, AppError> {
+    let session_id = resolve_session_id(&state, &session).await?;
+
+    let filter = db::StatsFilter {
+        session_ids: vec![session_id],
+        game_mode: query.game_mode,
+        character_type: query.character_type,
+        unit: query.unit.unwrap_or_default(),
+        ..Default::default()
+    };
+    let stats = stats::calculate_stats(&state.pool, &filter)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(stats))
+}
+
+pub async fn get_session_comparison(
+    State(state): State<AppState>,
+    Path(session): Path<String>,
+    Query(query): Query<StatsQuery>,
+) -> Result<Json<SessionComparison>, AppError> {
+    let session_id = resolve_session_id(&state, &session).await?;
+
+    let comparison = stats::compare_session_to_mode_global(&state.pool, session_id, query.game_mode)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(comparison))
+}
+
+pub async fn get_session_mode_stats(
+    State(state): State<AppState>,
+    Path(session): Path<String>,
+) -> Result<Json<ModeStats>, AppError> {
+    let session_id = resolve_session_id(&state, &session).await?;
+
+    let modes = stats::get_mode_stats_for_session(&state.pool, session_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(modes))
+}
+
+pub async fn get_session_map_stats(
+    State(state): State<AppState>,
+    Path(session): Path<String>,
+    Query(query): Query<StatsFilterQuery>,
+) -> Result<Json<Vec<MapStats>>, AppError> {
+    let session_id = resolve_session_id(&state, &session).await?;
+
+    let filter = db::StatsFilter {
+        session_ids: vec![session_id],
+        game_mode: query.game_mode,
+        character_type: query.character_type,
+        unit: query.unit.unwrap_or_default(),
+        ..Default::default()
+    };
+    let maps = stats::calculate_map_stats(&state.pool, &filter)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(maps))
+}
+
+pub async fn get_session_weapon_stats(
+    State(state): State<AppState>,
+    Path(session): Path<String>,
+    Query(query): Query<StatsFilterQuery>,
+) -> Result<Json<WeaponBreakdown>, AppError> {
+    let session_id = resolve_session_id(&state, &session).await?;
+
+    let filter = db::StatsFilter {
+        session_ids: vec![session_id],
+        game_mode: query.game_mode,
+        character_type: query.character_type,
+        unit: query.unit.unwrap_or_default(),
+        ..Default::default()
+    };
+    let weapons = stats::calculate_weapon_stats(&state.pool, &filter)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(weapons))
+}
+
+pub async fn get_session_gaps(
+    State(state): State<AppState>,
+    Path(session): Path<String>,
+) -> Result<Json<BetweenRaidsTime>, AppError> {
+    let session_id = resolve_session_id(&state, &session).await?;
+
+    let gaps = stats::calculate_time_between_raids_for_session(&state.pool, session_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(gaps))
+}
+
+pub async fn get_session_time_breakdown(
+    State(state): State<AppState>,
+    Path(session): Path<String>,
+) -> Result<Json<SessionTimeBreakdown>, AppError> {
+    let session_id = resolve_session_id(&state, &session).await?;
+
+    let breakdown = stats::calculate_session_time_breakdown(&state.pool, session_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(breakdown))
+}
+
+pub async fn get_session_wait_times(
+    State(state): State<AppState>,
+    Path(session): Path<String>,
+    Query(query): Query<WaitTimesQuery>,
+) -> Result<Json<Vec<GroupedWaitTimes>>, AppError> {
+    let session_id = resolve_session_id(&state, &session).await?;
+
+    let filter = db::StatsFilter {
+        session_ids: vec![session_id],
+        game_mode: query.game_mode,
+        character_type: None,
+        unit: RaidUnit::Segments,
+        ..Default::default()
+    };
+    let waits = stats::calculate_wait_times(&state.pool, &filter, query.by)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(waits))
+}
+
+pub async fn get_session_wait_comparison(
+    State(state): State<AppState>,
+    Path(session): Path<String>,
+    Query(query): Query<StatsQuery>,
+) -> Result<Json<WaitComparison>, AppError> {
+    let session_id = resolve_session_id(&state, &session).await?;
+
+    let comparison = stats::compare_session_wait_times(&state.pool, session_id, query.game_mode)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(comparison))
+}
+
+pub async fn get_all_time_stats(
+    State(state): State<AppState>,
+    Query(query): Query<StatsFilterQuery>,
+) -> Result<Json<SessionStats>, AppError> {
+    let filter = db::StatsFilter {
+        game_mode: query.game_mode,
+        character_type: query.character_type,
+        unit: query.unit.unwrap_or_default(),
+        ..Default::default()
+    };
+    let stats = stats::calculate_stats(&state.pool, &filter)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(stats))
+}
+
+// Any filter, split by any mix of groups
+pub async fn get_stats_breakdown(
+    State(state): State<AppState>,
+    Query(query): Query<StatsBreakdownQuery>,
+) -> Result<Json<Vec<GroupedStats>>, AppError> {
+    let filter = query.filter()?;
+    let groups = query.groups()?;
+
+    let stats = stats::calculate_stats_by(&state.pool, &filter, &groups)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(stats))
+}
+
+pub async fn get_all_time_grouped_stats(
+    State(state): State<AppState>,
+    Query(query): Query<GroupedStatsQuery>,
+) -> Result<Json<Vec<GroupedStats>>, AppError> {
+    let groups = stats::calculate_grouped_stats(&state.pool, query.by, query.game_mode)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(groups))
+}
+
+pub async fn get_all_time_map_stats(
+    State(state): State<AppState>,
+    Query(query): Query<StatsFilterQuery>,
+) -> Result<Json<Vec<MapStats>>, AppError> {
+    let filter = db::StatsFilter {
+        game_mode: query.game_mode,
+        character_type: query.character_type,
+        unit: query.unit.unwrap_or_default(),
+        ..Default::default()
+    };
+    let maps = stats::calculate_map_stats(&state.pool, &filter)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(maps))
+}
+
+pub async fn get_all_time_weapon_stats(
+    State(state): State<AppState>,
+    Query(query): Query<StatsFilterQuery>,
+) -> Result<Json<WeaponBreakdown>, AppError> {
+    let filter = db::StatsFilter {
+        game_mode: query.game_mode,
+        character_type: query.character_type,
+        unit: query.unit.unwrap_or_default(),
+        ..Default::default()
+    };
+    let weapons = stats::calculate_weapon_stats(&state.pool, &filter)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(weapons))
+}
+
+pub async fn get_all_time_gaps(
+    State(state): State<AppState>,
+) -> Result<Json<BetweenRaidsTime>, AppError> {
+    let gaps = stats::calculate_time_between_raids_global(&state.pool)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(gaps))
+}
+
+pub async fn get_all_time_wait_times(
+    State(state): State<AppState>,
+    Query(query): Query<WaitTimesQuery>,
+) -> Result<Json<Vec<GroupedWaitTimes>>, AppError> {
+    let filter = db::StatsFilter {
+        game_mode: query.game_mode,
+        character_type: None,
+        unit: RaidUnit::Segments,
+        ..Default::default()
+    };
+    let waits = stats::calculate_wait_times(&state.pool, &filter, query.by)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(waits))
+}
+
+pub async fn get_streaks(
+    State(state): State<AppState>,
+    Query(query): Query<StatsFilterQuery>,
+) -> Result<Json<Streaks>, AppError> {
+    let filter = db::StatsFilter {
+        game_mode: query.game_mode,
+        character_type: query.character_type,
+        unit: query.unit.unwrap_or_default(),
+        ..Default::default()
+    };
+    let streaks = stats::calculate_streaks(&state.pool, &filter)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(streaks))
+}
+
+pub async fn get_records(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Record>>, AppError> {
+    let records = stats::calculate_records(&state.pool)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(records))
+}
+
+async fn load_trend(state: &AppState, query: &TrendQuery) -> Result<Trend, AppError> {
+    let (count, window) = query.sizes()?;
+    let filter = db::StatsFilter {
+        game_mode: query.game_mode.clone(),
+        character_type: query.character_type.clone(),
+        unit: query.unit.unwrap_or_default(),
+        ..Default::default()
+    };
+
+    stats::calculate_trend(&state.pool, &filter, query.over.unwrap_or_default(), count, window)
+        .await.map_err(AppError::DatabaseError)
+}
+
+pub async fn get_trend(
+    State(state): State<AppState>,
+    Query(query): Query<TrendQuery>,
+) -> Result<Json<Trend>, AppError> {
+    Ok(Json(load_trend(&state, &query).await?))
+}
+
+pub async fn get_trend_sparkline(
+    State(state): State<AppState>,
+    Query(query): Query<TrendQuery>,
+) -> Result<Json<Vec<f64>>, AppError> {
+    let trend = load_trend(&state, &query).await?;
+
+    Ok(Json(trend.sparkline(query.metric.unwrap_or_default(), query.rolling)))
+}
+
+pub async fn get_first_raid_delay(
+    State(state): State<AppState>,
+) -> Result<Json<FirstRaidDelay>, AppError> {
+    let delay = stats::calculate_time_before_first_raid(&state.pool)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(delay))
+}
+
+pub async fn get_raid_state_breakdown(
+    State(state): State<AppState>,
+    Path(raid_id): Path<i64>,
+) -> Result<Json<Vec<StateTime>>, AppError> {
+    db::get_raid_by_id(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Raid {} not found", raid_id)))?;
+
+    let mut states = stats::calculate_time_in_state(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    // Longest first, the HashMap behind it has no stable order
+    states.sort_by(|a, b| b.duration.cmp(&a.duration).then_with(|| a.state.cmp(&b.state)));
+
+    Ok(Json(states))
+}
+
+pub async fn get_raid_wait_times(
+    State(state): State<AppState>,
+    Path(raid_id): Path<i64>,
+) -> Result<Json<WaitTimes>, AppError> {
+    db::get_raid_by_id(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Raid {} not found", raid_id)))?;
+
+    let waits = stats::calculate_raid_wait_times(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(waits))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use http::StatusCode;
+    use time::OffsetDateTime;
+    use tower::ServiceExt;
+    use crate::api::{state::AppState, routes::api_router};
+    use crate::db::{self, tests::setup_test_db};
+    use crate::models::{CharacterType, GameMode, SessionType};
+
+    async fn get_json(pool: &sqlx::SqlitePool, uri: &str) -> (StatusCode, serde_json::Value) {
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await.unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_stats_breakdown_query() {
+        let pool = setup_test_db().await.expect("setup db");
+        let ids = db::tests::seed_history(&pool).await.unwrap();
+        let practice = db::get_raid_by_id(&pool, ids[3]).await.unwrap().unwrap().session_id;
+
+        let (status, json) = get_json(&pool, "/api/stats").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["total_raids"], 5);
+        assert!(json[0].get("game_mode").is_none());
+
+        let (status, json) = get_json(&pool, "/api/stats?by=session_type,game_mode&map=customs").await;
+        assert_eq!(status, StatusCode::OK, "{}", json);
+        let rows = json.as_array().unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["session_type"], "practice");
+        assert_eq!(rows[0]["game_mode"], "pvp");
+        assert_eq!(rows[0]["total_kills"], 3);
+        assert!(rows[0].get("map_name").is_none());
+
+        let (_, json) = get_json(&pool, &format!("/api/stats?by=map&sessions={}&game_mode=pve", practice)).await;
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["map_name"], "Shoreline");
+
+        let (status, _) = get_json(&pool, "/api/stats?by=map,moon_phase").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = get_json(&pool, "/api/stats?sessions=1,two").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_json(&pool, "/api/stats?from=2026-02-01T00:00:00Z&to=2026-01-01T00:00:00Z").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    // One session with a survived PVE raid (2 kills) and a died PVP raid
+    async fn setup_session(pool: &sqlx::SqlitePool) -> (i64, i64) {
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(2);
+        let session_id = db::create_session(pool, SessionType::Stream, None, Some(base)).await.unwrap();
+
+        let r1 = db::create_raid(pool, session_id, "Customs", CharacterType::PMC, GameMode::PVE,
+            Some(base + time::Duration::minutes(10))).await.unwrap();
+        db::log_forced_transition(pool, r1, "queuing", Some(base + time::Duration::minutes(10))).await.unwrap();
+        db::log_forced_transition(pool, r1, "raid_active", Some(base + time::Duration::minutes(13))).await.unwrap();
+        db::log_state_transition(pool, r1, "survived", Some(base + time::Duration::minutes(40))).await.unwrap();
+        db::add_kill(pool, r1, "scav", None, None, Some(base + time::Duration::minutes(20))).await.unwrap();
+        db::add_kill(pool, r1, "pmc", None, None, Some(base + time::Duration::minutes(30))).await.unwrap();
+        db::end_raid(pool, r1, Some(base + time::Duration::minutes(40)), None).await.unwrap();
+
+        let r2 = db::create_raid(pool, session_id, "Woods", CharacterType::PMC, GameMode::PVP,
+            Some(base + time::Duration::minutes(50))).await.unwrap();
+        db::log_forced_transition(pool, r2, "died", Some(base + time::Duration::minutes(70))).await.unwrap();
+        db::end_raid(pool, r2, Some(base + time::Duration::minutes(70)), None).await.unwrap();
+
+        (session_id, r1)
+    }
+
+    #[tokio::test]
+    async fn test_current_session_stats() {
+        let pool = setup_test_db().await.expect("setup db");
+        setup_session(&pool).await;
+
+        let (status, json) = get_json(&pool, "/api/stats/session/current").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["total_raids"], 2);
+        assert_eq!(json["survived_raids"], 1);
+        assert_eq!(json["total_kills"], 2);
+        assert_eq!(json["avg_raid_duration"]["seconds"], 25 * 60);
+        assert_eq!(json["avg_raid_duration"]["human"], "25m 0s");
+    }
+
+    #[tokio::test]
+    async fn test_session_stats_by_id_not_found() {
+        let pool = setup_test_db().await.expect("setup db");
+
+        let (status, _) = get_json(&pool, "/api/stats/session/42").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get_json(&pool, "/api/stats/session/current").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get_json(&pool, "/api/stats/session/abc").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_session_modes_and_compare() {
+        let pool = setup_test_db().await.expect("setup db");
+        let (session_id, _) = setup_session(&pool).await;
+
+        let (status, json) = get_json(&pool, &format!("/api/stats/session/{}/modes", session_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["pve"]["total_raids"], 1);
+        assert_eq!(json["pvp"]["survived_raids"], 0);
+
+        let (status, json) = get_json(&pool, "/api/stats/session/current/compare?game_mode=pvp").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["current"]["total_raids"], 2);
+        assert_eq!(json["all_time"]["total_raids"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_all_time_grouped_stats() {
+        let pool = setup_test_db().await.expect("setup db");
+        setup_session(&pool).await;
+
+        let (status, json) = get_json(&pool, "/api/stats/all-time/grouped?by=game_mode").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json[0]["game_mode"], "pve");
+        assert_eq!(json[0]["total_kills"], 2);
+        assert_eq!(json[1]["game_mode"], "pvp");
+        assert_eq!(json[1]["survived_raids"], 0);
+        assert!(json[1].get("map_name").is_none());
+
+        let (_, json) = get_json(&pool, "/api/stats/all-time/grouped?by=map&game_mode=pvp").await;
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["map_name"], "Woods");
+    }
+
+    #[tokio::test]
+    async fn test_map_stats_endpoints() {
+        let pool = setup_test_db().await.expect("setup db");
+        setup_session(&pool).await;
+
+        let (status, json) = get_json(&pool, "/api/stats/session/current/maps").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(json[0]["map_name"], "Customs");
+        assert_eq!(json[0]["avg_queue_time"]["seconds"], 180);
+        assert_eq!(json[0]["kd_ratio"], 2.0);
+
+        let (status, json) = get_json(&pool, "/api/stats/all-time/maps?game_mode=pvp&character_type=pmc").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["map_name"], "Woods");
+        assert!(json[0]["most_used_extract"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_weapon_stats_endpoints() {
+        let pool = setup_test_db().await.expect("setup db");
+        let (_, r1) = setup_session(&pool).await;
+        let kill = db::get_kills_for_raid(&pool, r1).await.unwrap()[0].clone();
+        db::add_kill(&pool, r1, "pmc", Some("mp5".into()), Some(true), Some(kill.killed_at)).await.unwrap();
+        db::add_kill(&pool, r1, "scav", Some("MP5".into()), Some(false), Some(kill.killed_at)).await.unwrap();
+
+        let (status, json) = get_json(&pool, "/api/stats/session/current/weapons").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["top_weapon"], "MP5");
+        assert_eq!(json["weapons"][0]["kills"], 2);
+        assert_eq!(json["weapons"][0]["headshot_rate"], 0.5);
+        assert_eq!(json["weapons"][0]["enemy_types"]["pmc"], 1);
+        assert_eq!(json["total_kills"], 4);
+
+        let (status, json) = get_json(&pool, "/api/stats/all-time/weapons?game_mode=pvp").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(json["top_weapon"].is_null());
+        assert_eq!(json["weapons"].as_array().unwrap().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_session_time_breakdown() {
+        let pool = setup_test_db().await.expect("setup db");
+        let (session_id, _) = setup_session(&pool).await;
+        let started_at = db::get_session_by_id(&pool, session_id).await.unwrap().unwrap().started_at;
+        db::end_session(&pool, session_id, Some(started_at + time::Duration::minutes(80))).await.unwrap();
+
+        let (status, json) = get_json(&pool, &format!("/api/stats/session/{}/time", session_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        // 3m queuing, 27m raid_active, 10m between raids and 10m after the last one.
+        // r2 only logged its death.
+        assert_eq!(json["total_time"]["seconds"], 50 * 60);
+        assert_eq!(json["raid_active_share"], 0.54);
+        assert_eq!(json["states"][0]["state"], "raid_active");
+        assert_eq!(json["before_first_raid"]["human"], "10m 0s");
+        assert_eq!(json["categories"]["queue"], 0.06);
+
+        let (status, _) = get_json(&pool, "/api/stats/session/42/time").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_wait_time_endpoints() {
+        let pool = setup_test_db().await.expect("setup db");
+        let (session_id, r1) = setup_session(&pool).await;
+
+        let (status, json) = get_json(&pool, &format!("/api/stats/raid/{}/waits", r1)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["avg_queue"]["seconds"], 180);
+        assert_eq!(json["avg_total"]["human"], "3m 0s");
+
+        let (status, json) = get_json(&pool, "/api/stats/session/current/waits?by=game_mode").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        assert_eq!(json[0]["game_mode"], "pve");
+        assert_eq!(json[0]["raid_count"], 1);
+
+        let (status, json) = get_json(&pool, "/api/stats/all-time/waits").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(json[0].get("map_name").is_none());
+        assert_eq!(json[0]["avg_queue"]["seconds"], 180);
+
+        let (status, json) = get_json(&pool, &format!("/api/stats/session/{}/waits/compare", session_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["queue_change"], 0.0);
+        assert_eq!(json["maps"][0]["map_name"], "Customs");
+
+        let (status, _) = get_json(&pool, "/api/stats/raid/999/waits").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_streaks_and_records() {
+        let pool = setup_test_db().await.expect("setup db");
+        let (session_id, r1) = setup_session(&pool).await;
+
+        let (status, json) = get_json(&pool, "/api/stats/streaks").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["survival"]["current"]["length"], 0);
+        assert_eq!(json["survival"]["longest"]["first_raid_id"], r1);
+        assert_eq!(json["death"]["current"]["length"], 1);
+        assert_eq!(json["kill"]["longest"]["length"], 2);
+
+        let (status, json) = get_json(&pool, "/api/stats/records").await;
+        assert_eq!(status, StatusCode::OK);
+        // Two raids is too few for a session survival rate record
+        assert_eq!(json.as_array().unwrap().len(), 4);
+        assert_eq!(json[0]["kind"], "most_kills");
+        assert_eq!(json[0]["raid_id"], r1);
+        assert_eq!(json[0]["display"], "2 kills");
+        assert_eq!(json[3]["kind"], "most_raids_in_session");
+        assert_eq!(json[3]["session_id"], session_id);
+        assert!(json[3].get("raid_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_trend_and_sparkline() {
+        let pool = setup_test_db().await.expect("setup db");
+        let (session_id, r1) = setup_session(&pool).await;
+
+        let (status, json) = get_json(&pool, "/api/stats/trend").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["over"], "sessions");
+        assert_eq!(json["window"], 3);
+        assert_eq!(json["points"][0]["id"], session_id);
+        assert_eq!(json["points"][0]["total_raids"], 2);
+        assert_eq!(json["points"][0]["rolling"]["survival_rate"], 0.5);
+
+        let (status, json) = get_json(&pool, "/api/stats/trend?over=raids&window=1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["points"][0]["id"], r1);
+        assert_eq!(json["points"][0]["total_kills"], 2);
+
+        let (status, json) = get_json(&pool, "/api/stats/trend/sparkline?over=raids&metric=kd_ratio").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json, serde_json::json!([2.0, 0.0]));
+
+        let (status, json) = get_json(&pool, "/api/stats/trend/sparkline?over=raids&window=2&rolling=true").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json, serde_json::json!([1.0, 0.5]));
+
+        let (status, _) = get_json(&pool, "/api/stats/trend?count=0").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_all_time_stats_and_gaps() {
+        let pool = setup_test_db().await.expect("setup db");
+        setup_session(&pool).await;
+
+        let (status, json) = get_json(&pool, "/api/stats/all-time?game_mode=pve").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["total_raids"], 1);
+        assert_eq!(json["survival_rate"], 1.0);
+
+        let (status, json) = get_json(&pool, "/api/stats/all-time/gaps").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["gap_count"], 1);
+        assert_eq!(json["avg_gap"]["seconds"], 600);
+
+        let (status, json) = get_json(&pool, "/api/stats/session/current/gaps").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["total_gap"]["human"], "10m 0s");
+
+        let (status, json) = get_json(&pool, "/api/stats/first-raid-delay").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["sessions"], 1);
+        assert_eq!(json["duration"]["seconds"], 600);
+    }
+
+    #[tokio::test]
+    async fn test_raid_state_breakdown() {
+        let pool = setup_test_db().await.expect("setup db");
+        let (_, raid_id) = setup_session(&pool).await;
+
+        let (status, json) = get_json(&pool, &format!("/api/stats/raid/{}/states", raid_id)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let states = json.as_array().unwrap();
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[0]["state"], "raid_active");
+        assert_eq!(states[0]["duration"]["seconds"], 27 * 60);
+        assert_eq!(states[1]["state"], "queuing");
+
+        let (status, _) = get_json(&pool, "/api/stats/raid/999/states").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,300 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use crate::api::dto::{
+    DeleteTransitionQuery, EditQuery, TransitionResponse, UpdateTransitionRequest, check_not_after,
+    check_not_before, check_not_future, parse_timestamp,
+};
+use crate::api::handlers::audit;
+use crate::api::{error::AppError, state::AppState};
+use crate::db;
+use crate::events::LiveEvent;
+use crate::history::Action;
+use crate::models::{Raid, RaidState, RaidStateTransition};
+
+async fn find_raid(state: &AppState, raid_id: i64) -> Result<Raid, AppError> {
+    db::get_raid_by_id(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Raid {} not found", raid_id)))
+}
+
+async fn find_transition(state: &AppState, transition_id: i64) -> Result<RaidStateTransition, AppError> {
+    db::get_transition_by_id(&state.pool, transition_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Transition {} not found", transition_id)))
+}
+
+// Same order the chain is rebuilt in
+fn sort_chain(transitions: &mut [RaidStateTransition]) {
+    transitions.sort_by(|a, b| a.transitioned_at.cmp(&b.transitioned_at)
+        .then(a.transition_id.cmp(&b.transition_id)));
+}
+
+// Only the moves into and out of position `at` are checked, so an older forced
+// transition elsewhere in the raid doesn't block unrelated corrections
+fn check_links(raid: &Raid, transitions: &[RaidStateTransition], at: usize) -> Result<(), AppError> {
+    let state_at = |i: usize| transitions.get(i).map(|t| t.to_state.clone());
+    let before = match at {
+        0 => Some(raid.initial_state()),
+        _ => state_at(at - 1),
+    };
+
+    if let (Some(from), Some(to)) = (&before, state_at(at)) {
+        from.check_transition(&to)?;
+    }
+    if let (Some(from), Some(to)) = (state_at(at), state_at(at + 1)) {
+        from.check_transition(&to)?;
+    }
+    Ok(())
+}
+
+async fn publish_correction(state: &AppState, raid_id: i64) -> Result<(), AppError> {
+    let raid = db::get_raid_by_id(&state.pool, raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+    state.events.publish(LiveEvent::RaidCorrected { raid_id, raid_state: raid.map(|r| r.state()) });
+    Ok(())
+}
+
+pub async fn get_transitions(
+    State(state): State<AppState>,
+    Path(raid_id): Path<i64>,
+) -> Result<Json<Vec<TransitionResponse>>, AppError> {
+    let raid = find_raid(&state, raid_id).await?;
+
+    let transitions = db::get_raid_transitions(&state.pool, raid.raid_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    Ok(Json(transitions.iter().map(TransitionResponse::from).collect()))
+}
+
+pub async fn update_transition(
+    State(state): State<AppState>,
+    Path(transition_id): Path<i64>,
+    Query(query): Query<EditQuery>,
+    Json(req): Json<UpdateTransitionRequest>,
+) -> Result<Json<TransitionResponse>, AppError> {
+    let before = find_transition(&state, transition_id).await?;
+    let raid = find_raid(&state, before.raid_id).await?;
+    let mut edited = before.clone();
+
+    if let Some(to_state) = req.to_state {
+        edited.to_state = RaidState::parse(&to_state);
+    }
+
+    if let Some(ts) = parse_timestamp("transitioned_at", req.transitioned_at.as_deref())? {
+        check_not_future("transitioned_at", ts)?;
+        check_not_before("transitioned_at", ts, raid.started_at, "the raid started")?;
+        if let Some(ended_at) = raid.ended_at {
+            check_not_after("transitioned_at", ts, ended_at, "the raid ended")?;
+        }
+        edited.transitioned_at = ts;
+    }
+
+    if !req.force {
+        let mut chain = db::get_raid_transitions(&state.pool, raid.raid_id)
+            .await.map_err(AppError::DatabaseError)?;
+        for t in chain.iter_mut().filter(|t| t.transition_id == transition_id) {
+            *t = edited.clone();
+        }
+        sort_chain(&mut chain);
+
+        let at = chain.iter().position(|t| t.transition_id == transition_id).unwrap_or_default();
+        check_links(&raid, &chain, at)?;
+    }
+
+    let mut tx = state.pool.begin().await.map_err(AppError::DatabaseError)?;
+    db::update_transition(&mut *tx, &edited)
+        .await.map_err(AppError::DatabaseError)?;
+
+    // from_state may have been rebuilt, read back what was stored
+    let after = db::get_transition_by_id(&mut *tx, transition_id)
+        .await.map_err(AppError::DatabaseError)?
+        .ok_or_else(|| AppError::NotFound(format!("Transition {} not found", transition_id)))?;
+    audit::record(&mut tx, &query, "raid_state_transitions", transition_id,
+        &TransitionResponse::from(&before), Some(&TransitionResponse::from(&after))).await?;
+    tx.commit().await.map_err(AppError::DatabaseError)?;
+    state.history.record(raid.session_id, Action::TransitionUpdated { before, after: after.clone() }).await;
+    publish_correction(&state, raid.raid_id).await?;
+
+    Ok(Json(TransitionResponse::from(&after)))
+}
+
+pub async fn delete_transition(
+    State(state): State<AppState>,
+    Path(transition_id): Path<i64>,
+    Query(query): Query<DeleteTransitionQuery>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let transition = find_transition(&state, transition_id).await?;
+    let raid = find_raid(&state, transition.raid_id).await?;
+
+    if !query.force {
+        let mut chain = db::get_raid_transitions(&state.pool, raid.raid_id)
+            .await.map_err(AppError::DatabaseError)?;
+        sort_chain(&mut chain);
+
+        let at = chain.iter().position(|t| t.transition_id == transition_id).unwrap_or_default();
+        chain.remove(at);
+        check_links(&raid, &chain, at)?;
+    }
+
+    let mut tx = state.pool.begin().await.map_err(AppError::DatabaseError)?;
+    db::delete_transition(&mut *tx, transition_id)
+        .await.map_err(AppError::DatabaseError)?;
+
+    let edit = EditQuery { source: query.source };
+    audit::record(&mut tx, &edit, "raid_state_transitions", transition_id,
+        &TransitionResponse::from(&transition), None).await?;
+    tx.commit().await.map_err(AppError::DatabaseError)?;
+    state.history.record(raid.session_id, Action::TransitionDeleted(transition)).await;
+    publish_correction(&state, raid.raid_id).await?;
+
+    let raid = find_raid(&state, raid.raid_id).await?;
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "transition_id": transition_id,
+        "raid_id": raid.raid_id,
+        "current_state": raid.current_state,
+        "message": "transition deleted"
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use http::StatusCode;
+    use time::{Duration, OffsetDateTime};
+    use tower::ServiceExt;
+    use crate::api::{state::AppState, routes::api_router};
+    use crate::api::dto::format_timestamp;
+    use crate::db::{self, tests::setup_test_db};
+    use crate::models::{CharacterType, GameMode, SessionType};
+
+    async fn send(app: axum::Router, method: &str, uri: &str, body: &str) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if !body.is_empty() {
+            request = request.header("content-type", "application/json");
+        }
+        let response = app
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await.unwrap();
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    // A raid an hour ago that went stash -> pre_raid_setup -> queuing -> deploying_committed, one minute apart
+    async fn setup_raid(pool: &sqlx::SqlitePool) -> (i64, Vec<i64>, OffsetDateTime) {
+        let start = OffsetDateTime::now_utc() - Duration::hours(1);
+        let session_id = db::create_session(pool, SessionType::Stream, None, Some(start)).await.unwrap();
+        let raid_id = db::create_raid(pool, session_id, "Customs", CharacterType::PMC, GameMode::PVE, Some(start))
+            .await.unwrap();
+
+        let mut ids = Vec::new();
+        for (i, state) in ["pre_raid_setup", "queuing", "deploying_committed"].iter().enumerate() {
+            let ts = start + Duration::minutes(i as i64 + 1);
+            ids.push(db::log_state_transition(pool, raid_id, state, Some(ts)).await.unwrap());
+        }
+        (raid_id, ids, start)
+    }
+
+    #[tokio::test]
+    async fn test_delete_transition_rebuilds_chain() {
+        let pool = setup_test_db().await.unwrap();
+        let (raid_id, ids, _) = setup_raid(&pool).await;
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        // Without queuing the raid would jump pre_raid_setup -> deploying_committed
+        let (status, _) = send(app.clone(), "DELETE", &format!("/api/transitions/{}", ids[1]), "").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, json) = send(app.clone(), "DELETE", &format!("/api/transitions/{}?force=true", ids[1]), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["current_state"], "deploying_committed");
+
+        let transitions = db::get_raid_transitions(&pool, raid_id).await.unwrap();
+        assert_eq!(transitions.len(), 2);
+        assert_eq!(transitions[1].from_state, Some("pre_raid_setup".into()));
+
+        // Removing the last one rolls current_state back
+        let (_, json) = send(app.clone(), "DELETE", &format!("/api/transitions/{}?source=dashboard", ids[2]), "").await;
+        assert_eq!(json["current_state"], "pre_raid_setup");
+
+        let (_, json) = send(app, "GET", &format!("/api/audit?table=raid_state_transitions&row_id={}", ids[2]), "").await;
+        let entries = json.as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["action"], "delete");
+        assert_eq!(entries[0]["source"], "dashboard");
+        assert_eq!(entries[0]["before"]["to_state"], "deploying_committed");
+        assert!(entries[0]["after"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_edit_transition_state_and_time() {
+        let pool = setup_test_db().await.unwrap();
+        let (raid_id, ids, start) = setup_raid(&pool).await;
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        // queuing -> deploying_cancellable keeps the chain legal
+        let (status, json) = send(app.clone(), "PATCH", &format!("/api/transitions/{}", ids[1]),
+            r#"{"to_state": "deploying_cancellable"}"#).await;
+        assert_eq!(status, StatusCode::OK, "{}", json);
+        let transitions = db::get_raid_transitions(&pool, raid_id).await.unwrap();
+        assert_eq!(transitions[2].from_state, Some("deploying_cancellable".into()));
+
+        // Moving the last transition before the first reorders the chain, which is illegal...
+        let early = format_timestamp(start + Duration::seconds(30));
+        let body = format!(r#"{{"transitioned_at": "{}"}}"#, early);
+        let (status, _) = send(app.clone(), "PATCH", &format!("/api/transitions/{}", ids[2]), &body).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        // ...unless forced
+        let body = format!(r#"{{"transitioned_at": "{}", "force": true}}"#, early);
+        let (status, _) = send(app.clone(), "PATCH", &format!("/api/transitions/{}", ids[2]), &body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let raid = db::get_raid_by_id(&pool, raid_id).await.unwrap().unwrap();
+        assert_eq!(raid.current_state, "deploying_cancellable");
+        let transitions = db::get_raid_transitions(&pool, raid_id).await.unwrap();
+        assert_eq!(transitions[0].to_state, "deploying_committed");
+        assert_eq!(transitions[0].from_state, Some("stash_management".into()));
+        assert_eq!(transitions[1].from_state, Some("deploying_committed".into()));
+
+        // Before the raid started
+        let body = format!(r#"{{"transitioned_at": "{}"}}"#, format_timestamp(start - Duration::minutes(1)));
+        let (status, _) = send(app, "PATCH", &format!("/api/transitions/{}", ids[0]), &body).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_segment_transitions_start_from_transfer() {
+        let pool = setup_test_db().await.unwrap();
+        let (raid_id, _, start) = setup_raid(&pool).await;
+        db::log_state_transition(&pool, raid_id, "raid_active", Some(start + Duration::minutes(4))).await.unwrap();
+        let raid = db::get_raid_by_id(&pool, raid_id).await.unwrap().unwrap();
+        let transfer = db::transfer_raid(&pool, &raid, "Woods", start + Duration::minutes(5)).await.unwrap();
+        let segment_id = transfer.segment.raid_id;
+        let app = api_router().with_state(AppState::new(pool.clone()));
+
+        // transfer -> deploying_committed is legal, no force needed
+        let body = format!(r#"{{"transitioned_at": "{}"}}"#, format_timestamp(start + Duration::minutes(6)));
+        let (status, json) = send(app.clone(), "PATCH",
+            &format!("/api/transitions/{}", transfer.segment_transition_id), &body).await;
+        assert_eq!(status, StatusCode::OK, "{}", json);
+        assert_eq!(json["from_state"], "transfer");
+
+        let (status, _) = send(app.clone(), "POST", "/api/undo", "").await;
+        assert_eq!(status, StatusCode::OK);
+        let transitions = db::get_raid_transitions(&pool, segment_id).await.unwrap();
+        assert_eq!(transitions[0].from_state, Some("transfer".into()));
+
+        // With its only transition gone the segment is back where it was created
+        let (status, json) = send(app, "DELETE",
+            &format!("/api/transitions/{}?force=true", transfer.segment_transition_id), "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["current_state"], "transfer");
+
+        let head = db::get_raid_transitions(&pool, raid_id).await.unwrap();
+        assert_eq!(head[0].from_state, Some("stash_management".into()));
+        assert_eq!(head.last().unwrap().to_state, "transfer");
+    }
+}
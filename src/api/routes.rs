@@ -1,24 +1,96 @@
 use axum::Router;
-use crate::{api::state::AppState, db::end_session};
+use crate::api::state::AppState;
 use crate::api::handlers::health::health_check;
 use tower_http::trace::TraceLayer;
-use crate::api::handlers::session::{create_session, get_current_session, end_current_session};
-use crate::api::handlers::raid::{create_raid};
+use crate::api::handlers::session::{create_session, get_current_session, end_current_session, list_sessions};
+use crate::api::handlers::raid::{
+    create_raid, get_current_raid, transition_raid, end_current_raid, transfer_raid, get_raid_chain, update_raid,
+    delete_raid, list_raids,
+};
+use crate::api::handlers::transition::{get_transitions, update_transition, delete_transition};
+use crate::api::handlers::kill::{add_kill, add_kills_batch_current, get_kills, update_kill, delete_kill};
+use crate::api::handlers::events::{sse_events, ws_events};
+use crate::api::handlers::import::import_session;
+use crate::api::handlers::detection::{get_detection_status, pause_detection, resume_detection};
+use crate::api::handlers::audit::get_audit_log;
+use crate::api::handlers::history::{undo, redo};
+use crate::api::handlers::review::{
+    list_pending_events, submit_event, submit_batch, edit_pending_event, approve_pending_event,
+    reject_pending_event, bulk_approve,
+};
+use crate::api::handlers::stats::{
+    get_stats_breakdown, get_session_stats, get_session_comparison, get_session_mode_stats, get_session_map_stats,
+    get_session_weapon_stats, get_session_gaps, get_session_time_breakdown, get_session_wait_times,
+    get_session_wait_comparison, get_all_time_stats, get_all_time_grouped_stats, get_all_time_map_stats,
+    get_all_time_weapon_stats, get_all_time_gaps, get_all_time_wait_times, get_streaks, get_records, get_trend,
+    get_trend_sparkline, get_first_raid_delay, get_raid_state_breakdown, get_raid_wait_times,
+};
 
 pub fn api_router() -> Router<AppState> {
     Router::new()
         .route("/health", axum::routing::get(health_check))
         .route("/api/session", axum::routing::post(create_session))
+        .route("/api/sessions", axum::routing::get(list_sessions))
         .route("/api/session/current", axum::routing::get(get_current_session))
         .route("/api/session/end", axum::routing::post(end_current_session))
         .route("/api/raid", axum::routing::post(create_raid))
+        .route("/api/raids", axum::routing::get(list_raids))
+        .route("/api/raid/current", axum::routing::get(get_current_raid))
+        .route("/api/raid/transition", axum::routing::post(transition_raid))
+        .route("/api/raid/end", axum::routing::post(end_current_raid))
+        .route("/api/raid/transfer", axum::routing::post(transfer_raid))
+        .route("/api/raid/current/kills/batch", axum::routing::post(add_kills_batch_current))
+        .route("/api/raid/{id}", axum::routing::patch(update_raid).delete(delete_raid))
+        .route("/api/raid/{id}/kills", axum::routing::post(add_kill).get(get_kills))
+        .route("/api/raid/{id}/transitions", axum::routing::get(get_transitions))
+        .route("/api/raid/{id}/chain", axum::routing::get(get_raid_chain))
+        .route("/api/kills/{kill_id}", axum::routing::patch(update_kill).delete(delete_kill))
+        .route("/api/transitions/{transition_id}", axum::routing::patch(update_transition).delete(delete_transition))
+        .route("/api/audit", axum::routing::get(get_audit_log))
+        .route("/api/undo", axum::routing::post(undo))
+        .route("/api/redo", axum::routing::post(redo))
+        .route("/api/events", axum::routing::get(sse_events))
+        .route("/api/events/ws", axum::routing::get(ws_events))
+        .route("/api/import/session", axum::routing::post(import_session))
+        .route("/api/detection", axum::routing::get(get_detection_status))
+        .route("/api/detection/pause", axum::routing::post(pause_detection))
+        .route("/api/detection/resume", axum::routing::post(resume_detection))
+        .route("/api/review", axum::routing::get(list_pending_events).post(submit_event))
+        .route("/api/review/batch", axum::routing::post(submit_batch))
+        .route("/api/review/approve", axum::routing::post(bulk_approve))
+        .route("/api/review/{id}", axum::routing::patch(edit_pending_event))
+        .route("/api/review/{id}/approve", axum::routing::post(approve_pending_event))
+        .route("/api/review/{id}/reject", axum::routing::post(reject_pending_event))
+        .route("/api/stats", axum::routing::get(get_stats_breakdown))
+        .route("/api/stats/session/{session}", axum::routing::get(get_session_stats))
+        .route("/api/stats/session/{session}/compare", axum::routing::get(get_session_comparison))
+        .route("/api/stats/session/{session}/modes", axum::routing::get(get_session_mode_stats))
+        .route("/api/stats/session/{session}/maps", axum::routing::get(get_session_map_stats))
+        .route("/api/stats/session/{session}/weapons", axum::routing::get(get_session_weapon_stats))
+        .route("/api/stats/session/{session}/gaps", axum::routing::get(get_session_gaps))
+        .route("/api/stats/session/{session}/time", axum::routing::get(get_session_time_breakdown))
+        .route("/api/stats/session/{session}/waits", axum::routing::get(get_session_wait_times))
+        .route("/api/stats/session/{session}/waits/compare", axum::routing::get(get_session_wait_comparison))
+        .route("/api/stats/all-time", axum::routing::get(get_all_time_stats))
+        .route("/api/stats/all-time/grouped", axum::routing::get(get_all_time_grouped_stats))
+        .route("/api/stats/all-time/maps", axum::routing::get(get_all_time_map_stats))
+        .route("/api/stats/all-time/weapons", axum::routing::get(get_all_time_weapon_stats))
+        .route("/api/stats/all-time/gaps", axum::routing::get(get_all_time_gaps))
+        .route("/api/stats/all-time/waits", axum::routing::get(get_all_time_wait_times))
+        .route("/api/stats/streaks", axum::routing::get(get_streaks))
+        .route("/api/stats/records", axum::routing::get(get_records))
+        .route("/api/stats/trend", axum::routing::get(get_trend))
+        .route("/api/stats/trend/sparkline", axum::routing::get(get_trend_sparkline))
+        .route("/api/stats/first-raid-delay", axum::routing::get(get_first_raid_delay))
+        .route("/api/stats/raid/{id}/states", axum::routing::get(get_raid_state_breakdown))
+        .route("/api/stats/raid/{id}/waits", axum::routing::get(get_raid_wait_times))
         .layer(TraceLayer::new_for_http())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{api, db::tests::setup_test_db};
+    use crate::db::tests::setup_test_db;
     use axum::{body::Body, http::Request};
     use tower::ServiceExt;
 
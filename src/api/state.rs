@@ -1,13 +1,24 @@
 use sqlx::SqlitePool;
+use crate::detection::DetectionControl;
+use crate::events::EventBus;
+use crate::history::History;
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: SqlitePool,
+    pub events: EventBus,
+    pub detection: DetectionControl,
+    pub history: History,
 }
 
 impl AppState {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            events: EventBus::new(),
+            detection: DetectionControl::default(),
+            history: History::default(),
+        }
     }
 }
 
@@ -0,0 +1,310 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tracing::{debug, info, warn};
+
+use super::commands::ChatCommands;
+use super::irc::{self, ChatMessage, IrcMessage};
+
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+    // host:port, Twitch's TLS endpoint is irc.chat.twitch.tv:6697
+    pub addr: String,
+    // The login carries the oauth token, so plain TCP is only for local stand-ins
+    pub tls: bool,
+    pub token: String,
+    pub nick: String,
+    pub channel: String,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl BotConfig {
+    pub fn new(addr: impl Into<String>, token: &str, nick: &str, channel: &str) -> Self {
+        // Accept the token with or without the oauth: prefix like temp_bot did
+        let token = token.trim_start_matches("oauth:").to_string();
+
+        Self {
+            addr: addr.into(),
+            tls: true,
+            token,
+            nick: nick.to_lowercase(),
+            channel: channel.trim_start_matches('#').to_lowercase(),
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+// Why a connection ended
+#[derive(Debug, PartialEq)]
+pub enum Disconnect {
+    // Twitch asked us to reconnect (RECONNECT) or the socket closed
+    Reconnect,
+    Auth(String),
+    Io(String),
+}
+
+pub struct ChatBot {
+    config: BotConfig,
+    commands: ChatCommands,
+}
+
+impl ChatBot {
+    pub fn new(config: BotConfig, commands: ChatCommands) -> Self {
+        Self { config, commands }
+    }
+
+    pub fn spawn(mut self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut backoff = self.config.min_backoff;
+
+            loop {
+                let started = Instant::now();
+                match self.run_once().await {
+                    Disconnect::Reconnect => info!("Twitch chat disconnected, reconnecting"),
+                    Disconnect::Auth(e) => warn!("Twitch chat login failed: {}", e),
+                    Disconnect::Io(e) => warn!("Twitch chat connection error: {}", e),
+                }
+
+                // A connection that stayed up for a while resets the backoff
+                if started.elapsed() > self.config.max_backoff {
+                    backoff = self.config.min_backoff;
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(self.config.max_backoff);
+            }
+        })
+    }
+
+    // One connection from login until the server drops us
+    pub async fn run_once(&mut self) -> Disconnect {
+        let stream = match TcpStream::connect(&self.config.addr).await {
+            Ok(stream) => stream,
+            Err(e) => return Disconnect::Io(e.to_string()),
+        };
+        if !self.config.tls {
+            return self.session(stream).await;
+        }
+        match tls_connect(&self.config.addr, stream).await {
+            Ok(stream) => self.session(stream).await,
+            Err(e) => Disconnect::Io(e.to_string()),
+        }
+    }
+
+    async fn session(&mut self, stream: impl AsyncRead + AsyncWrite + Unpin) -> Disconnect {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+
+        let login = [
+            IrcMessage::new("CAP", &["REQ", "twitch.tv/tags twitch.tv/commands"]),
+            IrcMessage::new("PASS", &[&format!("oauth:{}", self.config.token)]),
+            IrcMessage::new("NICK", &[&self.config.nick]),
+            IrcMessage::new("JOIN", &[&format!("#{}", self.config.channel)]),
+        ];
+        for msg in &login {
+            if let Err(e) = send(&mut writer, msg).await {
+                return Disconnect::Io(e.to_string());
+            }
+        }
+
+        loop {
+            let line = match lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => return Disconnect::Reconnect,
+                Err(e) => return Disconnect::Io(e.to_string()),
+            };
+            let Some(msg) = IrcMessage::parse(&line) else { continue };
+
+            let response = match msg.command.as_str() {
+                "PING" => Some(IrcMessage::new("PONG", &[msg.params.first().map(String::as_str).unwrap_or("tmi.twitch.tv")])),
+                "RECONNECT" => return Disconnect::Reconnect,
+                "NOTICE" if msg.params.last().is_some_and(|t| t.contains("authentication failed") || t.contains("Improperly formatted auth")) => {
+                    return Disconnect::Auth(msg.params.last().cloned().unwrap_or_default());
+                }
+                "001" => {
+                    info!("Logged into Twitch chat as {}", self.config.nick);
+                    None
+                }
+                "JOIN" if msg.nick() == Some(self.config.nick.as_str()) => {
+                    info!("Joined #{}", self.config.channel);
+                    None
+                }
+                "PRIVMSG" => match ChatMessage::from_irc(&msg) {
+                    Some(chat) => {
+                        debug!("{}: {}", chat.user, chat.text);
+                        self.commands.handle(&chat, Instant::now()).await
+                            .map(|text| irc::reply(&chat.channel, chat.id.as_deref(), &text))
+                    }
+                    None => None,
+                },
+                _ => None,
+            };
+
+            if let Some(response) = response
+                && let Err(e) = send(&mut writer, &response).await
+            {
+                return Disconnect::Io(e.to_string());
+            }
+        }
+    }
+}
+
+async fn tls_connect(addr: &str, stream: TcpStream) -> std::io::Result<TlsStream<TcpStream>> {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+    let roots = RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+    let config = ClientConfig::builder().with_root_certificates(roots).with_no_client_auth();
+    TlsConnector::from(Arc::new(config)).connect(name, stream).await
+}
+
+async fn send(writer: &mut (impl AsyncWriteExt + Unpin), msg: &IrcMessage) -> std::io::Result<()> {
+    writer.write_all(format!("{}\r\n", msg.to_line()).as_bytes()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::commands::default_commands;
+    use crate::db::tests::setup_test_db;
+    use tokio::io::{Lines, AsyncWrite};
+    use tokio::net::TcpListener;
+    use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+    // The Twitch side of one connection
+    struct StandIn {
+        lines: Lines<BufReader<OwnedReadHalf>>,
+        writer: OwnedWriteHalf,
+    }
+
+    impl StandIn {
+        async fn accept(listener: &TcpListener) -> StandIn {
+            let (stream, _) = tokio::time::timeout(Duration::from_secs(5), listener.accept())
+                .await.expect("bot never connected").unwrap();
+            let (reader, writer) = stream.into_split();
+            StandIn { lines: BufReader::new(reader).lines(), writer }
+        }
+
+        async fn recv(&mut self) -> String {
+            tokio::time::timeout(Duration::from_secs(5), self.lines.next_line())
+                .await.expect("bot went quiet").unwrap().expect("bot hung up")
+        }
+
+        async fn send(&mut self, line: &str) {
+            write_line(&mut self.writer, line).await;
+        }
+    }
+
+    async fn write_line(writer: &mut (impl AsyncWrite + Unpin), line: &str) {
+        writer.write_all(format!("{}\r\n", line).as_bytes()).await.unwrap();
+    }
+
+    async fn start_bot() -> (TcpListener, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let pool = setup_test_db().await.unwrap();
+        let mut config = BotConfig::new(addr, "oauth:secret", "TarkovBot", "#PlaidCat");
+        config.tls = false;
+        config.min_backoff = Duration::from_millis(10);
+        config.max_backoff = Duration::from_millis(50);
+
+        let bot = ChatBot::new(config, ChatCommands::new(pool, default_commands()));
+        (listener, bot.spawn())
+    }
+
+    #[tokio::test]
+    async fn test_login_ping_and_command_reply() {
+        let (listener, bot) = start_bot().await;
+        let mut twitch = StandIn::accept(&listener).await;
+
+        assert_eq!(twitch.recv().await, "CAP REQ :twitch.tv/tags twitch.tv/commands");
+        assert_eq!(twitch.recv().await, "PASS oauth:secret");
+        assert_eq!(twitch.recv().await, "NICK tarkovbot");
+        assert_eq!(twitch.recv().await, "JOIN #plaidcat");
+
+        twitch.send(":tmi.twitch.tv 001 tarkovbot :Welcome, GLHF!").await;
+        twitch.send("PING :tmi.twitch.tv").await;
+        assert_eq!(twitch.recv().await, "PONG tmi.twitch.tv");
+
+        // Chatter that isn't a command gets no answer, the next line we see is the reply
+        twitch.send("@id=m1;mod=0 :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #plaidcat :hello").await;
+        twitch.send("@display-name=Viewer;id=m2;mod=0 :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #plaidcat :!map").await;
+        assert_eq!(twitch.recv().await, "@reply-parent-msg-id=m2 PRIVMSG #plaidcat :Not in a raid right now");
+
+        bot.abort();
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_when_asked() {
+        let (listener, bot) = start_bot().await;
+
+        let mut twitch = StandIn::accept(&listener).await;
+        for _ in 0..4 {
+            twitch.recv().await;
+        }
+        twitch.send(":tmi.twitch.tv RECONNECT").await;
+
+        let mut twitch = StandIn::accept(&listener).await;
+        assert_eq!(twitch.recv().await, "CAP REQ :twitch.tv/tags twitch.tv/commands");
+
+        bot.abort();
+    }
+
+    #[tokio::test]
+    async fn test_auth_failure_ends_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let pool = setup_test_db().await.unwrap();
+        let mut config = BotConfig::new(addr, "bad", "tarkovbot", "plaidcat");
+        config.tls = false;
+        let mut bot = ChatBot::new(config, ChatCommands::new(pool, default_commands()));
+
+        let server = tokio::spawn(async move {
+            let mut twitch = StandIn::accept(&listener).await;
+            for _ in 0..4 {
+                twitch.recv().await;
+            }
+            twitch.send(":tmi.twitch.tv NOTICE * :Login authentication failed").await;
+            // Keep the socket open so the bot has to act on the NOTICE itself
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        assert_eq!(bot.run_once().await, Disconnect::Auth("Login authentication failed".into()));
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_tls_by_default_never_sends_the_token_in_clear() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let pool = setup_test_db().await.unwrap();
+        let mut bot = ChatBot::new(
+            BotConfig::new(addr, "oauth:secret", "tarkovbot", "plaidcat"),
+            ChatCommands::new(pool, default_commands()),
+        );
+
+        // A plain IRC server on the other end, it answers the handshake with a text line
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut hello = vec![0; 1024];
+            let n = tokio::io::AsyncReadExt::read(&mut stream, &mut hello).await.unwrap();
+            write_line(&mut stream, ":tmi.twitch.tv NOTICE * :hello").await;
+            hello.truncate(n);
+            hello
+        });
+
+        assert!(matches!(bot.run_once().await, Disconnect::Io(_)));
+        let hello = server.await.unwrap();
+        assert!(!String::from_utf8_lossy(&hello).contains("secret"));
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+
+use crate::db;
+use crate::stats::{self, format_duration};
+use super::irc::ChatMessage;
+
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+// ============================================================
+// Command Table
+// ============================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandKind {
+    Stats,
+    Kd,
+    Raid,
+    Session,
+    Map,
+    Weapon,
+    Queue,
+    Commands,
+    // Static reply, what temp_bot served out of rules.txt and friends
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandSpec {
+    pub kind: CommandKind,
+    pub cooldown: Duration,
+    pub mod_only: bool,
+}
+
+impl CommandSpec {
+    pub fn new(kind: CommandKind) -> Self {
+        Self { kind, cooldown: DEFAULT_COOLDOWN, mod_only: false }
+    }
+}
+
+pub fn default_commands() -> BTreeMap<String, CommandSpec> {
+    [
+        ("stats", CommandKind::Stats),
+        ("kd", CommandKind::Kd),
+        ("raid", CommandKind::Raid),
+        ("session", CommandKind::Session),
+        ("map", CommandKind::Map),
+        ("weapon", CommandKind::Weapon),
+        ("queue", CommandKind::Queue),
+        ("commands", CommandKind::Commands),
+    ]
+    .into_iter()
+    .map(|(name, kind)| (name.to_string(), CommandSpec::new(kind)))
+    .collect()
+}
+
+// Every `name.txt` in `dir` becomes a `!name` command replying with the file contents
+pub fn load_text_commands(dir: &Path) -> Result<BTreeMap<String, CommandSpec>, String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut commands = BTreeMap::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("txt") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        // IRC lines can't contain newlines, fold the file onto one line
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        commands.insert(name.to_lowercase(), CommandSpec::new(CommandKind::Text(text)));
+    }
+
+    Ok(commands)
+}
+
+// "kd=10,stats=60"
+pub fn parse_cooldowns(s: &str) -> Result<HashMap<String, Duration>, String> {
+    let mut cooldowns = HashMap::new();
+
+    for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, secs) = entry.split_once('=')
+            .ok_or_else(|| format!("Expected `command=seconds`, got '{}'", entry))?;
+        let secs: u64 = secs.trim().parse()
+            .map_err(|_| format!("Invalid cooldown '{}' for !{}", secs.trim(), name.trim()))?;
+        cooldowns.insert(name.trim().trim_start_matches('!').to_lowercase(), Duration::from_secs(secs));
+    }
+
+    Ok(cooldowns)
+}
+
+// ============================================================
+// Dispatch
+// ============================================================
+
+pub struct ChatCommands {
+    pool: SqlitePool,
+    commands: BTreeMap<String, CommandSpec>,
+    last_used: HashMap<String, Instant>,
+}
+
+impl ChatCommands {
+    pub fn new(pool: SqlitePool, commands: BTreeMap<String, CommandSpec>) -> Self {
+        Self { pool, commands, last_used: HashMap::new() }
+    }
+
+    // None means stay quiet: not a command, on cooldown, or not allowed
+    pub async fn handle(&mut self, msg: &ChatMessage, now: Instant) -> Option<String> {
+        let name = msg.text.strip_prefix('!')?
+            .split_whitespace()
+            .next()?
+            .to_lowercase();
+        let spec = self.commands.get(&name)?;
+
+        if spec.mod_only && !msg.is_mod {
+            return None;
+        }
+
+        // Cooldowns are per command for the whole channel, mods skip them
+        if !msg.is_mod
+            && let Some(last) = self.last_used.get(&name)
+            && now.duration_since(*last) < spec.cooldown
+        {
+            return None;
+        }
+
+        let response = match self.respond(&spec.kind, msg.is_mod).await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::warn!("!{} failed: {}", name, e);
+                "Stats are unavailable right now".to_string()
+            }
+        };
+
+        self.last_used.insert(name, now);
+        Some(response)
+    }
+
+    async fn respond(&self, kind: &CommandKind, is_mod: bool) -> Result<String, sqlx::Error> {
+        match kind {
+            CommandKind::Stats => stats_response(&self.pool).await,
+            CommandKind::Kd => kd_response(&self.pool).await,
+            CommandKind::Raid => raid_response(&self.pool).await,
+            CommandKind::Session => session_response(&self.pool).await,
+            CommandKind::Map => map_response(&self.pool).await,
+            CommandKind::Weapon => weapon_response(&self.pool).await,
+            CommandKind::Queue => queue_response(&self.pool).await,
+            CommandKind::Commands => Ok(self.command_list(is_mod)),
+            CommandKind::Text(text) => Ok(text.clone()),
+        }
+    }
+
+    fn command_list(&self, is_mod: bool) -> String {
+        let names: Vec<String> = self.commands.iter()
+            .filter(|(_, spec)| is_mod || !spec.mod_only)
+            .map(|(name, _)| format!("!{}", name))
+            .collect();
+        format!("Available commands: {}", names.join(", "))
+    }
+}
+
+// ============================================================
+// Responses
+// ============================================================
+
+const NO_SESSION: &str = "No stream session running";
+const NO_RAID: &str = "Not in a raid right now";
+
+pub async fn stats_response(pool: &SqlitePool) -> Result<String, sqlx::Error> {
+    let Some(session) = db::get_active_session(pool).await? else {
+        return Ok(NO_SESSION.to_string());
+    };
+    let stats = stats::calculate_session_stats(pool, session.session_id).await?;
+
+    Ok(format!(
+        "This stream: {} raids, {} survived ({:.0}%), {} kills, K/D {:.2}",
+        stats.total_raids,
+        stats.survived_raids,
+        stats.survival_rate * 100.0,
+        stats.total_kills,
+        stats.kd_ratio,
+    ))
+}
+
+pub async fn kd_response(pool: &SqlitePool) -> Result<String, sqlx::Error> {
+    let Some(session) = db::get_active_session(pool).await? else {
+        return Ok(NO_SESSION.to_string());
+    };
+    let stats = stats::calculate_session_stats(pool, session.session_id).await?;
+
+    Ok(format!("K/D this stream: {:.2} ({} kills / {} deaths)", stats.kd_ratio, stats.total_kills, stats.deaths))
+}
+
+pub async fn raid_response(pool: &SqlitePool) -> Result<String, sqlx::Error> {
+    let Some(raid) = db::get_active_raid(pool).await? else {
+        return Ok(NO_RAID.to_string());
+    };
+    let kills = db::get_kills_for_raid(pool, raid.raid_id).await?;
+    let transitions = db::get_raid_transitions(pool, raid.raid_id).await?;
+    let entered_at = transitions.last()
+        .map(|t| t.transitioned_at)
+        .unwrap_or(raid.started_at);
+
+    Ok(format!(
+        "{} ({:?} {:?}) - {} for {}, {} kills",
+        raid.map_name,
+        raid.character_type,
+        raid.game_mode,
+        raid.current_state,
+        format_duration(OffsetDateTime::now_utc() - entered_at),
+        kills.len(),
+    ))
+}
+
+pub async fn session_response(pool: &SqlitePool) -> Result<String, sqlx::Error> {
+    let Some(session) = db::get_active_session(pool).await? else {
+        return Ok(NO_SESSION.to_string());
+    };
+    let stats = stats::calculate_session_stats(pool, session.session_id).await?;
+    let elapsed = format_duration(OffsetDateTime::now_utc() - session.started_at);
+
+    let mut response = format!("Live for {}, {} raids", elapsed, stats.total_raids);
+    if let Some(first_raid) = db::get_first_raid_for_session(pool, session.session_id).await? {
+        response.push_str(&format!(", first raid after {}", format_duration(first_raid.started_at - session.started_at)));
+    }
+
+    Ok(response)
+}
+
+pub async fn map_response(pool: &SqlitePool) -> Result<String, sqlx::Error> {
+    Ok(match db::get_active_raid(pool).await? {
+        Some(raid) => format!("Current map: {}", raid.map_name),
+        None => NO_RAID.to_string(),
+    })
+}
+
+pub async fn weapon_response(pool: &SqlitePool) -> Result<String, sqlx::Error> {
+    let Some(session) = db::get_active_session(pool).await? else {
+        return Ok(NO_SESSION.to_string());
+    };
+    let filter = db::StatsFilter { session_ids: vec![session.session_id], ..Default::default() };
+    let weapons = stats::calculate_weapon_stats(pool, &filter).await?;
+
+    let Some(top) = weapons.weapons.first() else {
+        return Ok("No weapon kills this stream yet".to_string());
+    };
+    Ok(format!(
+        "Weapon of the stream: {} ({} kills, {:.0}% headshots). Overall {:.0}% headshots on {} kills",
+        top.weapon,
+        top.kills,
+        top.headshot_rate * 100.0,
+        weapons.headshot_rate * 100.0,
+        weapons.total_kills,
+    ))
+}
+
+pub async fn queue_response(pool: &SqlitePool) -> Result<String, sqlx::Error> {
+    let Some(session) = db::get_active_session(pool).await? else {
+        return Ok(NO_SESSION.to_string());
+    };
+    let waits = stats::compare_session_wait_times(pool, session.session_id, None).await?;
+    if waits.current.raid_count == 0 {
+        return Ok("No queues logged this stream yet".to_string());
+    }
+
+    let mut response = format!("Average queue this stream: {}", format_duration(waits.current.avg_queue));
+    match waits.queue_change {
+        // Within 5% either way reads better as "the usual" than as "2% longer"
+        Some(change) if change.abs() < 0.05 => response.push_str(", about your usual"),
+        Some(change) => response.push_str(&format!(
+            ", {:.0}% {} than your average of {}",
+            change.abs() * 100.0,
+            if change > 0.0 { "longer" } else { "shorter" },
+            format_duration(waits.all_time.avg_queue),
+        )),
+        None => {}
+    }
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::setup_test_db;
+    use crate::models::{CharacterType, GameMode, SessionType};
+
+    fn chat(text: &str, is_mod: bool) -> ChatMessage {
+        ChatMessage {
+            id: None,
+            channel: "plaidcat".into(),
+            user: "viewer".into(),
+            text: text.into(),
+            is_mod,
+        }
+    }
+
+    async fn setup_session(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(1);
+        let session_id = db::create_session(pool, SessionType::Stream, None, Some(base)).await?;
+
+        let r1 = db::create_raid(pool, session_id, "Customs", CharacterType::PMC, GameMode::PVE,
+            Some(base + time::Duration::minutes(10))).await?;
+        db::add_kill(pool, r1, "pmc", None, None, Some(base + time::Duration::minutes(15))).await?;
+        db::add_kill(pool, r1, "scav", None, None, Some(base + time::Duration::minutes(16))).await?;
+        db::log_forced_transition(pool, r1, "survived", Some(base + time::Duration::minutes(30))).await?;
+        db::end_raid(pool, r1, Some(base + time::Duration::minutes(30)), None).await?;
+
+        let r2 = db::create_raid(pool, session_id, "Woods", CharacterType::Scav, GameMode::PVE,
+            Some(base + time::Duration::minutes(35))).await?;
+        db::log_forced_transition(pool, r2, "died", Some(base + time::Duration::minutes(45))).await?;
+        db::end_raid(pool, r2, Some(base + time::Duration::minutes(45)), None).await?;
+
+        db::create_raid(pool, session_id, "Lighthouse", CharacterType::PMC, GameMode::PVE,
+            Some(base + time::Duration::minutes(50))).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_responses_without_session() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+
+        assert_eq!(stats_response(&pool).await?, NO_SESSION);
+        assert_eq!(kd_response(&pool).await?, NO_SESSION);
+        assert_eq!(raid_response(&pool).await?, NO_RAID);
+        assert_eq!(map_response(&pool).await?, NO_RAID);
+        assert_eq!(weapon_response(&pool).await?, NO_SESSION);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_responses_with_session() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        setup_session(&pool).await?;
+
+        // The raid in progress counts as not survived, but it isn't a death yet
+        assert_eq!(stats_response(&pool).await?, "This stream: 3 raids, 1 survived (33%), 2 kills, K/D 2.00");
+        assert_eq!(kd_response(&pool).await?, "K/D this stream: 2.00 (2 kills / 1 deaths)");
+        assert_eq!(map_response(&pool).await?, "Current map: Lighthouse");
+        assert!(raid_response(&pool).await?.starts_with("Lighthouse (PMC PVE) - stash_management for "));
+
+        let session = session_response(&pool).await?;
+        assert!(session.starts_with("Live for 1h 0m"), "{}", session);
+        assert!(session.ends_with("3 raids, first raid after 10m 0s"), "{}", session);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_weapon_of_the_stream() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        setup_session(&pool).await?;
+        assert_eq!(weapon_response(&pool).await?, "No weapon kills this stream yet");
+
+        let raid = db::get_active_raid(&pool).await?.unwrap();
+        // Tied on count, the spelling used last wins
+        db::add_kill(&pool, raid.raid_id, "scav", Some("m4a1".into()), Some(false), None).await?;
+        db::add_kill(&pool, raid.raid_id, "pmc", Some("M4A1".into()), Some(true), None).await?;
+
+        assert_eq!(
+            weapon_response(&pool).await?,
+            "Weapon of the stream: M4A1 (2 kills, 50% headshots). Overall 50% headshots on 4 kills"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_queue_against_average() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        assert_eq!(queue_response(&pool).await?, NO_SESSION);
+
+        // A past stream with 2 minute queues
+        let past = OffsetDateTime::now_utc() - time::Duration::days(1);
+        let past_session = db::create_session(&pool, SessionType::Stream, None, Some(past)).await?;
+        for i in 0..2 {
+            let start = past + time::Duration::hours(i);
+            let raid = db::create_raid(&pool, past_session, "Customs", CharacterType::PMC, GameMode::PVE, Some(start)).await?;
+            db::log_forced_transition(&pool, raid, "queuing", Some(start)).await?;
+            db::log_forced_transition(&pool, raid, "raid_active", Some(start + time::Duration::minutes(2))).await?;
+            db::end_raid(&pool, raid, Some(start + time::Duration::minutes(20)), None).await?;
+        }
+        db::end_session(&pool, past_session, Some(past + time::Duration::hours(3))).await?;
+
+        setup_session(&pool).await?;
+        assert_eq!(queue_response(&pool).await?, "No queues logged this stream yet");
+
+        // 5 minutes today, the average is now 3m over the three queues
+        let raid = db::get_active_raid(&pool).await?.unwrap();
+        db::log_forced_transition(&pool, raid.raid_id, "queuing", Some(raid.started_at)).await?;
+        db::log_forced_transition(&pool, raid.raid_id, "raid_active", Some(raid.started_at + time::Duration::minutes(5))).await?;
+
+        assert_eq!(queue_response(&pool).await?, "Average queue this stream: 5m 0s, 67% longer than your average of 3m 0s");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cooldown_applies_to_viewers_not_mods() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let mut commands = ChatCommands::new(pool, default_commands());
+        let start = Instant::now();
+
+        assert!(commands.handle(&chat("!map", false), start).await.is_some());
+        assert!(commands.handle(&chat("!map", false), start + Duration::from_secs(5)).await.is_none());
+        // Other commands have their own cooldown
+        assert!(commands.handle(&chat("!kd", false), start + Duration::from_secs(5)).await.is_some());
+        assert!(commands.handle(&chat("!map", true), start + Duration::from_secs(5)).await.is_some());
+        assert!(commands.handle(&chat("!MAP", false), start + Duration::from_secs(40)).await.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mod_only_and_unknown_commands() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let mut table = default_commands();
+        table.get_mut("session").unwrap().mod_only = true;
+        let mut commands = ChatCommands::new(pool, table);
+        let now = Instant::now();
+
+        assert!(commands.handle(&chat("!session", false), now).await.is_none());
+        assert_eq!(commands.handle(&chat("!session", true), now).await.unwrap(), NO_SESSION);
+        assert!(commands.handle(&chat("!nope", true), now).await.is_none());
+        assert!(commands.handle(&chat("hello chat", true), now).await.is_none());
+
+        // Mod-only commands are hidden from viewers in the list
+        let list = commands.handle(&chat("!commands", false), now).await.unwrap();
+        assert!(list.contains("!kd") && !list.contains("!session"), "{}", list);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_text_commands() {
+        let dir = std::env::temp_dir().join(format!("chat_text_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Rules.txt"), "Be nice.\nNo spoilers.\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "ignored").unwrap();
+
+        let commands = load_text_commands(&dir).unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands["rules"].kind, CommandKind::Text("Be nice. No spoilers.".into()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_cooldowns() {
+        let cooldowns = parse_cooldowns("kd=10, !Stats=60").unwrap();
+        assert_eq!(cooldowns["kd"], Duration::from_secs(10));
+        assert_eq!(cooldowns["stats"], Duration::from_secs(60));
+
+        assert!(parse_cooldowns("kd").is_err());
+        assert!(parse_cooldowns("kd=soon").is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+// ============================================================
+// Messages
+// ============================================================
+
+// One IRC line, e.g.
+// @badges=moderator/1;mod=1;id=abc :nick!nick@nick.tmi.twitch.tv PRIVMSG #chan :!kd
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct IrcMessage {
+    pub tags: HashMap<String, String>,
+    pub prefix: Option<String>,
+    pub command: String,
+    pub params: Vec<String>,
+}
+
+impl IrcMessage {
+    pub fn new(command: &str, params: &[&str]) -> Self {
+        Self {
+            command: command.to_string(),
+            params: params.iter().map(|p| p.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<IrcMessage> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+        let mut msg = IrcMessage::default();
+
+        if let Some(stripped) = rest.strip_prefix('@') {
+            let (tags, after) = stripped.split_once(' ')?;
+            for tag in tags.split(';') {
+                let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+                msg.tags.insert(key.to_string(), unescape_tag(value));
+            }
+            rest = after.trim_start();
+        }
+
+        if let Some(stripped) = rest.strip_prefix(':') {
+            let (prefix, after) = stripped.split_once(' ')?;
+            msg.prefix = Some(prefix.to_string());
+            rest = after.trim_start();
+        }
+
+        let (command, mut rest) = rest.split_once(' ').unwrap_or((rest, ""));
+        if command.is_empty() {
+            return None;
+        }
+        msg.command = command.to_string();
+
+        while !rest.is_empty() {
+            if let Some(trailing) = rest.strip_prefix(':') {
+                msg.params.push(trailing.to_string());
+                break;
+            }
+            let (param, after) = rest.split_once(' ').unwrap_or((rest, ""));
+            msg.params.push(param.to_string());
+            rest = after.trim_start();
+        }
+
+        Some(msg)
+    }
+
+    // Serialized without the trailing CRLF
+    pub fn to_line(&self) -> String {
+        let mut line = String::new();
+
+        if !self.tags.is_empty() {
+            let mut tags: Vec<_> = self.tags.iter().collect();
+            tags.sort();
+            let tags: Vec<String> = tags.into_iter()
+                .map(|(k, v)| format!("{}={}", k, escape_tag(v)))
+                .collect();
+            line.push('@');
+            line.push_str(&tags.join(";"));
+            line.push(' ');
+        }
+
+        if let Some(prefix) = &self.prefix {
+            line.push(':');
+            line.push_str(prefix);
+            line.push(' ');
+        }
+
+        line.push_str(&self.command);
+
+        if let Some((last, middle)) = self.params.split_last() {
+            for param in middle {
+                line.push(' ');
+                line.push_str(param);
+            }
+            // Only use the trailing form when the param needs it
+            if last.is_empty() || last.contains(' ') || last.starts_with(':') {
+                line.push_str(" :");
+            } else {
+                line.push(' ');
+            }
+            line.push_str(last);
+        }
+
+        line
+    }
+
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+
+    // Nick part of "nick!user@host"
+    pub fn nick(&self) -> Option<&str> {
+        self.prefix.as_deref().map(|p| p.split('!').next().unwrap_or(p))
+    }
+}
+
+// IRCv3 tag value escaping: "\:" is ';', "\s" is ' ', "\\" is '\'
+fn unescape_tag(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => out.push(';'),
+            Some('s') => out.push(' '),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+
+    out
+}
+
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace(';', "\\:")
+        .replace(' ', "\\s")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+// ============================================================
+// Chat Messages
+// ============================================================
+
+// A PRIVMSG boiled down to what command handling cares about
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub id: Option<String>,
+    pub channel: String,
+    pub user: String,
+    pub text: String,
+    pub is_mod: bool,
+}
+
+impl ChatMessage {
+    pub fn from_irc(msg: &IrcMessage) -> Option<ChatMessage> {
+        if msg.command != "PRIVMSG" || msg.params.len() < 2 {
+            return None;
+        }
+
+        let user = msg.tag("display-name")
+            .filter(|n| !n.is_empty())
+            .or_else(|| msg.nick())?
+            .to_string();
+
+        // The broadcaster doesn't get mod=1 in their own channel, only the badge
+        let is_mod = msg.tag("mod") == Some("1")
+            || msg.tag("badges").is_some_and(|b| b.split(',').any(|badge| badge.starts_with("broadcaster/")));
+
+        Some(ChatMessage {
+            id: msg.tag("id").map(String::from),
+            channel: msg.params[0].trim_start_matches('#').to_string(),
+            user,
+            text: msg.params[1].clone(),
+            is_mod,
+        })
+    }
+}
+
+// PRIVMSG threaded as a reply when we know the parent message id
+pub fn reply(channel: &str, parent_id: Option<&str>, text: &str) -> IrcMessage {
+    let mut msg = IrcMessage::new("PRIVMSG", &[&format!("#{}", channel), text]);
+    if let Some(id) = parent_id {
+        msg.tags.insert("reply-parent-msg-id".into(), id.to_string());
+    }
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tagged_privmsg() {
+        let line = "@badge-info=;badges=moderator/1;display-name=Some\\sMod;id=b34c;mod=1 \
+                    :somemod!somemod@somemod.tmi.twitch.tv PRIVMSG #plaidcat :!kd please\r\n";
+        let msg = IrcMessage::parse(line).unwrap();
+
+        assert_eq!(msg.command, "PRIVMSG");
+        assert_eq!(msg.params, vec!["#plaidcat", "!kd please"]);
+        assert_eq!(msg.tag("display-name"), Some("Some Mod"));
+        assert_eq!(msg.tag("badge-info"), Some(""));
+        assert_eq!(msg.nick(), Some("somemod"));
+
+        let chat = ChatMessage::from_irc(&msg).unwrap();
+        assert_eq!(chat.channel, "plaidcat");
+        assert_eq!(chat.user, "Some Mod");
+        assert_eq!(chat.text, "!kd please");
+        assert_eq!(chat.id.as_deref(), Some("b34c"));
+        assert!(chat.is_mod);
+    }
+
+    #[test]
+    fn test_parse_untagged_lines() {
+        let ping = IrcMessage::parse("PING :tmi.twitch.tv").unwrap();
+        assert_eq!(ping.command, "PING");
+        assert_eq!(ping.params, vec!["tmi.twitch.tv"]);
+        assert!(ping.prefix.is_none());
+
+        let welcome = IrcMessage::parse(":tmi.twitch.tv 001 bot :Welcome, GLHF!").unwrap();
+        assert_eq!(welcome.command, "001");
+        assert_eq!(welcome.params, vec!["bot", "Welcome, GLHF!"]);
+
+        assert!(IrcMessage::parse("").is_none());
+    }
+
+    #[test]
+    fn test_broadcaster_badge_counts_as_mod() {
+        let msg = IrcMessage::parse("@badges=broadcaster/1,subscriber/0;mod=0 :me!me@me PRIVMSG #me :hi").unwrap();
+        assert!(ChatMessage::from_irc(&msg).unwrap().is_mod);
+
+        let msg = IrcMessage::parse("@badges=subscriber/0;mod=0 :viewer!viewer@viewer PRIVMSG #me :hi").unwrap();
+        let chat = ChatMessage::from_irc(&msg).unwrap();
+        assert!(!chat.is_mod);
+        // No display-name tag, fall back to the nick
+        assert_eq!(chat.user, "viewer");
+    }
+
+    #[test]
+    fn test_reply_round_trip() {
+        let msg = reply("plaidcat", Some("b34c"), "K/D 2.00");
+        assert_eq!(msg.to_line(), "@reply-parent-msg-id=b34c PRIVMSG #plaidcat :K/D 2.00");
+
+        assert_eq!(IrcMessage::parse(&msg.to_line()).unwrap(), msg);
+
+        assert_eq!(IrcMessage::new("JOIN", &["#plaidcat"]).to_line(), "JOIN #plaidcat");
+    }
+}
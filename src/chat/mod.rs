@@ -0,0 +1,57 @@
+// Twitch chat bot speaking IRC over TLS, replaces the Python bot in temp_bot/.
+// Answers stat commands straight from the database.
+pub mod bot;
+pub mod commands;
+pub mod irc;
+
+use std::path::Path;
+
+use sqlx::SqlitePool;
+
+use bot::{BotConfig, ChatBot};
+use commands::ChatCommands;
+
+const TWITCH_IRC_ADDR: &str = "irc.chat.twitch.tv:6697";
+
+// TWITCH_TOKEN and TWITCH_CHANNEL turn the bot on. Optional:
+//   TWITCH_BOT_USERNAME      - login nick, defaults to the channel
+//   TWITCH_IRC_ADDR          - host:port override
+//   TWITCH_IRC_PLAINTEXT     - "1" skips TLS, only for a local IRC server
+//   TWITCH_TEXT_COMMANDS_DIR - folder of name.txt static replies (e.g. temp_bot/)
+//   TWITCH_COOLDOWNS         - "kd=10,stats=60", seconds per command (default 30)
+//   TWITCH_MOD_ONLY          - "session,raid", commands only mods can use
+pub fn bot_from_env(pool: SqlitePool) -> Result<Option<ChatBot>, String> {
+    let (Ok(token), Ok(channel)) = (std::env::var("TWITCH_TOKEN"), std::env::var("TWITCH_CHANNEL")) else {
+        return Ok(None);
+    };
+
+    let nick = std::env::var("TWITCH_BOT_USERNAME").unwrap_or_else(|_| channel.clone());
+    let addr = std::env::var("TWITCH_IRC_ADDR").unwrap_or_else(|_| TWITCH_IRC_ADDR.to_string());
+
+    let mut table = commands::default_commands();
+
+    if let Ok(dir) = std::env::var("TWITCH_TEXT_COMMANDS_DIR") {
+        for (name, spec) in commands::load_text_commands(Path::new(&dir))? {
+            // Built-in commands win over a text file with the same name
+            table.entry(name).or_insert(spec);
+        }
+    }
+
+    if let Ok(cooldowns) = std::env::var("TWITCH_COOLDOWNS") {
+        for (name, cooldown) in commands::parse_cooldowns(&cooldowns)? {
+            let spec = table.get_mut(&name).ok_or_else(|| format!("Unknown command !{} in TWITCH_COOLDOWNS", name))?;
+            spec.cooldown = cooldown;
+        }
+    }
+
+    if let Ok(mod_only) = std::env::var("TWITCH_MOD_ONLY") {
+        for name in mod_only.split(',').map(|n| n.trim().trim_start_matches('!').to_lowercase()).filter(|n| !n.is_empty()) {
+            let spec = table.get_mut(&name).ok_or_else(|| format!("Unknown command !{} in TWITCH_MOD_ONLY", name))?;
+            spec.mod_only = true;
+        }
+    }
+
+    let mut config = BotConfig::new(addr, &token, &nick, &channel);
+    config.tls = !std::env::var("TWITCH_IRC_PLAINTEXT").is_ok_and(|v| v == "1");
+    Ok(Some(ChatBot::new(config, ChatCommands::new(pool, table))))
+}
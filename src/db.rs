@@ -1,8 +1,14 @@
 use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
-use sqlx::{Error, Transaction};
+use sqlx::{Acquire, Error, QueryBuilder, Sqlite, SqliteExecutor, Transaction};
 use time::OffsetDateTime;
 
-use crate::models::{CharacterType, Raid, GameMode, SessionType, StreamSession, RaidStateTransition};
+use crate::import::ImportPlan;
+use crate::models::{
+    AuditEntry, CharacterType, GameMode, InvalidTransition, Kill, MapExtract, MapQueueTime, NewAuditEntry, NewKill, PendingEvent, Raid,
+    RaidAggregate, RaidOutcome, RaidSort, RaidState, RaidStateTransition, RaidSummary, RaidUnit, RecordHolder, RecordKind,
+    ReviewStatus, SessionSort, SessionSummary, SessionType, SortOrder, StateDuration, StatsGroup, StreamSession,
+    TrendAggregate, TrendUnit, WaitAggregate, WeaponAggregate, WeaponEnemyCount,
+};
 
 pub async fn create_pool(database_url: &str) -> Result<SqlitePool, Error> {
     SqlitePoolOptions::new()
@@ -47,21 +53,28 @@ pub async fn create_session(
     Ok(id)
 }
 
-pub async fn end_session(pool: &SqlitePool, session_id: i64) -> Result<(), Error> {
+pub async fn end_session(
+    conn: impl SqliteExecutor<'_>,
+    session_id: i64,
+    ended_at: Option<OffsetDateTime>,
+) -> Result<(), Error> {
+    let ts = ended_at.unwrap_or_else(OffsetDateTime::now_utc);
+
     sqlx::query!(
         r#"
         UPDATE stream_sessions
-        SET ended_at = CURRENT_TIMESTAMP
+        SET ended_at = ?
         WHERE session_id = ?
         "#,
+        ts,
         session_id)
-        .execute(pool)
+        .execute(conn)
         .await?;
 
     Ok(())
 }
 
-pub async fn get_active_session(pool: &SqlitePool) -> Result<Option<StreamSession>, Error> {
+pub async fn get_active_session(conn: impl SqliteExecutor<'_>) -> Result<Option<StreamSession>, Error> {
     sqlx::query_as!(
         StreamSession,
         r#"
@@ -76,10 +89,10 @@ pub async fn get_active_session(pool: &SqlitePool) -> Result<Option<StreamSessio
         ORDER BY started_at DESC
         LIMIT 1
         "#
-    ).fetch_optional(pool).await
+    ).fetch_optional(conn).await
 }
 
-pub async fn get_session_by_id(pool: &SqlitePool, session_id: i64) -> Result<Option<StreamSession>, Error> {
+pub async fn get_session_by_id(conn: impl SqliteExecutor<'_>, session_id: i64) -> Result<Option<StreamSession>, Error> {
     sqlx::query_as!(
         StreamSession,
         r#"
@@ -93,10 +106,10 @@ pub async fn get_session_by_id(pool: &SqlitePool, session_id: i64) -> Result<Opt
         WHERE session_id = ?
         "#, 
         session_id
-    ).fetch_optional(pool).await
+    ).fetch_optional(conn).await
 }
 
-pub async fn get_all_sessions(pool: &SqlitePool) -> Result<Vec<StreamSession>, Error> {
+pub async fn get_all_sessions(conn: impl SqliteExecutor<'_>) -> Result<Vec<StreamSession>, Error> {
     sqlx::query_as!(
         StreamSession,
         r#"
@@ -109,14 +122,14 @@ pub async fn get_all_sessions(pool: &SqlitePool) -> Result<Vec<StreamSession>, E
         FROM stream_sessions
         ORDER BY started_at DESC
         "#,
-    ).fetch_all(pool).await
+    ).fetch_all(conn).await
 }
 
 // ================================================================================================
 // Raid Operations
 // ================================================================================================
 pub async fn create_raid(
-    pool: &SqlitePool,
+    conn: impl SqliteExecutor<'_>,
     session_id: i64,
     map_name: &str,
     character_type: CharacterType,
@@ -137,14 +150,14 @@ pub async fn create_raid(
         game_mode,
         ts
     )
-    .fetch_one(pool)
+    .fetch_one(conn)
     .await?
     .raid_id;
 
     Ok(id)
 }
 
-pub async fn end_raid(pool: &SqlitePool, raid_id: i64, end_time: Option<OffsetDateTime>, 
+pub async fn end_raid(conn: impl SqliteExecutor<'_>, raid_id: i64, end_time: Option<OffsetDateTime>, 
                       extract_location: Option<String>,) -> Result<(), Error> {
     sqlx::query!(
         r#"
@@ -156,13 +169,13 @@ pub async fn end_raid(pool: &SqlitePool, raid_id: i64, end_time: Option<OffsetDa
         extract_location,
         raid_id
     )
-    .execute(pool)
+    .execute(conn)
     .await?;
 
     Ok(())
 }
 
-pub async fn get_active_raid(pool: &SqlitePool) -> Result<Option<Raid>, Error> {
+pub async fn get_active_raid(conn: impl SqliteExecutor<'_>) -> Result<Option<Raid>, Error> {
     sqlx::query_as!(
         Raid,
         r#"
@@ -174,14 +187,39 @@ pub async fn get_active_raid(pool: &SqlitePool) -> Result<Option<Raid>, Error> {
             map_name as "map_name!",
             character_type AS "character_type: CharacterType",
             game_mode AS "game_mode: GameMode",
-            current_state as "current_state!",
-            extract_location
+            current_state as "current_state!: RaidState",
+            extract_location,
+            parent_raid_id,
+            segment_order
         FROM raids
         WHERE ended_at IS NULL
         ORDER BY started_at DESC
         LIMIT 1
         "#
-    ).fetch_optional(pool).await
+    ).fetch_optional(conn).await
+}
+
+pub async fn get_raid_by_id(conn: impl SqliteExecutor<'_>, raid_id: i64) -> Result<Option<Raid>, Error> {
+    sqlx::query_as!(
+        Raid,
+        r#"
+        SELECT
+            raid_id as "raid_id!",
+            session_id as "session_id!",
+            started_at,
+            ended_at,
+            map_name as "map_name!",
+            character_type AS "character_type: CharacterType",
+            game_mode AS "game_mode: GameMode",
+            current_state as "current_state!: RaidState",
+            extract_location,
+            parent_raid_id,
+            segment_order
+        FROM raids
+        WHERE raid_id = ?
+        "#,
+        raid_id
+    ).fetch_optional(conn).await
 }
 
 pub async fn get_first_raid_for_session(pool: &SqlitePool, session_id: i64) -> Result<Option<Raid>, Error> {
@@ -196,8 +234,10 @@ pub async fn get_first_raid_for_session(pool: &SqlitePool, session_id: i64) -> R
             map_name as "map_name!",
             character_type AS "character_type: CharacterType",
             game_mode as "game_mode: GameMode",
-            current_state as "current_state!",
-            extract_location
+            current_state as "current_state!: RaidState",
+            extract_location,
+            parent_raid_id,
+            segment_order
         FROM raids
         WHERE session_id = ?
         ORDER BY started_at ASC
@@ -208,7 +248,7 @@ pub async fn get_first_raid_for_session(pool: &SqlitePool, session_id: i64) -> R
 }
 
 pub async fn get_raids_for_session(
-    pool: &SqlitePool,
+    conn: impl SqliteExecutor<'_>,
     session_id: i64
 ) -> Result<Vec<Raid>, Error> {
     sqlx::query_as!(
@@ -222,14 +262,16 @@ pub async fn get_raids_for_session(
             map_name as "map_name!",
             character_type AS "character_type: CharacterType",
             game_mode as "game_mode: GameMode",
-            current_state as "current_state!",
-            extract_location
+            current_state as "current_state!: RaidState",
+            extract_location,
+            parent_raid_id,
+            segment_order
         FROM raids
         WHERE session_id = ?
         ORDER BY started_at ASC
         "#,
         session_id
-    ).fetch_all(pool).await
+    ).fetch_all(conn).await
 }
 
 pub async fn get_all_raids(pool: &SqlitePool) -> Result<Vec<Raid>, Error> {
@@ -244,61 +286,253 @@ pub async fn get_all_raids(pool: &SqlitePool) -> Result<Vec<Raid>, Error> {
             map_name as "map_name!",
             character_type AS "character_type: CharacterType",
             game_mode as "game_mode: GameMode",
-            current_state as "current_state!",
-            extract_location
+            current_state as "current_state!: RaidState",
+            extract_location,
+            parent_raid_id,
+            segment_order
         FROM raids
         ORDER BY started_at ASC
         "#
     ).fetch_all(pool).await
 }
 
+// The next segment of a transfer chain, on a new map from `started_at`. It starts
+// out in `transfer`, the segment it continues from is left for the caller to end.
+pub async fn create_raid_segment(
+    conn: impl SqliteExecutor<'_>,
+    previous: &Raid,
+    map_name: &str,
+    started_at: OffsetDateTime,
+) -> Result<i64, Error> {
+    let chain_id = previous.chain_id();
+
+    let id = sqlx::query!(
+        r#"
+        INSERT INTO raids (session_id, map_name, character_type, game_mode, started_at, current_state,
+                           parent_raid_id, segment_order)
+        VALUES (?, ?, ?, ?, ?, 'transfer', ?,
+                (SELECT MAX(segment_order) + 1 FROM raids WHERE raid_id = ? OR parent_raid_id = ?))
+        RETURNING raid_id
+        "#,
+        previous.session_id,
+        map_name,
+        previous.character_type,
+        previous.game_mode,
+        started_at,
+        chain_id,
+        chain_id,
+        chain_id
+    )
+    .fetch_one(conn)
+    .await?
+    .raid_id;
+
+    Ok(id)
+}
+
+// Every segment of the chain `chain_id` starts, in order
+pub async fn get_raid_chain(conn: impl SqliteExecutor<'_>, chain_id: i64) -> Result<Vec<Raid>, Error> {
+    sqlx::query_as!(
+        Raid,
+        r#"
+        SELECT
+            raid_id as "raid_id!",
+            session_id as "session_id!",
+            started_at,
+            ended_at,
+            map_name as "map_name!",
+            character_type AS "character_type: CharacterType",
+            game_mode as "game_mode: GameMode",
+            current_state as "current_state!: RaidState",
+            extract_location,
+            parent_raid_id,
+            segment_order
+        FROM raids
+        WHERE raid_id = ? OR parent_raid_id = ?
+        ORDER BY segment_order ASC
+        "#,
+        chain_id,
+        chain_id
+    ).fetch_all(conn).await
+}
+
+// What transfer_raid wrote, the segment as it was created before deploying
+pub struct RaidTransfer {
+    pub transition_id: i64,
+    pub segment: Raid,
+    pub segment_transition_id: i64,
+}
+
+// Ends `raid` in `transfer` and carries on as a new segment on `map_name`, deployed at
+// the same moment. All of it or none of it, a half transfer leaves no raid running.
+pub async fn transfer_raid(
+    pool: &SqlitePool,
+    raid: &Raid,
+    map_name: &str,
+    transferred_at: OffsetDateTime,
+) -> Result<RaidTransfer, TransitionError> {
+    let mut tx = pool.begin().await?;
+
+    let transition_id = log_state_transition(&mut *tx, raid.raid_id, "transfer", Some(transferred_at)).await?;
+    end_raid(&mut *tx, raid.raid_id, Some(transferred_at), None).await?;
+
+    let segment_id = create_raid_segment(&mut *tx, raid, map_name, transferred_at).await?;
+    let segment = get_raid_by_id(&mut *tx, segment_id).await?.ok_or(Error::RowNotFound)?;
+    let segment_transition_id =
+        log_state_transition(&mut *tx, segment_id, "deploying_committed", Some(transferred_at)).await?;
+
+    tx.commit().await?;
+    Ok(RaidTransfer { transition_id, segment, segment_transition_id })
+}
+
+// Corrections, current_state is left alone since it follows the transitions
+pub async fn update_raid(conn: impl SqliteExecutor<'_>, raid: &Raid) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        UPDATE raids
+        SET map_name = ?, character_type = ?, game_mode = ?, extract_location = ?,
+            started_at = ?, ended_at = ?
+        WHERE raid_id = ?
+        "#,
+        raid.map_name,
+        raid.character_type,
+        raid.game_mode,
+        raid.extract_location,
+        raid.started_at,
+        raid.ended_at,
+        raid.raid_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// Transitions and kills go with it
+pub async fn delete_raid(conn: impl SqliteExecutor<'_>, raid_id: i64) -> Result<bool, Error> {
+    let result = sqlx::query!("DELETE FROM raids WHERE raid_id = ?", raid_id)
+        .execute(conn)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
 // ================================================================================================
 // State Transition Operations
 // ================================================================================================
+#[derive(Debug)]
+pub enum TransitionError {
+    Invalid(InvalidTransition),
+    Database(Error),
+}
+
+impl std::fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransitionError::Invalid(e) => write!(f, "{}", e),
+            TransitionError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<Error> for TransitionError {
+    fn from(e: Error) -> Self {
+        TransitionError::Database(e)
+    }
+}
+
+// For callers that only deal in sqlx errors, an illegal move comes through as InvalidArgument
+impl From<TransitionError> for Error {
+    fn from(e: TransitionError) -> Self {
+        match e {
+            TransitionError::Invalid(e) => Error::InvalidArgument(e.to_string()),
+            TransitionError::Database(e) => e,
+        }
+    }
+}
+
+// Checked against the state the raid is in when the row is written, not whatever
+// the caller read earlier, so two writers can't both slip an illegal move past the graph
 pub async fn log_state_transition(
-    pool: &SqlitePool,
+    conn: impl Acquire<'_, Database = Sqlite>,
     raid_id: i64,
     to_state: &str,
     timestamp: Option<OffsetDateTime>,
-) -> Result<(), Error> {
-    let mut tx: Transaction<'_, sqlx::Sqlite> = pool.begin().await?;
+) -> Result<i64, TransitionError> {
+    let mut tx = conn.begin().await?;
+
+    let from_state = get_current_state(&mut tx, raid_id).await?;
+    let to_state = RaidState::parse(to_state);
+    from_state.check_transition(&to_state).map_err(TransitionError::Invalid)?;
+
+    let transition_id = insert_transition(&mut tx, raid_id, &from_state, &to_state, timestamp).await?;
+    tx.commit().await?;
+    Ok(transition_id)
+}
+
+// The force escape hatch, logs moves the graph doesn't know about (uncatalogued states,
+// fixing up a raid the detector lost track of)
+pub async fn log_forced_transition(
+    conn: impl Acquire<'_, Database = Sqlite>,
+    raid_id: i64,
+    to_state: &str,
+    timestamp: Option<OffsetDateTime>,
+) -> Result<i64, Error> {
+    let mut tx = conn.begin().await?;
+
+    let from_state = get_current_state(&mut tx, raid_id).await?;
+    let transition_id = insert_transition(&mut tx, raid_id, &from_state, &RaidState::parse(to_state), timestamp).await?;
+
+    tx.commit().await?;
+    Ok(transition_id)
+}
 
-    let from_state: String = sqlx::query_scalar!(
-        "SELECT current_state FROM raids WHERE raid_id = ?",
+async fn get_current_state(tx: &mut Transaction<'_, Sqlite>, raid_id: i64) -> Result<RaidState, Error> {
+    sqlx::query_scalar!(
+        r#"SELECT current_state as "current_state: RaidState" FROM raids WHERE raid_id = ?"#,
         raid_id
     )
-    .fetch_one(&mut *tx)
-    .await?;
+    .fetch_one(&mut **tx)
+    .await
+}
 
+async fn insert_transition(
+    tx: &mut Transaction<'_, Sqlite>,
+    raid_id: i64,
+    from_state: &RaidState,
+    to_state: &RaidState,
+    timestamp: Option<OffsetDateTime>,
+) -> Result<i64, Error> {
     let ts = timestamp.unwrap_or_else(|| OffsetDateTime::now_utc());
 
-    sqlx::query!(
+    let transition_id = sqlx::query!(
         r#"
         INSERT INTO raid_state_transitions (raid_id, from_state, to_state, transitioned_at)
         VALUES (?, ?, ?, ?)
+        RETURNING transition_id as "transition_id!"
         "#,
         raid_id,
         from_state,
         to_state,
         ts
     )
-    .execute(&mut *tx)
-    .await?;
+    .fetch_one(&mut **tx)
+    .await?
+    .transition_id;
 
     sqlx::query!(
         "UPDATE raids SET current_state = ? WHERE raid_id = ?",
         to_state,
         raid_id
     )
-    .execute(&mut *tx)
+    .execute(&mut **tx)
     .await?;
 
-    tx.commit().await?;
-    Ok(())
+    Ok(transition_id)
 }
 
 pub async fn get_raid_transitions(
-    pool: &SqlitePool, 
+    conn: impl SqliteExecutor<'_>, 
     raid_id: i64
 ) -> Result<Vec<RaidStateTransition>, Error> {
     sqlx::query_as!(
@@ -307,8 +541,8 @@ pub async fn get_raid_transitions(
         SELECT
             transition_id as "transition_id!",
             raid_id as "raid_id!",
-            from_state,
-            to_state as "to_state!",
+            from_state as "from_state: RaidState",
+            to_state as "to_state!: RaidState",
             transitioned_at
         FROM raid_state_transitions
         WHERE raid_id = ?
@@ -316,15 +550,136 @@ pub async fn get_raid_transitions(
         "#,
         raid_id
     )
-    .fetch_all(pool)
+    .fetch_all(conn)
     .await
 }
 
+pub async fn get_transition_by_id(
+    conn: impl SqliteExecutor<'_>,
+    transition_id: i64,
+) -> Result<Option<RaidStateTransition>, Error> {
+    sqlx::query_as!(
+        RaidStateTransition,
+        r#"
+        SELECT
+            transition_id as "transition_id!",
+            raid_id as "raid_id!",
+            from_state as "from_state: RaidState",
+            to_state as "to_state!: RaidState",
+            transitioned_at
+        FROM raid_state_transitions
+        WHERE transition_id = ?
+        "#,
+        transition_id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+// Walks the raid's transitions in time order and rewrites each from_state to the state
+// before it, then sets current_state to wherever the chain ends. Needed after any
+// transition is edited, removed or put back out of order.
+async fn rebuild_state_chain(tx: &mut Transaction<'_, sqlx::Sqlite>, raid_id: i64) -> Result<(), Error> {
+    let raid = get_raid_by_id(&mut **tx, raid_id).await?.ok_or(Error::RowNotFound)?;
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT transition_id as "transition_id!", from_state as "from_state: RaidState", to_state as "to_state!: RaidState"
+        FROM raid_state_transitions
+        WHERE raid_id = ?
+        ORDER BY transitioned_at ASC, transition_id ASC
+        "#,
+        raid_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let mut state = raid.initial_state();
+    for row in rows {
+        if row.from_state.as_ref() != Some(&state) {
+            sqlx::query!(
+                "UPDATE raid_state_transitions SET from_state = ? WHERE transition_id = ?",
+                state,
+                row.transition_id
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        state = row.to_state;
+    }
+
+    sqlx::query!("UPDATE raids SET current_state = ? WHERE raid_id = ?", state, raid_id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn update_transition(conn: impl Acquire<'_, Database = Sqlite>, transition: &RaidStateTransition) -> Result<(), Error> {
+    let mut tx = conn.begin().await?;
+
+    sqlx::query!(
+        "UPDATE raid_state_transitions SET to_state = ?, transitioned_at = ? WHERE transition_id = ?",
+        transition.to_state,
+        transition.transitioned_at,
+        transition.transition_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    rebuild_state_chain(&mut tx, transition.raid_id).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+// Returns false if there was no such transition
+pub async fn delete_transition(conn: impl Acquire<'_, Database = Sqlite>, transition_id: i64) -> Result<bool, Error> {
+    let mut tx = conn.begin().await?;
+
+    let raid_id = sqlx::query_scalar!(
+        "DELETE FROM raid_state_transitions WHERE transition_id = ? RETURNING raid_id",
+        transition_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(raid_id) = raid_id else {
+        return Ok(false);
+    };
+
+    rebuild_state_chain(&mut tx, raid_id).await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+// Puts a deleted transition back with its original id
+pub async fn restore_transition(conn: impl Acquire<'_, Database = Sqlite>, transition: &RaidStateTransition) -> Result<(), Error> {
+    let mut tx = conn.begin().await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO raid_state_transitions (transition_id, raid_id, from_state, to_state, transitioned_at)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+        transition.transition_id,
+        transition.raid_id,
+        transition.from_state,
+        transition.to_state,
+        transition.transitioned_at
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    rebuild_state_chain(&mut tx, transition.raid_id).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
 // ================================================================================================
 // Kill Operations
 // ================================================================================================
 pub async fn add_kill(
-    pool: &SqlitePool,
+    conn: impl SqliteExecutor<'_>,
     raid_id: i64,
     enemy_type: &str,
     weapon_used: Option<String>,
@@ -345,16 +700,16 @@ pub async fn add_kill(
         headshot,
         ts
     )
-    .fetch_one(pool)
+    .fetch_one(conn)
     .await?
     .kill_id;
 
     Ok(id)
 }
 
-pub async fn get_kills_for_raid(pool: &SqlitePool, raid_id: i64) -> Result<Vec<crate::models::Kill>, Error> {
+pub async fn get_kills_for_raid(conn: impl SqliteExecutor<'_>, raid_id: i64) -> Result<Vec<Kill>, Error> {
     sqlx::query_as!(
-        crate::models::Kill,
+        Kill,
         r#"
         SELECT
             kill_id as "kill_id!",
@@ -369,54 +724,1209 @@ pub async fn get_kills_for_raid(pool: &SqlitePool, raid_id: i64) -> Result<Vec<c
         "#,
         raid_id
     )
-    .fetch_all(pool)
+    .fetch_all(conn)
     .await
 }
 
+// All-or-nothing insert for entering a whole kill list at once
+pub async fn add_kills(
+    pool: &SqlitePool,
+    raid_id: i64,
+    kills: &[NewKill],
+) -> Result<Vec<i64>, Error> {
+    let mut tx: Transaction<'_, sqlx::Sqlite> = pool.begin().await?;
+    let mut ids = Vec::with_capacity(kills.len());
 
-#[cfg(test)]
-pub mod tests {
-    use tokio::time::sleep;
-    use std::time::Duration;
+    for kill in kills {
+        let ts = kill.killed_at.unwrap_or_else(OffsetDateTime::now_utc);
 
-    use super::*;
+        let id = sqlx::query!(
+            r#"
+            INSERT INTO kills (raid_id, enemy_type, weapon_used, headshot, killed_at)
+            VALUES (?, ?, ?, ?, ?)
+            RETURNING kill_id as "kill_id!"
+            "#,
+            raid_id,
+            kill.enemy_type,
+            kill.weapon_used,
+            kill.headshot,
+            ts
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .kill_id;
 
-    pub async fn setup_test_db() -> Result<SqlitePool, Error> {
-        let pool = create_pool("sqlite::memory:")
-            .await
-            .expect("Failed to create pool");
-        run_migrations(&pool)
-            .await
-            .expect("Failed to run migrations");
-        Ok(pool)
+        ids.push(id);
     }
 
-    #[tokio::test]
-    async fn test_init_schema() {
-        let pool = setup_test_db().await.expect("Failed to setup_test_db");
+    tx.commit().await?;
+    Ok(ids)
+}
 
-        let table_exists: (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='stream_sessions'"
-        )
-        .fetch_one(&pool)
-        .await
-        .expect("Failed to check table exists");
+pub async fn get_kill_by_id(conn: impl SqliteExecutor<'_>, kill_id: i64) -> Result<Option<Kill>, Error> {
+    sqlx::query_as!(
+        Kill,
+        r#"
+        SELECT
+            kill_id as "kill_id!",
+            raid_id as "raid_id!",
+            killed_at as "killed_at!",
+            enemy_type as "enemy_type!",
+            weapon_used,
+            headshot as "headshot: bool"
+        FROM kills
+        WHERE kill_id = ?
+        "#,
+        kill_id
+    )
+    .fetch_optional(conn)
+    .await
+}
 
-        assert_eq!(table_exists.0, 1);
-    }
+pub async fn update_kill(conn: impl SqliteExecutor<'_>, kill: &Kill) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        UPDATE kills
+        SET enemy_type = ?, weapon_used = ?, headshot = ?, killed_at = ?
+        WHERE kill_id = ?
+        "#,
+        kill.enemy_type,
+        kill.weapon_used,
+        kill.headshot,
+        kill.killed_at,
+        kill.kill_id
+    )
+    .execute(conn)
+    .await?;
 
-    #[tokio::test]
-    async fn test_get_transitions_for_nonexistent_raid() -> Result<(), Error> {
-        let pool = setup_test_db().await?;
+    Ok(())
+}
 
-        let transition = get_raid_transitions(&pool, 999).await?;
-        assert_eq!(transition.len(), 0); //should return empty vec, not error
+// Returns false if there was no such kill
+pub async fn delete_kill(conn: impl SqliteExecutor<'_>, kill_id: i64) -> Result<bool, Error> {
+    let result = sqlx::query!("DELETE FROM kills WHERE kill_id = ?", kill_id)
+        .execute(conn)
+        .await?;
 
-        Ok(())
-    }
+    Ok(result.rows_affected() > 0)
+}
 
-    #[tokio::test]
-    async fn test_get_session_by_id() -> Result<(), Error> {
+// ================================================================================================
+// History Listing Operations
+// ================================================================================================
+
+// Filters for the raid and session listings. The date range is on started_at, `to` is exclusive.
+// For sessions the raid filters choose which raids are counted, and sessions left with
+// no matching raid are dropped. has_kills goes by the session total there.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub from: Option<OffsetDateTime>,
+    pub to: Option<OffsetDateTime>,
+    pub map_name: Option<String>,
+    pub character_type: Option<CharacterType>,
+    pub game_mode: Option<GameMode>,
+    pub session_type: Option<SessionType>,
+    pub outcome: Option<String>,
+    pub has_kills: Option<bool>,
+}
+
+impl HistoryFilter {
+    fn filters_raids(&self) -> bool {
+        self.map_name.is_some() || self.character_type.is_some() || self.game_mode.is_some()
+            || self.outcome.is_some()
+    }
+}
+
+// Keyset position, the sort value and id of the last row on the previous page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor {
+    pub key: f64,
+    pub id: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    // None on the last page
+    pub next: Option<Cursor>,
+}
+
+#[derive(sqlx::FromRow)]
+struct Keyed<T> {
+    #[sqlx(flatten)]
+    item: T,
+    sort_key: f64,
+}
+
+// Timestamps are compared through julianday() since a backdated one can carry any UTC offset
+const RAID_ROWS: &str = r#"
+    WITH raid_rows AS (
+        SELECT
+            r.raid_id, r.session_id, r.started_at, r.ended_at, r.map_name, r.character_type,
+            r.game_mode, r.current_state, r.extract_location, r.parent_raid_id, r.segment_order, s.session_type,
+            (SELECT COUNT(*) FROM kills k WHERE k.raid_id = r.raid_id) AS kill_count,
+            CAST(ROUND((julianday(r.ended_at) - julianday(r.started_at)) * 86400) AS INTEGER) AS duration_seconds,
+            CAST(ROUND((julianday(r.ended_at) - julianday(r.started_at)) * 86400
+                - COALESCE(np.non_play_ms, 0) / 1000.0) AS INTEGER) AS active_duration_seconds
+        FROM raids r
+        LEFT JOIN stream_sessions s ON s.session_id = r.session_id
+        LEFT JOIN raid_non_play_time np ON np.raid_id = r.raid_id
+    )"#;
+
+fn push_raid_filters<'a>(qb: &mut QueryBuilder<'a, Sqlite>, filter: &'a HistoryFilter) {
+    if let Some(map_name) = &filter.map_name {
+        qb.push(" AND map_name = ").push_bind(map_name).push(" COLLATE NOCASE");
+    }
+    if let Some(character_type) = &filter.character_type {
+        qb.push(" AND character_type = ").push_bind(character_type.clone());
+    }
+    if let Some(game_mode) = &filter.game_mode {
+        qb.push(" AND game_mode = ").push_bind(game_mode.clone());
+    }
+    if let Some(outcome) = &filter.outcome {
+        qb.push(" AND current_state = ").push_bind(outcome);
+    }
+}
+
+fn push_date_filters<'a>(qb: &mut QueryBuilder<'a, Sqlite>, filter: &'a HistoryFilter, column: &str) {
+    if let Some(from) = filter.from {
+        qb.push(format!(" AND julianday({}) >= julianday(", column)).push_bind(from).push(")");
+    }
+    if let Some(to) = filter.to {
+        qb.push(format!(" AND julianday({}) < julianday(", column)).push_bind(to).push(")");
+    }
+}
+
+fn push_has_kills(qb: &mut QueryBuilder<'_, Sqlite>, has_kills: Option<bool>) {
+    match has_kills {
+        Some(true) => { qb.push(" AND kill_count > 0"); }
+        Some(false) => { qb.push(" AND kill_count = 0"); }
+        None => {}
+    }
+}
+
+// Wraps the filtered rows so the cursor can compare against sort_key, ties go by id
+fn push_page(qb: &mut QueryBuilder<'_, Sqlite>, id: &str, order: SortOrder, after: Option<Cursor>, limit: i64) {
+    let (cmp, dir) = match order {
+        SortOrder::Asc => (">", "ASC"),
+        SortOrder::Desc => ("<", "DESC"),
+    };
+
+    qb.push(") WHERE 1 = 1");
+    if let Some(cursor) = after {
+        qb.push(format!(" AND (sort_key, {}) {} (", id, cmp))
+            .push_bind(cursor.key)
+            .push(", ")
+            .push_bind(cursor.id)
+            .push(")");
+    }
+    // One extra row tells us whether there is another page
+    qb.push(format!(" ORDER BY sort_key {dir}, {id} {dir} LIMIT ")).push_bind(limit + 1);
+}
+
+fn into_page<T>(mut rows: Vec<Keyed<T>>, limit: i64, id: impl Fn(&T) -> i64) -> Page<T> {
+    let more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+
+    let next = match rows.last() {
+        Some(last) if more => Some(Cursor { key: last.sort_key, id: id(&last.item) }),
+        _ => None,
+    };
+    Page { items: rows.into_iter().map(|r| r.item).collect(), next }
+}
+
+pub async fn list_raids(
+    pool: &SqlitePool,
+    filter: &HistoryFilter,
+    sort: RaidSort,
+    order: SortOrder,
+    after: Option<Cursor>,
+    limit: i64,
+) -> Result<Page<RaidSummary>, Error> {
+    // Running raids sort as zero length
+    let sort_key = match sort {
+        RaidSort::StartedAt => "julianday(started_at)",
+        RaidSort::Duration => "COALESCE(duration_seconds, 0)",
+        RaidSort::Kills => "kill_count",
+    };
+
+    let mut qb = QueryBuilder::new(RAID_ROWS);
+    qb.push(format!(" SELECT * FROM (SELECT *, CAST({} AS REAL) AS sort_key FROM raid_rows WHERE 1 = 1", sort_key));
+    push_raid_filters(&mut qb, filter);
+    push_date_filters(&mut qb, filter, "started_at");
+    push_has_kills(&mut qb, filter.has_kills);
+    if let Some(session_type) = &filter.session_type {
+        qb.push(" AND session_type = ").push_bind(session_type.clone());
+    }
+    push_page(&mut qb, "raid_id", order, after, limit);
+
+    let rows: Vec<Keyed<RaidSummary>> = qb.build_query_as().fetch_all(pool).await?;
+    Ok(into_page(rows, limit, |r| r.raid.raid_id))
+}
+
+pub async fn list_sessions(
+    pool: &SqlitePool,
+    filter: &HistoryFilter,
+    sort: SessionSort,
+    order: SortOrder,
+    after: Option<Cursor>,
+    limit: i64,
+) -> Result<Page<SessionSummary>, Error> {
+    // Running sessions sort as zero length
+    let sort_key = match sort {
+        SessionSort::StartedAt => "julianday(started_at)",
+        SessionSort::Duration => "COALESCE(duration_seconds, 0)",
+        SessionSort::Kills => "kill_count",
+        SessionSort::Raids => "raid_count",
+    };
+
+    let mut qb = QueryBuilder::new(RAID_ROWS);
+    qb.push(r#",
+    session_rows AS (
+        SELECT
+            s.session_id, s.started_at, s.ended_at, s.session_type, s.notes,
+            COUNT(r.raid_id) AS raid_count,
+            COALESCE(SUM(r.kill_count), 0) AS kill_count,
+            CAST(ROUND((julianday(s.ended_at) - julianday(s.started_at)) * 86400) AS INTEGER) AS duration_seconds
+        FROM stream_sessions s
+        LEFT JOIN (SELECT * FROM raid_rows WHERE 1 = 1"#);
+    push_raid_filters(&mut qb, filter);
+    qb.push(") r ON r.session_id = s.session_id WHERE 1 = 1");
+    push_date_filters(&mut qb, filter, "s.started_at");
+    if let Some(session_type) = &filter.session_type {
+        qb.push(" AND s.session_type = ").push_bind(session_type.clone());
+    }
+    qb.push(" GROUP BY s.session_id");
+    if filter.filters_raids() {
+        qb.push(" HAVING COUNT(r.raid_id) > 0");
+    }
+    qb.push(format!(")
+    SELECT * FROM (SELECT *, CAST({} AS REAL) AS sort_key FROM session_rows WHERE 1 = 1", sort_key));
+    push_has_kills(&mut qb, filter.has_kills);
+    push_page(&mut qb, "session_id", order, after, limit);
+
+    let rows: Vec<Keyed<SessionSummary>> = qb.build_query_as().fetch_all(pool).await?;
+    Ok(into_page(rows, limit, |s| s.session.session_id))
+}
+
+// ================================================================================================
+// Stats Operations
+// ================================================================================================
+
+// States whose time is left out of a raid's active duration, e.g. reconnecting
+pub async fn get_non_play_states(pool: &SqlitePool) -> Result<Vec<String>, Error> {
+    sqlx::query_scalar!("SELECT state FROM non_play_states ORDER BY state")
+        .fetch_all(pool)
+        .await
+}
+
+// Replaces the whole set, stats pick it up on their next query
+pub async fn set_non_play_states(pool: &SqlitePool, states: &[String]) -> Result<(), Error> {
+    let mut tx: Transaction<'_, sqlx::Sqlite> = pool.begin().await?;
+
+    sqlx::query!("DELETE FROM non_play_states").execute(&mut *tx).await?;
+    for state in states {
+        sqlx::query!("INSERT OR IGNORE INTO non_play_states (state) VALUES (?)", state)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+// Which raids the stats cover, None or no session_ids means no restriction. unit
+// decides whether a transfer chain counts once or once per segment in the raid
+// totals, trends and streaks. from/to bound when the raid started, to is exclusive.
+#[derive(Debug, Clone, Default)]
+pub struct StatsFilter {
+    pub session_ids: Vec<i64>,
+    pub game_mode: Option<GameMode>,
+    pub character_type: Option<CharacterType>,
+    pub map_name: Option<String>,
+    pub session_type: Option<SessionType>,
+    pub from: Option<OffsetDateTime>,
+    pub to: Option<OffsetDateTime>,
+    pub unit: RaidUnit,
+}
+
+fn push_stats_filter<'a>(qb: &mut QueryBuilder<'a, Sqlite>, filter: &'a StatsFilter) {
+    if !filter.session_ids.is_empty() {
+        qb.push(" AND r.session_id IN (");
+        let mut ids = qb.separated(", ");
+        for session_id in &filter.session_ids {
+            ids.push_bind(*session_id);
+        }
+        qb.push(")");
+    }
+    if let Some(game_mode) = &filter.game_mode {
+        qb.push(" AND r.game_mode = ").push_bind(game_mode.clone());
+    }
+    if let Some(character_type) = &filter.character_type {
+        qb.push(" AND r.character_type = ").push_bind(character_type.clone());
+    }
+    if let Some(map_name) = &filter.map_name {
+        qb.push(" AND r.map_name = ").push_bind(map_name).push(" COLLATE NOCASE");
+    }
+    if let Some(session_type) = &filter.session_type {
+        qb.push(" AND r.session_id IN (SELECT session_id FROM stream_sessions WHERE session_type = ")
+            .push_bind(session_type.clone())
+            .push(")");
+    }
+    if let Some(from) = filter.from {
+        qb.push(" AND julianday(r.started_at) >= julianday(").push_bind(from).push(")");
+    }
+    if let Some(to) = filter.to {
+        qb.push(" AND julianday(r.started_at) < julianday(").push_bind(to).push(")");
+    }
+}
+
+// Map names are typed in by hand, so "customs" and "Customs" are one group. The name
+// shown for it is the spelling used for the most raids, the most recent one on a tie.
+const MAP_GROUP: &str = "r.map_name COLLATE NOCASE";
+const MAP_NAME: &str = r#"(
+    SELECT m.map_name FROM raids m
+    WHERE m.map_name = r.map_name COLLATE NOCASE
+    GROUP BY m.map_name
+    ORDER BY COUNT(*) DESC, MAX(julianday(m.started_at)) DESC, MAX(m.raid_id) DESC
+    LIMIT 1
+)"#;
+
+// Every raid row with its kills and time spent in non-play states
+const SEGMENT_ROWS: &str = r#"(
+    SELECT
+        raids.*,
+        (SELECT COUNT(*) FROM kills k WHERE k.raid_id = raids.raid_id) AS kill_count,
+        COALESCE(np.non_play_ms, 0) AS non_play_ms
+    FROM raids
+    LEFT JOIN raid_non_play_time np ON np.raid_id = raids.raid_id
+)"#;
+
+// One row per transfer chain under the first segment's id, map and start. The
+// outcome and end come from the last segment, kills and non-play time are summed
+// over all of them.
+const CHAIN_ROWS: &str = r#"(
+    SELECT
+        head.raid_id, head.session_id, head.started_at, tail.ended_at, head.map_name,
+        head.character_type, head.game_mode, tail.current_state, tail.extract_location,
+        head.parent_raid_id, head.segment_order,
+        (SELECT COUNT(*) FROM kills k JOIN raids seg ON seg.raid_id = k.raid_id
+         WHERE seg.raid_id = head.raid_id OR seg.parent_raid_id = head.raid_id) AS kill_count,
+        (SELECT COALESCE(SUM(np.non_play_ms), 0) FROM raid_non_play_time np
+         WHERE np.chain_id = head.raid_id) AS non_play_ms
+    FROM raids head
+    JOIN raids tail ON tail.raid_id = (
+        SELECT seg.raid_id FROM raids seg
+        WHERE seg.raid_id = head.raid_id OR seg.parent_raid_id = head.raid_id
+        ORDER BY seg.segment_order DESC LIMIT 1
+    )
+    WHERE head.parent_raid_id IS NULL
+)"#;
+
+// What the stats queries read as `r`
+fn raid_rows(unit: RaidUnit) -> &'static str {
+    match unit {
+        RaidUnit::Segments => SEGMENT_ROWS,
+        RaidUnit::Chains => CHAIN_ROWS,
+    }
+}
+
+// The RaidAggregate totals over the raids `r` in a group. A segment that ended in a
+// transfer has no outcome of its own, it isn't counted as a raid, its kills and time
+// are. Only died and mia are deaths, a raid still running isn't one yet. Durations only
+// cover ended raids, active_duration_ms leaves out their non-play time.
+const RAID_TOTALS: &str = r#"
+    COALESCE(SUM(r.current_state <> 'transfer'), 0) AS raid_count,
+    COALESCE(SUM(r.current_state = 'survived'), 0) AS survived_count,
+    COALESCE(SUM(r.current_state IN ('died', 'mia')), 0) AS died_count,
+    COALESCE(SUM(r.kill_count), 0) AS kill_count,
+    COUNT(r.ended_at) AS ended_count,
+    COALESCE(SUM(CAST(ROUND((julianday(r.ended_at) - julianday(r.started_at)) * 86400000) AS INTEGER)), 0)
+        AS duration_ms,
+    COALESCE(SUM(CASE WHEN r.ended_at IS NOT NULL THEN MAX(0,
+        CAST(ROUND((julianday(r.ended_at) - julianday(r.started_at)) * 86400000) AS INTEGER) - r.non_play_ms) END), 0)
+        AS active_duration_ms"#;
+
+// The group columns every aggregate row has, in select order
+const GROUP_COLUMNS: [&str; 6] = ["game_mode", "character_type", "map_name", "session_type", "session_id", "weekday"];
+
+// (output column, its value, what it is grouped and ordered by) for a group
+fn group_sql(group: StatsGroup) -> (&'static str, &'static str, &'static str) {
+    const SESSION_TYPE: &str = "(SELECT s.session_type FROM stream_sessions s WHERE s.session_id = r.session_id)";
+    const WEEKDAY: &str = "strftime('%w', r.started_at)";
+
+    match group {
+        StatsGroup::GameMode => ("game_mode", "r.game_mode", "r.game_mode"),
+        StatsGroup::CharacterType => ("character_type", "r.character_type", "r.character_type"),
+        StatsGroup::Map => ("map_name", MAP_NAME, MAP_GROUP),
+        StatsGroup::SessionType => ("session_type", SESSION_TYPE, SESSION_TYPE),
+        StatsGroup::Session => ("session_id", "r.session_id", "r.session_id"),
+        // Sorted Sunday first like %w
+        StatsGroup::Weekday => ("weekday", r#"CASE strftime('%w', r.started_at)
+            WHEN '0' THEN 'sunday' WHEN '1' THEN 'monday' WHEN '2' THEN 'tuesday' WHEN '3' THEN 'wednesday'
+            WHEN '4' THEN 'thursday' WHEN '5' THEN 'friday' ELSE 'saturday' END"#, WEEKDAY),
+    }
+}
+
+// The group columns to select, NULL for the ones not grouped on, and the GROUP BY /
+// ORDER BY clause. Without groups there is no clause and one row comes back.
+fn group_columns(group_by: &[StatsGroup]) -> (String, String) {
+    let groups: Vec<_> = group_by.iter().map(|g| group_sql(*g)).collect();
+
+    let columns = GROUP_COLUMNS.iter().map(|column| {
+        match groups.iter().find(|(name, _, _)| name == column) {
+            Some((_, expr, _)) => format!("{expr} AS {column}"),
+            None => format!("NULL AS {column}"),
+        }
+    }).collect::<Vec<_>>().join(", ");
+
+    let group_exprs = groups.iter().map(|(_, _, group_expr)| *group_expr).collect::<Vec<_>>().join(", ");
+    let clause = if groups.is_empty() {
+        String::new()
+    } else {
+        format!(" GROUP BY {group_exprs} ORDER BY {group_exprs}")
+    };
+
+    (columns, clause)
+}
+
+// Raid count, survivals, kills and durations in one pass, split by any mix of groups.
+// Without a group there is always exactly one row, zeros included.
+pub async fn aggregate_raids(
+    pool: &SqlitePool,
+    filter: &StatsFilter,
+    group_by: &[StatsGroup],
+) -> Result<Vec<RaidAggregate>, Error> {
+    let (columns, group_clause) = group_columns(group_by);
+
+    let mut qb = QueryBuilder::new(format!("SELECT {columns}, {RAID_TOTALS} FROM {} r WHERE 1 = 1",
+        raid_rows(filter.unit)));
+    push_stats_filter(&mut qb, filter);
+    qb.push(group_clause);
+
+    qb.build_query_as().fetch_all(pool).await
+}
+
+// Totals for each of the last `limit` sessions or raids, oldest first. Sessions
+// without a matching raid are skipped so they don't show up as zeros.
+pub async fn aggregate_recent(
+    pool: &SqlitePool,
+    filter: &StatsFilter,
+    unit: TrendUnit,
+    limit: i64,
+) -> Result<Vec<TrendAggregate>, Error> {
+    let (id, started_at) = match unit {
+        TrendUnit::Sessions => ("s.session_id", "MIN(s.started_at)"),
+        TrendUnit::Raids => ("r.raid_id", "MIN(r.started_at)"),
+    };
+    let rows = raid_rows(filter.unit);
+
+    let mut qb = QueryBuilder::new(format!(r#"
+        SELECT * FROM (
+            SELECT
+                {id} AS id,
+                {started_at} AS started_at,
+                NULL AS game_mode, NULL AS character_type, NULL AS map_name,
+                NULL AS session_type, NULL AS session_id, NULL AS weekday,
+                {RAID_TOTALS}
+            FROM {rows} r
+            JOIN stream_sessions s ON s.session_id = r.session_id
+            WHERE 1 = 1"#));
+    push_stats_filter(&mut qb, filter);
+    qb.push(format!(" GROUP BY {id} ORDER BY julianday({started_at}) DESC, {id} DESC LIMIT "))
+        .push_bind(limit)
+        .push(") ORDER BY julianday(started_at), id");
+
+    qb.build_query_as().fetch_all(pool).await
+}
+
+// Opens a query with `spans` (raid_id, to_state, span_ms), the time each transition of
+// the matching raids lasted. A state runs until the raid's next transition, the last one
+// until the raid ended. A running raid's current state has no end yet, its span_ms is NULL.
+fn spans_query<'a>(filter: &'a StatsFilter, raid_id: Option<i64>) -> QueryBuilder<'a, Sqlite> {
+    let mut qb = QueryBuilder::new(r#"
+        WITH spans AS (
+            SELECT
+                t.raid_id,
+                t.to_state,
+                (julianday(COALESCE(
+                    LEAD(t.transitioned_at) OVER (
+                        PARTITION BY t.raid_id ORDER BY julianday(t.transitioned_at), t.transition_id
+                    ),
+                    r.ended_at
+                )) - julianday(t.transitioned_at)) * 86400000 AS span_ms
+            FROM raid_state_transitions t
+            JOIN raids r ON r.raid_id = t.raid_id
+            WHERE 1 = 1"#);
+    push_stats_filter(&mut qb, filter);
+    if let Some(raid_id) = raid_id {
+        qb.push(" AND r.raid_id = ").push_bind(raid_id);
+    }
+    qb.push(")");
+    qb
+}
+
+// Total time spent in queuing per map, a raid that cancelled and requeued counts
+// both queues
+pub async fn aggregate_queue_times(pool: &SqlitePool, filter: &StatsFilter) -> Result<Vec<MapQueueTime>, Error> {
+    let mut qb = spans_query(filter, None);
+    qb.push(format!(r#"
+        SELECT
+            {MAP_NAME} AS map_name,
+            COUNT(DISTINCT r.raid_id) AS raid_count,
+            CAST(ROUND(SUM(s.span_ms)) AS INTEGER) AS queue_ms
+        FROM spans s
+        JOIN raids r ON r.raid_id = s.raid_id
+        WHERE s.to_state = 'queuing' AND s.span_ms IS NOT NULL
+        GROUP BY {MAP_GROUP}"#));
+
+    qb.build_query_as().fetch_all(pool).await
+}
+
+// Time per state over the matching raids
+pub async fn aggregate_state_times(pool: &SqlitePool, filter: &StatsFilter) -> Result<Vec<StateDuration>, Error> {
+    let mut qb = spans_query(filter, None);
+    qb.push(r#"
+        SELECT
+            to_state AS state,
+            CAST(ROUND(SUM(span_ms)) AS INTEGER) AS duration_ms
+        FROM spans
+        WHERE span_ms IS NOT NULL
+        GROUP BY to_state"#);
+
+    qb.build_query_as().fetch_all(pool).await
+}
+
+// Queue and deploy time per raid are the spans of those states. Grouped like
+// aggregate_raids, one row when ungrouped.
+async fn aggregate_waits(
+    pool: &SqlitePool,
+    filter: &StatsFilter,
+    raid_id: Option<i64>,
+    group_by: &[StatsGroup],
+) -> Result<Vec<WaitAggregate>, Error> {
+    let mut qb = spans_query(filter, raid_id);
+
+    let (columns, group_clause) = group_columns(group_by);
+    qb.push(format!(r#",
+        waits AS (
+            SELECT
+                raid_id,
+                SUM(CASE WHEN to_state = 'queuing' THEN span_ms ELSE 0 END) AS queue_ms,
+                SUM(CASE WHEN to_state = 'deploying_cancellable' THEN span_ms ELSE 0 END) AS cancellable_ms,
+                SUM(CASE WHEN to_state = 'deploying_committed' THEN span_ms ELSE 0 END) AS committed_ms
+            FROM spans
+            WHERE span_ms IS NOT NULL
+              AND to_state IN ('queuing', 'deploying_cancellable', 'deploying_committed')
+            GROUP BY raid_id
+        )
+        SELECT
+            {columns},
+            COUNT(*) AS raid_count,
+            COALESCE(CAST(ROUND(SUM(w.queue_ms)) AS INTEGER), 0) AS queue_ms,
+            COALESCE(CAST(ROUND(SUM(w.cancellable_ms)) AS INTEGER), 0) AS deploying_cancellable_ms,
+            COALESCE(CAST(ROUND(SUM(w.committed_ms)) AS INTEGER), 0) AS deploying_committed_ms
+        FROM waits w
+        JOIN raids r ON r.raid_id = w.raid_id"#));
+    qb.push(group_clause);
+
+    qb.build_query_as().fetch_all(pool).await
+}
+
+pub async fn aggregate_wait_times(
+    pool: &SqlitePool,
+    filter: &StatsFilter,
+    group_by: &[StatsGroup],
+) -> Result<Vec<WaitAggregate>, Error> {
+    aggregate_waits(pool, filter, None, group_by).await
+}
+
+pub async fn get_raid_wait_times(pool: &SqlitePool, raid_id: i64) -> Result<WaitAggregate, Error> {
+    aggregate_waits(pool, &StatsFilter::default(), Some(raid_id), &[]).await?
+        .pop()
+        .ok_or(Error::RowNotFound)
+}
+
+// Ended raids in the order they were played
+pub async fn get_raid_outcomes(pool: &SqlitePool, filter: &StatsFilter) -> Result<Vec<RaidOutcome>, Error> {
+    let mut qb = QueryBuilder::new(format!(r#"
+        SELECT
+            r.raid_id,
+            r.current_state = 'survived' AS survived,
+            r.current_state = 'transfer' AS transferred,
+            r.kill_count
+        FROM {} r
+        WHERE r.ended_at IS NOT NULL"#, raid_rows(filter.unit)));
+    push_stats_filter(&mut qb, filter);
+    qb.push(" ORDER BY julianday(r.started_at), r.raid_id");
+
+    qb.build_query_as().fetch_all(pool).await
+}
+
+// A session needs this many ended raids before its survival rate can be a record,
+// otherwise one lucky raid is 100% forever
+pub const MIN_RECORD_SESSION_RAIDS: i64 = 3;
+
+// The best `limit` holders of a record, best first. On a tie whoever got there
+// first keeps it.
+pub async fn get_record_holders(pool: &SqlitePool, kind: RecordKind, limit: i64) -> Result<Vec<RecordHolder>, Error> {
+    let sql = match kind {
+        RecordKind::MostKills => r#"
+            SELECT r.raid_id, r.session_id, CAST(COUNT(*) AS REAL) AS value, r.ended_at AS set_at
+            FROM raids r
+            JOIN kills k ON k.raid_id = r.raid_id
+            WHERE r.ended_at IS NOT NULL
+            GROUP BY r.raid_id
+            ORDER BY value DESC, julianday(set_at), r.raid_id"#,
+        RecordKind::LongestRaid => r#"
+            SELECT r.raid_id, r.session_id,
+                ROUND((julianday(r.ended_at) - julianday(r.started_at)) * 86400) AS value, r.ended_at AS set_at
+            FROM raids r
+            WHERE r.ended_at IS NOT NULL
+            ORDER BY value DESC, julianday(set_at), r.raid_id"#,
+        RecordKind::FastestExtract => r#"
+            SELECT r.raid_id, r.session_id,
+                ROUND((julianday(r.ended_at) - julianday(r.started_at)) * 86400) AS value, r.ended_at AS set_at
+            FROM raids r
+            WHERE r.ended_at IS NOT NULL AND r.current_state = 'survived'
+              AND julianday(r.ended_at) > julianday(r.started_at)
+            ORDER BY value, julianday(set_at), r.raid_id"#,
+        RecordKind::MostRaidsInSession => r#"
+            SELECT NULL AS raid_id, r.session_id, CAST(COUNT(*) AS REAL) AS value, MAX(r.ended_at) AS set_at
+            FROM raids r
+            WHERE r.ended_at IS NOT NULL AND r.current_state <> 'transfer'
+            GROUP BY r.session_id
+            ORDER BY value DESC, julianday(set_at), r.session_id"#,
+        RecordKind::BestSessionSurvivalRate => r#"
+            SELECT NULL AS raid_id, r.session_id, AVG(r.current_state = 'survived') AS value, MAX(r.ended_at) AS set_at
+            FROM raids r
+            WHERE r.ended_at IS NOT NULL AND r.current_state <> 'transfer'
+            GROUP BY r.session_id
+            HAVING COUNT(*) >= ?
+            ORDER BY value DESC, julianday(set_at), r.session_id"#,
+    };
+
+    let sql = format!("{sql} LIMIT ?");
+    let mut query = sqlx::query_as(&sql);
+    if kind == RecordKind::BestSessionSurvivalRate {
+        query = query.bind(MIN_RECORD_SESSION_RAIDS);
+    }
+    query.bind(limit).fetch_all(pool).await
+}
+
+// Extracts used per map, most used first with the latest use breaking ties
+pub async fn get_map_extracts(pool: &SqlitePool, filter: &StatsFilter) -> Result<Vec<MapExtract>, Error> {
+    let mut qb = QueryBuilder::new(format!(r#"
+        SELECT
+            {MAP_NAME} AS map_name,
+            MIN(r.extract_location) AS extract_location,
+            COUNT(*) AS uses
+        FROM raids r
+        WHERE r.extract_location IS NOT NULL"#));
+    push_stats_filter(&mut qb, filter);
+    qb.push(format!(
+        " GROUP BY {MAP_GROUP}, r.extract_location COLLATE NOCASE ORDER BY uses DESC, MAX(julianday(r.started_at)) DESC"
+    ));
+
+    qb.build_query_as().fetch_all(pool).await
+}
+
+// "M4A1", "m4a1" and "M4-A1" are the same gun. Like maps, the name shown is the
+// spelling with the most kills, the most recent one on a tie.
+const WEAPON_KEY: &str = "lower(replace(replace(replace(trim(k.weapon_used), ' ', ''), '-', ''), '.', ''))";
+const WEAPON_NAME: &str = r#"(
+    SELECT trim(w.weapon_used) FROM kills w
+    WHERE lower(replace(replace(replace(trim(w.weapon_used), ' ', ''), '-', ''), '.', ''))
+        = lower(replace(replace(replace(trim(k.weapon_used), ' ', ''), '-', ''), '.', ''))
+    GROUP BY trim(w.weapon_used)
+    ORDER BY COUNT(*) DESC, MAX(julianday(w.killed_at)) DESC, MAX(w.kill_id) DESC
+    LIMIT 1
+)"#;
+
+// Most kills first, headshots break ties. Kills without a weapon are left out.
+pub async fn aggregate_weapons(pool: &SqlitePool, filter: &StatsFilter) -> Result<Vec<WeaponAggregate>, Error> {
+    let mut qb = QueryBuilder::new(format!(r#"
+        SELECT
+            {WEAPON_KEY} AS weapon_key,
+            {WEAPON_NAME} AS weapon,
+            COUNT(*) AS kill_count,
+            COALESCE(SUM(k.headshot = 1), 0) AS headshot_count,
+            COUNT(k.headshot) AS headshot_known
+        FROM kills k
+        JOIN raids r ON r.raid_id = k.raid_id
+        WHERE {WEAPON_KEY} != ''"#));
+    push_stats_filter(&mut qb, filter);
+    qb.push(" GROUP BY weapon_key ORDER BY kill_count DESC, headshot_count DESC, weapon_key");
+
+    qb.build_query_as().fetch_all(pool).await
+}
+
+pub async fn count_weapon_enemies(pool: &SqlitePool, filter: &StatsFilter) -> Result<Vec<WeaponEnemyCount>, Error> {
+    let mut qb = QueryBuilder::new(format!(r#"
+        SELECT
+            {WEAPON_KEY} AS weapon_key,
+            lower(trim(k.enemy_type)) AS enemy_type,
+            COUNT(*) AS kill_count
+        FROM kills k
+        JOIN raids r ON r.raid_id = k.raid_id
+        WHERE {WEAPON_KEY} != ''"#));
+    push_stats_filter(&mut qb, filter);
+    qb.push(" GROUP BY weapon_key, lower(trim(k.enemy_type))");
+
+    qb.build_query_as().fetch_all(pool).await
+}
+
+// Headshots over every kill, weapon or not
+pub async fn count_headshots(pool: &SqlitePool, filter: &StatsFilter) -> Result<(i64, i64, i64), Error> {
+    let mut qb = QueryBuilder::new(r#"
+        SELECT COUNT(*), COALESCE(SUM(k.headshot = 1), 0), COUNT(k.headshot)
+        FROM kills k
+        JOIN raids r ON r.raid_id = k.raid_id
+        WHERE 1 = 1"#);
+    push_stats_filter(&mut qb, filter);
+
+    qb.build_query_as().fetch_one(pool).await
+}
+
+// ================================================================================================
+// Import Operations
+// ================================================================================================
+
+// Writes a validated import in one transaction, returns the session id and raid ids in order
+pub async fn insert_import(conn: impl Acquire<'_, Database = Sqlite>, plan: &ImportPlan) -> Result<(i64, Vec<i64>), Error> {
+    let mut tx = conn.begin().await?;
+
+    let session_id = sqlx::query!(
+        r#"
+        INSERT INTO stream_sessions (session_type, notes, started_at, ended_at)
+        VALUES (?, ?, ?, ?)
+        RETURNING session_id
+        "#,
+        plan.session.session_type,
+        plan.session.notes,
+        plan.session.started_at,
+        plan.session.ended_at
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .session_id;
+
+    let mut raid_ids = Vec::with_capacity(plan.raids.len());
+
+    for raid in &plan.raids {
+        let current_state = raid.current_state.as_str();
+
+        let raid_id = sqlx::query!(
+            r#"
+            INSERT INTO raids (session_id, map_name, character_type, game_mode, started_at,
+                               ended_at, current_state, extract_location)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING raid_id
+            "#,
+            session_id,
+            raid.map_name,
+            raid.character_type,
+            raid.game_mode,
+            raid.started_at,
+            raid.ended_at,
+            current_state,
+            raid.extract_location
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .raid_id;
+
+        for (from_state, to_state, ts) in &raid.transitions {
+            let (from_state, to_state) = (from_state.as_str(), to_state.as_str());
+
+            sqlx::query!(
+                r#"
+                INSERT INTO raid_state_transitions (raid_id, from_state, to_state, transitioned_at)
+                VALUES (?, ?, ?, ?)
+                "#,
+                raid_id,
+                from_state,
+                to_state,
+                ts
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for kill in &raid.kills {
+            sqlx::query!(
+                r#"
+                INSERT INTO kills (raid_id, enemy_type, weapon_used, headshot, killed_at)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+                raid_id,
+                kill.enemy_type,
+                kill.weapon_used,
+                kill.headshot,
+                kill.killed_at
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        raid_ids.push(raid_id);
+    }
+
+    tx.commit().await?;
+    Ok((session_id, raid_ids))
+}
+
+// ================================================================================================
+// Audit Operations
+// ================================================================================================
+pub async fn add_audit_entry(conn: impl SqliteExecutor<'_>, entry: &NewAuditEntry) -> Result<i64, Error> {
+    let changed_at = OffsetDateTime::now_utc();
+
+    let id = sqlx::query!(
+        r#"
+        INSERT INTO audit_log (table_name, row_id, action, before_json, after_json, source, changed_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        RETURNING audit_id as "audit_id!"
+        "#,
+        entry.table_name,
+        entry.row_id,
+        entry.action,
+        entry.before_json,
+        entry.after_json,
+        entry.source,
+        changed_at
+    )
+    .fetch_one(conn)
+    .await?
+    .audit_id;
+
+    Ok(id)
+}
+
+// Newest first
+pub async fn get_audit_entries(
+    pool: &SqlitePool,
+    table_name: Option<&str>,
+    row_id: Option<i64>,
+) -> Result<Vec<AuditEntry>, Error> {
+    sqlx::query_as!(
+        AuditEntry,
+        r#"
+        SELECT
+            audit_id as "audit_id!",
+            table_name as "table_name!",
+            row_id as "row_id!",
+            action as "action!",
+            before_json,
+            after_json,
+            source as "source!",
+            changed_at as "changed_at!"
+        FROM audit_log
+        WHERE (?1 IS NULL OR table_name = ?1)
+          AND (?2 IS NULL OR row_id = ?2)
+        ORDER BY audit_id DESC
+        "#,
+        table_name,
+        row_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// ================================================================================================
+// Undo Operations
+// ================================================================================================
+
+// These put rows back exactly as they were, ids included, so later history entries
+// still point at the right rows after an undo/redo round trip
+pub async fn restore_session(conn: impl SqliteExecutor<'_>, session: &StreamSession) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO stream_sessions (session_id, started_at, ended_at, session_type, notes)
+        VALUES (?, ?, ?, ?, ?)
+        "#,
+        session.session_id,
+        session.started_at,
+        session.ended_at,
+        session.session_type,
+        session.notes
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_session(conn: impl SqliteExecutor<'_>, session_id: i64) -> Result<bool, Error> {
+    let result = sqlx::query!("DELETE FROM stream_sessions WHERE session_id = ?", session_id)
+        .execute(conn)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn reopen_session(conn: impl SqliteExecutor<'_>, session_id: i64) -> Result<(), Error> {
+    sqlx::query!("UPDATE stream_sessions SET ended_at = NULL WHERE session_id = ?", session_id)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn restore_raid(conn: impl SqliteExecutor<'_>, raid: &Raid) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO raids (raid_id, session_id, started_at, ended_at, map_name, character_type,
+                           game_mode, current_state, extract_location, parent_raid_id, segment_order)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+        raid.raid_id,
+        raid.session_id,
+        raid.started_at,
+        raid.ended_at,
+        raid.map_name,
+        raid.character_type,
+        raid.game_mode,
+        raid.current_state,
+        raid.extract_location,
+        raid.parent_raid_id,
+        raid.segment_order
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn reopen_raid(conn: impl SqliteExecutor<'_>, raid_id: i64) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE raids SET ended_at = NULL, extract_location = NULL WHERE raid_id = ?",
+        raid_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn restore_kill(conn: impl SqliteExecutor<'_>, kill: &Kill) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO kills (kill_id, raid_id, killed_at, enemy_type, weapon_used, headshot)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#,
+        kill.kill_id,
+        kill.raid_id,
+        kill.killed_at,
+        kill.enemy_type,
+        kill.weapon_used,
+        kill.headshot
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+// ================================================================================================
+// Review Queue Operations
+// ================================================================================================
+pub async fn queue_event(
+    pool: &SqlitePool,
+    source: &str,
+    event_type: &str,
+    confidence: Option<f64>,
+    payload: &str,
+    occurred_at: Option<OffsetDateTime>,
+) -> Result<i64, Error> {
+    let received_at = OffsetDateTime::now_utc();
+
+    let id = sqlx::query!(
+        r#"
+        INSERT INTO pending_events (source, event_type, confidence, payload, occurred_at, received_at)
+        VALUES (?, ?, ?, ?, ?, ?)
+        RETURNING pending_id as "pending_id!"
+        "#,
+        source,
+        event_type,
+        confidence,
+        payload,
+        occurred_at,
+        received_at
+    )
+    .fetch_one(pool)
+    .await?
+    .pending_id;
+
+    Ok(id)
+}
+
+pub async fn get_pending_event(conn: impl SqliteExecutor<'_>, pending_id: i64) -> Result<Option<PendingEvent>, Error> {
+    sqlx::query_as!(
+        PendingEvent,
+        r#"
+        SELECT
+            pending_id as "pending_id!",
+            source as "source!",
+            event_type as "event_type!",
+            confidence,
+            payload as "payload!",
+            occurred_at,
+            received_at as "received_at!",
+            status as "status!: ReviewStatus",
+            reviewed_at,
+            review_note
+        FROM pending_events
+        WHERE pending_id = ?
+        "#,
+        pending_id
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+// Oldest first, which is also the order they should be applied in
+pub async fn get_pending_events(
+    pool: &SqlitePool,
+    status: Option<ReviewStatus>,
+    source: Option<&str>,
+) -> Result<Vec<PendingEvent>, Error> {
+    sqlx::query_as!(
+        PendingEvent,
+        r#"
+        SELECT
+            pending_id as "pending_id!",
+            source as "source!",
+            event_type as "event_type!",
+            confidence,
+            payload as "payload!",
+            occurred_at,
+            received_at as "received_at!",
+            status as "status!: ReviewStatus",
+            reviewed_at,
+            review_note
+        FROM pending_events
+        WHERE (?1 IS NULL OR status = ?1)
+          AND (?2 IS NULL OR source = ?2)
+        ORDER BY pending_id ASC
+        "#,
+        status,
+        source
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Operator edits, only the event itself can change
+// Both of these only touch an event that's still pending, false means it got reviewed first
+pub async fn update_pending_event(conn: impl SqliteExecutor<'_>, event: &PendingEvent) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE pending_events
+        SET event_type = ?, payload = ?, occurred_at = ?
+        WHERE pending_id = ? AND status = 'pending'
+        "#,
+        event.event_type,
+        event.payload,
+        event.occurred_at,
+        event.pending_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn set_review_status(
+    conn: impl SqliteExecutor<'_>,
+    pending_id: i64,
+    status: ReviewStatus,
+    note: Option<String>,
+) -> Result<bool, Error> {
+    let reviewed_at = OffsetDateTime::now_utc();
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE pending_events
+        SET status = ?, reviewed_at = ?, review_note = ?
+        WHERE pending_id = ? AND status = 'pending'
+        "#,
+        status,
+        reviewed_at,
+        note,
+        pending_id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+
+#[cfg(test)]
+pub mod tests {
+    use tokio::time::sleep;
+    use std::time::Duration;
+
+    use super::*;
+
+    pub async fn setup_test_db() -> Result<SqlitePool, Error> {
+        let pool = create_pool("sqlite::memory:")
+            .await
+            .expect("Failed to create pool");
+        run_migrations(&pool)
+            .await
+            .expect("Failed to run migrations");
+        Ok(pool)
+    }
+
+    #[tokio::test]
+    async fn test_init_schema() {
+        let pool = setup_test_db().await.expect("Failed to setup_test_db");
+
+        let table_exists: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='stream_sessions'"
+        )
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to check table exists");
+
+        assert_eq!(table_exists.0, 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_transitions_for_nonexistent_raid() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+
+        let transition = get_raid_transitions(&pool, 999).await?;
+        assert_eq!(transition.len(), 0); //should return empty vec, not error
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_log_state_transition_checks_the_graph() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+        let session_id = create_session(&pool, SessionType::Stream, None, None).await?;
+        let raid_id = create_raid(&pool, session_id, "Customs", CharacterType::PMC, GameMode::PVE, None).await?;
+
+        // Straight from the stash into a raid skips the queue
+        let err = log_state_transition(&pool, raid_id, "raid_active", None).await.unwrap_err();
+        assert!(matches!(err, TransitionError::Invalid(_)));
+        assert!(get_raid_transitions(&pool, raid_id).await?.is_empty());
+
+        log_state_transition(&pool, raid_id, "pre_raid_setup", None).await.expect("legal move");
+        log_forced_transition(&pool, raid_id, "raid_active", None).await?;
+
+        let raid = get_raid_by_id(&pool, raid_id).await?.unwrap();
+        assert_eq!(raid.current_state, "raid_active");
+        assert_eq!(get_raid_transitions(&pool, raid_id).await?.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_session_by_id() -> Result<(), Error> {
         let pool = setup_test_db().await?;
 
         let session_id = create_session(&pool, SessionType::Stream, Some("Test Stream".into()), None).await?;
@@ -437,7 +1947,7 @@ pub mod tests {
         assert_eq!(session_id, 1);
         let _ = sleep(Duration::from_millis(100)).await;
 
-        end_session(&pool, session_id).await?;
+        end_session(&pool, session_id, None).await?;
 
         let session_id_2 = create_session(&pool, SessionType::Stream, Some("Test Stream".into()), None).await?;
         assert_eq!(session_id_2, 2);
@@ -455,6 +1965,21 @@ pub mod tests {
 
     }
 
+    #[tokio::test]
+    async fn test_end_session_with_explicit_time() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+        let started_at = OffsetDateTime::now_utc() - time::Duration::hours(3);
+        let ended_at = started_at + time::Duration::hours(2);
+
+        let session_id = create_session(&pool, SessionType::Stream, None, Some(started_at)).await?;
+        end_session(&pool, session_id, Some(ended_at)).await?;
+
+        let session = get_session_by_id(&pool, session_id).await?.expect("session exists");
+        assert_eq!(session.ended_at, Some(ended_at));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_session_lifecycle() -> Result<(), Error> {
         let pool = setup_test_db().await.expect("Failed to setup_test_db");
@@ -480,9 +2005,9 @@ pub mod tests {
         let mut time = OffsetDateTime::now_utc();
 
         // Transitions
-        log_state_transition(&pool, raid_id, "queue", Some(time)).await.expect("Should have done a state transition");
+        log_forced_transition(&pool, raid_id, "queue", Some(time)).await.expect("Should have done a state transition");
         time = time + time::Duration::seconds(10);
-        log_state_transition(&pool, raid_id, "in_raid", Some(time)).await.expect("Should have done another state transition");
+        log_forced_transition(&pool, raid_id, "in_raid", Some(time)).await.expect("Should have done another state transition");
 
         let transitions = get_raid_transitions(&pool, raid_id).await?;
         assert_eq!(transitions.len(), 2);
@@ -535,7 +2060,7 @@ pub mod tests {
         end_raid(&pool, raid_id, None, None).await.expect("Failed to end_raid()");
 
         //End Session
-        end_session(&pool, session_id).await.expect("Failed to end_session()");
+        end_session(&pool, session_id, None).await.expect("Failed to end_session()");
 
         let active = get_active_session(&pool).await?;
         assert!(active.is_none());
@@ -600,4 +2125,246 @@ pub mod tests {
         pool.close().await;
         Ok(())
     }
+
+    fn new_kill(enemy_type: &str, killed_at: Option<OffsetDateTime>) -> NewKill {
+        NewKill {
+            enemy_type: enemy_type.to_string(),
+            weapon_used: None,
+            headshot: None,
+            killed_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_kills_batch() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+        let base_time = OffsetDateTime::now_utc();
+
+        let s1 = create_session(&pool, SessionType::Stream, None, Some(base_time)).await?;
+        let r1 = create_raid(&pool, s1, "Woods", CharacterType::PMC, GameMode::PVE, Some(base_time)).await?;
+
+        let ids = add_kills(&pool, r1, &[
+            new_kill("scav", Some(base_time + time::Duration::minutes(2))),
+            new_kill("pmc", Some(base_time + time::Duration::minutes(1))),
+        ]).await?;
+        assert_eq!(ids.len(), 2);
+
+        let kills = get_kills_for_raid(&pool, r1).await?;
+        assert_eq!(kills.len(), 2);
+        assert_eq!(kills[0].enemy_type, "pmc");
+
+        pool.close().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_add_kills_batch_is_atomic() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+
+        let s1 = create_session(&pool, SessionType::Stream, None, None).await?;
+        let r1 = create_raid(&pool, s1, "Woods", CharacterType::PMC, GameMode::PVE, None).await?;
+
+        // Make the second insert of the batch fail
+        sqlx::query(
+            "CREATE TRIGGER fail_kill BEFORE INSERT ON kills WHEN NEW.enemy_type = 'boom'
+             BEGIN SELECT RAISE(ABORT, 'boom'); END"
+        ).execute(&pool).await?;
+
+        let result = add_kills(&pool, r1, &[new_kill("scav", None), new_kill("boom", None)]).await;
+        assert!(result.is_err());
+
+        let kills = get_kills_for_raid(&pool, r1).await?;
+        assert!(kills.is_empty(), "first kill of a failed batch must not persist");
+
+        pool.close().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete_kill() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+
+        let s1 = create_session(&pool, SessionType::Stream, None, None).await?;
+        let r1 = create_raid(&pool, s1, "Woods", CharacterType::PMC, GameMode::PVE, None).await?;
+        let kill_id = add_kill(&pool, r1, "scav", Some("AK-74M".into()), Some(false), None).await?;
+
+        let mut kill = get_kill_by_id(&pool, kill_id).await?.expect("kill should exist");
+        kill.enemy_type = "pmc".into();
+        kill.headshot = Some(true);
+        update_kill(&pool, &kill).await?;
+
+        let updated = get_kill_by_id(&pool, kill_id).await?.expect("kill should exist");
+        assert_eq!(updated.enemy_type, "pmc");
+        assert_eq!(updated.headshot, Some(true));
+        assert_eq!(updated.weapon_used, Some("AK-74M".into()));
+
+        assert!(delete_kill(&pool, kill_id).await?);
+        assert!(!delete_kill(&pool, kill_id).await?);
+        assert!(get_kill_by_id(&pool, kill_id).await?.is_none());
+
+        pool.close().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_raid_by_id() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+
+        let s1 = create_session(&pool, SessionType::Stream, None, None).await?;
+        let r1 = create_raid(&pool, s1, "Lighthouse", CharacterType::Scav, GameMode::PVP, None).await?;
+
+        let raid = get_raid_by_id(&pool, r1).await?.expect("raid should exist");
+        assert_eq!(raid.map_name, "Lighthouse");
+        assert!(get_raid_by_id(&pool, 999).await?.is_none());
+
+        pool.close().await;
+        Ok(())
+    }
+
+    // A stream session and a practice session two days later, raid i lasts 21 + i minutes
+    pub async fn seed_history(pool: &SqlitePool) -> Result<Vec<i64>, Error> {
+        let start = OffsetDateTime::now_utc() - time::Duration::days(3);
+        let stream = create_session(pool, SessionType::Stream, None, Some(start)).await?;
+        let practice = create_session(pool, SessionType::Practice, None, Some(start + time::Duration::days(2))).await?;
+        let raids = [
+            (0, "Customs", GameMode::PVP, "died", 2),
+            (0, "customs", GameMode::PVE, "survived", 0),
+            (0, "Woods", GameMode::PVP, "survived", 1),
+            (2, "Customs", GameMode::PVP, "mia", 3),
+            (2, "Shoreline", GameMode::PVE, "died", 0),
+        ];
+
+        let mut raid_ids = Vec::new();
+        for (i, (day, map, mode, outcome, kills)) in raids.into_iter().enumerate() {
+            let raid_start = start + time::Duration::days(day) + time::Duration::hours(i as i64);
+            let session_id = if day == 0 { stream } else { practice };
+
+            let raid_id = create_raid(pool, session_id, map, CharacterType::PMC, mode, Some(raid_start)).await?;
+            for _ in 0..kills {
+                add_kill(pool, raid_id, "pmc", None, None, Some(raid_start)).await?;
+            }
+            let ended_at = raid_start + time::Duration::minutes(21 + i as i64);
+            log_forced_transition(pool, raid_id, outcome, Some(ended_at)).await?;
+            end_raid(pool, raid_id, Some(ended_at), None).await?;
+            raid_ids.push(raid_id);
+        }
+        Ok(raid_ids)
+    }
+
+    #[tokio::test]
+    async fn test_list_raids_filters() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+        let ids = seed_history(&pool).await?;
+
+        let all = list_raids(&pool, &HistoryFilter::default(), RaidSort::StartedAt, SortOrder::Desc, None, 50).await?;
+        let listed: Vec<i64> = all.items.iter().map(|r| r.raid.raid_id).collect();
+        assert_eq!(listed, ids.iter().rev().copied().collect::<Vec<_>>());
+        assert!(all.next.is_none());
+        assert_eq!(all.items[4].kill_count, 2);
+        assert_eq!(all.items[4].duration_seconds, Some(21 * 60));
+
+        // Map names match regardless of case
+        let filter = HistoryFilter { map_name: Some("CUSTOMS".into()), game_mode: Some(GameMode::PVP), ..Default::default() };
+        let customs = list_raids(&pool, &filter, RaidSort::Kills, SortOrder::Desc, None, 50).await?;
+        let listed: Vec<i64> = customs.items.iter().map(|r| r.raid.raid_id).collect();
+        assert_eq!(listed, vec![ids[3], ids[0]]);
+
+        let filter = HistoryFilter {
+            outcome: Some("survived".into()),
+            has_kills: Some(false),
+            session_type: Some(SessionType::Stream),
+            ..Default::default()
+        };
+        let survived = list_raids(&pool, &filter, RaidSort::StartedAt, SortOrder::Asc, None, 50).await?;
+        assert_eq!(survived.items.len(), 1);
+        assert_eq!(survived.items[0].raid.raid_id, ids[1]);
+
+        // Only the second day
+        let second_day = survived.items[0].raid.started_at + time::Duration::days(1);
+        let filter = HistoryFilter { from: Some(second_day), ..Default::default() };
+        let later = list_raids(&pool, &filter, RaidSort::StartedAt, SortOrder::Asc, None, 50).await?;
+        assert_eq!(later.items.len(), 2);
+        assert_eq!(later.items[0].session_type, Some(SessionType::Practice));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_raids_cursor_pagination() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+        let ids = seed_history(&pool).await?;
+
+        // Walking the pages gives every raid once, longest first
+        let mut seen = Vec::new();
+        let mut after = None;
+        loop {
+            let page = list_raids(&pool, &HistoryFilter::default(), RaidSort::Duration, SortOrder::Desc, after, 2).await?;
+            assert!(page.items.len() <= 2);
+            seen.extend(page.items.iter().map(|r| r.raid.raid_id));
+            match page.next {
+                Some(cursor) => after = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(seen, ids.iter().rev().copied().collect::<Vec<_>>());
+
+        // Ties on the sort key fall back to the id, 0 kills appears twice
+        let first = list_raids(&pool, &HistoryFilter::default(), RaidSort::Kills, SortOrder::Asc, None, 1).await?;
+        let second = list_raids(&pool, &HistoryFilter::default(), RaidSort::Kills, SortOrder::Asc, first.next, 1).await?;
+        assert_eq!(first.items[0].raid.raid_id, ids[1]);
+        assert_eq!(second.items[0].raid.raid_id, ids[4]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_sessions_counts_matching_raids() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+        seed_history(&pool).await?;
+
+        let all = list_sessions(&pool, &HistoryFilter::default(), SessionSort::StartedAt, SortOrder::Asc, None, 50).await?;
+        assert_eq!(all.items.len(), 2);
+        assert_eq!(all.items[0].raid_count, 3);
+        assert_eq!(all.items[0].kill_count, 3);
+        assert_eq!(all.items[1].raid_count, 2);
+
+        // Only the Woods raid matches, so only the first session is left
+        let filter = HistoryFilter { map_name: Some("woods".into()), ..Default::default() };
+        let woods = list_sessions(&pool, &filter, SessionSort::Raids, SortOrder::Desc, None, 50).await?;
+        assert_eq!(woods.items.len(), 1);
+        assert_eq!(woods.items[0].raid_count, 1);
+        assert_eq!(woods.items[0].kill_count, 1);
+
+        let filter = HistoryFilter { has_kills: Some(true), session_type: Some(SessionType::Practice), ..Default::default() };
+        let practice = list_sessions(&pool, &filter, SessionSort::Kills, SortOrder::Desc, None, 50).await?;
+        assert_eq!(practice.items.len(), 1);
+        assert_eq!(practice.items[0].kill_count, 3);
+
+        let first = list_sessions(&pool, &HistoryFilter::default(), SessionSort::StartedAt, SortOrder::Desc, None, 1).await?;
+        let second = list_sessions(&pool, &HistoryFilter::default(), SessionSort::StartedAt, SortOrder::Desc, first.next, 1).await?;
+        assert!(first.next.is_some());
+        assert!(second.next.is_none());
+        assert_ne!(first.items[0].session.session_id, second.items[0].session.session_id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_review_updates_only_touch_pending_events() -> Result<(), Error> {
+        let pool = setup_test_db().await?;
+        let pending_id = queue_event(&pool, "chat", "kill", None, r#"{"type":"kill","enemy_type":"scav"}"#, None).await?;
+        let mut event = get_pending_event(&pool, pending_id).await?.unwrap();
+
+        assert!(set_review_status(&pool, pending_id, ReviewStatus::Rejected, None).await?);
+
+        // A second reviewer working from the same read loses
+        assert!(!set_review_status(&pool, pending_id, ReviewStatus::Approved, None).await?);
+        event.event_type = "death".into();
+        assert!(!update_pending_event(&pool, &event).await?);
+
+        let event = get_pending_event(&pool, pending_id).await?.unwrap();
+        assert_eq!(event.status, ReviewStatus::Rejected);
+        assert_eq!(event.event_type, "kill");
+        Ok(())
+    }
 }
@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+
+use sqlx::{SqliteConnection, SqlitePool};
+use time::OffsetDateTime;
+use tracing::{debug, info};
+
+use crate::api::dto::{check_not_before, check_not_future};
+use crate::api::error::AppError;
+use crate::db;
+use crate::events::{EventBus, LiveEvent};
+use crate::models::{CharacterType, GameMode, Raid, RaidState};
+use super::DetectionControl;
+use super::schema::{DetectionEvent, DetectionKind};
+
+// Per the phase 4 plan, only act on detections the model is fairly sure about
+pub const DEFAULT_THRESHOLD: f64 = 0.8;
+
+// Which detections go to the review queue instead of straight into the raid tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReviewMode {
+    // Below-threshold events are dropped
+    Off,
+    #[default]
+    LowConfidence,
+    All,
+}
+
+impl ReviewMode {
+    pub fn parse(s: &str) -> Option<ReviewMode> {
+        match s {
+            "off" => Some(ReviewMode::Off),
+            "low_confidence" => Some(ReviewMode::LowConfidence),
+            "all" => Some(ReviewMode::All),
+            _ => None,
+        }
+    }
+}
+
+// What happened to a detection event
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Applied,
+    Paused,
+    BelowThreshold { confidence: f64, threshold: f64 },
+    // Waiting in pending_events under this id
+    Queued(i64),
+    // Raid details kept until a map is detected, nothing written yet
+    Held,
+    // Valid event that doesn't fit the current data, e.g. a kill with no raid running
+    Ignored(String),
+}
+
+// Detected raid details waiting for a raid to be created
+#[derive(Debug, Clone, Default, PartialEq)]
+struct PendingRaid {
+    map: Option<String>,
+    character_type: Option<CharacterType>,
+    game_mode: Option<GameMode>,
+}
+
+pub struct Applier {
+    pool: SqlitePool,
+    events: EventBus,
+    control: DetectionControl,
+    // event type -> minimum confidence, anything missing uses DEFAULT_THRESHOLD
+    thresholds: HashMap<String, f64>,
+    // Used when the service spots a map but not who we're playing
+    default_character_type: CharacterType,
+    default_game_mode: GameMode,
+    review: ReviewMode,
+    pending: PendingRaid,
+}
+
+impl Applier {
+    pub fn new(pool: SqlitePool, events: EventBus, control: DetectionControl) -> Self {
+        Self {
+            pool,
+            events,
+            control,
+            thresholds: HashMap::new(),
+            default_character_type: CharacterType::PMC,
+            default_game_mode: GameMode::PVE,
+            review: ReviewMode::default(),
+            pending: PendingRaid::default(),
+        }
+    }
+
+    pub fn with_thresholds(mut self, thresholds: HashMap<String, f64>) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    pub fn with_defaults(mut self, character_type: CharacterType, game_mode: GameMode) -> Self {
+        self.default_character_type = character_type;
+        self.default_game_mode = game_mode;
+        self
+    }
+
+    pub fn with_review(mut self, review: ReviewMode) -> Self {
+        self.review = review;
+        self
+    }
+
+    pub fn threshold(&self, event_type: &str) -> f64 {
+        self.thresholds.get(event_type).copied().unwrap_or(DEFAULT_THRESHOLD)
+    }
+
+    pub async fn apply(&mut self, event: DetectionEvent) -> Result<Outcome, sqlx::Error> {
+        // The operator has taken over, keep listening but don't touch anything
+        if self.control.is_paused() {
+            return Ok(Outcome::Paused);
+        }
+
+        let threshold = self.threshold(event.kind.name());
+        let below = event.confidence < threshold;
+        match self.review {
+            ReviewMode::All => return self.queue(event).await,
+            ReviewMode::LowConfidence if below => return self.queue(event).await,
+            _ if below => return Ok(Outcome::BelowThreshold { confidence: event.confidence, threshold }),
+            _ => {}
+        }
+
+        let ts = event.timestamp.unwrap_or_else(OffsetDateTime::now_utc);
+        self.write(event.kind, ts).await
+    }
+
+    // Applies an event with no pause or confidence checks, in one transaction
+    pub async fn write(&mut self, kind: DetectionKind, ts: OffsetDateTime) -> Result<Outcome, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut pending = self.pending.clone();
+        let mut published = Vec::new();
+        let outcome = self.write_with(&mut tx, kind, ts, &mut pending, &mut published).await?;
+        tx.commit().await?;
+
+        // Held raid details only stick once the transaction they came from has
+        self.pending = pending;
+        for event in published {
+            self.events.publish(event);
+        }
+        Ok(outcome)
+    }
+
+    // Does the writes on `conn` and leaves the live events in `published` for the caller to
+    // send once its transaction commits. Approvals from the review queue land here, they
+    // don't keep raid details around for later events.
+    pub async fn write_in(
+        &self,
+        conn: &mut SqliteConnection,
+        kind: DetectionKind,
+        ts: OffsetDateTime,
+        published: &mut Vec<LiveEvent>,
+    ) -> Result<Outcome, sqlx::Error> {
+        self.write_with(conn, kind, ts, &mut self.pending.clone(), published).await
+    }
+
+    async fn write_with(
+        &self,
+        conn: &mut SqliteConnection,
+        kind: DetectionKind,
+        ts: OffsetDateTime,
+        pending: &mut PendingRaid,
+        published: &mut Vec<LiveEvent>,
+    ) -> Result<Outcome, sqlx::Error> {
+        match kind {
+            DetectionKind::RaidInfo { map, character_type, game_mode } => {
+                pending.map = map.or(pending.map.take());
+                pending.character_type = character_type.or(pending.character_type.take());
+                pending.game_mode = game_mode.or(pending.game_mode.take());
+                self.start_raid(conn, ts, pending, published).await
+            }
+            DetectionKind::StateChange { state } => {
+                let to_state = RaidState::parse(&state);
+                if to_state.is_terminal() {
+                    return self.end_raid(conn, to_state, None, ts, published).await;
+                }
+
+                let Some(raid) = db::get_active_raid(&mut *conn).await? else {
+                    return Ok(Outcome::Ignored(format!("No active raid to move to {}", to_state)));
+                };
+                if raid.state() == to_state {
+                    return Ok(Outcome::Ignored(format!("Raid is already in {}", to_state)));
+                }
+                // No force here, a misread frame must not be able to walk the raid off the graph
+                transition(conn, &raid, to_state, ts, published).await
+            }
+            DetectionKind::Kill { enemy_type, weapon, headshot } => {
+                let Some(raid) = db::get_active_raid(&mut *conn).await? else {
+                    return Ok(Outcome::Ignored("No active raid for kill".into()));
+                };
+                // Held to the same window as kills posted to the API
+                if let Err(AppError::ValidationError(reason)) = check_not_future("killed_at", ts)
+                    .and_then(|_| check_not_before("killed_at", ts, raid.started_at, "the raid started"))
+                {
+                    return Ok(Outcome::Ignored(reason));
+                }
+
+                let kill_id = db::add_kill(&mut *conn, raid.raid_id, &enemy_type, weapon.clone(), headshot, Some(ts)).await?;
+                published.push(LiveEvent::KillAdded {
+                    kill_id,
+                    raid_id: raid.raid_id,
+                    enemy_type,
+                    weapon_used: weapon,
+                    headshot,
+                    killed_at: ts,
+                });
+                Ok(Outcome::Applied)
+            }
+            DetectionKind::RaidEnd { outcome, extract } => {
+                self.end_raid(conn, RaidState::parse(&outcome), extract, ts, published).await
+            }
+        }
+    }
+
+    async fn queue(&self, event: DetectionEvent) -> Result<Outcome, sqlx::Error> {
+        let event_type = event.kind.name();
+        let payload = serde_json::to_string(&event.kind).expect("detection events serialize");
+
+        let pending_id = db::queue_event(&self.pool, "detection", event_type,
+            Some(event.confidence), &payload, event.timestamp).await?;
+        self.events.publish(LiveEvent::EventQueued {
+            pending_id,
+            source: "detection".into(),
+            event_type: event_type.into(),
+        });
+        Ok(Outcome::Queued(pending_id))
+    }
+
+    // Creates the raid once we know the map, the rest falls back to the defaults
+    async fn start_raid(
+        &self,
+        conn: &mut SqliteConnection,
+        ts: OffsetDateTime,
+        pending: &mut PendingRaid,
+        published: &mut Vec<LiveEvent>,
+    ) -> Result<Outcome, sqlx::Error> {
+        if db::get_active_raid(&mut *conn).await?.is_some() {
+            *pending = PendingRaid::default();
+            return Ok(Outcome::Ignored("Raid already in progress".into()));
+        }
+        let Some(session) = db::get_active_session(&mut *conn).await? else {
+            return Ok(Outcome::Ignored("No active session, cannot start a raid".into()));
+        };
+        let Some(map_name) = pending.map.clone() else {
+            debug!("Holding raid info until a map is detected");
+            return Ok(Outcome::Held);
+        };
+
+        let pending = std::mem::take(pending);
+        let character_type = pending.character_type.unwrap_or_else(|| self.default_character_type.clone());
+        let game_mode = pending.game_mode.unwrap_or_else(|| self.default_game_mode.clone());
+
+        let raid_id = db::create_raid(&mut *conn, session.session_id, &map_name,
+            character_type.clone(), game_mode.clone(), Some(ts)).await?;
+        info!("Detected raid on {} (raid {})", map_name, raid_id);
+
+        published.push(LiveEvent::RaidCreated {
+            raid_id,
+            session_id: session.session_id,
+            map_name,
+            character_type,
+            game_mode,
+            started_at: ts,
+        });
+        Ok(Outcome::Applied)
+    }
+
+    async fn end_raid(
+        &self,
+        conn: &mut SqliteConnection,
+        final_state: RaidState,
+        extract: Option<String>,
+        ts: OffsetDateTime,
+        published: &mut Vec<LiveEvent>,
+    ) -> Result<Outcome, sqlx::Error> {
+        if !final_state.is_terminal() {
+            return Ok(Outcome::Ignored(format!("'{}' is not a raid outcome", final_state)));
+        }
+        let Some(raid) = db::get_active_raid(&mut *conn).await? else {
+            return Ok(Outcome::Ignored("No active raid to end".into()));
+        };
+
+        if raid.state() != final_state
+            && let Outcome::Ignored(reason) = transition(conn, &raid, final_state.clone(), ts, published).await?
+        {
+            return Ok(Outcome::Ignored(reason));
+        }
+
+        db::end_raid(&mut *conn, raid.raid_id, Some(ts), extract.clone()).await?;
+        published.push(LiveEvent::RaidEnded {
+            raid_id: raid.raid_id,
+            final_state,
+            extract_location: extract,
+            ended_at: ts,
+        });
+        Ok(Outcome::Applied)
+    }
+}
+
+// Moves off the graph come back as Ignored, the db checks them against the current state
+async fn transition(
+    conn: &mut SqliteConnection,
+    raid: &Raid,
+    to_state: RaidState,
+    ts: OffsetDateTime,
+    published: &mut Vec<LiveEvent>,
+) -> Result<Outcome, sqlx::Error> {
+    match db::log_state_transition(&mut *conn, raid.raid_id, to_state.as_str(), Some(ts)).await {
+        Ok(_) => {}
+        Err(db::TransitionError::Invalid(e)) => return Ok(Outcome::Ignored(e.to_string())),
+        Err(db::TransitionError::Database(e)) => return Err(e),
+    }
+
+    published.push(LiveEvent::StateTransitioned {
+        raid_id: raid.raid_id,
+        from_state: raid.state(),
+        to_state,
+        transitioned_at: ts,
+    });
+    Ok(Outcome::Applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::setup_test_db;
+    use crate::models::SessionType;
+
+    fn event(kind: DetectionKind, confidence: f64) -> DetectionEvent {
+        DetectionEvent { kind, timestamp: None, confidence }
+    }
+
+    fn state_change(state: &str) -> DetectionEvent {
+        event(DetectionKind::StateChange { state: state.into() }, 0.99)
+    }
+
+    fn map_detected(map: &str) -> DetectionEvent {
+        event(DetectionKind::RaidInfo { map: Some(map.into()), character_type: None, game_mode: None }, 0.99)
+    }
+
+    async fn setup() -> Result<(SqlitePool, Applier, DetectionControl), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        db::create_session(&pool, SessionType::Stream, None, None).await?;
+        let control = DetectionControl::default();
+        let applier = Applier::new(pool.clone(), EventBus::new(), control.clone());
+        Ok((pool, applier, control))
+    }
+
+    #[tokio::test]
+    async fn test_full_raid_from_detections() -> Result<(), sqlx::Error> {
+        let (pool, mut applier, _) = setup().await?;
+
+        // Mode first, map later, the raid only starts once the map is known
+        let mode = event(DetectionKind::RaidInfo { map: None, character_type: Some(CharacterType::Scav), game_mode: Some(GameMode::PVP) }, 0.9);
+        assert_eq!(applier.apply(mode).await?, Outcome::Held);
+        assert!(db::get_active_raid(&pool).await?.is_none());
+
+        assert_eq!(applier.apply(map_detected("Woods")).await?, Outcome::Applied);
+        let raid = db::get_active_raid(&pool).await?.unwrap();
+        assert_eq!(raid.map_name, "Woods");
+        assert_eq!(raid.character_type, CharacterType::Scav);
+        assert_eq!(raid.game_mode, GameMode::PVP);
+
+        for state in ["pre_raid_setup", "queuing", "deploying_committed", "raid_active"] {
+            assert_eq!(applier.apply(state_change(state)).await?, Outcome::Applied, "{}", state);
+        }
+
+        let kill = event(DetectionKind::Kill { enemy_type: "pmc".into(), weapon: Some("M4A1".into()), headshot: Some(true) }, 0.95);
+        assert_eq!(applier.apply(kill).await?, Outcome::Applied);
+
+        let end = event(DetectionKind::RaidEnd { outcome: "survived".into(), extract: Some("Outskirts".into()) }, 0.95);
+        assert_eq!(applier.apply(end).await?, Outcome::Applied);
+
+        let raid = db::get_raid_by_id(&pool, raid.raid_id).await?.unwrap();
+        assert_eq!(raid.state(), RaidState::Survived);
+        assert_eq!(raid.extract_location.as_deref(), Some("Outskirts"));
+        assert!(raid.ended_at.is_some());
+        assert_eq!(db::get_kills_for_raid(&pool, raid.raid_id).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_thresholds_per_event_type() -> Result<(), sqlx::Error> {
+        let (pool, applier, _) = setup().await?;
+        let mut applier = applier
+            .with_thresholds(HashMap::from([("kill".to_string(), 0.95)]))
+            .with_review(ReviewMode::Off);
+        applier.apply(map_detected("Customs")).await?;
+
+        let kill = |confidence| event(DetectionKind::Kill { enemy_type: "scav".into(), weapon: None, headshot: None }, confidence);
+        assert_eq!(applier.apply(kill(0.9)).await?, Outcome::BelowThreshold { confidence: 0.9, threshold: 0.95 });
+        assert_eq!(applier.apply(kill(0.96)).await?, Outcome::Applied);
+
+        // Other types keep the default
+        let weak = event(DetectionKind::StateChange { state: "pre_raid_setup".into() }, 0.7);
+        assert_eq!(applier.apply(weak).await?, Outcome::BelowThreshold { confidence: 0.7, threshold: DEFAULT_THRESHOLD });
+
+        let raid = db::get_active_raid(&pool).await?.unwrap();
+        assert_eq!(db::get_kills_for_raid(&pool, raid.raid_id).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_kills_outside_the_raid_are_ignored() -> Result<(), sqlx::Error> {
+        let (pool, mut applier, _) = setup().await?;
+        applier.apply(map_detected("Customs")).await?;
+        let raid = db::get_active_raid(&pool).await?.unwrap();
+
+        let kill_at = |ts| DetectionEvent {
+            kind: DetectionKind::Kill { enemy_type: "scav".into(), weapon: None, headshot: None },
+            timestamp: Some(ts),
+            confidence: 0.99,
+        };
+        let early = applier.apply(kill_at(raid.started_at - time::Duration::minutes(5))).await?;
+        assert!(matches!(early, Outcome::Ignored(ref reason) if reason.contains("before the raid started")), "{:?}", early);
+        let future = applier.apply(kill_at(OffsetDateTime::now_utc() + time::Duration::hours(1))).await?;
+        assert!(matches!(future, Outcome::Ignored(ref reason) if reason.contains("in the future")), "{:?}", future);
+
+        assert!(db::get_kills_for_raid(&pool, raid.raid_id).await?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_review_defaults_to_low_confidence() {
+        // Must agree with DETECTION_REVIEW's default
+        assert_eq!(ReviewMode::default(), ReviewMode::LowConfidence);
+    }
+
+    #[tokio::test]
+    async fn test_review_modes_queue_instead_of_writing() -> Result<(), sqlx::Error> {
+        let (pool, applier, _) = setup().await?;
+        let mut applier = applier.with_review(ReviewMode::LowConfidence);
+        applier.apply(map_detected("Customs")).await?;
+
+        let kill = |confidence| event(DetectionKind::Kill { enemy_type: "pmc".into(), weapon: None, headshot: None }, confidence);
+        let Outcome::Queued(pending_id) = applier.apply(kill(0.5)).await? else {
+            panic!("low confidence kill should be queued");
+        };
+        assert_eq!(applier.apply(kill(0.9)).await?, Outcome::Applied);
+
+        let queued = db::get_pending_event(&pool, pending_id).await?.unwrap();
+        assert_eq!(queued.source, "detection");
+        assert_eq!(queued.event_type, "kill");
+        assert_eq!(queued.confidence, Some(0.5));
+        let payload: DetectionKind = serde_json::from_str(&queued.payload).unwrap();
+        assert_eq!(payload, kill(0.5).kind);
+
+        // Everything waits for the operator
+        let mut applier = applier.with_review(ReviewMode::All);
+        assert!(matches!(applier.apply(kill(0.99)).await?, Outcome::Queued(_)));
+
+        let raid = db::get_active_raid(&pool).await?.unwrap();
+        assert_eq!(db::get_kills_for_raid(&pool, raid.raid_id).await?.len(), 1);
+        assert_eq!(db::get_pending_events(&pool, None, None).await?.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_manual_override_pauses() -> Result<(), sqlx::Error> {
+        let (pool, mut applier, control) = setup().await?;
+
+        control.set_paused(true);
+        assert_eq!(applier.apply(map_detected("Customs")).await?, Outcome::Paused);
+        assert!(db::get_active_raid(&pool).await?.is_none());
+
+        control.set_paused(false);
+        assert_eq!(applier.apply(map_detected("Customs")).await?, Outcome::Applied);
+        assert!(db::get_active_raid(&pool).await?.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_illegal_or_orphan_events_are_ignored() -> Result<(), sqlx::Error> {
+        let (pool, mut applier, _) = setup().await?;
+
+        assert!(matches!(applier.apply(state_change("queuing")).await?, Outcome::Ignored(_)));
+
+        applier.apply(map_detected("Customs")).await?;
+        // Can't jump from the stash straight into a raid
+        assert!(matches!(applier.apply(state_change("raid_active")).await?, Outcome::Ignored(_)));
+        assert!(matches!(applier.apply(state_change("stash_management")).await?, Outcome::Ignored(_)));
+        // A second map detection mid raid doesn't start another raid
+        assert!(matches!(applier.apply(map_detected("Woods")).await?, Outcome::Ignored(_)));
+
+        let raid = db::get_active_raid(&pool).await?.unwrap();
+        assert_eq!(raid.state(), RaidState::StashManagement);
+        assert!(db::get_raid_transitions(&pool, raid.raid_id).await?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+use super::DetectionControl;
+use super::apply::{Applier, Outcome};
+use super::schema::parse_event;
+
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub url: String,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl ClientConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+pub fn spawn(config: ClientConfig, mut applier: Applier, control: DetectionControl) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut backoff = config.min_backoff;
+
+        loop {
+            match tokio_tungstenite::connect_async(config.url.as_str()).await {
+                Ok((ws, _)) => {
+                    info!("Connected to detection service at {}", config.url);
+                    control.set_connected(true);
+                    backoff = config.min_backoff;
+
+                    listen(ws, &mut applier).await;
+
+                    control.set_connected(false);
+                    warn!("Lost connection to detection service, reconnecting");
+                }
+                Err(e) => warn!("Detection service unreachable: {} (retrying in {:?})", e, backoff),
+            }
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(config.max_backoff);
+        }
+    })
+}
+
+async fn listen<S>(mut ws: S, applier: &mut Applier)
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    while let Some(msg) = ws.next().await {
+        let text = match msg {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) | Err(_) => return,
+            Ok(_) => continue,
+        };
+
+        let event = match parse_event(&text) {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("{}", e);
+                continue;
+            }
+        };
+
+        let kind = event.kind.name();
+        match applier.apply(event).await {
+            Ok(Outcome::Applied) => debug!("Applied detected {}", kind),
+            Ok(Outcome::Paused) => debug!("Detection paused, skipped {}", kind),
+            Ok(Outcome::BelowThreshold { confidence, threshold }) => {
+                debug!("Skipped {} at {:.2} confidence (needs {:.2})", kind, confidence, threshold)
+            }
+            Ok(Outcome::Queued(pending_id)) => info!("Queued detected {} for review (#{})", kind, pending_id),
+            Ok(Outcome::Held) => debug!("Holding detected {} until a map shows up", kind),
+            Ok(Outcome::Ignored(reason)) => info!("Ignored detected {}: {}", kind, reason),
+            Err(e) => warn!("Failed to apply detected {}: {}", kind, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::db::tests::setup_test_db;
+    use crate::events::EventBus;
+    use crate::models::SessionType;
+    use futures_util::SinkExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_events_from_service_reach_database() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        db::create_session(&pool, SessionType::Stream, None, None).await?;
+
+        // Stand-in for the Python service: pushes a few events then holds the socket open
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for event in [
+                r#"{"version": 1, "type": "raid_info", "confidence": 0.99, "data": {"map": "Interchange"}}"#,
+                r#"{"version": 9, "type": "kill", "confidence": 0.99, "data": {"enemy_type": "pmc"}}"#,
+                r#"{"version": 1, "type": "kill", "confidence": 0.2, "data": {"enemy_type": "pmc"}}"#,
+                r#"{"version": 1, "type": "kill", "confidence": 0.99, "data": {"enemy_type": "scav"}}"#,
+            ] {
+                ws.send(Message::Text(event.into())).await.unwrap();
+            }
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+
+        let control = DetectionControl::default();
+        let applier = Applier::new(pool.clone(), EventBus::new(), control.clone());
+        let task = spawn(ClientConfig::new(url), applier, control.clone());
+
+        // Wait for the one good kill to land
+        let mut kills = Vec::new();
+        for _ in 0..100 {
+            if let Some(raid) = db::get_active_raid(&pool).await? {
+                kills = db::get_kills_for_raid(&pool, raid.raid_id).await?;
+                if !kills.is_empty() {
+                    break;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        assert!(control.is_connected());
+        assert_eq!(kills.len(), 1);
+        assert_eq!(kills[0].enemy_type, "scav");
+
+        task.abort();
+        Ok(())
+    }
+}
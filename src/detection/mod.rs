@@ -0,0 +1,125 @@
+// Consumes events from the phase 4 vision service (docs/phase_4_video_detection_plan.md)
+// and turns them into raids, transitions and kills
+pub mod apply;
+pub mod client;
+pub mod schema;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::models::{CharacterType, GameMode};
+
+// Shared between the client task and the API so the operator can take over
+#[derive(Debug, Clone, Default)]
+pub struct DetectionControl {
+    paused: Arc<AtomicBool>,
+    connected: Arc<AtomicBool>,
+}
+
+impl DetectionControl {
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DetectionConfig {
+    pub client: client::ClientConfig,
+    pub thresholds: HashMap<String, f64>,
+    pub default_character_type: CharacterType,
+    pub default_game_mode: GameMode,
+    pub review: apply::ReviewMode,
+}
+
+// "kill=0.9,state_change=0.85"
+pub fn parse_thresholds(s: &str) -> Result<HashMap<String, f64>, String> {
+    let mut thresholds = HashMap::new();
+
+    for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, value) = entry.split_once('=')
+            .ok_or_else(|| format!("Expected `event_type=confidence`, got '{}'", entry))?;
+        let name = name.trim();
+        if !schema::EVENT_TYPES.contains(&name) {
+            return Err(format!("Unknown detection event type '{}'", name));
+        }
+        let value: f64 = value.trim().parse()
+            .ok()
+            .filter(|v| (0.0..=1.0).contains(v))
+            .ok_or_else(|| format!("Threshold for {} must be between 0 and 1", name))?;
+        thresholds.insert(name.to_string(), value);
+    }
+
+    Ok(thresholds)
+}
+
+// DETECTION_WS_URL turns this on. Optional:
+//   DETECTION_THRESHOLDS        - "kill=0.9,state_change=0.85", default 0.8
+//   DETECTION_CHARACTER_TYPE    - pmc / scav when the service doesn't say (default pmc)
+//   DETECTION_GAME_MODE         - pve / pvp when the service doesn't say (default pve)
+//   DETECTION_REVIEW            - off / low_confidence / all, what goes to the review queue
+//                                 instead of being written (default low_confidence)
+pub fn config_from_env() -> Result<Option<DetectionConfig>, String> {
+    let Ok(url) = std::env::var("DETECTION_WS_URL") else {
+        return Ok(None);
+    };
+
+    let thresholds = match std::env::var("DETECTION_THRESHOLDS") {
+        Ok(s) => parse_thresholds(&s)?,
+        Err(_) => HashMap::new(),
+    };
+
+    let default_character_type = match std::env::var("DETECTION_CHARACTER_TYPE") {
+        Ok(s) => serde_json::from_value(serde_json::Value::String(s.to_lowercase()))
+            .map_err(|_| format!("Invalid DETECTION_CHARACTER_TYPE '{}'", s))?,
+        Err(_) => CharacterType::PMC,
+    };
+
+    let default_game_mode = match std::env::var("DETECTION_GAME_MODE") {
+        Ok(s) => serde_json::from_value(serde_json::Value::String(s.to_lowercase()))
+            .map_err(|_| format!("Invalid DETECTION_GAME_MODE '{}'", s))?,
+        Err(_) => GameMode::PVE,
+    };
+
+    let review = match std::env::var("DETECTION_REVIEW") {
+        Ok(s) => apply::ReviewMode::parse(&s.to_lowercase())
+            .ok_or_else(|| format!("Invalid DETECTION_REVIEW '{}' (off, low_confidence or all)", s))?,
+        Err(_) => apply::ReviewMode::default(),
+    };
+
+    Ok(Some(DetectionConfig {
+        client: client::ClientConfig::new(url),
+        thresholds,
+        default_character_type,
+        default_game_mode,
+        review,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_thresholds() {
+        let thresholds = parse_thresholds("kill=0.9, state_change=0.85").unwrap();
+        assert_eq!(thresholds["kill"], 0.9);
+        assert_eq!(thresholds["state_change"], 0.85);
+
+        assert!(parse_thresholds("kil=0.9").is_err());
+        assert!(parse_thresholds("kill=1.5").is_err());
+        assert!(parse_thresholds("kill").is_err());
+    }
+}
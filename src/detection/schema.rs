@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::models::{CharacterType, GameMode};
+
+// Bump when the wire format changes in a way old clients can't read
+pub const SCHEMA_VERSION: u32 = 1;
+
+// ============================================================
+// Events
+// ============================================================
+
+// One message from the detection service, e.g.
+// {"version": 1, "type": "kill", "timestamp": 1702564800.123, "confidence": 0.95,
+//  "data": {"enemy_type": "scav", "weapon": "AK-74M", "headshot": true}}
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectionEvent {
+    pub kind: DetectionKind,
+    // When the frame was captured, None means "now"
+    pub timestamp: Option<OffsetDateTime>,
+    pub confidence: f64,
+}
+
+// Also the payload format of the review queue
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum DetectionKind {
+    Kill {
+        enemy_type: String,
+        #[serde(default)]
+        weapon: Option<String>,
+        #[serde(default)]
+        headshot: Option<bool>,
+    },
+    StateChange {
+        state: String,
+    },
+    RaidEnd {
+        outcome: String,
+        #[serde(default)]
+        extract: Option<String>,
+    },
+    // Any of these can show up on their own, e.g. the map from the loading screen
+    // and the mode from the menu
+    RaidInfo {
+        #[serde(default)]
+        map: Option<String>,
+        #[serde(default)]
+        character_type: Option<CharacterType>,
+        #[serde(default)]
+        game_mode: Option<GameMode>,
+    },
+}
+
+impl DetectionKind {
+    // Matches the "type" field, used as the key for confidence thresholds
+    pub fn name(&self) -> &'static str {
+        match self {
+            DetectionKind::Kill { .. } => "kill",
+            DetectionKind::StateChange { .. } => "state_change",
+            DetectionKind::RaidEnd { .. } => "raid_end",
+            DetectionKind::RaidInfo { .. } => "raid_info",
+        }
+    }
+}
+
+pub const EVENT_TYPES: [&str; 4] = ["kill", "state_change", "raid_end", "raid_info"];
+
+// ============================================================
+// Parsing
+// ============================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaError {
+    UnsupportedVersion(u32),
+    Invalid(String),
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaError::UnsupportedVersion(v) => write!(f,
+                "Unsupported detection schema version {} (expected {})", v, SCHEMA_VERSION),
+            SchemaError::Invalid(msg) => write!(f, "Invalid detection event: {}", msg),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct EnvelopeV1 {
+    #[serde(flatten)]
+    kind: DetectionKind,
+    #[serde(default)]
+    timestamp: Option<f64>,
+    confidence: f64,
+}
+
+pub fn parse_event(text: &str) -> Result<DetectionEvent, SchemaError> {
+    let value: serde_json::Value = serde_json::from_str(text)
+        .map_err(|e| SchemaError::Invalid(e.to_string()))?;
+
+    // Check the version first so a newer service gets a clear error instead of a field mismatch
+    let header: Header = serde_json::from_value(value.clone())
+        .map_err(|e| SchemaError::Invalid(e.to_string()))?;
+    if header.version != SCHEMA_VERSION {
+        return Err(SchemaError::UnsupportedVersion(header.version));
+    }
+
+    let envelope: EnvelopeV1 = serde_json::from_value(value)
+        .map_err(|e| SchemaError::Invalid(e.to_string()))?;
+
+    if !(0.0..=1.0).contains(&envelope.confidence) {
+        return Err(SchemaError::Invalid(format!("confidence {} is outside 0..1", envelope.confidence)));
+    }
+
+    // Unix seconds with a fractional part, what Python's time.time() gives us
+    let timestamp = envelope.timestamp
+        .map(|ts| OffsetDateTime::from_unix_timestamp_nanos((ts * 1e9) as i128)
+            .map_err(|e| SchemaError::Invalid(format!("timestamp: {}", e))))
+        .transpose()?;
+
+    Ok(DetectionEvent { kind: envelope.kind, timestamp, confidence: envelope.confidence })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kill() {
+        let event = parse_event(r#"{"version": 1, "type": "kill", "timestamp": 1702564800.5, "confidence": 0.95,
+            "data": {"enemy_type": "scav", "weapon": "AK-74M", "headshot": true}}"#).unwrap();
+
+        assert_eq!(event.kind, DetectionKind::Kill {
+            enemy_type: "scav".into(),
+            weapon: Some("AK-74M".into()),
+            headshot: Some(true),
+        });
+        assert_eq!(event.kind.name(), "kill");
+        assert_eq!(event.confidence, 0.95);
+        assert_eq!(event.timestamp.unwrap().unix_timestamp_nanos(), 1_702_564_800_500_000_000);
+    }
+
+    #[test]
+    fn test_parse_raid_info_and_end() {
+        let event = parse_event(r#"{"version": 1, "type": "raid_info", "confidence": 0.9,
+            "data": {"map": "Customs", "game_mode": "pvp"}}"#).unwrap();
+        assert_eq!(event.kind, DetectionKind::RaidInfo {
+            map: Some("Customs".into()),
+            character_type: None,
+            game_mode: Some(GameMode::PVP),
+        });
+        assert!(event.timestamp.is_none());
+
+        let event = parse_event(r#"{"version": 1, "type": "raid_end", "confidence": 0.9,
+            "data": {"outcome": "survived", "extract": "Crossroads"}}"#).unwrap();
+        assert_eq!(event.kind, DetectionKind::RaidEnd {
+            outcome: "survived".into(),
+            extract: Some("Crossroads".into()),
+        });
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_events() {
+        assert_eq!(
+            parse_event(r#"{"version": 2, "type": "kill", "confidence": 1.0, "data": {}}"#),
+            Err(SchemaError::UnsupportedVersion(2))
+        );
+        assert!(parse_event(r#"{"type": "kill", "confidence": 1.0, "data": {"enemy_type": "pmc"}}"#).is_err());
+        assert!(parse_event(r#"{"version": 1, "type": "teleport", "confidence": 1.0, "data": {}}"#).is_err());
+        assert!(parse_event(r#"{"version": 1, "type": "kill", "confidence": 1.5, "data": {"enemy_type": "pmc"}}"#).is_err());
+        assert!(parse_event("not json").is_err());
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+
+use crate::models::{CharacterType, GameMode, RaidState, RecordKind, SessionType};
+
+// How many events are kept around for clients resuming with a last event id
+const HISTORY_SIZE: usize = 256;
+
+// ============================================================
+// Events
+// ============================================================
+
+// Whoever makes a change publishes it, and only once its transaction has committed so
+// nobody sees a write that got rolled back. The API handlers do it for their own writes,
+// the import handler publishes SessionImported for the whole backfill, and detection and
+// review::approve publish whatever the applied event changed. A new write path that skips
+// this leaves the overlay and chat behind, so it needs a test subscribing to the bus.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    SessionStarted {
+        session_id: i64,
+        session_type: SessionType,
+        #[serde(with = "time::serde::rfc3339")]
+        started_at: OffsetDateTime,
+    },
+    SessionEnded {
+        session_id: i64,
+        #[serde(with = "time::serde::rfc3339")]
+        ended_at: OffsetDateTime,
+    },
+    RaidCreated {
+        raid_id: i64,
+        session_id: i64,
+        map_name: String,
+        character_type: CharacterType,
+        game_mode: GameMode,
+        #[serde(with = "time::serde::rfc3339")]
+        started_at: OffsetDateTime,
+    },
+    StateTransitioned {
+        raid_id: i64,
+        from_state: RaidState,
+        to_state: RaidState,
+        #[serde(with = "time::serde::rfc3339")]
+        transitioned_at: OffsetDateTime,
+    },
+    KillAdded {
+        kill_id: i64,
+        raid_id: i64,
+        enemy_type: String,
+        weapon_used: Option<String>,
+        headshot: Option<bool>,
+        #[serde(with = "time::serde::rfc3339")]
+        killed_at: OffsetDateTime,
+    },
+    RaidEnded {
+        raid_id: i64,
+        final_state: RaidState,
+        extract_location: Option<String>,
+        #[serde(with = "time::serde::rfc3339")]
+        ended_at: OffsetDateTime,
+    },
+    // The raid moved to another map. from_raid_id ended in `transfer`, raid_id is the new
+    // segment and chain_id the first segment, shared by the whole chain.
+    RaidTransferred {
+        from_raid_id: i64,
+        raid_id: i64,
+        chain_id: i64,
+        segment_order: i64,
+        map_name: String,
+        #[serde(with = "time::serde::rfc3339")]
+        transferred_at: OffsetDateTime,
+    },
+    // A past session was backfilled from a recording, raids and all
+    SessionImported {
+        session_id: i64,
+        raid_count: usize,
+        #[serde(with = "time::serde::rfc3339")]
+        started_at: OffsetDateTime,
+        #[serde(with = "time::serde::rfc3339::option")]
+        ended_at: Option<OffsetDateTime>,
+    },
+    // A raid, transition or kill was corrected after the fact. raid_state is None when
+    // the raid itself was deleted.
+    RaidCorrected {
+        raid_id: i64,
+        raid_state: Option<RaidState>,
+    },
+    // An operator action was reversed or replayed, raid_state is where the raid ended up
+    ActionUndone {
+        session_id: i64,
+        action: String,
+        raid_id: Option<i64>,
+        raid_state: Option<RaidState>,
+    },
+    ActionRedone {
+        session_id: i64,
+        action: String,
+        raid_id: Option<i64>,
+        raid_state: Option<RaidState>,
+    },
+    // Something landed in the review queue instead of the raid tables
+    EventQueued {
+        pending_id: i64,
+        source: String,
+        event_type: String,
+    },
+    // A raid that just ended beat a personal best, display is e.g. "12 kills"
+    RecordSet {
+        record: RecordKind,
+        value: f64,
+        display: String,
+        previous_display: String,
+        raid_id: Option<i64>,
+        session_id: i64,
+    },
+}
+
+impl LiveEvent {
+    // Used as the SSE "event:" name, matches the serde tag
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LiveEvent::SessionStarted { .. } => "session_started",
+            LiveEvent::SessionEnded { .. } => "session_ended",
+            LiveEvent::RaidCreated { .. } => "raid_created",
+            LiveEvent::StateTransitioned { .. } => "state_transitioned",
+            LiveEvent::KillAdded { .. } => "kill_added",
+            LiveEvent::RaidEnded { .. } => "raid_ended",
+            LiveEvent::RaidTransferred { .. } => "raid_transferred",
+            LiveEvent::SessionImported { .. } => "session_imported",
+            LiveEvent::RaidCorrected { .. } => "raid_corrected",
+            LiveEvent::ActionUndone { .. } => "action_undone",
+            LiveEvent::ActionRedone { .. } => "action_redone",
+            LiveEvent::EventQueued { .. } => "event_queued",
+            LiveEvent::RecordSet { .. } => "record_set",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventEnvelope {
+    pub id: u64,
+    #[serde(flatten)]
+    pub event: LiveEvent,
+}
+
+// ============================================================
+// Event Bus
+// ============================================================
+
+// What a new subscriber gets: anything it missed plus a live receiver.
+// `complete` is false when the client has no last id or asked for events that
+// already fell out of the history, in that case it needs a fresh snapshot.
+pub struct Subscription {
+    pub replay: Vec<EventEnvelope>,
+    pub complete: bool,
+    pub receiver: broadcast::Receiver<EventEnvelope>,
+}
+
+#[derive(Clone)]
+pub struct EventBus {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    sender: broadcast::Sender<EventEnvelope>,
+    history: Mutex<History>,
+}
+
+struct History {
+    next_id: u64,
+    events: VecDeque<EventEnvelope>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(HISTORY_SIZE);
+
+        Self {
+            inner: Arc::new(Inner {
+                sender,
+                history: Mutex::new(History { next_id: 1, events: VecDeque::new() }),
+            }),
+        }
+    }
+
+    pub fn publish(&self, event: LiveEvent) -> u64 {
+        // Hold the lock while sending so ids reach subscribers in order
+        let mut history = self.inner.history.lock().expect("event history poisoned");
+
+        let envelope = EventEnvelope { id: history.next_id, event };
+        history.next_id += 1;
+
+        if history.events.len() == HISTORY_SIZE {
+            history.events.pop_front();
+        }
+        history.events.push_back(envelope.clone());
+
+        // No receivers is not an error, nobody is watching yet
+        let _ = self.inner.sender.send(envelope.clone());
+
+        envelope.id
+    }
+
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> Subscription {
+        let history = self.inner.history.lock().expect("event history poisoned");
+        let receiver = self.inner.sender.subscribe();
+
+        let Some(last_id) = last_event_id else {
+            return Subscription { replay: Vec::new(), complete: false, receiver };
+        };
+
+        // The oldest id still buffered, or the next id if nothing is buffered.
+        // A client sending u64::MAX can't be behind on anything we have.
+        let oldest = history.events.front().map(|e| e.id).unwrap_or(history.next_id);
+        let complete = last_id.checked_add(1).is_some_and(|next| next >= oldest) && last_id < history.next_id;
+
+        let replay = if complete {
+            history.events.iter().filter(|e| e.id > last_id).cloned().collect()
+        } else {
+            Vec::new()
+        };
+
+        Subscription { replay, complete, receiver }
+    }
+
+    pub fn last_event_id(&self) -> u64 {
+        self.inner.history.lock().expect("event history poisoned").next_id - 1
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ended(session_id: i64) -> LiveEvent {
+        LiveEvent::SessionEnded { session_id, ended_at: OffsetDateTime::UNIX_EPOCH }
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let envelope = EventEnvelope {
+            id: 3,
+            event: LiveEvent::StateTransitioned {
+                raid_id: 7,
+                from_state: RaidState::Queuing,
+                to_state: RaidState::DeployingCommitted,
+                transitioned_at: OffsetDateTime::UNIX_EPOCH,
+            },
+        };
+
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["id"], 3);
+        assert_eq!(json["type"], "state_transitioned");
+        assert_eq!(json["to_state"], "deploying_committed");
+        assert_eq!(json["transitioned_at"], "1970-01-01T00:00:00Z");
+        assert_eq!(envelope.event.kind(), "state_transitioned");
+    }
+
+    #[tokio::test]
+    async fn test_publish_reaches_subscriber() {
+        let bus = EventBus::new();
+        let mut sub = bus.subscribe(None);
+        assert!(!sub.complete);
+
+        let id = bus.publish(ended(1));
+        let received = sub.receiver.recv().await.unwrap();
+
+        assert_eq!(received.id, id);
+        assert_eq!(received.event, ended(1));
+    }
+
+    #[test]
+    fn test_resume_replays_missed_events() {
+        let bus = EventBus::new();
+        bus.publish(ended(1));
+        bus.publish(ended(2));
+        bus.publish(ended(3));
+
+        let sub = bus.subscribe(Some(1));
+        assert!(sub.complete);
+        assert_eq!(sub.replay.iter().map(|e| e.id).collect::<Vec<_>>(), vec![2, 3]);
+
+        let sub = bus.subscribe(Some(3));
+        assert!(sub.complete);
+        assert!(sub.replay.is_empty());
+
+        assert_eq!(bus.last_event_id(), 3);
+    }
+
+    #[test]
+    fn test_resume_from_max_id_does_not_overflow() {
+        let bus = EventBus::new();
+        bus.publish(ended(1));
+
+        let sub = bus.subscribe(Some(u64::MAX));
+        assert!(!sub.complete);
+        assert!(sub.replay.is_empty());
+    }
+
+    #[test]
+    fn test_resume_too_old_needs_snapshot() {
+        let bus = EventBus::new();
+        for i in 0..(HISTORY_SIZE as i64 + 10) {
+            bus.publish(ended(i));
+        }
+
+        let sub = bus.subscribe(Some(2));
+        assert!(!sub.complete);
+        assert!(sub.replay.is_empty());
+
+        // An id from the future (server restarted) also needs a snapshot
+        let sub = bus.subscribe(Some(10_000));
+        assert!(!sub.complete);
+    }
+}
@@ -0,0 +1,597 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use sqlx::{SqliteConnection, SqlitePool};
+use tokio::sync::Mutex;
+use time::OffsetDateTime;
+
+use crate::db;
+use crate::models::{Kill, NewAuditEntry, Raid, RaidStateTransition, StreamSession};
+
+// How many operator actions per session can be undone
+pub const HISTORY_SIZE: usize = 50;
+
+// ============================================================
+// Actions
+// ============================================================
+
+// A mutating API call, with enough of the rows it touched to reverse it and replay it
+#[derive(Debug, Clone)]
+pub enum Action {
+    SessionStarted(StreamSession),
+    SessionEnded { session_id: i64, ended_at: OffsetDateTime },
+    RaidCreated(Raid),
+    Transitioned(RaidStateTransition),
+    // transition is the terminal state change logged along with the end, if there was one
+    RaidEnded {
+        raid_id: i64,
+        ended_at: OffsetDateTime,
+        extract_location: Option<String>,
+        transition: Option<RaidStateTransition>,
+    },
+    // Map transfer: raid_id ended with `transition` into transfer and `segment` carried on,
+    // starting with `segment_transition`
+    RaidTransferred {
+        raid_id: i64,
+        ended_at: OffsetDateTime,
+        transition: RaidStateTransition,
+        segment: Raid,
+        segment_transition: RaidStateTransition,
+    },
+    KillsAdded(Vec<Kill>),
+    KillUpdated { before: Kill, after: Kill },
+    KillDeleted(Kill),
+    // Corrections
+    RaidUpdated { before: Raid, after: Raid },
+    RaidDeleted { raid: Raid, transitions: Vec<RaidStateTransition>, kills: Vec<Kill> },
+    TransitionUpdated { before: RaidStateTransition, after: RaidStateTransition },
+    TransitionDeleted(RaidStateTransition),
+}
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::SessionStarted(_) => "session_started",
+            Action::SessionEnded { .. } => "session_ended",
+            Action::RaidCreated(_) => "raid_created",
+            Action::Transitioned(_) => "state_transitioned",
+            Action::RaidEnded { .. } => "raid_ended",
+            Action::RaidTransferred { .. } => "raid_transferred",
+            Action::KillsAdded(_) => "kills_added",
+            Action::KillUpdated { .. } => "kill_updated",
+            Action::KillDeleted(_) => "kill_deleted",
+            Action::RaidUpdated { .. } => "raid_updated",
+            Action::RaidDeleted { .. } => "raid_deleted",
+            Action::TransitionUpdated { .. } => "transition_updated",
+            Action::TransitionDeleted(_) => "transition_deleted",
+        }
+    }
+
+    pub fn raid_id(&self) -> Option<i64> {
+        match self {
+            Action::SessionStarted(_) | Action::SessionEnded { .. } => None,
+            Action::RaidCreated(raid) => Some(raid.raid_id),
+            Action::Transitioned(t) => Some(t.raid_id),
+            Action::RaidEnded { raid_id, .. } => Some(*raid_id),
+            Action::RaidTransferred { segment, .. } => Some(segment.raid_id),
+            Action::KillsAdded(kills) => kills.first().map(|k| k.raid_id),
+            Action::KillUpdated { after, .. } => Some(after.raid_id),
+            Action::KillDeleted(kill) => Some(kill.raid_id),
+            Action::RaidUpdated { after, .. } => Some(after.raid_id),
+            Action::RaidDeleted { raid, .. } => Some(raid.raid_id),
+            Action::TransitionUpdated { after, .. } => Some(after.raid_id),
+            Action::TransitionDeleted(t) => Some(t.raid_id),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HistoryError {
+    Empty(String),
+    // The data moved on since the action, e.g. detection logged another transition
+    Stale(String),
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryError::Empty(msg) | HistoryError::Stale(msg) => f.write_str(msg),
+            HistoryError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for HistoryError {
+    fn from(e: sqlx::Error) -> Self {
+        HistoryError::Database(e)
+    }
+}
+
+// ============================================================
+// History
+// ============================================================
+
+#[derive(Debug, Default)]
+struct Stacks {
+    undo: VecDeque<Action>,
+    redo: Vec<Action>,
+}
+
+impl Stacks {
+    fn push_undo(&mut self, action: Action, capacity: usize) {
+        self.undo.push_back(action);
+        if self.undo.len() > capacity {
+            self.undo.pop_front();
+        }
+    }
+}
+
+// Undo/redo stacks per session, kept in memory like the event history
+#[derive(Debug, Clone)]
+pub struct History {
+    sessions: Arc<Mutex<HashMap<i64, Stacks>>>,
+    capacity: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::with_capacity(HISTORY_SIZE)
+    }
+}
+
+impl History {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { sessions: Arc::new(Mutex::new(HashMap::new())), capacity }
+    }
+
+    // A new action makes anything undone unreachable, same as any editor
+    pub async fn record(&self, session_id: i64, action: Action) {
+        let mut sessions = self.sessions.lock().await;
+        let stacks = sessions.entry(session_id).or_default();
+
+        stacks.redo.clear();
+        stacks.push_undo(action, self.capacity);
+    }
+
+    // (undoable, redoable)
+    pub async fn depth(&self, session_id: i64) -> (usize, usize) {
+        let sessions = self.sessions.lock().await;
+        sessions.get(&session_id).map_or((0, 0), |s| (s.undo.len(), s.redo.len()))
+    }
+
+    // The session's stacks stay locked until the action is applied, so a second undo waits
+    // for this one instead of racing it. Each action is reversed in one transaction, along
+    // with the audit rows `audit` gives for it.
+    pub async fn undo(
+        &self,
+        pool: &SqlitePool,
+        session_id: i64,
+        audit: impl FnOnce(&Action) -> Vec<NewAuditEntry>,
+    ) -> Result<Action, HistoryError> {
+        let mut sessions = self.sessions.lock().await;
+        let stacks = sessions.entry(session_id).or_default();
+        let action = stacks.undo.back().cloned()
+            .ok_or_else(|| HistoryError::Empty(format!("Nothing to undo in session {}", session_id)))?;
+
+        // Left on the stack if it can't be reversed so the operator can fix things and retry
+        let mut tx = pool.begin().await?;
+        reverse(&mut tx, &action).await?;
+        for entry in audit(&action) {
+            db::add_audit_entry(&mut *tx, &entry).await?;
+        }
+        tx.commit().await?;
+
+        stacks.undo.pop_back();
+        stacks.redo.push(action.clone());
+        Ok(action)
+    }
+
+    pub async fn redo(
+        &self,
+        pool: &SqlitePool,
+        session_id: i64,
+        audit: impl FnOnce(&Action) -> Vec<NewAuditEntry>,
+    ) -> Result<Action, HistoryError> {
+        let mut sessions = self.sessions.lock().await;
+        let stacks = sessions.entry(session_id).or_default();
+        let action = stacks.redo.last().cloned()
+            .ok_or_else(|| HistoryError::Empty(format!("Nothing to redo in session {}", session_id)))?;
+
+        let mut tx = pool.begin().await?;
+        replay(&mut tx, &action).await?;
+        for entry in audit(&action) {
+            db::add_audit_entry(&mut *tx, &entry).await?;
+        }
+        tx.commit().await?;
+
+        stacks.redo.pop();
+        stacks.push_undo(action.clone(), self.capacity);
+        Ok(action)
+    }
+}
+
+// ============================================================
+// Inverses
+// ============================================================
+
+async fn find_raid(conn: &mut SqliteConnection, raid_id: i64) -> Result<Raid, HistoryError> {
+    db::get_raid_by_id(&mut *conn, raid_id).await?
+        .ok_or_else(|| HistoryError::Stale(format!("Raid {} no longer exists", raid_id)))
+}
+
+// Only the newest transition can come off, otherwise the from_state chain breaks
+async fn check_last_transition(conn: &mut SqliteConnection, transition: &RaidStateTransition) -> Result<(), HistoryError> {
+    let raid = find_raid(&mut *conn, transition.raid_id).await?;
+    let transitions = db::get_raid_transitions(&mut *conn, transition.raid_id).await?;
+
+    let is_last = transitions.last().map(|t| t.transition_id) == Some(transition.transition_id);
+    if !is_last || raid.current_state != transition.to_state {
+        return Err(HistoryError::Stale(format!(
+            "Raid {} has moved on from {} since, undo the later changes first",
+            transition.raid_id, transition.to_state
+        )));
+    }
+    Ok(())
+}
+
+async fn check_no_active_raid(conn: &mut SqliteConnection) -> Result<(), HistoryError> {
+    if let Some(active) = db::get_active_raid(&mut *conn).await? {
+        return Err(HistoryError::Stale(format!("Raid {} is in progress", active.raid_id)));
+    }
+    Ok(())
+}
+
+async fn reverse(conn: &mut SqliteConnection, action: &Action) -> Result<(), HistoryError> {
+    match action {
+        Action::SessionStarted(session) => {
+            if !db::get_raids_for_session(&mut *conn, session.session_id).await?.is_empty() {
+                return Err(HistoryError::Stale(format!(
+                    "Session {} has raids, undo or remove them first", session.session_id
+                )));
+            }
+            db::delete_session(&mut *conn, session.session_id).await?;
+        }
+        Action::SessionEnded { session_id, ended_at } => {
+            if let Some(active) = db::get_active_session(&mut *conn).await? {
+                return Err(HistoryError::Stale(format!("Session {} is already running", active.session_id)));
+            }
+            let session = db::get_session_by_id(&mut *conn, *session_id).await?;
+            if session.and_then(|s| s.ended_at) != Some(*ended_at) {
+                return Err(HistoryError::Stale(format!("Session {} end time has changed", session_id)));
+            }
+            db::reopen_session(&mut *conn, *session_id).await?;
+        }
+        Action::RaidCreated(raid) => {
+            let has_transitions = !db::get_raid_transitions(&mut *conn, raid.raid_id).await?.is_empty();
+            let has_kills = !db::get_kills_for_raid(&mut *conn, raid.raid_id).await?.is_empty();
+            if has_transitions || has_kills {
+                return Err(HistoryError::Stale(format!(
+                    "Raid {} has transitions or kills, undo or remove them first", raid.raid_id
+                )));
+            }
+            db::delete_raid(&mut *conn, raid.raid_id).await?;
+        }
+        Action::Transitioned(transition) => {
+            check_last_transition(&mut *conn, transition).await?;
+            db::delete_transition(&mut *conn, transition.transition_id).await?;
+        }
+        Action::RaidEnded { raid_id, ended_at, transition, .. } => {
+            let raid = find_raid(&mut *conn, *raid_id).await?;
+            if raid.ended_at != Some(*ended_at) {
+                return Err(HistoryError::Stale(format!("Raid {} end time has changed", raid_id)));
+            }
+            check_no_active_raid(&mut *conn).await?;
+            if let Some(transition) = transition {
+                check_last_transition(&mut *conn, transition).await?;
+            }
+
+            db::reopen_raid(&mut *conn, *raid_id).await?;
+            if let Some(transition) = transition {
+                db::delete_transition(&mut *conn, transition.transition_id).await?;
+            }
+        }
+        Action::RaidTransferred { raid_id, ended_at, transition, segment, segment_transition } => {
+            // The new segment has to be as fresh as the transfer left it
+            check_last_transition(&mut *conn, segment_transition).await?;
+            let transitions = db::get_raid_transitions(&mut *conn, segment.raid_id).await?;
+            let has_kills = !db::get_kills_for_raid(&mut *conn, segment.raid_id).await?.is_empty();
+            if transitions.len() > 1 || has_kills {
+                return Err(HistoryError::Stale(format!(
+                    "Raid {} has transitions or kills since the transfer, undo or remove them first", segment.raid_id
+                )));
+            }
+            let raid = find_raid(&mut *conn, *raid_id).await?;
+            if raid.ended_at != Some(*ended_at) {
+                return Err(HistoryError::Stale(format!("Raid {} end time has changed", raid_id)));
+            }
+            check_last_transition(&mut *conn, transition).await?;
+
+            db::delete_raid(&mut *conn, segment.raid_id).await?;
+            db::reopen_raid(&mut *conn, *raid_id).await?;
+            db::delete_transition(&mut *conn, transition.transition_id).await?;
+        }
+        Action::KillsAdded(kills) => {
+            for kill in kills {
+                db::delete_kill(&mut *conn, kill.kill_id).await?;
+            }
+        }
+        Action::KillUpdated { before, .. } => db::update_kill(&mut *conn, before).await?,
+        Action::KillDeleted(kill) => db::restore_kill(&mut *conn, kill).await?,
+        Action::RaidUpdated { before, .. } => db::update_raid(&mut *conn, before).await?,
+        Action::RaidDeleted { raid, transitions, kills } => {
+            db::restore_raid(&mut *conn, raid).await?;
+            for transition in transitions {
+                db::restore_transition(&mut *conn, transition).await?;
+            }
+            for kill in kills {
+                db::restore_kill(&mut *conn, kill).await?;
+            }
+        }
+        Action::TransitionUpdated { before, .. } => db::update_transition(&mut *conn, before).await?,
+        Action::TransitionDeleted(transition) => db::restore_transition(&mut *conn, transition).await?,
+    }
+
+    Ok(())
+}
+
+async fn replay(conn: &mut SqliteConnection, action: &Action) -> Result<(), HistoryError> {
+    match action {
+        Action::SessionStarted(session) => {
+            if let Some(active) = db::get_active_session(&mut *conn).await? {
+                return Err(HistoryError::Stale(format!("Session {} is already running", active.session_id)));
+            }
+            db::restore_session(&mut *conn, session).await?;
+        }
+        Action::SessionEnded { session_id, ended_at } => {
+            db::end_session(&mut *conn, *session_id, Some(*ended_at)).await?;
+        }
+        Action::RaidCreated(raid) => {
+            check_no_active_raid(&mut *conn).await?;
+            db::restore_raid(&mut *conn, raid).await?;
+        }
+        Action::Transitioned(transition) => {
+            let raid = find_raid(&mut *conn, transition.raid_id).await?;
+            let from_state = transition.from_state.clone().unwrap_or_else(|| raid.initial_state());
+            if raid.current_state != from_state {
+                return Err(HistoryError::Stale(format!(
+                    "Raid {} is in {}, not {}", raid.raid_id, raid.current_state, from_state
+                )));
+            }
+            db::restore_transition(&mut *conn, transition).await?;
+        }
+        Action::RaidEnded { raid_id, ended_at, extract_location, transition } => {
+            let raid = find_raid(&mut *conn, *raid_id).await?;
+            if raid.ended_at.is_some() {
+                return Err(HistoryError::Stale(format!("Raid {} has already ended", raid_id)));
+            }
+            if let Some(transition) = transition {
+                db::restore_transition(&mut *conn, transition).await?;
+            }
+            db::end_raid(&mut *conn, *raid_id, Some(*ended_at), extract_location.clone()).await?;
+        }
+        Action::RaidTransferred { raid_id, ended_at, transition, segment, segment_transition } => {
+            let raid = find_raid(&mut *conn, *raid_id).await?;
+            if raid.ended_at.is_some() {
+                return Err(HistoryError::Stale(format!("Raid {} has already ended", raid_id)));
+            }
+            db::restore_transition(&mut *conn, transition).await?;
+            db::end_raid(&mut *conn, *raid_id, Some(*ended_at), None).await?;
+            db::restore_raid(&mut *conn, segment).await?;
+            db::restore_transition(&mut *conn, segment_transition).await?;
+        }
+        Action::KillsAdded(kills) => {
+            for kill in kills {
+                db::restore_kill(&mut *conn, kill).await?;
+            }
+        }
+        Action::KillUpdated { after, .. } => db::update_kill(&mut *conn, after).await?,
+        Action::KillDeleted(kill) => {
+            db::delete_kill(&mut *conn, kill.kill_id).await?;
+        }
+        Action::RaidUpdated { after, .. } => db::update_raid(&mut *conn, after).await?,
+        Action::RaidDeleted { raid, .. } => {
+            if db::get_raid_chain(&mut *conn, raid.raid_id).await?.len() > 1 {
+                return Err(HistoryError::Stale(format!("Raid {} has transfer segments now", raid.raid_id)));
+            }
+            db::delete_raid(&mut *conn, raid.raid_id).await?;
+        }
+        Action::TransitionUpdated { after, .. } => db::update_transition(&mut *conn, after).await?,
+        Action::TransitionDeleted(transition) => {
+            db::delete_transition(&mut *conn, transition.transition_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::setup_test_db;
+    use crate::models::{CharacterType, GameMode, SessionType};
+
+    fn no_audit(_: &Action) -> Vec<NewAuditEntry> {
+        Vec::new()
+    }
+
+    async fn setup() -> Result<(SqlitePool, i64, Raid), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let session_id = db::create_session(&pool, SessionType::Stream, None, None).await?;
+        let raid_id = db::create_raid(&pool, session_id, "Customs", CharacterType::PMC, GameMode::PVE, None).await?;
+        let raid = db::get_raid_by_id(&pool, raid_id).await?.unwrap();
+        Ok((pool, session_id, raid))
+    }
+
+    async fn transition(pool: &SqlitePool, raid: &Raid, to_state: &str) -> Result<Action, sqlx::Error> {
+        let from_state = db::get_raid_by_id(pool, raid.raid_id).await?.unwrap().current_state;
+        let ts = OffsetDateTime::now_utc();
+        let transition_id = db::log_state_transition(pool, raid.raid_id, to_state, Some(ts)).await?;
+        Ok(Action::Transitioned(RaidStateTransition {
+            transition_id,
+            raid_id: raid.raid_id,
+            from_state: Some(from_state),
+            to_state: to_state.into(),
+            transitioned_at: ts,
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_undo_transition_restores_state_and_deletes_row() -> Result<(), HistoryError> {
+        let (pool, session_id, raid) = setup().await?;
+        let history = History::default();
+
+        history.record(session_id, transition(&pool, &raid, "pre_raid_setup").await?).await;
+        history.record(session_id, transition(&pool, &raid, "queuing").await?).await;
+
+        let undone = history.undo(&pool, session_id, no_audit).await?;
+        assert_eq!(undone.name(), "state_transitioned");
+
+        let after = db::get_raid_by_id(&pool, raid.raid_id).await?.unwrap();
+        assert_eq!(after.current_state, "pre_raid_setup");
+        let transitions = db::get_raid_transitions(&pool, raid.raid_id).await?;
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].to_state, "pre_raid_setup");
+
+        // Redo brings back the same row
+        history.redo(&pool, session_id, no_audit).await?;
+        let transitions = db::get_raid_transitions(&pool, raid.raid_id).await?;
+        assert_eq!(transitions.len(), 2);
+        assert_eq!(transitions[1].to_state, "queuing");
+        assert_eq!(db::get_raid_by_id(&pool, raid.raid_id).await?.unwrap().current_state, "queuing");
+        assert_eq!(history.depth(session_id).await, (2, 0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_undos_take_turns() -> Result<(), HistoryError> {
+        let (pool, session_id, raid) = setup().await?;
+        let history = History::default();
+        for state in ["pre_raid_setup", "queuing", "deploying_committed"] {
+            history.record(session_id, transition(&pool, &raid, state).await?).await;
+        }
+
+        // Each one only sees the stack after the other has finished
+        let (first, second) = tokio::join!(
+            history.undo(&pool, session_id, no_audit),
+            history.undo(&pool, session_id, no_audit),
+        );
+        first?;
+        second?;
+        assert_eq!(db::get_raid_by_id(&pool, raid.raid_id).await?.unwrap().current_state, "pre_raid_setup");
+        assert_eq!(history.depth(session_id).await, (1, 2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_undo_leaves_nothing_half_restored() -> Result<(), HistoryError> {
+        let (pool, session_id, raid) = setup().await?;
+        let history = History::default();
+        let kill_id = db::add_kill(&pool, raid.raid_id, "scav", None, None, None).await?;
+        let kill = db::get_kills_for_raid(&pool, raid.raid_id).await?.pop().unwrap();
+        assert_eq!(kill.kill_id, kill_id);
+
+        // The same kill twice, the second restore fails after the raid is already back
+        db::delete_raid(&pool, raid.raid_id).await?;
+        history.record(session_id, Action::RaidDeleted {
+            raid: raid.clone(),
+            transitions: Vec::new(),
+            kills: vec![kill.clone(), kill],
+        }).await;
+
+        assert!(matches!(history.undo(&pool, session_id, no_audit).await, Err(HistoryError::Database(_))));
+        assert!(db::get_raid_by_id(&pool, raid.raid_id).await?.is_none());
+        assert_eq!(history.depth(session_id).await, (1, 0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_undo_end_raid_reopens_it() -> Result<(), HistoryError> {
+        let (pool, session_id, raid) = setup().await?;
+        let history = History::default();
+        for state in ["pre_raid_setup", "queuing", "deploying_committed", "raid_active"] {
+            transition(&pool, &raid, state).await?;
+        }
+
+        // The mispress: died instead of survived
+        let Action::Transitioned(died) = transition(&pool, &raid, "died").await? else { unreachable!() };
+        let ended_at = OffsetDateTime::now_utc();
+        db::end_raid(&pool, raid.raid_id, Some(ended_at), None).await?;
+        history.record(session_id, Action::RaidEnded {
+            raid_id: raid.raid_id,
+            ended_at,
+            extract_location: None,
+            transition: Some(died),
+        }).await;
+
+        history.undo(&pool, session_id, no_audit).await?;
+        let reopened = db::get_active_raid(&pool).await?.expect("raid should be active again");
+        assert_eq!(reopened.raid_id, raid.raid_id);
+        assert_eq!(reopened.current_state, "raid_active");
+        assert!(reopened.ended_at.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stale_undo_is_refused_and_kept() -> Result<(), HistoryError> {
+        let (pool, session_id, raid) = setup().await?;
+        let history = History::default();
+
+        history.record(session_id, transition(&pool, &raid, "pre_raid_setup").await?).await;
+        // Detection moves the raid on without going through the history
+        transition(&pool, &raid, "queuing").await?;
+
+        assert!(matches!(history.undo(&pool, session_id, no_audit).await, Err(HistoryError::Stale(_))));
+        assert_eq!(history.depth(session_id).await, (1, 0));
+        assert_eq!(db::get_raid_by_id(&pool, raid.raid_id).await?.unwrap().current_state, "queuing");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_history_is_bounded_and_new_actions_clear_redo() -> Result<(), HistoryError> {
+        let (pool, session_id, raid) = setup().await?;
+        let history = History::with_capacity(2);
+
+        for state in ["pre_raid_setup", "queuing", "deploying_committed"] {
+            history.record(session_id, transition(&pool, &raid, state).await?).await;
+        }
+        assert_eq!(history.depth(session_id).await, (2, 0));
+
+        history.undo(&pool, session_id, no_audit).await?;
+        assert_eq!(history.depth(session_id).await, (1, 1));
+
+        history.record(session_id, transition(&pool, &raid, "deploying_committed").await?).await;
+        assert_eq!(history.depth(session_id).await, (2, 0));
+        assert!(matches!(history.redo(&pool, session_id, no_audit).await, Err(HistoryError::Empty(_))));
+
+        // Other sessions have their own history
+        assert_eq!(history.depth(session_id + 1).await, (0, 0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_kill_actions_round_trip() -> Result<(), HistoryError> {
+        let (pool, session_id, raid) = setup().await?;
+        let history = History::default();
+
+        let kill_id = db::add_kill(&pool, raid.raid_id, "scav", None, None, None).await?;
+        let kill = db::get_kill_by_id(&pool, kill_id).await?.unwrap();
+        history.record(session_id, Action::KillsAdded(vec![kill.clone()])).await;
+
+        let mut edited = kill.clone();
+        edited.enemy_type = "pmc".into();
+        db::update_kill(&pool, &edited).await?;
+        history.record(session_id, Action::KillUpdated { before: kill.clone(), after: edited }).await;
+
+        history.undo(&pool, session_id, no_audit).await?;
+        assert_eq!(db::get_kill_by_id(&pool, kill_id).await?.unwrap().enemy_type, "scav");
+
+        history.undo(&pool, session_id, no_audit).await?;
+        assert!(db::get_kill_by_id(&pool, kill_id).await?.is_none());
+
+        history.redo(&pool, session_id, no_audit).await?;
+        history.redo(&pool, session_id, no_audit).await?;
+        assert_eq!(db::get_kill_by_id(&pool, kill_id).await?.unwrap().enemy_type, "pmc");
+        Ok(())
+    }
+}
@@ -0,0 +1,525 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{SqliteConnection, SqlitePool};
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::db;
+use crate::models::{CharacterType, GameMode, RaidState, SessionType};
+
+// ============================================================
+// Import Document
+// ============================================================
+
+// A whole session replayed from a recording, e.g. the output of the batch
+// pipeline in docs/phase_4_expanded_batch_processing_plan.md:
+// {
+//   "session": {"session_type": "stream", "started_at": "2026-01-15T18:00:00Z", "ended_at": "..."},
+//   "events": [
+//     {"type": "raid_start", "timestamp": "...", "map_name": "Customs", "character_type": "pmc", "game_mode": "pve"},
+//     {"type": "transition", "timestamp": "...", "to_state": "queuing"},
+//     {"type": "kill", "timestamp": "...", "enemy_type": "scav", "weapon_used": "AK-74M", "headshot": true},
+//     {"type": "raid_end", "timestamp": "...", "final_state": "survived", "extract_location": "Crossroads"}
+//   ]
+// }
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionImport {
+    pub session: SessionMeta,
+    pub events: Vec<ImportEvent>,
+    // Same escape hatch as the transition endpoint, accept moves outside the RaidState graph
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionMeta {
+    pub session_type: SessionType,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub ended_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImportEvent {
+    RaidStart {
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
+        map_name: String,
+        character_type: CharacterType,
+        game_mode: GameMode,
+    },
+    Transition {
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
+        to_state: String,
+    },
+    Kill {
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
+        enemy_type: String,
+        #[serde(default)]
+        weapon_used: Option<String>,
+        #[serde(default)]
+        headshot: Option<bool>,
+    },
+    RaidEnd {
+        #[serde(with = "time::serde::rfc3339")]
+        timestamp: OffsetDateTime,
+        final_state: String,
+        #[serde(default)]
+        extract_location: Option<String>,
+    },
+}
+
+impl ImportEvent {
+    // In UTC, like every timestamp the API stores
+    pub fn timestamp(&self) -> OffsetDateTime {
+        match self {
+            ImportEvent::RaidStart { timestamp, .. }
+            | ImportEvent::Transition { timestamp, .. }
+            | ImportEvent::Kill { timestamp, .. }
+            | ImportEvent::RaidEnd { timestamp, .. } => timestamp.to_offset(UtcOffset::UTC),
+        }
+    }
+}
+
+// ============================================================
+// Plan
+// ============================================================
+
+// The validated document, shaped the way the tables want it
+#[derive(Debug, Clone)]
+pub struct ImportPlan {
+    pub session: SessionMeta,
+    pub raids: Vec<PlannedRaid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedRaid {
+    pub map_name: String,
+    pub character_type: CharacterType,
+    pub game_mode: GameMode,
+    pub started_at: OffsetDateTime,
+    pub ended_at: Option<OffsetDateTime>,
+    pub extract_location: Option<String>,
+    pub current_state: RaidState,
+    // (from_state, to_state, transitioned_at)
+    pub transitions: Vec<(RaidState, RaidState, OffsetDateTime)>,
+    pub kills: Vec<PlannedKill>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedKill {
+    pub enemy_type: String,
+    pub weapon_used: Option<String>,
+    pub headshot: Option<bool>,
+    pub killed_at: OffsetDateTime,
+}
+
+// Walks the events in order and builds the plan, every problem is collected
+// so a long event log can be fixed in one pass
+pub fn plan(doc: &SessionImport) -> Result<ImportPlan, Vec<String>> {
+    let mut errors = Vec::new();
+    let mut raids: Vec<PlannedRaid> = Vec::new();
+    let mut open = false;
+    let session = &doc.session;
+
+    if let Some(ended_at) = session.ended_at
+        && ended_at < session.started_at
+    {
+        errors.push("session: ended_at is before started_at".to_string());
+    }
+
+    let mut previous = session.started_at;
+
+    for (i, event) in doc.events.iter().enumerate() {
+        let at = format!("events[{}]", i);
+        let ts = event.timestamp();
+
+        if ts < previous {
+            errors.push(format!("{}: timestamp is earlier than the event before it", at));
+        }
+        if let Some(ended_at) = session.ended_at
+            && ts > ended_at
+        {
+            errors.push(format!("{}: timestamp is after the session ended", at));
+        }
+        previous = previous.max(ts);
+
+        match event {
+            ImportEvent::RaidStart { map_name, character_type, game_mode, .. } => {
+                if open {
+                    errors.push(format!("{}: raid_start while the previous raid is still running", at));
+                    continue;
+                }
+                if map_name.trim().is_empty() {
+                    errors.push(format!("{}: map_name cannot be empty", at));
+                }
+                raids.push(PlannedRaid {
+                    map_name: map_name.clone(),
+                    character_type: character_type.clone(),
+                    game_mode: game_mode.clone(),
+                    started_at: ts,
+                    ended_at: None,
+                    extract_location: None,
+                    current_state: RaidState::StashManagement,
+                    transitions: Vec::new(),
+                    kills: Vec::new(),
+                });
+                open = true;
+            }
+            ImportEvent::Transition { to_state, .. } => {
+                let Some(raid) = raids.last_mut().filter(|_| open) else {
+                    errors.push(format!("{}: transition outside of a raid", at));
+                    continue;
+                };
+                let to_state = RaidState::parse(to_state);
+                if to_state.is_terminal() {
+                    errors.push(format!("{}: use raid_end to record the '{}' outcome", at, to_state));
+                    continue;
+                }
+                if let Err(e) = check(&raid.current_state, &to_state, doc.force) {
+                    errors.push(format!("{}: {}", at, e));
+                }
+                raid.transitions.push((raid.current_state.clone(), to_state.clone(), ts));
+                raid.current_state = to_state;
+            }
+            ImportEvent::Kill { enemy_type, weapon_used, headshot, .. } => {
+                let Some(raid) = raids.last_mut().filter(|_| open) else {
+                    errors.push(format!("{}: kill outside of a raid", at));
+                    continue;
+                };
+                if enemy_type.trim().is_empty() {
+                    errors.push(format!("{}: enemy_type cannot be empty", at));
+                }
+                raid.kills.push(PlannedKill {
+                    enemy_type: enemy_type.clone(),
+                    weapon_used: weapon_used.clone(),
+                    headshot: *headshot,
+                    killed_at: ts,
+                });
+            }
+            ImportEvent::RaidEnd { final_state, extract_location, .. } => {
+                let Some(raid) = raids.last_mut().filter(|_| open) else {
+                    errors.push(format!("{}: raid_end without a running raid", at));
+                    continue;
+                };
+                let final_state = RaidState::parse(final_state);
+                if !final_state.is_terminal() {
+                    errors.push(format!("{}: final_state must be survived, died or mia, got '{}'", at, final_state));
+                    continue;
+                }
+                if raid.current_state != final_state {
+                    if let Err(e) = check(&raid.current_state, &final_state, doc.force) {
+                        errors.push(format!("{}: {}", at, e));
+                    }
+                    raid.transitions.push((raid.current_state.clone(), final_state.clone(), ts));
+                    raid.current_state = final_state;
+                }
+                raid.ended_at = Some(ts);
+                raid.extract_location = extract_location.clone();
+                open = false;
+            }
+        }
+    }
+
+    // A finished session can't have a raid still running in it
+    if open && session.ended_at.is_some() {
+        errors.push("events: last raid has no raid_end but the session has ended_at".to_string());
+    }
+
+    if errors.is_empty() {
+        let session = SessionMeta {
+            started_at: session.started_at.to_offset(UtcOffset::UTC),
+            ended_at: session.ended_at.map(|ts| ts.to_offset(UtcOffset::UTC)),
+            ..session.clone()
+        };
+        Ok(ImportPlan { session, raids })
+    } else {
+        Err(errors)
+    }
+}
+
+fn check(from: &RaidState, to: &RaidState, force: bool) -> Result<(), String> {
+    if force {
+        return Ok(());
+    }
+    from.check_transition(to).map_err(|e| e.to_string())
+}
+
+// ============================================================
+// Import
+// ============================================================
+
+#[derive(Debug)]
+pub enum ImportError {
+    Invalid(Vec<String>),
+    // Clashes with sessions already in the database
+    Overlap(String),
+    Database(sqlx::Error),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Invalid(errors) => write!(f, "Invalid import: {}", errors.join("; ")),
+            ImportError::Overlap(msg) => write!(f, "{}", msg),
+            ImportError::Database(e) => write!(f, "Database error: {}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for ImportError {
+    fn from(e: sqlx::Error) -> Self {
+        ImportError::Database(e)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    // None on a dry run
+    pub session_id: Option<i64>,
+    pub raids: Vec<RaidReport>,
+    pub total_transitions: usize,
+    pub total_kills: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RaidReport {
+    pub raid_id: Option<i64>,
+    pub map_name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub ended_at: Option<OffsetDateTime>,
+    pub final_state: RaidState,
+    pub transitions: usize,
+    pub kills: usize,
+}
+
+// Existing sessions still running count as open ended
+async fn check_overlap(conn: &mut SqliteConnection, plan: &ImportPlan) -> Result<(), ImportError> {
+    let start = plan.session.started_at;
+    let end = plan.session.ended_at;
+
+    for existing in db::get_all_sessions(conn).await? {
+        let starts_before_existing_ends = existing.ended_at.is_none_or(|e| start < e);
+        let ends_after_existing_starts = end.is_none_or(|e| e > existing.started_at);
+
+        if starts_before_existing_ends && ends_after_existing_starts {
+            return Err(ImportError::Overlap(format!(
+                "Imported session overlaps existing session {}", existing.session_id
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn import_session(pool: &SqlitePool, doc: &SessionImport, dry_run: bool) -> Result<ImportReport, ImportError> {
+    let plan = plan(doc).map_err(ImportError::Invalid)?;
+
+    // The overlap check and the insert share a transaction, so a session started or
+    // imported in between can't slip past it
+    let mut tx = pool.begin().await?;
+    check_overlap(&mut tx, &plan).await?;
+
+    let (session_id, raid_ids) = if dry_run {
+        (None, vec![None; plan.raids.len()])
+    } else {
+        let (session_id, raid_ids) = db::insert_import(&mut *tx, &plan).await?;
+        tx.commit().await?;
+        (Some(session_id), raid_ids.into_iter().map(Some).collect())
+    };
+
+    let raids = plan.raids.iter().zip(raid_ids)
+        .map(|(raid, raid_id)| RaidReport {
+            raid_id,
+            map_name: raid.map_name.clone(),
+            started_at: raid.started_at,
+            ended_at: raid.ended_at,
+            final_state: raid.current_state.clone(),
+            transitions: raid.transitions.len(),
+            kills: raid.kills.len(),
+        })
+        .collect();
+
+    Ok(ImportReport {
+        dry_run,
+        session_id,
+        raids,
+        total_transitions: plan.raids.iter().map(|r| r.transitions.len()).sum(),
+        total_kills: plan.raids.iter().map(|r| r.kills.len()).sum(),
+    })
+}
+
+// `tarkov_stream_producer import <file.json> [--dry-run]`
+pub async fn run_cli(pool: &SqlitePool, args: &[String]) -> Result<String, String> {
+    let dry_run = args.iter().any(|a| a == "--dry-run");
+    let path = args.iter().find(|a| !a.starts_with("--"))
+        .ok_or("Usage: import <session.json> [--dry-run]")?;
+
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let doc: SessionImport = serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+
+    let report = import_session(pool, &doc, dry_run).await.map_err(|e| e.to_string())?;
+    serde_json::to_string_pretty(&report).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::setup_test_db;
+    use serde_json::json;
+
+    fn sample() -> serde_json::Value {
+        json!({
+            "session": {
+                "session_type": "stream",
+                "started_at": "2026-01-15T18:00:00Z",
+                "ended_at": "2026-01-15T20:00:00Z"
+            },
+            "events": [
+                {"type": "raid_start", "timestamp": "2026-01-15T18:05:00Z", "map_name": "Customs", "character_type": "pmc", "game_mode": "pve"},
+                {"type": "transition", "timestamp": "2026-01-15T18:06:00Z", "to_state": "pre_raid_setup"},
+                {"type": "transition", "timestamp": "2026-01-15T18:07:00Z", "to_state": "queuing"},
+                {"type": "transition", "timestamp": "2026-01-15T18:09:00Z", "to_state": "deploying_committed"},
+                {"type": "transition", "timestamp": "2026-01-15T18:10:00Z", "to_state": "raid_active"},
+                {"type": "kill", "timestamp": "2026-01-15T18:15:00Z", "enemy_type": "scav", "weapon_used": "AK-74M", "headshot": true},
+                {"type": "raid_end", "timestamp": "2026-01-15T18:40:00Z", "final_state": "survived", "extract_location": "Crossroads"},
+                {"type": "raid_start", "timestamp": "2026-01-15T18:45:00Z", "map_name": "Woods", "character_type": "scav", "game_mode": "pve"},
+                {"type": "transition", "timestamp": "2026-01-15T18:46:00Z", "to_state": "pre_raid_setup"},
+                {"type": "transition", "timestamp": "2026-01-15T18:47:00Z", "to_state": "deploying_cancellable"},
+                {"type": "transition", "timestamp": "2026-01-15T18:48:00Z", "to_state": "deploying_committed"},
+                {"type": "transition", "timestamp": "2026-01-15T18:49:00Z", "to_state": "raid_active"},
+                {"type": "raid_end", "timestamp": "2026-01-15T19:00:00Z", "final_state": "died"}
+            ]
+        })
+    }
+
+    fn doc(value: serde_json::Value) -> SessionImport {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_plan_builds_transition_chain() {
+        let plan = plan(&doc(sample())).unwrap();
+
+        assert_eq!(plan.raids.len(), 2);
+        let raid = &plan.raids[0];
+        assert_eq!(raid.current_state, RaidState::Survived);
+        assert_eq!(raid.transitions.len(), 5);
+        assert_eq!(raid.transitions[0].0, RaidState::StashManagement);
+        assert_eq!(raid.transitions[4], (RaidState::RaidActive, RaidState::Survived, raid.ended_at.unwrap()));
+        assert_eq!(raid.kills.len(), 1);
+        assert_eq!(plan.raids[1].current_state, RaidState::Died);
+    }
+
+    #[test]
+    fn test_plan_collects_ordering_and_overlap_errors() {
+        let mut value = sample();
+        let events = value["events"].as_array_mut().unwrap();
+        // Kill out of order
+        events[5]["timestamp"] = json!("2026-01-15T18:00:30Z");
+        // Second raid starts before the first one ended
+        events.remove(6);
+
+        let errors = plan(&doc(value)).unwrap_err();
+        assert!(errors.iter().any(|e| e.starts_with("events[5]: timestamp is earlier")), "{:?}", errors);
+        assert!(errors.iter().any(|e| e.starts_with("events[6]: raid_start while")), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_plan_checks_graph_unless_forced() {
+        let value = json!({
+            "session": {"session_type": "practice", "started_at": "2026-01-15T18:00:00Z"},
+            "events": [
+                {"type": "raid_start", "timestamp": "2026-01-15T18:05:00Z", "map_name": "Customs", "character_type": "pmc", "game_mode": "pve"},
+                {"type": "transition", "timestamp": "2026-01-15T18:06:00Z", "to_state": "raid_active"}
+            ]
+        });
+        let errors = plan(&doc(value.clone())).unwrap_err();
+        assert_eq!(errors, vec!["events[1]: Illegal state transition: stash_management -> raid_active"]);
+
+        let mut forced = value;
+        forced["force"] = json!(true);
+        assert!(plan(&doc(forced)).is_ok());
+    }
+
+    #[test]
+    fn test_plan_rejects_open_raid_in_ended_session() {
+        let mut value = sample();
+        value["events"].as_array_mut().unwrap().pop();
+
+        let errors = plan(&doc(value)).unwrap_err();
+        assert_eq!(errors, vec!["events: last raid has no raid_end but the session has ended_at"]);
+    }
+
+    #[tokio::test]
+    async fn test_import_inserts_with_original_timestamps() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let report = import_session(&pool, &doc(sample()), false).await.unwrap();
+
+        let session_id = report.session_id.unwrap();
+        let session = db::get_session_by_id(&pool, session_id).await?.unwrap();
+        assert_eq!(session.started_at, time::macros::datetime!(2026-01-15 18:00 UTC));
+        assert_eq!(session.ended_at, Some(time::macros::datetime!(2026-01-15 20:00 UTC)));
+
+        let raids = db::get_raids_for_session(&pool, session_id).await?;
+        assert_eq!(raids.len(), 2);
+        assert_eq!(raids[0].current_state, "survived");
+        assert_eq!(raids[0].extract_location.as_deref(), Some("Crossroads"));
+        assert_eq!(raids[0].ended_at, Some(time::macros::datetime!(2026-01-15 18:40 UTC)));
+
+        let transitions = db::get_raid_transitions(&pool, raids[0].raid_id).await?;
+        assert_eq!(transitions.len(), 5);
+        assert_eq!(transitions[0].from_state, Some("stash_management".into()));
+        assert_eq!(transitions[0].transitioned_at, time::macros::datetime!(2026-01-15 18:06 UTC));
+
+        let kills = db::get_kills_for_raid(&pool, raids[0].raid_id).await?;
+        assert_eq!(kills[0].killed_at, time::macros::datetime!(2026-01-15 18:15 UTC));
+        assert_eq!(report.total_kills, 1);
+        assert_eq!(report.total_transitions, 10);
+
+        // Nothing left running
+        assert!(db::get_active_session(&pool).await?.is_none());
+        assert!(db::get_active_raid(&pool).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_writes_nothing() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let report = import_session(&pool, &doc(sample()), true).await.unwrap();
+
+        assert!(report.dry_run);
+        assert!(report.session_id.is_none());
+        assert_eq!(report.raids.len(), 2);
+        assert_eq!(report.raids[1].final_state, RaidState::Died);
+        assert!(db::get_all_sessions(&pool).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_overlapping_session() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        import_session(&pool, &doc(sample()), false).await.unwrap();
+
+        let err = import_session(&pool, &doc(sample()), true).await.unwrap_err();
+        assert!(matches!(err, ImportError::Overlap(_)), "{}", err);
+
+        // Still running sessions block anything after their start
+        db::create_session(&pool, SessionType::Stream, None, Some(time::macros::datetime!(2026-02-01 12:00 UTC))).await?;
+        let mut later = sample();
+        later["session"]["started_at"] = json!("2026-03-01T18:00:00Z");
+        later["session"]["ended_at"] = json!("2026-03-01T20:00:00Z");
+        later["events"] = json!([]);
+        assert!(matches!(import_session(&pool, &doc(later), true).await, Err(ImportError::Overlap(_))));
+        Ok(())
+    }
+}
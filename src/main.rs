@@ -1,9 +1,18 @@
 mod api;
+mod chat;
 mod db;
+mod detection;
+mod events;
+mod history;
+mod import;
 mod models;
+mod obs;
+mod obs_text;
+mod records;
+mod review;
 mod stats;
 
-use tracing::{info};
+use tracing::{info, warn};
 use tracing_subscriber::{self, EnvFilter};
 
 use crate::api::state::AppState;
@@ -36,8 +45,85 @@ async fn main() {
 
     info!("Database Initialized");
 
+    // NON_PLAY_STATES=reconnecting,error,paused replaces the states left out of active raid time
+    if let Ok(states) = std::env::var("NON_PLAY_STATES") {
+        let states: Vec<String> = states.split(',')
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+        db::set_non_play_states(&pool, &states)
+            .await
+            .expect("Failed to set non-play states");
+    }
+    match db::get_non_play_states(&pool).await {
+        Ok(states) => info!("Non-play states: {}", states.join(", ")),
+        Err(e) => warn!("Cannot read non-play states: {}", e),
+    }
+
+    // `import <session.json> [--dry-run]` backfills a recorded session and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("import") {
+        match import::run_cli(&pool, &args[1..]).await {
+            Ok(report) => println!("{}", report),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let state = AppState::new(pool);
+
+    // Announces new personal bests as raids end
+    records::RecordWatcher::spawn(state.pool.clone(), state.events.clone());
+
+    // Optional OBS "Read from file" text sources
+    match obs_text::TextSinkConfig::from_env() {
+        Ok(Some(config)) => {
+            obs_text::TextSink::new(state.pool.clone(), config).spawn(state.events.clone());
+        }
+        Ok(None) => info!("OBS_TEXT_DIR not set, OBS text files disabled"),
+        Err(e) => warn!("OBS text files disabled: {}", e),
+    }
+
+    // Optional obs-websocket connection for scene switching
+    match obs::config_from_env() {
+        Ok(Some((connect, reactions))) => {
+            let client = obs::ObsClient::connect(connect);
+            if reactions.is_empty() {
+                warn!("OBS_WS_URL is set but no OBS_STATE_SCENES / OBS_STATE_TEXT_SOURCE / OBS_SOURCE_VISIBILITY configured");
+            }
+            obs::reactions::spawn(client, reactions, state.events.clone());
+        }
+        Ok(None) => info!("OBS_WS_URL not set, obs-websocket disabled"),
+        Err(e) => warn!("obs-websocket disabled: {}", e),
+    }
+
+    // Optional Twitch chat bot
+    match chat::bot_from_env(state.pool.clone()) {
+        Ok(Some(bot)) => {
+            bot.spawn();
+        }
+        Ok(None) => info!("TWITCH_TOKEN / TWITCH_CHANNEL not set, chat bot disabled"),
+        Err(e) => warn!("Chat bot disabled: {}", e),
+    }
+
+    // Optional detection service feed
+    match detection::config_from_env() {
+        Ok(Some(config)) => {
+            let applier = detection::apply::Applier::new(state.pool.clone(), state.events.clone(), state.detection.clone())
+                .with_thresholds(config.thresholds)
+                .with_defaults(config.default_character_type, config.default_game_mode)
+                .with_review(config.review);
+            detection::client::spawn(config.client, applier, state.detection.clone());
+        }
+        Ok(None) => info!("DETECTION_WS_URL not set, automated tracking disabled"),
+        Err(e) => warn!("Automated tracking disabled: {}", e),
+    }
+
     // Build the router and attach the database pool
-    let app = api_router().with_state(AppState::new(pool));
+    let app = api_router().with_state(state);
 
     // Start listening on localhost port 3000
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
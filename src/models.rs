@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
 #[sqlx(type_name = "TEXT", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")] // Ensure serde matches sqlx's lowercase
+#[allow(clippy::upper_case_acronyms)] // Matches how the game names them
 pub enum CharacterType {
     PMC,
     Scav,
@@ -15,6 +16,7 @@ pub enum CharacterType {
 #[derive(Debug, Clone, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
 #[sqlx(type_name = "TEXT", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")] // Ensure serde matches sqlx's lowercase
+#[allow(clippy::upper_case_acronyms)] // Matches how the game names them
 pub enum GameMode {
     PVE,
     PVP,
@@ -29,6 +31,356 @@ pub enum SessionType {
     Casual,
 }
 
+// Where a queued event is in review
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl ReviewStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReviewStatus::Pending => "pending",
+            ReviewStatus::Approved => "approved",
+            ReviewStatus::Rejected => "rejected",
+        }
+    }
+}
+
+// Orderings for the history listings, newest first unless asked otherwise
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RaidSort {
+    #[default]
+    StartedAt,
+    Duration,
+    Kills,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSort {
+    #[default]
+    StartedAt,
+    Duration,
+    Kills,
+    Raids,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+// Column the raid aggregates can be split by, several can be combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsGroup {
+    GameMode,
+    CharacterType,
+    Map,
+    SessionType,
+    Session,
+    // Day the raid started on, in UTC
+    Weekday,
+}
+
+impl StatsGroup {
+    pub const ALL: [StatsGroup; 6] = [
+        StatsGroup::GameMode,
+        StatsGroup::CharacterType,
+        StatsGroup::Map,
+        StatsGroup::SessionType,
+        StatsGroup::Session,
+        StatsGroup::Weekday,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatsGroup::GameMode => "game_mode",
+            StatsGroup::CharacterType => "character_type",
+            StatsGroup::Map => "map",
+            StatsGroup::SessionType => "session_type",
+            StatsGroup::Session => "session",
+            StatsGroup::Weekday => "weekday",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<StatsGroup> {
+        Self::ALL.into_iter().find(|g| g.as_str() == s)
+    }
+}
+
+// What counts as one raid in the stats when a raid transferred maps
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RaidUnit {
+    // Every map played is its own raid
+    #[default]
+    Segments,
+    // A raid and its transfers are one, with the last segment's outcome
+    Chains,
+}
+
+// What one point of a trend covers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendUnit {
+    #[default]
+    Sessions,
+    Raids,
+}
+
+// The value a trend sparkline plots
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendMetric {
+    #[default]
+    SurvivalRate,
+    KdRatio,
+    Kills,
+    Raids,
+}
+
+// Personal bests on the records board. Raid records point at a raid, session
+// records only at the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    MostKills,
+    LongestRaid,
+    FastestExtract,
+    MostRaidsInSession,
+    BestSessionSurvivalRate,
+}
+
+impl RecordKind {
+    pub const ALL: [RecordKind; 5] = [
+        RecordKind::MostKills,
+        RecordKind::LongestRaid,
+        RecordKind::FastestExtract,
+        RecordKind::MostRaidsInSession,
+        RecordKind::BestSessionSurvivalRate,
+    ];
+
+    // Fastest extract is the only one where less is better
+    pub fn is_better(&self, value: f64, than: f64) -> bool {
+        match self {
+            RecordKind::FastestExtract => value < than,
+            _ => value > than,
+        }
+    }
+}
+
+// ============================================================
+// Raid State Machine
+// ============================================================
+
+// The raids/raid_state_transitions tables store states as plain TEXT so Phase 4
+// detection can discover new ones. RaidState is the typed view over that column,
+// anything not in the catalog lands in Uncatalogued instead of failing to parse.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum RaidState {
+    Idle,
+    StashManagement,
+    PreRaidSetup,
+    Queuing,
+    DeployingCancellable,
+    DeployingCommitted,
+    RaidActive,
+    RaidEnding,
+    PostRaidReview,
+    Survived,
+    Died,
+    Mia,
+    Transfer,
+    Reconnecting,
+    Paused,
+    Error,
+    Uncatalogued(String),
+}
+
+impl RaidState {
+    pub fn as_str(&self) -> &str {
+        match self {
+            RaidState::Idle => "idle",
+            RaidState::StashManagement => "stash_management",
+            RaidState::PreRaidSetup => "pre_raid_setup",
+            RaidState::Queuing => "queuing",
+            RaidState::DeployingCancellable => "deploying_cancellable",
+            RaidState::DeployingCommitted => "deploying_committed",
+            RaidState::RaidActive => "raid_active",
+            RaidState::RaidEnding => "raid_ending",
+            RaidState::PostRaidReview => "post_raid_review",
+            RaidState::Survived => "survived",
+            RaidState::Died => "died",
+            RaidState::Mia => "mia",
+            RaidState::Transfer => "transfer",
+            RaidState::Reconnecting => "reconnecting",
+            RaidState::Paused => "paused",
+            RaidState::Error => "error",
+            RaidState::Uncatalogued(s) => s,
+        }
+    }
+
+    pub fn parse(s: &str) -> RaidState {
+        match s {
+            "idle" => RaidState::Idle,
+            "stash_management" => RaidState::StashManagement,
+            "pre_raid_setup" => RaidState::PreRaidSetup,
+            "queuing" => RaidState::Queuing,
+            "deploying_cancellable" => RaidState::DeployingCancellable,
+            "deploying_committed" => RaidState::DeployingCommitted,
+            "raid_active" => RaidState::RaidActive,
+            "raid_ending" => RaidState::RaidEnding,
+            "post_raid_review" => RaidState::PostRaidReview,
+            "survived" => RaidState::Survived,
+            "died" => RaidState::Died,
+            "mia" => RaidState::Mia,
+            "transfer" => RaidState::Transfer,
+            "reconnecting" => RaidState::Reconnecting,
+            "paused" => RaidState::Paused,
+            "error" => RaidState::Error,
+            other => RaidState::Uncatalogued(other.to_string()),
+        }
+    }
+
+    pub fn is_catalogued(&self) -> bool {
+        !matches!(self, RaidState::Uncatalogued(_))
+    }
+
+    // Survived / Died / MIA - the raid outcome
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, RaidState::Survived | RaidState::Died | RaidState::Mia)
+    }
+
+    // The declared graph of legal moves, see docs/state_flow.md.
+    // Backing out is allowed up until deploying_committed (point of no return).
+    pub fn can_transition_to(&self, next: &RaidState) -> bool {
+        use RaidState::*;
+
+        match self {
+            Idle => matches!(next, StashManagement | PreRaidSetup),
+            StashManagement => matches!(next, Idle | PreRaidSetup),
+            PreRaidSetup => matches!(next, Idle | StashManagement | Queuing | DeployingCancellable),
+            Queuing => matches!(next,
+                Idle | StashManagement | PreRaidSetup | DeployingCancellable | DeployingCommitted),
+            DeployingCancellable => matches!(next,
+                StashManagement | PreRaidSetup | Queuing | DeployingCommitted),
+            DeployingCommitted => matches!(next, RaidActive | Reconnecting | Error),
+            RaidActive => matches!(next,
+                RaidEnding | Transfer | Reconnecting | Paused | Error | Survived | Died | Mia),
+            RaidEnding => matches!(next, PostRaidReview | Transfer | Survived | Died | Mia),
+            PostRaidReview => matches!(next, Survived | Died | Mia | Idle | StashManagement),
+            Transfer => matches!(next, DeployingCancellable | DeployingCommitted),
+            Reconnecting => matches!(next, RaidActive | RaidEnding | Error | Died | Mia),
+            Paused => matches!(next, RaidActive | Reconnecting | Error | Died | Mia),
+            Error => matches!(next, Reconnecting | RaidActive | Idle | StashManagement | Died | Mia),
+            Survived | Died | Mia => false,
+            Uncatalogued(_) => false,
+        }
+    }
+
+    // Uncatalogued states are never part of the graph, callers have to opt in
+    // through the force escape hatch to move into or out of one
+    pub fn check_transition(&self, next: &RaidState) -> Result<(), InvalidTransition> {
+        if self.can_transition_to(next) {
+            Ok(())
+        } else {
+            Err(InvalidTransition { from: self.clone(), to: next.clone() })
+        }
+    }
+}
+
+impl std::fmt::Display for RaidState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for RaidState {
+    fn from(s: String) -> Self {
+        RaidState::parse(&s)
+    }
+}
+
+impl From<&str> for RaidState {
+    fn from(s: &str) -> Self {
+        RaidState::parse(s)
+    }
+}
+
+impl From<RaidState> for String {
+    fn from(state: RaidState) -> Self {
+        state.as_str().to_string()
+    }
+}
+
+// So a state can be checked against its name, e.g. raid.current_state == "survived"
+impl PartialEq<str> for RaidState {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for RaidState {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+// The columns stay TEXT, reading one goes through parse so unknown states still load
+impl sqlx::Type<sqlx::Sqlite> for RaidState {
+    fn type_info() -> sqlx::sqlite::SqliteTypeInfo {
+        <str as sqlx::Type<sqlx::Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &sqlx::sqlite::SqliteTypeInfo) -> bool {
+        <str as sqlx::Type<sqlx::Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> sqlx::Encode<'q, sqlx::Sqlite> for RaidState {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <sqlx::Sqlite as sqlx::Database>::ArgumentBuffer<'q>,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <String as sqlx::Encode<sqlx::Sqlite>>::encode(self.as_str().to_string(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, sqlx::Sqlite> for RaidState {
+    fn decode(value: sqlx::sqlite::SqliteValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(RaidState::parse(<&str as sqlx::Decode<sqlx::Sqlite>>::decode(value)?))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTransition {
+    pub from: RaidState,
+    pub to: RaidState,
+}
+
+impl std::fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.to.is_catalogued() {
+            write!(f, "Unknown state '{}' (set force to log it anyway)", self.to)
+        } else if !self.from.is_catalogued() {
+            write!(f, "Cannot leave unknown state '{}' (set force to log it anyway)", self.from)
+        } else {
+            write!(f, "Illegal state transition: {} -> {}", self.from, self.to)
+        }
+    }
+}
+
 // ============================================================
 // Structs
 // ============================================================
@@ -51,16 +403,19 @@ pub struct Raid {
     pub map_name: String,
     pub character_type: CharacterType,
     pub game_mode: GameMode,
-    pub current_state: String, //String for extensibility
+    pub current_state: RaidState,
     pub extract_location: Option<String>,
+    // Set on the segments after a map transfer, see chain_id()
+    pub parent_raid_id: Option<i64>,
+    pub segment_order: i64,
 }
 
 #[derive(Debug, Clone, sqlx::FromRow)]
 pub struct RaidStateTransition {
     pub transition_id: i64,
     pub raid_id: i64,
-    pub from_state: Option<String>,
-    pub to_state: String,
+    pub from_state: Option<RaidState>,
+    pub to_state: RaidState,
     pub transitioned_at: OffsetDateTime,
 }
 
@@ -73,3 +428,288 @@ pub struct Kill {
     pub weapon_used: Option<String>,
     pub headshot: Option<bool>,
 }
+
+// A kill that has not been written yet, killed_at None means "now"
+#[derive(Debug, Clone)]
+pub struct NewKill {
+    pub enemy_type: String,
+    pub weapon_used: Option<String>,
+    pub headshot: Option<bool>,
+    pub killed_at: Option<OffsetDateTime>,
+}
+
+// One correction, before/after are JSON snapshots of the row
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub audit_id: i64,
+    pub table_name: String,
+    pub row_id: i64,
+    pub action: String, // "update", "delete", "undo" or "redo"
+    pub before_json: Option<String>,
+    pub after_json: Option<String>,
+    pub source: String,
+    pub changed_at: OffsetDateTime,
+}
+
+// An audit row that has not been written yet, changed_at is when it is
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    pub table_name: String,
+    pub row_id: i64,
+    pub action: String,
+    pub before_json: Option<String>,
+    pub after_json: Option<String>,
+    pub source: String,
+}
+
+// An event held back for the operator, see the review queue
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PendingEvent {
+    pub pending_id: i64,
+    pub source: String, // "detection", "chat", ...
+    pub event_type: String,
+    pub confidence: Option<f64>,
+    pub payload: String, // JSON in the detection schema shape
+    pub occurred_at: Option<OffsetDateTime>,
+    pub received_at: OffsetDateTime,
+    pub status: ReviewStatus,
+    pub reviewed_at: Option<OffsetDateTime>,
+    pub review_note: Option<String>,
+}
+
+// Totals over a set of raids, computed in SQL. The group columns are only set
+// for the ones the query was grouped by. Durations only cover ended raids.
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+pub struct RaidAggregate {
+    pub game_mode: Option<GameMode>,
+    pub character_type: Option<CharacterType>,
+    pub map_name: Option<String>,
+    pub session_type: Option<SessionType>,
+    pub session_id: Option<i64>,
+    pub weekday: Option<String>,
+    pub raid_count: i64,
+    pub survived_count: i64,
+    pub died_count: i64,
+    pub kill_count: i64,
+    pub ended_count: i64,
+    pub duration_ms: i64,
+    pub active_duration_ms: i64,
+}
+
+// Totals for one session or raid of a trend, id is a session_id or raid_id to match
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TrendAggregate {
+    pub id: i64,
+    pub started_at: OffsetDateTime,
+    #[sqlx(flatten)]
+    pub totals: RaidAggregate,
+}
+
+// Time spent queuing over the raids of one map that queued at least once
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MapQueueTime {
+    pub map_name: String,
+    pub raid_count: i64,
+    pub queue_ms: i64,
+}
+
+// One ended raid as the streaks see it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RaidOutcome {
+    pub raid_id: i64,
+    pub survived: bool,
+    // Left for the next segment, neither a survival nor a death
+    pub transferred: bool,
+    pub kill_count: i64,
+}
+
+// A candidate for a record. set_at is when the value was reached: the raid's end,
+// or the last counted raid's end for session records.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RecordHolder {
+    pub raid_id: Option<i64>,
+    pub session_id: i64,
+    pub value: f64,
+    pub set_at: OffsetDateTime,
+}
+
+// Time spent in one state summed over raids
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct StateDuration {
+    pub state: String,
+    pub duration_ms: i64,
+}
+
+// Queue and deploy time over the raids of a group. raid_count only counts raids
+// that logged at least one of those states.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WaitAggregate {
+    pub game_mode: Option<GameMode>,
+    pub character_type: Option<CharacterType>,
+    pub map_name: Option<String>,
+    pub session_type: Option<SessionType>,
+    pub session_id: Option<i64>,
+    pub weekday: Option<String>,
+    pub raid_count: i64,
+    pub queue_ms: i64,
+    pub deploying_cancellable_ms: i64,
+    pub deploying_committed_ms: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MapExtract {
+    pub map_name: String,
+    pub extract_location: String,
+}
+
+// Kills with one weapon. weapon_key is the normalised name the rows were merged on,
+// weapon is the spelling shown. headshot_known counts kills with the flag set either way.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WeaponAggregate {
+    pub weapon_key: String,
+    pub weapon: String,
+    pub kill_count: i64,
+    pub headshot_count: i64,
+    pub headshot_known: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WeaponEnemyCount {
+    pub weapon_key: String,
+    pub enemy_type: String,
+    pub kill_count: i64,
+}
+
+// A raid as listed in the history, with its kill count and length.
+// duration_seconds is None while the raid is running.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RaidSummary {
+    #[sqlx(flatten)]
+    pub raid: Raid,
+    pub session_type: Option<SessionType>,
+    pub kill_count: i64,
+    pub duration_seconds: Option<i64>,
+    // duration_seconds without the time spent in non-play states
+    pub active_duration_seconds: Option<i64>,
+}
+
+// raid_count and kill_count only cover the raids that matched the filters
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SessionSummary {
+    #[sqlx(flatten)]
+    pub session: StreamSession,
+    pub raid_count: i64,
+    pub kill_count: i64,
+    pub duration_seconds: Option<i64>,
+}
+
+impl Raid {
+    pub fn state(&self) -> RaidState {
+        self.current_state.clone()
+    }
+
+    // The first segment's id, shared by every segment of a transfer chain
+    pub fn chain_id(&self) -> i64 {
+        self.parent_raid_id.unwrap_or(self.raid_id)
+    }
+
+    // Where the raid sits before its first transition, later segments start in transfer
+    pub fn initial_state(&self) -> RaidState {
+        if self.parent_raid_id.is_some() {
+            RaidState::Transfer
+        } else {
+            RaidState::StashManagement
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raid_state_round_trip() {
+        let states = [
+            "idle", "stash_management", "pre_raid_setup", "queuing", "deploying_cancellable",
+            "deploying_committed", "raid_active", "raid_ending", "post_raid_review",
+            "survived", "died", "mia", "transfer", "reconnecting", "paused", "error",
+        ];
+
+        for s in states {
+            let state = RaidState::parse(s);
+            assert!(state.is_catalogued(), "{} should be in the catalog", s);
+            assert_eq!(state.as_str(), s);
+        }
+    }
+
+    #[test]
+    fn test_raid_state_typo_is_uncatalogued() {
+        let state = RaidState::parse("queing");
+        assert_eq!(state, RaidState::Uncatalogued("queing".into()));
+        assert_eq!(state.to_string(), "queing");
+    }
+
+    #[test]
+    fn test_raid_state_serde_as_string() {
+        let json = serde_json::to_string(&RaidState::DeployingCommitted).unwrap();
+        assert_eq!(json, r#""deploying_committed""#);
+
+        let state: RaidState = serde_json::from_str(r#""raid_active""#).unwrap();
+        assert_eq!(state, RaidState::RaidActive);
+    }
+
+    #[test]
+    fn test_happy_path_is_legal() {
+        let path = [
+            RaidState::StashManagement,
+            RaidState::PreRaidSetup,
+            RaidState::Queuing,
+            RaidState::DeployingCancellable,
+            RaidState::DeployingCommitted,
+            RaidState::RaidActive,
+            RaidState::RaidEnding,
+            RaidState::PostRaidReview,
+            RaidState::Survived,
+        ];
+
+        for pair in path.windows(2) {
+            assert!(pair[0].check_transition(&pair[1]).is_ok(), "{} -> {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_queue_cancel_and_transfer_are_legal() {
+        assert!(RaidState::Queuing.can_transition_to(&RaidState::StashManagement));
+        assert!(RaidState::RaidActive.can_transition_to(&RaidState::Transfer));
+        assert!(RaidState::Transfer.can_transition_to(&RaidState::DeployingCommitted));
+        assert!(RaidState::RaidActive.can_transition_to(&RaidState::Reconnecting));
+        assert!(RaidState::Reconnecting.can_transition_to(&RaidState::RaidActive));
+        assert!(RaidState::RaidActive.can_transition_to(&RaidState::Paused));
+        assert!(RaidState::Paused.can_transition_to(&RaidState::RaidActive));
+        assert_eq!(RaidState::parse("paused"), RaidState::Paused);
+    }
+
+    #[test]
+    fn test_illegal_transitions_rejected() {
+        // Can't back out once committed
+        assert!(!RaidState::DeployingCommitted.can_transition_to(&RaidState::StashManagement));
+        // Can't skip the queue straight into a raid
+        assert!(!RaidState::StashManagement.can_transition_to(&RaidState::RaidActive));
+        // Terminal states are final
+        assert!(!RaidState::Died.can_transition_to(&RaidState::Survived));
+        // Same state is not a transition
+        assert!(!RaidState::Queuing.can_transition_to(&RaidState::Queuing));
+
+        let err = RaidState::StashManagement.check_transition(&RaidState::RaidActive).unwrap_err();
+        assert_eq!(err.to_string(), "Illegal state transition: stash_management -> raid_active");
+    }
+
+    #[test]
+    fn test_uncatalogued_requires_escape_hatch() {
+        let typo = RaidState::parse("queing");
+        let err = RaidState::PreRaidSetup.check_transition(&typo).unwrap_err();
+        assert!(err.to_string().contains("Unknown state 'queing'"));
+
+        assert!(!typo.can_transition_to(&RaidState::Queuing));
+    }
+}
@@ -0,0 +1,529 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{info, warn};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// obs-websocket v5 opcodes
+const OP_HELLO: u64 = 0;
+const OP_IDENTIFY: u64 = 1;
+const OP_IDENTIFIED: u64 = 2;
+const OP_REQUEST: u64 = 6;
+const OP_REQUEST_RESPONSE: u64 = 7;
+
+const RPC_VERSION: u64 = 1;
+// WebSocketCloseCode::AuthenticationFailed
+const CLOSE_AUTH_FAILED: u16 = 4009;
+
+// ============================================================
+// Errors
+// ============================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObsError {
+    Connection(String),
+    Auth(String),
+    Protocol(String),
+    NotConnected,
+    Timeout,
+    Request { code: i64, comment: Option<String> },
+}
+
+impl std::fmt::Display for ObsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObsError::Connection(msg) => write!(f, "OBS connection error: {}", msg),
+            ObsError::Auth(msg) => write!(f, "OBS authentication failed: {}", msg),
+            ObsError::Protocol(msg) => write!(f, "OBS protocol error: {}", msg),
+            ObsError::NotConnected => write!(f, "Not connected to OBS"),
+            ObsError::Timeout => write!(f, "OBS request timed out"),
+            ObsError::Request { code, comment } => write!(f, "OBS request failed ({}): {}",
+                code, comment.as_deref().unwrap_or("no comment")),
+        }
+    }
+}
+
+// ============================================================
+// Config
+// ============================================================
+
+#[derive(Debug, Clone)]
+pub struct ObsConnectConfig {
+    pub url: String,
+    pub password: Option<String>,
+    pub request_timeout: Duration,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl ObsConnectConfig {
+    pub fn new(url: impl Into<String>, password: Option<String>) -> Self {
+        Self {
+            url: url.into(),
+            password,
+            request_timeout: Duration::from_secs(5),
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+// base64(sha256(base64(sha256(password + salt)) + challenge)), see the obs-websocket protocol docs
+pub fn auth_string(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = BASE64.encode(Sha256::digest(format!("{}{}", password, salt)));
+    BASE64.encode(Sha256::digest(format!("{}{}", secret, challenge)))
+}
+
+// ============================================================
+// Client
+// ============================================================
+
+struct Command {
+    request_type: String,
+    request_data: Value,
+    reply: oneshot::Sender<Result<Value, ObsError>>,
+}
+
+// Cheap to clone handle, the socket lives in a background task that reconnects on its own
+#[derive(Clone)]
+pub struct ObsClient {
+    commands: mpsc::Sender<Command>,
+    connected: watch::Receiver<bool>,
+    request_timeout: Duration,
+}
+
+impl ObsClient {
+    pub fn connect(config: ObsConnectConfig) -> Self {
+        let (commands, rx) = mpsc::channel(32);
+        let (connected_tx, connected) = watch::channel(false);
+        let request_timeout = config.request_timeout;
+
+        tokio::spawn(run_connection(config, rx, connected_tx));
+
+        Self { commands, connected, request_timeout }
+    }
+
+    // Flips to true once identified and back to false whenever the socket drops
+    pub fn watch_connected(&self) -> watch::Receiver<bool> {
+        self.connected.clone()
+    }
+
+    // Sends a raw request and returns its responseData
+    pub async fn request(&self, request_type: &str, request_data: Value) -> Result<Value, ObsError> {
+        let (reply, response) = oneshot::channel();
+
+        self.commands.send(Command {
+            request_type: request_type.to_string(),
+            request_data,
+            reply,
+        }).await.map_err(|_| ObsError::NotConnected)?;
+
+        match tokio::time::timeout(self.request_timeout, response).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(ObsError::NotConnected),
+            Err(_) => Err(ObsError::Timeout),
+        }
+    }
+
+    pub async fn set_text(&self, input_name: &str, text: &str) -> Result<(), ObsError> {
+        self.request("SetInputSettings", json!({
+            "inputName": input_name,
+            "inputSettings": { "text": text },
+            "overlay": true,
+        })).await.map(|_| ())
+    }
+
+    pub async fn set_scene(&self, scene_name: &str) -> Result<(), ObsError> {
+        self.request("SetCurrentProgramScene", json!({ "sceneName": scene_name }))
+            .await.map(|_| ())
+    }
+
+    pub async fn set_source_visible(&self, scene_name: &str, source_name: &str, visible: bool) -> Result<(), ObsError> {
+        let item = self.request("GetSceneItemId", json!({
+            "sceneName": scene_name,
+            "sourceName": source_name,
+        })).await?;
+
+        let scene_item_id = item["sceneItemId"].as_i64()
+            .ok_or_else(|| ObsError::Protocol("GetSceneItemId returned no sceneItemId".into()))?;
+
+        self.request("SetSceneItemEnabled", json!({
+            "sceneName": scene_name,
+            "sceneItemId": scene_item_id,
+            "sceneItemEnabled": visible,
+        })).await.map(|_| ())
+    }
+}
+
+// ============================================================
+// Connection Task
+// ============================================================
+
+enum ServeEnd {
+    Disconnected,
+    Shutdown,
+}
+
+async fn run_connection(
+    config: ObsConnectConfig,
+    mut commands: mpsc::Receiver<Command>,
+    connected: watch::Sender<bool>,
+) {
+    let mut backoff = config.min_backoff;
+
+    loop {
+        match connect_and_identify(&config).await {
+            Ok(ws) => {
+                info!("Connected to OBS at {}", config.url);
+                backoff = config.min_backoff;
+                connected.send_replace(true);
+
+                let end = serve(ws, &mut commands).await;
+                connected.send_replace(false);
+
+                match end {
+                    ServeEnd::Shutdown => return,
+                    ServeEnd::Disconnected => warn!("Lost connection to OBS, reconnecting"),
+                }
+            }
+            Err(e) => warn!("{} (retrying in {:?})", e, backoff),
+        }
+
+        // Requests made while we are down fail fast instead of queueing up stale scene changes
+        let sleep = tokio::time::sleep(backoff);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => break,
+                cmd = commands.recv() => match cmd {
+                    Some(cmd) => { let _ = cmd.reply.send(Err(ObsError::NotConnected)); }
+                    None => return,
+                },
+            }
+        }
+
+        backoff = (backoff * 2).min(config.max_backoff);
+    }
+}
+
+async fn next_message(ws: &mut WsStream) -> Result<Value, ObsError> {
+    loop {
+        match ws.next().await {
+            Some(Ok(Message::Text(text))) => {
+                return serde_json::from_str(&text)
+                    .map_err(|e| ObsError::Protocol(format!("invalid JSON: {}", e)));
+            }
+            Some(Ok(Message::Close(frame))) => {
+                return Err(match frame {
+                    Some(f) if u16::from(f.code) == CLOSE_AUTH_FAILED => ObsError::Auth(f.reason.to_string()),
+                    Some(f) => ObsError::Connection(format!("closed by OBS ({}): {}", u16::from(f.code), f.reason)),
+                    None => ObsError::Connection("closed by OBS".into()),
+                });
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(ObsError::Connection(e.to_string())),
+            None => return Err(ObsError::Connection("connection closed".into())),
+        }
+    }
+}
+
+async fn connect_and_identify(config: &ObsConnectConfig) -> Result<WsStream, ObsError> {
+    let (mut ws, _) = tokio_tungstenite::connect_async(config.url.as_str())
+        .await
+        .map_err(|e| ObsError::Connection(e.to_string()))?;
+
+    let hello = next_message(&mut ws).await?;
+    if hello["op"].as_u64() != Some(OP_HELLO) {
+        return Err(ObsError::Protocol(format!("expected Hello, got {}", hello)));
+    }
+
+    let mut identify = json!({ "rpcVersion": RPC_VERSION, "eventSubscriptions": 0 });
+
+    let auth = &hello["d"]["authentication"];
+    if !auth.is_null() {
+        let password = config.password.as_deref()
+            .ok_or_else(|| ObsError::Auth("OBS requires a password but none is configured".into()))?;
+        let (Some(salt), Some(challenge)) = (auth["salt"].as_str(), auth["challenge"].as_str()) else {
+            return Err(ObsError::Protocol("Hello authentication is missing salt or challenge".into()));
+        };
+        identify["authentication"] = json!(auth_string(password, salt, challenge));
+    }
+
+    ws.send(Message::Text(json!({ "op": OP_IDENTIFY, "d": identify }).to_string().into()))
+        .await
+        .map_err(|e| ObsError::Connection(e.to_string()))?;
+
+    let identified = next_message(&mut ws).await?;
+    if identified["op"].as_u64() != Some(OP_IDENTIFIED) {
+        return Err(ObsError::Protocol(format!("expected Identified, got {}", identified)));
+    }
+
+    Ok(ws)
+}
+
+async fn serve(ws: WsStream, commands: &mut mpsc::Receiver<Command>) -> ServeEnd {
+    let (mut sink, mut stream) = ws.split();
+    let mut pending: HashMap<String, oneshot::Sender<Result<Value, ObsError>>> = HashMap::new();
+    let mut next_id: u64 = 0;
+
+    let end = loop {
+        tokio::select! {
+            cmd = commands.recv() => {
+                let Some(cmd) = cmd else { break ServeEnd::Shutdown };
+
+                next_id += 1;
+                let request_id = next_id.to_string();
+                let msg = json!({
+                    "op": OP_REQUEST,
+                    "d": {
+                        "requestType": cmd.request_type,
+                        "requestId": request_id,
+                        "requestData": cmd.request_data,
+                    }
+                });
+
+                if let Err(e) = sink.send(Message::Text(msg.to_string().into())).await {
+                    let _ = cmd.reply.send(Err(ObsError::Connection(e.to_string())));
+                    break ServeEnd::Disconnected;
+                }
+                pending.insert(request_id, cmd.reply);
+            }
+            msg = stream.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let Ok(msg) = serde_json::from_str::<Value>(&text) else {
+                        warn!("Ignoring invalid JSON from OBS");
+                        continue;
+                    };
+                    // Events (op 5) and anything else are not subscribed to, skip them
+                    if msg["op"].as_u64() != Some(OP_REQUEST_RESPONSE) {
+                        continue;
+                    }
+
+                    let d = &msg["d"];
+                    let Some(reply) = d["requestId"].as_str().and_then(|id| pending.remove(id)) else {
+                        continue;
+                    };
+
+                    let status = &d["requestStatus"];
+                    let result = if status["result"].as_bool() == Some(true) {
+                        Ok(d["responseData"].clone())
+                    } else {
+                        Err(ObsError::Request {
+                            code: status["code"].as_i64().unwrap_or(0),
+                            comment: status["comment"].as_str().map(String::from),
+                        })
+                    };
+                    let _ = reply.send(result);
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break ServeEnd::Disconnected,
+                Some(Ok(_)) => {}
+            },
+        }
+    };
+
+    for (_, reply) in pending.drain() {
+        let _ = reply.send(Err(ObsError::NotConnected));
+    }
+
+    end
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+    pub const SALT: &str = "lM1GncleQOaCu9lT1yeUZhFYnqhsLLP1G5lAGo3ixaI=";
+    pub const CHALLENGE: &str = "+IxH4CnCiqpX1rM9scsNynZzbOe4KhDeYcTNS3PDaeY=";
+
+    // A stand-in for OBS: does the handshake, answers every request with success
+    // (GetSceneItemId returns id 42) and forwards what it received to the test
+    pub struct MockObs {
+        pub url: String,
+        pub requests: mpsc::UnboundedReceiver<Value>,
+        pub connections: mpsc::UnboundedReceiver<()>,
+    }
+
+    impl MockObs {
+        // Each accepted connection is dropped after `drop_after` requests if set
+        pub async fn start(password: Option<&'static str>, drop_after: Option<usize>) -> MockObs {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ws://{}", listener.local_addr().unwrap());
+            let (req_tx, requests) = mpsc::unbounded_channel();
+            let (conn_tx, connections) = mpsc::unbounded_channel();
+
+            tokio::spawn(async move {
+                loop {
+                    let Ok((stream, _)) = listener.accept().await else { return };
+                    let req_tx = req_tx.clone();
+                    let conn_tx = conn_tx.clone();
+                    tokio::spawn(async move {
+                        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                        mock_session(&mut ws, password, drop_after, req_tx, conn_tx).await;
+                    });
+                }
+            });
+
+            MockObs { url, requests, connections }
+        }
+    }
+
+    async fn mock_session(
+        ws: &mut WebSocketStream<TcpStream>,
+        password: Option<&str>,
+        drop_after: Option<usize>,
+        requests: mpsc::UnboundedSender<Value>,
+        connections: mpsc::UnboundedSender<()>,
+    ) {
+        let mut hello = json!({ "op": 0, "d": { "obsWebSocketVersion": "5.0.0", "rpcVersion": 1 } });
+        if password.is_some() {
+            hello["d"]["authentication"] = json!({ "challenge": CHALLENGE, "salt": SALT });
+        }
+        ws.send(Message::Text(hello.to_string().into())).await.unwrap();
+
+        let Some(Ok(Message::Text(identify))) = ws.next().await else { return };
+        let identify: Value = serde_json::from_str(&identify).unwrap();
+        assert_eq!(identify["op"], 1);
+
+        if let Some(password) = password
+            && identify["d"]["authentication"].as_str() != Some(auth_string(password, SALT, CHALLENGE).as_str())
+        {
+            let _ = ws.close(Some(CloseFrame {
+                code: CloseCode::from(CLOSE_AUTH_FAILED),
+                reason: "Authentication failed.".into(),
+            })).await;
+            return;
+        }
+
+        ws.send(Message::Text(json!({ "op": 2, "d": { "negotiatedRpcVersion": 1 } }).to_string().into()))
+            .await.unwrap();
+        let _ = connections.send(());
+
+        let mut handled = 0;
+        while let Some(Ok(msg)) = ws.next().await {
+            let Message::Text(text) = msg else { continue };
+            let req: Value = serde_json::from_str(&text).unwrap();
+            let d = &req["d"];
+
+            if drop_after == Some(handled) {
+                return;
+            }
+            handled += 1;
+
+            let response_data = match d["requestType"].as_str() {
+                Some("GetSceneItemId") => json!({ "sceneItemId": 42 }),
+                _ => json!({}),
+            };
+            let status = if d["requestType"] == "FailMe" {
+                json!({ "result": false, "code": 600, "comment": "No source was found" })
+            } else {
+                json!({ "result": true, "code": 100 })
+            };
+
+            let _ = requests.send(d.clone());
+            ws.send(Message::Text(json!({
+                "op": 7,
+                "d": {
+                    "requestType": d["requestType"],
+                    "requestId": d["requestId"],
+                    "requestStatus": status,
+                    "responseData": response_data,
+                }
+            }).to_string().into())).await.unwrap();
+        }
+    }
+
+    pub async fn wait_connected(client: &ObsClient, timeout: Duration) -> bool {
+        let mut connected = client.watch_connected();
+        tokio::time::timeout(timeout, connected.wait_for(|c| *c)).await
+            .map(|r| r.is_ok())
+            .unwrap_or(false)
+    }
+
+    fn fast_config(url: &str, password: Option<&str>) -> ObsConnectConfig {
+        let mut config = ObsConnectConfig::new(url, password.map(String::from));
+        config.min_backoff = Duration::from_millis(20);
+        config.max_backoff = Duration::from_millis(100);
+        config.request_timeout = Duration::from_secs(2);
+        config
+    }
+
+    #[test]
+    fn test_auth_string() {
+        // Worked example from the obs-websocket protocol docs
+        assert_eq!(
+            auth_string("supersecretpassword", SALT, CHALLENGE),
+            "1Ct943GAT+6YQUUX47Ia/ncufilbe6+oD6lY+5kaCu4="
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handshake_with_password_and_requests() {
+        let mut mock = MockObs::start(Some("hunter2"), None).await;
+        let client = ObsClient::connect(fast_config(&mock.url, Some("hunter2")));
+        assert!(wait_connected(&client, Duration::from_secs(5)).await);
+
+        client.set_scene("Gameplay").await.unwrap();
+        let req = mock.requests.recv().await.unwrap();
+        assert_eq!(req["requestType"], "SetCurrentProgramScene");
+        assert_eq!(req["requestData"]["sceneName"], "Gameplay");
+
+        client.set_text("State", "raid_active").await.unwrap();
+        let req = mock.requests.recv().await.unwrap();
+        assert_eq!(req["requestType"], "SetInputSettings");
+        assert_eq!(req["requestData"]["inputSettings"]["text"], "raid_active");
+
+        client.set_source_visible("Gameplay", "KillFeed", false).await.unwrap();
+        assert_eq!(mock.requests.recv().await.unwrap()["requestType"], "GetSceneItemId");
+        let req = mock.requests.recv().await.unwrap();
+        assert_eq!(req["requestType"], "SetSceneItemEnabled");
+        assert_eq!(req["requestData"]["sceneItemId"], 42);
+        assert_eq!(req["requestData"]["sceneItemEnabled"], false);
+    }
+
+    #[tokio::test]
+    async fn test_request_failure_is_reported() {
+        let mock = MockObs::start(None, None).await;
+        let client = ObsClient::connect(fast_config(&mock.url, None));
+        assert!(wait_connected(&client, Duration::from_secs(5)).await);
+
+        let err = client.request("FailMe", json!({})).await.unwrap_err();
+        assert_eq!(err, ObsError::Request { code: 600, comment: Some("No source was found".into()) });
+    }
+
+    #[tokio::test]
+    async fn test_wrong_password_never_connects() {
+        let mock = MockObs::start(Some("hunter2"), None).await;
+        let client = ObsClient::connect(fast_config(&mock.url, Some("wrong")));
+
+        assert!(!wait_connected(&client, Duration::from_millis(300)).await);
+        assert_eq!(client.request("GetVersion", json!({})).await.unwrap_err(), ObsError::NotConnected);
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_after_drop() {
+        // The mock hangs up on the first request of every connection
+        let mut mock = MockObs::start(None, Some(0)).await;
+        let client = ObsClient::connect(fast_config(&mock.url, None));
+
+        mock.connections.recv().await.unwrap();
+        let err = client.request("GetVersion", json!({})).await.unwrap_err();
+        assert_eq!(err, ObsError::NotConnected);
+
+        // A second connection comes up on its own after the backoff
+        tokio::time::timeout(Duration::from_secs(5), mock.connections.recv())
+            .await.expect("client should reconnect").unwrap();
+    }
+}
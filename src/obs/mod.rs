@@ -0,0 +1,28 @@
+// obs-websocket v5 integration: a reconnecting client plus a task that
+// switches scenes / text / visibility as the raid state changes
+pub mod client;
+pub mod reactions;
+
+pub use client::{ObsClient, ObsConnectConfig};
+pub use reactions::ReactionsConfig;
+
+// OBS_WS_URL turns this on. OBS_WS_PASSWORD, OBS_STATE_SCENES, OBS_STATE_TEXT_SOURCE
+// and OBS_SOURCE_VISIBILITY describe what to do, see reactions.rs for the formats
+pub fn config_from_env() -> Result<Option<(ObsConnectConfig, ReactionsConfig)>, String> {
+    let Ok(url) = std::env::var("OBS_WS_URL") else {
+        return Ok(None);
+    };
+
+    let connect = ObsConnectConfig::new(url, std::env::var("OBS_WS_PASSWORD").ok());
+
+    let mut reactions = ReactionsConfig::default();
+    if let Ok(scenes) = std::env::var("OBS_STATE_SCENES") {
+        reactions.scenes = reactions::parse_scene_map(&scenes)?;
+    }
+    reactions.state_text_source = std::env::var("OBS_STATE_TEXT_SOURCE").ok();
+    if let Ok(rules) = std::env::var("OBS_SOURCE_VISIBILITY") {
+        reactions.visibility = reactions::parse_visibility_rules(&rules)?;
+    }
+
+    Ok(Some((connect, reactions)))
+}
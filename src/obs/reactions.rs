@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::events::{EventBus, LiveEvent};
+use crate::models::RaidState;
+use super::client::{ObsClient, ObsError};
+
+// ============================================================
+// Config
+// ============================================================
+
+// Show `source` in `scene` only while the raid is in one of `visible_in`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VisibilityRule {
+    pub scene: String,
+    pub source: String,
+    pub visible_in: Vec<RaidState>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReactionsConfig {
+    // States without an entry leave the current scene alone
+    pub scenes: HashMap<RaidState, String>,
+    // Text source that gets the raw state name written to it
+    pub state_text_source: Option<String>,
+    pub visibility: Vec<VisibilityRule>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObsAction {
+    Scene(String),
+    Text { input: String, text: String },
+    Visibility { scene: String, source: String, visible: bool },
+}
+
+impl ReactionsConfig {
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty() && self.state_text_source.is_none() && self.visibility.is_empty()
+    }
+
+    // What OBS should do when the raid lands in `state`
+    pub fn actions_for(&self, state: &RaidState) -> Vec<ObsAction> {
+        let mut actions = Vec::new();
+
+        if let Some(scene) = self.scenes.get(state) {
+            actions.push(ObsAction::Scene(scene.clone()));
+        }
+
+        if let Some(input) = &self.state_text_source {
+            actions.push(ObsAction::Text { input: input.clone(), text: state.to_string() });
+        }
+
+        for rule in &self.visibility {
+            actions.push(ObsAction::Visibility {
+                scene: rule.scene.clone(),
+                source: rule.source.clone(),
+                visible: rule.visible_in.contains(state),
+            });
+        }
+
+        actions
+    }
+}
+
+// "raid_active=Gameplay,stash_management=Stash"
+pub fn parse_scene_map(s: &str) -> Result<HashMap<RaidState, String>, String> {
+    let mut scenes = HashMap::new();
+
+    for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((state, scene)) = entry.split_once('=') else {
+            return Err(format!("Expected `state=scene`, got '{}'", entry));
+        };
+        scenes.insert(parse_known_state(state)?, scene.trim().to_string());
+    }
+
+    Ok(scenes)
+}
+
+// "Gameplay:KillFeed=raid_active|raid_ending;Stash:Loadout=stash_management"
+pub fn parse_visibility_rules(s: &str) -> Result<Vec<VisibilityRule>, String> {
+    let mut rules = Vec::new();
+
+    for entry in s.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((target, states)) = entry.split_once('=') else {
+            return Err(format!("Expected `scene:source=state|state`, got '{}'", entry));
+        };
+        let Some((scene, source)) = target.split_once(':') else {
+            return Err(format!("Expected `scene:source`, got '{}'", target));
+        };
+
+        let visible_in = states.split('|')
+            .map(parse_known_state)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rules.push(VisibilityRule {
+            scene: scene.trim().to_string(),
+            source: source.trim().to_string(),
+            visible_in,
+        });
+    }
+
+    Ok(rules)
+}
+
+// A typo in the config would otherwise silently never match
+fn parse_known_state(s: &str) -> Result<RaidState, String> {
+    let state = RaidState::parse(s.trim());
+    if state.is_catalogued() {
+        Ok(state)
+    } else {
+        Err(format!("Unknown raid state '{}'", s.trim()))
+    }
+}
+
+// ============================================================
+// Background Task
+// ============================================================
+
+// The state a raid is in after this event, if the event moves it anywhere
+fn state_after(event: &LiveEvent) -> Option<RaidState> {
+    match event {
+        LiveEvent::RaidCreated { .. } => Some(RaidState::StashManagement),
+        LiveEvent::StateTransitioned { to_state, .. } => Some(to_state.clone()),
+        LiveEvent::RaidEnded { final_state, .. } => Some(final_state.clone()),
+        LiveEvent::RaidCorrected { raid_state, .. }
+        | LiveEvent::ActionUndone { raid_state, .. }
+        | LiveEvent::ActionRedone { raid_state, .. } => raid_state.clone(),
+        _ => None,
+    }
+}
+
+pub async fn apply(client: &ObsClient, action: &ObsAction) -> Result<(), ObsError> {
+    match action {
+        ObsAction::Scene(scene) => client.set_scene(scene).await,
+        ObsAction::Text { input, text } => client.set_text(input, text).await,
+        ObsAction::Visibility { scene, source, visible } => {
+            client.set_source_visible(scene, source, *visible).await
+        }
+    }
+}
+
+async fn apply_all(client: &ObsClient, config: &ReactionsConfig, state: &RaidState) {
+    for action in config.actions_for(state) {
+        if let Err(e) = apply(client, &action).await {
+            warn!("OBS {:?} failed: {}", action, e);
+        }
+    }
+}
+
+pub fn spawn(client: ObsClient, config: ReactionsConfig, events: EventBus) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        info!("Driving OBS scenes from raid state changes");
+
+        let mut receiver = events.subscribe(None).receiver;
+        let mut connected = client.watch_connected();
+        // Ending a raid publishes both the terminal transition and RaidEnded, only react once
+        let mut last_state: Option<RaidState> = None;
+
+        loop {
+            tokio::select! {
+                event = receiver.recv() => {
+                    let event = match event {
+                        Ok(envelope) => envelope.event,
+                        Err(RecvError::Lagged(n)) => {
+                            warn!("OBS reactions skipped {} events", n);
+                            continue;
+                        }
+                        Err(RecvError::Closed) => break,
+                    };
+
+                    let Some(state) = state_after(&event) else { continue };
+                    if last_state.as_ref() == Some(&state) {
+                        continue;
+                    }
+
+                    apply_all(&client, &config, &state).await;
+                    last_state = Some(state);
+                }
+                changed = connected.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    // OBS may have restarted with a different scene up, put it back in sync
+                    let is_up = *connected.borrow_and_update();
+                    if is_up && let Some(state) = &last_state {
+                        apply_all(&client, &config, state).await;
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use serde_json::Value;
+    use time::OffsetDateTime;
+    use crate::obs::client::ObsConnectConfig;
+    use crate::obs::client::tests::{wait_connected, MockObs};
+
+    fn test_config() -> ReactionsConfig {
+        ReactionsConfig {
+            scenes: parse_scene_map("raid_active=Gameplay, stash_management=Stash").unwrap(),
+            state_text_source: Some("StateText".into()),
+            visibility: parse_visibility_rules("Gameplay:KillFeed=raid_active|raid_ending").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_scene_map() {
+        let scenes = parse_scene_map("raid_active=Gameplay, stash_management=Stash").unwrap();
+        assert_eq!(scenes[&RaidState::RaidActive], "Gameplay");
+        assert_eq!(scenes[&RaidState::StashManagement], "Stash");
+
+        assert!(parse_scene_map("raid_actve=Gameplay").is_err());
+        assert!(parse_scene_map("Gameplay").is_err());
+    }
+
+    #[test]
+    fn test_parse_visibility_rules() {
+        let rules = parse_visibility_rules("Gameplay:KillFeed=raid_active|raid_ending; Stash:Loadout=stash_management").unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].scene, "Gameplay");
+        assert_eq!(rules[0].source, "KillFeed");
+        assert_eq!(rules[0].visible_in, vec![RaidState::RaidActive, RaidState::RaidEnding]);
+
+        assert!(parse_visibility_rules("KillFeed=raid_active").is_err());
+    }
+
+    #[test]
+    fn test_actions_for_state() {
+        let config = test_config();
+
+        assert_eq!(config.actions_for(&RaidState::RaidActive), vec![
+            ObsAction::Scene("Gameplay".into()),
+            ObsAction::Text { input: "StateText".into(), text: "raid_active".into() },
+            ObsAction::Visibility { scene: "Gameplay".into(), source: "KillFeed".into(), visible: true },
+        ]);
+
+        // No scene mapped for queuing, the feed gets hidden
+        assert_eq!(config.actions_for(&RaidState::Queuing), vec![
+            ObsAction::Text { input: "StateText".into(), text: "queuing".into() },
+            ObsAction::Visibility { scene: "Gameplay".into(), source: "KillFeed".into(), visible: false },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_reacts_to_state_transition() {
+        let mut mock = MockObs::start(None, None).await;
+        let client = ObsClient::connect(ObsConnectConfig::new(&mock.url, None));
+        assert!(wait_connected(&client, Duration::from_secs(5)).await);
+
+        let config = ReactionsConfig {
+            scenes: parse_scene_map("raid_active=Gameplay").unwrap(),
+            ..Default::default()
+        };
+        let events = EventBus::new();
+        spawn(client, config, events.clone());
+        // Let the task subscribe before publishing
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        events.publish(LiveEvent::StateTransitioned {
+            raid_id: 1,
+            from_state: RaidState::DeployingCommitted,
+            to_state: RaidState::RaidActive,
+            transitioned_at: OffsetDateTime::now_utc(),
+        });
+
+        let req: Value = tokio::time::timeout(Duration::from_secs(5), mock.requests.recv())
+            .await.unwrap().unwrap();
+        assert_eq!(req["requestType"], "SetCurrentProgramScene");
+        assert_eq!(req["requestData"]["sceneName"], "Gameplay");
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::db;
+use crate::events::EventBus;
+use crate::stats::{self, format_duration};
+
+// ============================================================
+// Config
+// ============================================================
+
+// file name -> template, e.g. "kd.txt" -> "K/D {kd}"
+pub type Templates = BTreeMap<String, String>;
+
+#[derive(Debug, Clone)]
+pub struct TextSinkConfig {
+    pub dir: PathBuf,
+    pub templates: Templates,
+    // Elapsed time in the current state changes without any event, so re-render on a timer too
+    pub tick: std::time::Duration,
+}
+
+impl TextSinkConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            templates: default_templates(),
+            tick: std::time::Duration::from_secs(1),
+        }
+    }
+
+    // OBS_TEXT_DIR turns the sink on, OBS_TEXT_TEMPLATES optionally points at a template file
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Ok(dir) = std::env::var("OBS_TEXT_DIR") else {
+            return Ok(None);
+        };
+
+        let mut config = Self::new(dir);
+
+        if let Ok(path) = std::env::var("OBS_TEXT_TEMPLATES") {
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            config.templates = parse_templates(&contents)?;
+        }
+
+        Ok(Some(config))
+    }
+}
+
+pub fn default_templates() -> Templates {
+    [
+        ("map.txt", "{map}"),
+        ("state.txt", "{state} ({state_elapsed})"),
+        ("kd.txt", "K/D {kd}"),
+        ("survival_rate.txt", "Survival {survival_rate}%"),
+        ("raids.txt", "Raids this stream: {raids}"),
+        ("first_raid.txt", "First raid after {time_before_first_raid}"),
+    ]
+    .into_iter()
+    .map(|(file, template)| (file.to_string(), template.to_string()))
+    .collect()
+}
+
+// One `file_name = template` per line, blank lines and # comments are skipped
+pub fn parse_templates(contents: &str) -> Result<Templates, String> {
+    let mut templates = Templates::new();
+
+    for (n, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((file, template)) = line.split_once('=') else {
+            return Err(format!("Line {}: expected `file_name = template`", n + 1));
+        };
+
+        let file = file.trim();
+        // Templates only ever write directly into the output directory
+        if file.is_empty() || file.contains(['/', '\\']) || file.starts_with('.') {
+            return Err(format!("Line {}: invalid file name '{}'", n + 1, file));
+        }
+
+        templates.insert(file.to_string(), template.trim().to_string());
+    }
+
+    Ok(templates)
+}
+
+// ============================================================
+// Rendering
+// ============================================================
+
+// Everything a template can reference
+pub const KEYS: [&str; 13] = [
+    "map", "state", "state_elapsed", "raid_elapsed", "raids", "survived", "kills", "kd",
+    "survival_rate", "avg_raid_duration", "avg_active_raid_duration", "session_elapsed", "time_before_first_raid",
+];
+
+// Values are already formatted, keys with nothing to show right now are left out
+#[derive(Debug, Clone, Default)]
+pub struct TextValues {
+    pub values: BTreeMap<&'static str, String>,
+}
+
+impl TextValues {
+    // A placeholder without a value blanks the whole file, "First raid after " on its own
+    // says nothing. Unknown placeholders are left in so typos show up
+    pub fn render(&self, template: &str) -> String {
+        let missing = KEYS.iter().any(|key| {
+            !self.values.contains_key(key) && template.contains(&format!("{{{}}}", key))
+        });
+        if missing {
+            return String::new();
+        }
+
+        let mut out = template.to_string();
+        for (key, value) in &self.values {
+            out = out.replace(&format!("{{{}}}", key), value);
+        }
+        out
+    }
+}
+
+pub async fn load_values(pool: &SqlitePool) -> Result<TextValues, sqlx::Error> {
+    let mut values = BTreeMap::new();
+    let now = OffsetDateTime::now_utc();
+
+    if let Some(raid) = db::get_active_raid(pool).await? {
+        let transitions = db::get_raid_transitions(pool, raid.raid_id).await?;
+        let entered_at = transitions.last()
+            .map(|t| t.transitioned_at)
+            .unwrap_or(raid.started_at);
+
+        values.insert("map", raid.map_name.clone());
+        values.insert("state", raid.current_state.to_string());
+        values.insert("state_elapsed", format_duration(now - entered_at));
+        values.insert("raid_elapsed", format_duration(now - raid.started_at));
+    }
+
+    if let Some(session) = db::get_active_session(pool).await? {
+        let stats = stats::calculate_session_stats(pool, session.session_id).await?;
+
+        values.insert("raids", stats.total_raids.to_string());
+        values.insert("survived", stats.survived_raids.to_string());
+        values.insert("kills", stats.total_kills.to_string());
+        values.insert("kd", format!("{:.2}", stats.kd_ratio));
+        values.insert("survival_rate", format!("{:.0}", stats.survival_rate * 100.0));
+        values.insert("avg_raid_duration", format_duration(stats.avg_raid_duration));
+        values.insert("avg_active_raid_duration", format_duration(stats.avg_active_duration));
+        values.insert("session_elapsed", format_duration(now - session.started_at));
+
+        if let Some(first_raid) = db::get_first_raid_for_session(pool, session.session_id).await? {
+            values.insert("time_before_first_raid", format_duration(first_raid.started_at - session.started_at));
+        }
+    }
+
+    Ok(TextValues { values })
+}
+
+// Write to a temp file in the same directory then rename over the target,
+// OBS polls these files and must never see a half-written one
+pub async fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let file_name = path.file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "no file name"))?;
+    let tmp = path.with_file_name(format!(".{}.tmp", file_name));
+
+    tokio::fs::write(&tmp, contents).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+// ============================================================
+// Background Task
+// ============================================================
+
+pub struct TextSink {
+    pool: SqlitePool,
+    config: TextSinkConfig,
+    // Last contents written per file, unchanged files are not rewritten
+    written: BTreeMap<String, String>,
+}
+
+impl TextSink {
+    pub fn new(pool: SqlitePool, config: TextSinkConfig) -> Self {
+        Self { pool, config, written: BTreeMap::new() }
+    }
+
+    pub async fn refresh(&mut self) -> Result<(), String> {
+        let values = load_values(&self.pool).await.map_err(|e| e.to_string())?;
+
+        for (file, template) in &self.config.templates {
+            let contents = values.render(template);
+            if self.written.get(file) == Some(&contents) {
+                continue;
+            }
+
+            write_atomic(&self.config.dir.join(file), &contents)
+                .await
+                .map_err(|e| format!("Failed to write {}: {}", file, e))?;
+            self.written.insert(file.clone(), contents);
+        }
+
+        Ok(())
+    }
+
+    pub fn spawn(mut self, events: EventBus) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            if let Err(e) = tokio::fs::create_dir_all(&self.config.dir).await {
+                warn!("OBS text sink disabled, cannot create {}: {}", self.config.dir.display(), e);
+                return;
+            }
+            info!("Writing OBS text files to {}", self.config.dir.display());
+
+            let mut receiver = events.subscribe(None).receiver;
+            let mut tick = tokio::time::interval(self.config.tick);
+
+            loop {
+                tokio::select! {
+                    _ = tick.tick() => {}
+                    event = receiver.recv() => match event {
+                        Ok(_) | Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => break,
+                    },
+                }
+
+                if let Err(e) = self.refresh().await {
+                    warn!("OBS text sink refresh failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::setup_test_db;
+    use crate::models::{CharacterType, GameMode, SessionType};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let nanos = OffsetDateTime::now_utc().unix_timestamp_nanos();
+        std::env::temp_dir().join(format!("obs_text_{}_{}_{}", name, std::process::id(), nanos))
+    }
+
+    #[test]
+    fn test_parse_templates() {
+        let templates = parse_templates("# comment\n\nkd.txt = K/D {kd}\nmap.txt={map}\n").unwrap();
+
+        assert_eq!(templates.len(), 2);
+        assert_eq!(templates["kd.txt"], "K/D {kd}");
+        assert_eq!(templates["map.txt"], "{map}");
+    }
+
+    #[test]
+    fn test_parse_templates_rejects_bad_lines() {
+        assert!(parse_templates("just some text").is_err());
+        assert!(parse_templates("../escape.txt = {map}").is_err());
+        assert!(parse_templates(".hidden = {map}").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let mut values = TextValues::default();
+        values.values.insert("kd", "2.50".into());
+
+        assert_eq!(values.render("K/D {kd}"), "K/D 2.50");
+        assert_eq!(values.render("Map: {map}"), "");
+        assert_eq!(values.render("K/D {kd} on {map}"), "");
+        assert_eq!(values.render("No placeholders"), "No placeholders");
+        assert_eq!(values.render("{unknown}"), "{unknown}");
+    }
+
+    #[tokio::test]
+    async fn test_write_atomic_replaces_file() {
+        let dir = test_dir("atomic");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("kd.txt");
+
+        write_atomic(&path, "K/D 1.00").await.unwrap();
+        write_atomic(&path, "K/D 2.00").await.unwrap();
+
+        assert_eq!(tokio::fs::read_to_string(&path).await.unwrap(), "K/D 2.00");
+        assert!(!dir.join(".kd.txt.tmp").exists());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_refresh_writes_session_files() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(1);
+
+        let session_id = db::create_session(&pool, SessionType::Stream, None, Some(base)).await?;
+        let r1 = db::create_raid(&pool, session_id, "Customs", CharacterType::PMC, GameMode::PVE,
+            Some(base + time::Duration::minutes(12))).await?;
+        db::add_kill(&pool, r1, "pmc", None, None, Some(base + time::Duration::minutes(20))).await?;
+        db::log_forced_transition(&pool, r1, "died", Some(base + time::Duration::minutes(30))).await?;
+        db::end_raid(&pool, r1, Some(base + time::Duration::minutes(30)), None).await?;
+
+        db::create_raid(&pool, session_id, "Lighthouse", CharacterType::PMC, GameMode::PVE,
+            Some(base + time::Duration::minutes(40))).await?;
+
+        let dir = test_dir("refresh");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let mut sink = TextSink::new(pool, TextSinkConfig::new(&dir));
+        sink.refresh().await.unwrap();
+
+        let read = |f: &str| std::fs::read_to_string(dir.join(f)).unwrap();
+        assert_eq!(read("map.txt"), "Lighthouse");
+        assert!(read("state.txt").starts_with("stash_management ("));
+        // The raid still in progress counts as not survived, but not as a death
+        assert_eq!(read("kd.txt"), "K/D 1.00");
+        assert_eq!(read("survival_rate.txt"), "Survival 0%");
+        assert_eq!(read("raids.txt"), "Raids this stream: 2");
+        assert_eq!(read("first_raid.txt"), "First raid after 12m 0s");
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_without_session_writes_blanks() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let dir = test_dir("blank");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let mut sink = TextSink::new(pool, TextSinkConfig::new(&dir));
+        sink.refresh().await.unwrap();
+
+        for file in default_templates().keys() {
+            assert_eq!(std::fs::read_to_string(dir.join(file)).unwrap(), "", "{}", file);
+        }
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+        Ok(())
+    }
+}
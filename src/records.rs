@@ -0,0 +1,171 @@
+// Watches raids end and announces personal bests as they fall. Works off the event
+// bus so raids ended by the API and by detection are both covered.
+use sqlx::SqlitePool;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::events::{EventBus, LiveEvent};
+use crate::stats::{self, NewRecord, Record};
+
+pub struct RecordWatcher {
+    pool: SqlitePool,
+    // The board as of the last event, what a finished raid is compared against
+    board: Vec<Record>,
+}
+
+impl RecordWatcher {
+    pub async fn new(pool: SqlitePool) -> Result<Self, sqlx::Error> {
+        let board = stats::calculate_records(&pool).await?;
+        Ok(Self { pool, board })
+    }
+
+    pub async fn on_event(&mut self, event: &LiveEvent) -> Result<Vec<NewRecord>, sqlx::Error> {
+        match event {
+            LiveEvent::RaidEnded { .. } => {
+                let board = stats::calculate_records(&self.pool).await?;
+                let new_records = stats::find_new_records(&self.board, &board);
+                self.board = board;
+                Ok(new_records)
+            }
+            // Corrections, undo and backfills can move records either way, nothing to announce
+            LiveEvent::RaidCorrected { .. } | LiveEvent::ActionUndone { .. } | LiveEvent::ActionRedone { .. }
+            | LiveEvent::SessionImported { .. } => {
+                self.board = stats::calculate_records(&self.pool).await?;
+                Ok(Vec::new())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    pub fn spawn(pool: SqlitePool, events: EventBus) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            // Subscribe before reading the board so a raid ending in between isn't missed
+            let mut receiver = events.subscribe(None).receiver;
+            let mut watcher = match RecordWatcher::new(pool).await {
+                Ok(watcher) => watcher,
+                Err(e) => {
+                    warn!("Record announcements disabled, cannot load records: {}", e);
+                    return;
+                }
+            };
+
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(envelope) => envelope.event,
+                    Err(RecvError::Lagged(n)) => {
+                        warn!("Record watcher skipped {} events", n);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                match watcher.on_event(&event).await {
+                    Ok(new_records) => {
+                        for new in new_records {
+                            info!("New record: {:?} {} (was {})", new.record.kind, new.record.display, new.previous.display);
+                            events.publish(LiveEvent::RecordSet {
+                                record: new.record.kind,
+                                value: new.record.value,
+                                display: new.record.display,
+                                previous_display: new.previous.display,
+                                raid_id: new.record.raid_id,
+                                session_id: new.record.session_id,
+                            });
+                        }
+                    }
+                    Err(e) => warn!("Record check failed: {}", e),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use time::OffsetDateTime;
+    use crate::db::{self, tests::setup_test_db};
+    use crate::models::{CharacterType, GameMode, RaidState, RecordKind, SessionType};
+
+    async fn play_raid(pool: &SqlitePool, session_id: i64, start: OffsetDateTime, kills: usize) -> i64 {
+        let raid_id = db::create_raid(pool, session_id, "Customs", CharacterType::PMC, GameMode::PVP, Some(start))
+            .await.unwrap();
+        for _ in 0..kills {
+            db::add_kill(pool, raid_id, "pmc", None, None, Some(start + time::Duration::minutes(5))).await.unwrap();
+        }
+        db::log_forced_transition(pool, raid_id, "died", Some(start + time::Duration::minutes(10))).await.unwrap();
+        db::end_raid(pool, raid_id, Some(start + time::Duration::minutes(10)), None).await.unwrap();
+        raid_id
+    }
+
+    fn ended(raid_id: i64) -> LiveEvent {
+        LiveEvent::RaidEnded {
+            raid_id,
+            final_state: RaidState::Died,
+            extract_location: None,
+            ended_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_announces_only_beaten_records() {
+        let pool = setup_test_db().await.expect("setup db");
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(3);
+        let session_id = db::create_session(&pool, SessionType::Stream, None, Some(base)).await.unwrap();
+
+        // Nothing held before, the first raid doesn't get announced
+        let mut watcher = RecordWatcher::new(pool.clone()).await.unwrap();
+        let r1 = play_raid(&pool, session_id, base, 2).await;
+        assert!(watcher.on_event(&ended(r1)).await.unwrap().is_empty());
+
+        // Same kills, shorter raid: nothing beaten, the raid count goes up though
+        let r2 = play_raid(&pool, session_id, base + time::Duration::minutes(20), 2).await;
+        let new = watcher.on_event(&ended(r2)).await.unwrap();
+        assert_eq!(new.iter().map(|n| n.record.kind).collect::<Vec<_>>(), vec![RecordKind::MostRaidsInSession]);
+
+        let r3 = play_raid(&pool, session_id, base + time::Duration::minutes(40), 3).await;
+        let new = watcher.on_event(&ended(r3)).await.unwrap();
+        let kills = new.iter().find(|n| n.record.kind == RecordKind::MostKills).unwrap();
+        assert_eq!(kills.record.raid_id, Some(r3));
+        assert_eq!(kills.record.display, "3 kills");
+        assert_eq!(kills.previous.raid_id, Some(r1));
+
+        // Deleting the record raid only resets the board
+        db::delete_raid(&pool, r3).await.unwrap();
+        let corrected = LiveEvent::RaidCorrected { raid_id: r3, raid_state: None };
+        assert!(watcher.on_event(&corrected).await.unwrap().is_empty());
+        let r4 = play_raid(&pool, session_id, base + time::Duration::minutes(60), 3).await;
+        let new = watcher.on_event(&ended(r4)).await.unwrap();
+        assert!(new.iter().any(|n| n.record.kind == RecordKind::MostKills && n.previous.display == "2 kills"));
+    }
+
+    #[tokio::test]
+    async fn test_spawned_watcher_publishes_record_set() {
+        let pool = setup_test_db().await.expect("setup db");
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(1);
+        let session_id = db::create_session(&pool, SessionType::Stream, None, Some(base)).await.unwrap();
+        play_raid(&pool, session_id, base, 1).await;
+
+        let events = EventBus::new();
+        let mut receiver = events.subscribe(None).receiver;
+        RecordWatcher::spawn(pool.clone(), events.clone());
+        // Let the task subscribe and load the board before publishing
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let r2 = play_raid(&pool, session_id, base + time::Duration::minutes(20), 4).await;
+        events.publish(ended(r2));
+
+        let record = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let LiveEvent::RecordSet { record: RecordKind::MostKills, display, raid_id, .. } =
+                    receiver.recv().await.unwrap().event
+                {
+                    return (display, raid_id);
+                }
+            }
+        }).await.unwrap();
+        assert_eq!(record, ("4 kills".to_string(), Some(r2)));
+    }
+}
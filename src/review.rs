@@ -0,0 +1,207 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+use time::OffsetDateTime;
+
+use crate::db;
+use crate::detection::DetectionControl;
+use crate::detection::apply::{Applier, Outcome};
+use crate::detection::schema::DetectionKind;
+use crate::events::{EventBus, LiveEvent};
+use crate::models::{PendingEvent, ReviewStatus};
+
+// ============================================================
+// Review Queue
+// ============================================================
+
+// Events from detection or chat that an operator has to sign off on before they
+// touch raids, transitions or kills. Approving runs them through the same Applier
+// the detection feed uses, so the transition graph still applies.
+
+pub fn parse_payload(event: &PendingEvent) -> Result<DetectionKind, String> {
+    serde_json::from_str(&event.payload)
+        .map_err(|e| format!("Pending event {} has an unreadable payload: {}", event.pending_id, e))
+}
+
+pub async fn submit(
+    pool: &SqlitePool,
+    events: &EventBus,
+    source: &str,
+    confidence: Option<f64>,
+    kind: &DetectionKind,
+    occurred_at: Option<OffsetDateTime>,
+) -> Result<i64, sqlx::Error> {
+    let payload = serde_json::to_string(kind).expect("detection events serialize");
+    let pending_id = db::queue_event(pool, source, kind.name(), confidence, &payload, occurred_at).await?;
+
+    events.publish(LiveEvent::EventQueued {
+        pending_id,
+        source: source.to_string(),
+        event_type: kind.name().to_string(),
+    });
+    Ok(pending_id)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum ApprovalResult {
+    Approved,
+    // Valid event that doesn't fit the current data, it stays pending so it can be edited
+    NotApplied { reason: String },
+    AlreadyReviewed { status: ReviewStatus },
+    NotFound,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Approval {
+    pub pending_id: i64,
+    #[serde(flatten)]
+    pub result: ApprovalResult,
+}
+
+// Applies the events in the order given, each one together with its status change so an
+// approval is never half done. A raid_info has to be able to start the raid on its own,
+// nothing holds a map-less one around for a later approval to complete.
+pub async fn approve(pool: &SqlitePool, events: &EventBus, ids: &[i64]) -> Result<Vec<Approval>, sqlx::Error> {
+    // The operator is the one deciding here, detection being paused doesn't matter
+    let applier = Applier::new(pool.clone(), events.clone(), DetectionControl::default());
+    let mut approvals = Vec::with_capacity(ids.len());
+
+    for &pending_id in ids {
+        let mut tx = pool.begin().await?;
+        let mut published = Vec::new();
+
+        let result = match db::get_pending_event(&mut *tx, pending_id).await? {
+            None => ApprovalResult::NotFound,
+            Some(event) if event.status != ReviewStatus::Pending => {
+                ApprovalResult::AlreadyReviewed { status: event.status }
+            }
+            Some(event) => match parse_payload(&event) {
+                Err(reason) => ApprovalResult::NotApplied { reason },
+                Ok(DetectionKind::RaidInfo { map: None, .. }) => ApprovalResult::NotApplied {
+                    reason: "raid_info has no map so it can't start a raid, edit in the map or reject it".into(),
+                },
+                Ok(kind) => {
+                    let ts = event.occurred_at.unwrap_or_else(OffsetDateTime::now_utc);
+                    match applier.write_in(&mut tx, kind, ts, &mut published).await? {
+                        Outcome::Ignored(reason) => ApprovalResult::NotApplied { reason },
+                        _ if db::set_review_status(&mut *tx, pending_id, ReviewStatus::Approved, None).await? => {
+                            ApprovalResult::Approved
+                        }
+                        // Reviewed by someone else since we read it, the rollback undoes the write
+                        _ => match db::get_pending_event(&mut *tx, pending_id).await? {
+                            Some(event) => ApprovalResult::AlreadyReviewed { status: event.status },
+                            None => ApprovalResult::NotFound,
+                        },
+                    }
+                }
+            },
+        };
+
+        if result == ApprovalResult::Approved {
+            tx.commit().await?;
+            for event in published {
+                events.publish(event);
+            }
+        } else {
+            tx.rollback().await?;
+        }
+        approvals.push(Approval { pending_id, result });
+    }
+
+    Ok(approvals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::setup_test_db;
+    use crate::models::{GameMode, SessionType};
+
+    fn kill(enemy_type: &str) -> DetectionKind {
+        DetectionKind::Kill { enemy_type: enemy_type.into(), weapon: None, headshot: None }
+    }
+
+    #[tokio::test]
+    async fn test_bulk_approve_applies_in_order() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let events = EventBus::new();
+        db::create_session(&pool, SessionType::Stream, None, None).await?;
+
+        let raid_info = DetectionKind::RaidInfo { map: Some("Shoreline".into()), character_type: None, game_mode: None };
+        let mut ids = Vec::new();
+        for kind in [raid_info, kill("scav"), kill("pmc")] {
+            ids.push(submit(&pool, &events, "chat", None, &kind, None).await?);
+        }
+        // Nothing is written until approved
+        assert!(db::get_active_raid(&pool).await?.is_none());
+
+        let approvals = approve(&pool, &events, &ids).await?;
+        assert!(approvals.iter().all(|a| a.result == ApprovalResult::Approved), "{:?}", approvals);
+
+        let raid = db::get_active_raid(&pool).await?.unwrap();
+        assert_eq!(raid.map_name, "Shoreline");
+        assert_eq!(db::get_kills_for_raid(&pool, raid.raid_id).await?.len(), 2);
+
+        // A second pass doesn't apply anything twice
+        let again = approve(&pool, &events, &[ids[1], 999]).await?;
+        assert_eq!(again[0].result, ApprovalResult::AlreadyReviewed { status: ReviewStatus::Approved });
+        assert_eq!(again[1].result, ApprovalResult::NotFound);
+        assert_eq!(db::get_kills_for_raid(&pool, raid.raid_id).await?.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_approval_publishes_what_it_applied() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let events = EventBus::new();
+        db::create_session(&pool, SessionType::Stream, None, None).await?;
+        let raid_info = DetectionKind::RaidInfo { map: Some("Woods".into()), character_type: None, game_mode: None };
+        let ids = [
+            submit(&pool, &events, "chat", None, &raid_info, None).await?,
+            submit(&pool, &events, "chat", None, &kill("scav"), None).await?,
+        ];
+
+        let mut subscription = events.subscribe(None);
+        approve(&pool, &events, &ids).await?;
+
+        let kinds: Vec<_> = std::iter::from_fn(|| subscription.receiver.try_recv().ok())
+            .map(|envelope| envelope.event.kind())
+            .collect();
+        assert_eq!(kinds, ["raid_created", "kill_added"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unappliable_event_stays_pending() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let events = EventBus::new();
+
+        // No raid running, so the kill has nowhere to go
+        let pending_id = submit(&pool, &events, "detection", Some(0.4), &kill("scav"), None).await?;
+        let approvals = approve(&pool, &events, &[pending_id]).await?;
+        assert!(matches!(approvals[0].result, ApprovalResult::NotApplied { .. }));
+
+        let event = db::get_pending_event(&pool, pending_id).await?.unwrap();
+        assert_eq!(event.status, ReviewStatus::Pending);
+        assert!(event.reviewed_at.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raid_info_without_map_stays_pending() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let events = EventBus::new();
+        db::create_session(&pool, SessionType::Stream, None, None).await?;
+
+        let mode_only = DetectionKind::RaidInfo { map: None, character_type: None, game_mode: Some(GameMode::PVP) };
+        let pending_id = submit(&pool, &events, "chat", None, &mode_only, None).await?;
+        let approvals = approve(&pool, &events, &[pending_id]).await?;
+        assert!(matches!(approvals[0].result, ApprovalResult::NotApplied { .. }));
+
+        // Not marked approved and then forgotten
+        let event = db::get_pending_event(&pool, pending_id).await?.unwrap();
+        assert_eq!(event.status, ReviewStatus::Pending);
+        assert!(db::get_active_raid(&pool).await?.is_none());
+        Ok(())
+    }
+}
@@ -1,50 +1,313 @@
 use sqlx::sqlite::{SqlitePool};
-use time::{Duration};
+use serde::{Serialize, Serializer};
+use time::{Duration, OffsetDateTime};
 use crate::db::*;
 
 use crate::models::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+// "1h 5m 3s" style, zero leading units are dropped
+pub fn format_duration(duration: Duration) -> String {
+    let sign = if duration.is_negative() { "-" } else { "" };
+    let total = duration.whole_seconds().abs();
+    let (hours, minutes, seconds) = (total / 3600, (total % 3600) / 60, total % 60);
+
+    if hours > 0 {
+        format!("{}{}h {}m {}s", sign, hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}{}m {}s", sign, minutes, seconds)
+    } else {
+        format!("{}{}s", sign, seconds)
+    }
+}
+
+// Durations go over the wire as {"seconds": 63, "human": "1m 3s"} so overlays
+// and chat can use whichever they need without doing the math themselves
+fn serialize_duration<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct DurationJson {
+        seconds: i64,
+        human: String,
+    }
 
-#[derive(Debug, Clone)]
+    DurationJson {
+        seconds: duration.whole_seconds(),
+        human: format_duration(*duration),
+    }.serialize(serializer)
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct StateTime {
     pub state: String,
+    #[serde(serialize_with = "serialize_duration")]
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StateShare {
+    pub state: String,
+    #[serde(serialize_with = "serialize_duration")]
     pub duration: Duration,
+    pub share: f64,
 }
 
-#[derive(Debug, Clone)]
+// Where the stream's time went. states covers every tracked state plus the gaps
+// between raids as stash_management, longest first. categories folds those into
+// in_raid / menus / queue / loading / other shares.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionTimeBreakdown {
+    #[serde(serialize_with = "serialize_duration")]
+    pub total_time: Duration,
+    pub raid_active_share: f64,
+    pub states: Vec<StateShare>,
+    pub categories: BTreeMap<&'static str, f64>,
+    #[serde(serialize_with = "serialize_duration")]
+    pub before_first_raid: Duration,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct FirstRaidDelay {
+    #[serde(serialize_with = "serialize_duration")]
     pub duration: Duration,
     pub sessions: i64,
+    #[serde(serialize_with = "serialize_duration")]
     pub last_session: Duration,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SessionStats {
     pub total_raids: i64,
     pub survived_raids: i64,
+    // Ended in died or mia, the raid still running isn't one yet
+    pub deaths: i64,
     pub survival_rate: f64,
     pub total_kills: i64,
     pub kd_ratio: f64,
+    // Wall clock, start to end
+    #[serde(serialize_with = "serialize_duration")]
     pub avg_raid_duration: Duration,
+    // The same without time in non-play states like reconnecting
+    #[serde(serialize_with = "serialize_duration")]
+    pub avg_active_duration: Duration,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SessionComparison {
     pub current: SessionStats,
     pub all_time: SessionStats,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ModeStats {
     pub pve: SessionStats,
     pub pvp: SessionStats,
 }
 
-#[derive(Debug, Clone)]
+// Only the columns that were grouped on are present
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupedStats {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_mode: Option<GameMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub character_type: Option<CharacterType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_type: Option<SessionType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weekday: Option<String>,
+    #[serde(flatten)]
+    pub stats: SessionStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MapStats {
+    pub map_name: String,
+    #[serde(flatten)]
+    pub stats: SessionStats,
+    // Over the raids that went through queuing
+    #[serde(serialize_with = "serialize_duration")]
+    pub avg_queue_time: Duration,
+    pub most_used_extract: Option<String>,
+}
+
+// Average queue and deploy times per raid that logged them. total is the three added up.
+#[derive(Debug, Clone, Serialize)]
+pub struct WaitTimes {
+    pub raid_count: i64,
+    #[serde(serialize_with = "serialize_duration")]
+    pub avg_queue: Duration,
+    #[serde(serialize_with = "serialize_duration")]
+    pub avg_deploying_cancellable: Duration,
+    #[serde(serialize_with = "serialize_duration")]
+    pub avg_deploying_committed: Duration,
+    #[serde(serialize_with = "serialize_duration")]
+    pub avg_total: Duration,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupedWaitTimes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_mode: Option<GameMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub character_type: Option<CharacterType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_type: Option<SessionType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weekday: Option<String>,
+    #[serde(flatten)]
+    pub waits: WaitTimes,
+}
+
+// queue_change is how much longer (or shorter, negative) this stream's queues are
+// than the all-time average, 0.4 being 40% longer. None until both sides have a queue.
+#[derive(Debug, Clone, Serialize)]
+pub struct WaitComparison {
+    pub current: WaitTimes,
+    pub all_time: WaitTimes,
+    pub queue_change: Option<f64>,
+    pub maps: Vec<MapWaitComparison>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MapWaitComparison {
+    pub map_name: String,
+    pub current: WaitTimes,
+    pub all_time: WaitTimes,
+    pub queue_change: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WeaponStats {
+    pub weapon: String,
+    pub kills: i64,
+    pub headshots: i64,
+    pub headshot_rate: f64,
+    // "pmc" -> 3, "scav" -> 5
+    pub enemy_types: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WeaponBreakdown {
+    pub total_kills: i64,
+    pub headshots: i64,
+    pub headshot_rate: f64,
+    // Most kills, for a session this is the weapon of the session
+    pub top_weapon: Option<String>,
+    pub weapons: Vec<WeaponStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrendPoint {
+    // session_id or raid_id, see Trend::over
+    pub id: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: time::OffsetDateTime,
+    #[serde(flatten)]
+    pub stats: SessionStats,
+    // Pooled over this point and the window - 1 before it, so a session with one
+    // raid doesn't count as much as one with ten
+    pub rolling: SessionStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Trend {
+    pub over: TrendUnit,
+    pub window: usize,
+    pub points: Vec<TrendPoint>,
+}
+
+impl Trend {
+    // Bare numbers oldest first for overlay sparklines, rounded so the array stays short
+    pub fn sparkline(&self, metric: TrendMetric, rolling: bool) -> Vec<f64> {
+        self.points.iter().map(|p| {
+            let stats = if rolling { &p.rolling } else { &p.stats };
+            let value = match metric {
+                TrendMetric::SurvivalRate => stats.survival_rate,
+                TrendMetric::KdRatio => stats.kd_ratio,
+                TrendMetric::Kills => stats.total_kills as f64,
+                TrendMetric::Raids => stats.total_raids as f64,
+            };
+            (value * 1000.0).round() / 1000.0
+        }).collect()
+    }
+}
+
+// A run of raids, first/last are None while it's empty
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Streak {
+    pub length: i64,
+    pub first_raid_id: Option<i64>,
+    pub last_raid_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StreakPair {
+    pub current: Streak,
+    pub longest: Streak,
+}
+
+impl StreakPair {
+    fn extend(&mut self, raid_id: i64, amount: i64) {
+        if self.current.length == 0 {
+            self.current.first_raid_id = Some(raid_id);
+        }
+        self.current.length += amount;
+        self.current.last_raid_id = Some(raid_id);
+
+        if self.current.length > self.longest.length {
+            self.longest = self.current.clone();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.current = Streak::default();
+    }
+}
+
+// Survival and death streaks count raids in a row. The kill streak counts kills
+// since the last death, the kills in the raid you died in included.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Streaks {
+    pub survival: StreakPair,
+    pub death: StreakPair,
+    pub kill: StreakPair,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Record {
+    pub kind: RecordKind,
+    pub value: f64,
+    pub display: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raid_id: Option<i64>,
+    pub session_id: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub set_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NewRecord {
+    #[serde(flatten)]
+    pub record: Record,
+    pub previous: Record,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct BetweenRaidsTime {
+    #[serde(serialize_with = "serialize_duration")]
     pub avg_gap: Duration,
+    #[serde(serialize_with = "serialize_duration")]
     pub shortest_gap: Duration,
+    #[serde(serialize_with = "serialize_duration")]
     pub longest_gap: Duration,
+    #[serde(serialize_with = "serialize_duration")]
     pub total_gap: Duration,
     pub gap_count: i64,
 }
@@ -146,92 +409,204 @@ pub async fn get_mode_stats_for_session (
     pool: &SqlitePool,
     session_id: i64,
 ) -> Result<ModeStats, sqlx::Error> {
-    let raids = get_raids_for_session(pool, session_id).await?;
+    let filter = StatsFilter { session_ids: vec![session_id], ..Default::default() };
+    let groups = calculate_stats_by(pool, &filter, &[StatsGroup::GameMode]).await?;
+    let mode = |mode: GameMode| groups.iter()
+        .find(|g| g.game_mode == Some(mode.clone()))
+        .map_or_else(|| stats_from_aggregate(None), |g| g.stats.clone());
 
-    let pve_raids: Vec<Raid> = raids.iter().filter(|r| r.game_mode == GameMode::PVE).cloned().collect();
-    let pvp_raids: Vec<Raid> = raids.iter().filter(|r| r.game_mode == GameMode::PVP).cloned().collect();
+    Ok(ModeStats { pve: mode(GameMode::PVE), pvp: mode(GameMode::PVP) })
+}
 
-    let pve = calculate_stats_from_raids(pool, pve_raids).await?;
-    let pvp = calculate_stats_from_raids(pool, pvp_raids).await?;
+// The stats engine: any filter, split by any mix of groups. A group with no raids has
+// no row, without groups there is always the one overall row.
+pub async fn calculate_stats_by(
+    pool: &SqlitePool,
+    filter: &StatsFilter,
+    group_by: &[StatsGroup],
+) -> Result<Vec<GroupedStats>, sqlx::Error> {
+    let groups = aggregate_raids(pool, filter, group_by).await?;
+
+    Ok(groups.iter().map(|g| GroupedStats {
+        game_mode: g.game_mode.clone(),
+        character_type: g.character_type.clone(),
+        map_name: g.map_name.clone(),
+        session_type: g.session_type.clone(),
+        session_id: g.session_id,
+        weekday: g.weekday.clone(),
+        stats: stats_from_aggregate(Some(g)),
+    }).collect())
+}
 
-    Ok(ModeStats { pve, pvp })
+pub async fn calculate_stats(pool: &SqlitePool, filter: &StatsFilter) -> Result<SessionStats, sqlx::Error> {
+    let totals = calculate_stats_by(pool, filter, &[]).await?;
+    Ok(totals.into_iter().next().map_or_else(|| stats_from_aggregate(None), |g| g.stats))
 }
 
 pub async fn calculate_session_stats(
     pool: &SqlitePool,
     session_id: i64
 ) -> Result<SessionStats, sqlx::Error> {
-    let raids = get_raids_for_session(pool, session_id).await?;
-    calculate_stats_from_raids(pool, raids).await
+    calculate_stats(pool, &StatsFilter { session_ids: vec![session_id], ..Default::default() }).await
 }
 
 pub async fn calculate_global_stats(
     pool: &SqlitePool,
     game_mode_filter: Option<GameMode>
 ) -> Result<SessionStats, sqlx::Error> {
-    let all_raids = get_all_raids(pool).await?;
+    calculate_stats(pool, &StatsFilter { game_mode: game_mode_filter, ..Default::default() }).await
+}
 
-    let filtered_raids: Vec<Raid> = if let Some(mode) = game_mode_filter {
-        all_raids.into_iter().filter(|r| r.game_mode == mode).collect()
-    } else {
-        all_raids
-    };
+// All-time stats split by one group
+pub async fn calculate_grouped_stats(
+    pool: &SqlitePool,
+    group_by: StatsGroup,
+    game_mode_filter: Option<GameMode>,
+) -> Result<Vec<GroupedStats>, sqlx::Error> {
+    calculate_stats_by(pool, &StatsFilter { game_mode: game_mode_filter, ..Default::default() }, &[group_by]).await
+}
+
+// "How do you do on Lighthouse?" - per map stats for a session or all time, most played first
+pub async fn calculate_map_stats(pool: &SqlitePool, filter: &StatsFilter) -> Result<Vec<MapStats>, sqlx::Error> {
+    let maps = aggregate_raids(pool, filter, &[StatsGroup::Map]).await?;
+    let queues = aggregate_queue_times(pool, filter).await?;
+    let extracts = get_map_extracts(pool, filter).await?;
+
+    let mut map_stats: Vec<MapStats> = maps.iter().map(|agg| {
+        let map_name = agg.map_name.clone().unwrap_or_default();
+        let same_map = |name: &str| name.eq_ignore_ascii_case(&map_name);
+
+        let avg_queue_time = queues.iter()
+            .find(|q| same_map(&q.map_name) && q.raid_count > 0)
+            .map(|q| Duration::milliseconds(q.queue_ms / q.raid_count))
+            .unwrap_or(Duration::ZERO);
 
-    calculate_stats_from_raids(pool, filtered_raids).await
+        // Already ordered most used first
+        let most_used_extract = extracts.iter()
+            .find(|e| same_map(&e.map_name))
+            .map(|e| e.extract_location.clone());
+
+        MapStats { map_name, stats: stats_from_aggregate(Some(agg)), avg_queue_time, most_used_extract }
+    }).collect();
+
+    map_stats.sort_by(|a, b| b.stats.total_raids.cmp(&a.stats.total_raids).then_with(|| a.map_name.cmp(&b.map_name)));
+    Ok(map_stats)
 }
 
-async fn calculate_stats_from_raids(
+// The last `count` sessions or raids with a rolling average over `window` of them.
+// Enough extra history is loaded that the first point has a full window too.
+pub async fn calculate_trend(
     pool: &SqlitePool,
-    raids: Vec<Raid>
-) -> Result<SessionStats, sqlx::Error> {
-    let total_raids = raids.len() as i64;
-    let mut survived_raids = 0;
-    let mut total_kills = 0;
-    let mut total_duration = Duration::ZERO;
-    let mut duration_count = 0;
-
-    for raid in &raids {
-        if raid.current_state == "survived" {
-            survived_raids += 1;
+    filter: &StatsFilter,
+    over: TrendUnit,
+    count: usize,
+    window: usize,
+) -> Result<Trend, sqlx::Error> {
+    let window = window.max(1);
+    let history = aggregate_recent(pool, filter, over, (count + window - 1) as i64).await?;
+    let skip = history.len().saturating_sub(count);
+
+    let points = history.iter().enumerate().skip(skip).map(|(i, point)| {
+        let start = (i + 1).saturating_sub(window);
+        let rolling = sum_aggregates(history[start..=i].iter().map(|p| &p.totals));
+
+        TrendPoint {
+            id: point.id,
+            started_at: point.started_at,
+            stats: stats_from_aggregate(Some(&point.totals)),
+            rolling: stats_from_aggregate(Some(&rolling)),
         }
+    }).collect();
 
-        if let Some(ended_at) = raid.ended_at {
-            total_duration += ended_at - raid.started_at;
-            duration_count += 1;
-        }
+    Ok(Trend { over, window, points })
+}
 
-        let kills = get_kills_for_raid(pool, raid.raid_id).await?;
-        total_kills += kills.len() as i64;
-    }
+fn sum_aggregates<'a>(aggregates: impl Iterator<Item = &'a RaidAggregate>) -> RaidAggregate {
+    aggregates.fold(RaidAggregate::default(), |mut sum, agg| {
+        sum.raid_count += agg.raid_count;
+        sum.survived_count += agg.survived_count;
+        sum.died_count += agg.died_count;
+        sum.kill_count += agg.kill_count;
+        sum.ended_count += agg.ended_count;
+        sum.duration_ms += agg.duration_ms;
+        sum.active_duration_ms += agg.active_duration_ms;
+        sum
+    })
+}
+
+// Kills where nobody recorded whether it was a headshot don't count either way
+fn headshot_rate(headshots: i64, known: i64) -> f64 {
+    if known > 0 { headshots as f64 / known as f64 } else { 0.0 }
+}
 
-    let deaths = total_raids - survived_raids;
+pub async fn calculate_weapon_stats(pool: &SqlitePool, filter: &StatsFilter) -> Result<WeaponBreakdown, sqlx::Error> {
+    let weapons = aggregate_weapons(pool, filter).await?;
+    let enemies = count_weapon_enemies(pool, filter).await?;
+    let (total_kills, headshots, headshot_known) = count_headshots(pool, filter).await?;
+
+    let weapons: Vec<WeaponStats> = weapons.into_iter().map(|w| WeaponStats {
+        enemy_types: enemies.iter()
+            .filter(|e| e.weapon_key == w.weapon_key)
+            .map(|e| (e.enemy_type.clone(), e.kill_count))
+            .collect(),
+        headshot_rate: headshot_rate(w.headshot_count, w.headshot_known),
+        weapon: w.weapon,
+        kills: w.kill_count,
+        headshots: w.headshot_count,
+    }).collect();
+
+    Ok(WeaponBreakdown {
+        total_kills,
+        headshots,
+        headshot_rate: headshot_rate(headshots, headshot_known),
+        top_weapon: weapons.first().map(|w| w.weapon.clone()),
+        weapons,
+    })
+}
 
-    let survival_rate = if total_raids > 0 {
-        survived_raids as f64 / total_raids as f64
+// A group with no raids has no row, which reads as all zeros
+fn stats_from_aggregate(aggregate: Option<&RaidAggregate>) -> SessionStats {
+    let Some(agg) = aggregate else {
+        return SessionStats {
+            total_raids: 0,
+            survived_raids: 0,
+            deaths: 0,
+            survival_rate: 0.0,
+            total_kills: 0,
+            kd_ratio: 0.0,
+            avg_raid_duration: Duration::ZERO,
+            avg_active_duration: Duration::ZERO,
+        };
+    };
+
+    let survival_rate = if agg.raid_count > 0 {
+        agg.survived_count as f64 / agg.raid_count as f64
     } else {
         0.0
     };
 
-    let kd_ratio = if deaths > 0 {
-        total_kills as f64 / deaths as f64
+    let kd_ratio = if agg.died_count > 0 {
+        agg.kill_count as f64 / agg.died_count as f64
     } else {
-        total_kills as f64
+        agg.kill_count as f64
     };
 
-    let avg_duration = if duration_count > 0 {
-        total_duration / duration_count as i32
+    let average = |total_ms: i64| if agg.ended_count > 0 {
+        Duration::milliseconds(total_ms / agg.ended_count)
     } else {
         Duration::ZERO
     };
 
-    Ok(SessionStats {
-        total_raids,
-        survived_raids,
+    SessionStats {
+        total_raids: agg.raid_count,
+        survived_raids: agg.survived_count,
+        deaths: agg.died_count,
         survival_rate,
-        total_kills,
+        total_kills: agg.kill_count,
         kd_ratio,
-        avg_raid_duration: avg_duration,
-    })
+        avg_raid_duration: average(agg.duration_ms),
+        avg_active_duration: average(agg.active_duration_ms),
+    }
 }
 
 pub async fn calculate_time_before_first_raid(
@@ -269,15 +644,234 @@ pub async fn calculate_time_before_first_raid(
 
 }
 
+fn wait_times_from_aggregate(agg: Option<&WaitAggregate>) -> WaitTimes {
+    let raid_count = agg.map_or(0, |a| a.raid_count);
+    let avg = |ms: fn(&WaitAggregate) -> i64| match agg {
+        Some(agg) if raid_count > 0 => Duration::milliseconds(ms(agg) / raid_count),
+        _ => Duration::ZERO,
+    };
+
+    WaitTimes {
+        raid_count,
+        avg_queue: avg(|a| a.queue_ms),
+        avg_deploying_cancellable: avg(|a| a.deploying_cancellable_ms),
+        avg_deploying_committed: avg(|a| a.deploying_committed_ms),
+        avg_total: avg(|a| a.queue_ms + a.deploying_cancellable_ms + a.deploying_committed_ms),
+    }
+}
+
+fn queue_change(current: &WaitTimes, all_time: &WaitTimes) -> Option<f64> {
+    if current.avg_queue.is_zero() || all_time.avg_queue.is_zero() {
+        return None;
+    }
+    Some(current.avg_queue.whole_milliseconds() as f64 / all_time.avg_queue.whole_milliseconds() as f64 - 1.0)
+}
+
+pub async fn calculate_raid_wait_times(pool: &SqlitePool, raid_id: i64) -> Result<WaitTimes, sqlx::Error> {
+    Ok(wait_times_from_aggregate(Some(&get_raid_wait_times(pool, raid_id).await?)))
+}
+
+pub async fn calculate_wait_times(
+    pool: &SqlitePool,
+    filter: &StatsFilter,
+    group_by: Option<StatsGroup>,
+) -> Result<Vec<GroupedWaitTimes>, sqlx::Error> {
+    let groups = aggregate_wait_times(pool, filter, group_by.as_slice()).await?;
+
+    Ok(groups.iter().map(|g| GroupedWaitTimes {
+        game_mode: g.game_mode.clone(),
+        character_type: g.character_type.clone(),
+        map_name: g.map_name.clone(),
+        session_type: g.session_type.clone(),
+        session_id: g.session_id,
+        weekday: g.weekday.clone(),
+        waits: wait_times_from_aggregate(Some(g)),
+    }).collect())
+}
+
+// This stream's queues against all time, overall and for each map played this stream
+pub async fn compare_session_wait_times(
+    pool: &SqlitePool,
+    session_id: i64,
+    game_mode_filter: Option<GameMode>,
+) -> Result<WaitComparison, sqlx::Error> {
+    let all_time_filter = StatsFilter { game_mode: game_mode_filter, ..Default::default() };
+    let session_filter = StatsFilter { session_ids: vec![session_id], ..all_time_filter.clone() };
+
+    let current = wait_times_from_aggregate(aggregate_wait_times(pool, &session_filter, &[]).await?.first());
+    let all_time = wait_times_from_aggregate(aggregate_wait_times(pool, &all_time_filter, &[]).await?.first());
+
+    let all_time_maps: HashMap<String, WaitTimes> = aggregate_wait_times(pool, &all_time_filter, &[StatsGroup::Map]).await?
+        .iter()
+        .filter_map(|g| Some((g.map_name.as_ref()?.to_lowercase(), wait_times_from_aggregate(Some(g)))))
+        .collect();
+    let maps = aggregate_wait_times(pool, &session_filter, &[StatsGroup::Map]).await?
+        .iter()
+        .filter_map(|g| {
+            let map_name = g.map_name.clone()?;
+            let current = wait_times_from_aggregate(Some(g));
+            let all_time = all_time_maps.get(&map_name.to_lowercase())?.clone();
+            let queue_change = queue_change(&current, &all_time);
+            Some(MapWaitComparison { map_name, current, all_time, queue_change })
+        })
+        .collect();
+
+    Ok(WaitComparison { queue_change: queue_change(&current, &all_time), current, all_time, maps })
+}
+
+pub async fn calculate_streaks(pool: &SqlitePool, filter: &StatsFilter) -> Result<Streaks, sqlx::Error> {
+    let mut streaks = Streaks::default();
+
+    for raid in get_raid_outcomes(pool, filter).await? {
+        if raid.kill_count > 0 {
+            streaks.kill.extend(raid.raid_id, raid.kill_count);
+        }
+
+        if raid.transferred {
+            continue;
+        }
+        if raid.survived {
+            streaks.survival.extend(raid.raid_id, 1);
+            streaks.death.reset();
+        } else {
+            streaks.death.extend(raid.raid_id, 1);
+            streaks.survival.reset();
+            streaks.kill.reset();
+        }
+    }
+
+    Ok(streaks)
+}
+
+fn record_display(kind: RecordKind, value: f64) -> String {
+    match kind {
+        RecordKind::MostKills => format!("{} kills", value as i64),
+        RecordKind::LongestRaid | RecordKind::FastestExtract => format_duration(Duration::seconds(value.round() as i64)),
+        RecordKind::MostRaidsInSession => format!("{} raids", value as i64),
+        RecordKind::BestSessionSurvivalRate => format!("{:.0}% survived", value * 100.0),
+    }
+}
+
+// The current holder of every record that has one
+pub async fn calculate_records(pool: &SqlitePool) -> Result<Vec<Record>, sqlx::Error> {
+    let mut records = Vec::new();
+
+    for kind in RecordKind::ALL {
+        if let Some(holder) = get_record_holders(pool, kind, 1).await?.pop() {
+            records.push(Record {
+                kind,
+                value: holder.value,
+                display: record_display(kind, holder.value),
+                raid_id: holder.raid_id,
+                session_id: holder.session_id,
+                set_at: holder.set_at,
+            });
+        }
+    }
+
+    Ok(records)
+}
+
+// Records on `after` that beat what `before` had. A record nobody held before isn't
+// announced, otherwise the first raid ever would break all of them.
+pub fn find_new_records(before: &[Record], after: &[Record]) -> Vec<NewRecord> {
+    after.iter()
+        .filter_map(|record| {
+            let previous = before.iter().find(|r| r.kind == record.kind)?;
+            record.kind.is_better(record.value, previous.value).then(|| NewRecord {
+                record: record.clone(),
+                previous: previous.clone(),
+            })
+        })
+        .collect()
+}
+
+fn state_category(state: &str) -> &'static str {
+    match state {
+        "raid_active" | "raid_ending" => "in_raid",
+        "idle" | "stash_management" | "pre_raid_setup" | "post_raid_review" | "survived" | "died" | "mia" => "menus",
+        "queuing" => "queue",
+        "deploying_cancellable" | "deploying_committed" | "transfer" => "loading",
+        _ => "other",
+    }
+}
+
+fn share_of(part: Duration, total: Duration) -> f64 {
+    if total.is_positive() {
+        part.whole_milliseconds() as f64 / total.whole_milliseconds() as f64
+    } else {
+        0.0
+    }
+}
+
+// Nobody logs a transition between raids, that time is spent in the stash. Counted from
+// every ended raid until the next one starts, the last one until the session ended
+// (or now while it's still going). Raids come in started_at order.
+fn stash_time_between_raids(raids: &[Raid], session_end: OffsetDateTime) -> Duration {
+    raids.iter().enumerate()
+        .filter_map(|(i, raid)| {
+            let ended_at = raid.ended_at?;
+            let next_start = raids.get(i + 1).map_or(session_end, |next| next.started_at);
+            Some((next_start - ended_at).max(Duration::ZERO))
+        })
+        .sum()
+}
+
+pub async fn calculate_session_time_breakdown(
+    pool: &SqlitePool,
+    session_id: i64,
+) -> Result<SessionTimeBreakdown, sqlx::Error> {
+    let filter = StatsFilter { session_ids: vec![session_id], ..Default::default() };
+    let mut durations: HashMap<String, Duration> = aggregate_state_times(pool, &filter).await?
+        .into_iter()
+        .map(|row| (row.state, Duration::milliseconds(row.duration_ms)))
+        .collect();
+
+    let session = get_session_by_id(pool, session_id).await?;
+    let raids = get_raids_for_session(pool, session_id).await?;
+    let session_end = session.as_ref().and_then(|s| s.ended_at).unwrap_or_else(OffsetDateTime::now_utc);
+    let stash_time = stash_time_between_raids(&raids, session_end);
+    if stash_time.is_positive() {
+        *durations.entry(RaidState::StashManagement.as_str().to_string()).or_default() += stash_time;
+    }
+
+    let total_time: Duration = durations.values().copied().sum();
+    let mut categories: BTreeMap<&'static str, f64> = ["in_raid", "menus", "queue", "loading", "other"]
+        .into_iter()
+        .map(|category| (category, 0.0))
+        .collect();
+    for (state, duration) in &durations {
+        *categories.entry(state_category(state)).or_default() += share_of(*duration, total_time);
+    }
+
+    let mut states: Vec<StateShare> = durations.into_iter()
+        .map(|(state, duration)| StateShare { state, duration, share: share_of(duration, total_time) })
+        .collect();
+    states.sort_by(|a, b| b.duration.cmp(&a.duration).then_with(|| a.state.cmp(&b.state)));
+
+    let raid_active_share = states.iter()
+        .find(|s| s.state == RaidState::RaidActive.as_str())
+        .map_or(0.0, |s| s.share);
+
+    // Overhead before the first raid isn't part of total_time, it's setup and chatting
+    let before_first_raid = match (session, raids.first()) {
+        (Some(session), Some(first)) => (first.started_at - session.started_at).max(Duration::ZERO),
+        _ => Duration::ZERO,
+    };
+
+    Ok(SessionTimeBreakdown { total_time, raid_active_share, states, categories, before_first_raid })
+}
+
 pub async fn calculate_time_in_state(
     pool: &SqlitePool,
     raid_id: i64
 ) -> Result<Vec<StateTime>, sqlx::Error> {
-    let transitions = get_raid_transitions(&pool, raid_id).await?;
+    let transitions = get_raid_transitions(pool, raid_id).await?;
 
     let mut state_durations: HashMap<String, Duration> = HashMap::new();
 
-    for i in 0..transitions.len() - 1 {
+    // saturating_sub so a raid with no transitions yet returns empty instead of underflowing
+    for i in 0..transitions.len().saturating_sub(1) {
         let current = &transitions[i];
         let next = &transitions[i + 1];
 
@@ -291,7 +885,7 @@ pub async fn calculate_time_in_state(
         // }
         // I've seen the below and had Claude explain it to me, this is a note to explain this
         // pattern to me
-        state_durations.entry(current.to_state.clone())
+        state_durations.entry(current.to_state.to_string())
             .and_modify(|d| *d = *d + duration)
             .or_insert(duration);
     }
@@ -313,6 +907,298 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        assert_eq!(format_duration(Duration::seconds(45)), "45s");
+        assert_eq!(format_duration(Duration::seconds(63)), "1m 3s");
+        assert_eq!(format_duration(Duration::seconds(3723)), "1h 2m 3s");
+        assert_eq!(format_duration(Duration::seconds(-90)), "-1m 30s");
+    }
+
+    #[test]
+    fn test_session_stats_serializes_durations() {
+        let stats = SessionStats {
+            total_raids: 2,
+            survived_raids: 1,
+            deaths: 1,
+            survival_rate: 0.5,
+            total_kills: 3,
+            kd_ratio: 3.0,
+            avg_raid_duration: Duration::minutes(25),
+            avg_active_duration: Duration::minutes(20),
+        };
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["avg_raid_duration"]["seconds"], 1500);
+        assert_eq!(json["avg_active_duration"]["seconds"], 1200);
+        assert_eq!(json["avg_raid_duration"]["human"], "25m 0s");
+        assert_eq!(json["kd_ratio"], 3.0);
+    }
+
+    #[tokio::test]
+    async fn test_calculate_time_in_state_no_transitions() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+
+        let session_id = create_session(&pool, SessionType::Stream, None, None).await?;
+        let raid_id = create_raid(&pool, session_id, "Factory", CharacterType::PMC, GameMode::PVE, None).await?;
+
+        let states = calculate_time_in_state(&pool, raid_id).await?;
+        assert!(states.is_empty());
+
+        pool.close().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_session_time_breakdown() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(2);
+        let at = |minutes: i64| Some(base + time::Duration::minutes(minutes));
+
+        let session_id = create_session(&pool, SessionType::Stream, None, at(0)).await?;
+
+        let r1 = create_raid(&pool, session_id, "Customs", CharacterType::PMC, GameMode::PVP, at(5)).await?;
+        log_forced_transition(&pool, r1, "queuing", at(5)).await?;
+        log_state_transition(&pool, r1, "deploying_committed", at(8)).await?;
+        log_state_transition(&pool, r1, "raid_active", at(10)).await?;
+        log_state_transition(&pool, r1, "survived", at(40)).await?;
+        end_raid(&pool, r1, at(40), None).await?;
+
+        // 10 minutes in the stash, then a second raid
+        let r2 = create_raid(&pool, session_id, "Woods", CharacterType::PMC, GameMode::PVP, at(50)).await?;
+        log_forced_transition(&pool, r2, "queuing", at(50)).await?;
+        log_forced_transition(&pool, r2, "raid_active", at(52)).await?;
+        log_state_transition(&pool, r2, "died", at(72)).await?;
+        end_raid(&pool, r2, at(72), None).await?;
+
+        // Still queuing, no end yet so its own states don't count, the 8 minutes before it do
+        let r3 = create_raid(&pool, session_id, "Factory", CharacterType::PMC, GameMode::PVP, at(80)).await?;
+        log_forced_transition(&pool, r3, "queuing", at(80)).await?;
+
+        let breakdown = calculate_session_time_breakdown(&pool, session_id).await?;
+        let state = |name: &str| breakdown.states.iter().find(|s| s.state == name).map(|s| s.duration);
+
+        assert_eq!(breakdown.total_time, Duration::minutes(75));
+        assert_eq!(breakdown.states[0].state, "raid_active");
+        assert_eq!(state("raid_active"), Some(Duration::minutes(50)));
+        assert_eq!(state("stash_management"), Some(Duration::minutes(18)));
+        assert_eq!(state("queuing"), Some(Duration::minutes(5)));
+        assert_eq!(state("deploying_committed"), Some(Duration::minutes(2)));
+        assert!((breakdown.raid_active_share - 50.0 / 75.0).abs() < 1e-9);
+        assert!((breakdown.categories["menus"] - 18.0 / 75.0).abs() < 1e-9);
+        assert!((breakdown.categories["loading"] - 2.0 / 75.0).abs() < 1e-9);
+        assert_eq!(breakdown.categories["other"], 0.0);
+        assert_eq!(breakdown.before_first_raid, Duration::minutes(5));
+
+        // Once the last raid and the session are over, the time in between is stash time too
+        log_forced_transition(&pool, r3, "died", at(90)).await?;
+        end_raid(&pool, r3, at(90), None).await?;
+        end_session(&pool, session_id, at(100)).await?;
+        let breakdown = calculate_session_time_breakdown(&pool, session_id).await?;
+        let state = |name: &str| breakdown.states.iter().find(|s| s.state == name).map(|s| s.duration);
+        assert_eq!(state("stash_management"), Some(Duration::minutes(28)));
+        assert_eq!(state("queuing"), Some(Duration::minutes(15)));
+
+        let empty_session = create_session(&pool, SessionType::Stream, None, None).await?;
+        let empty = calculate_session_time_breakdown(&pool, empty_session).await?;
+        assert_eq!(empty.total_time, Duration::ZERO);
+        assert_eq!(empty.raid_active_share, 0.0);
+        assert!(empty.states.is_empty());
+
+        pool.close().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wait_times_and_comparison() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let base = OffsetDateTime::now_utc() - time::Duration::days(2);
+
+        // (session, map, mode, queue, cancellable, committed) in minutes
+        let old = create_session(&pool, SessionType::Stream, None, Some(base)).await?;
+        let today = create_session(&pool, SessionType::Stream, None, Some(base + time::Duration::days(1))).await?;
+        let raids = [
+            (old, "Customs", GameMode::PVP, 2, 1, 1),
+            (old, "Woods", GameMode::PVE, 4, 0, 2),
+            (today, "customs", GameMode::PVP, 6, 1, 1),
+        ];
+
+        let mut ids = Vec::new();
+        for (i, (session, map, mode, queue, cancellable, committed)) in raids.into_iter().enumerate() {
+            let start = base + time::Duration::days(if session == old { 0 } else { 1 }) + time::Duration::hours(i as i64);
+            let at = |minutes: i64| Some(start + time::Duration::minutes(minutes));
+            let raid = create_raid(&pool, session, map, CharacterType::PMC, mode, at(0)).await?;
+            log_forced_transition(&pool, raid, "queuing", at(0)).await?;
+            if cancellable > 0 {
+                log_state_transition(&pool, raid, "deploying_cancellable", at(queue)).await?;
+            }
+            log_state_transition(&pool, raid, "deploying_committed", at(queue + cancellable)).await?;
+            log_state_transition(&pool, raid, "raid_active", at(queue + cancellable + committed)).await?;
+            end_raid(&pool, raid, at(40), None).await?;
+            ids.push(raid);
+        }
+        // Requeued after a cancel: both queues count towards the one raid
+        let requeue = create_raid(&pool, today, "Woods", CharacterType::PMC, GameMode::PVE,
+            Some(base + time::Duration::days(1) + time::Duration::hours(5))).await?;
+        let at = |minutes: i64| Some(base + time::Duration::days(1) + time::Duration::hours(5) + time::Duration::minutes(minutes));
+        log_forced_transition(&pool, requeue, "queuing", at(0)).await?;
+        log_state_transition(&pool, requeue, "deploying_cancellable", at(3)).await?;
+        log_state_transition(&pool, requeue, "queuing", at(4)).await?;
+        log_forced_transition(&pool, requeue, "raid_active", at(7)).await?;
+        // No waits logged at all, not part of the averages
+        create_raid(&pool, today, "Factory", CharacterType::PMC, GameMode::PVP, at(60)).await?;
+
+        let raid = calculate_raid_wait_times(&pool, ids[0]).await?;
+        assert_eq!(raid.avg_queue, Duration::minutes(2));
+        assert_eq!(raid.avg_total, Duration::minutes(4));
+        let raid = calculate_raid_wait_times(&pool, requeue).await?;
+        assert_eq!(raid.avg_queue, Duration::minutes(6));
+        assert_eq!(raid.avg_deploying_cancellable, Duration::minutes(1));
+
+        let all_time = calculate_wait_times(&pool, &StatsFilter::default(), None).await?;
+        assert_eq!(all_time.len(), 1);
+        assert_eq!(all_time[0].waits.raid_count, 4);
+        assert_eq!(all_time[0].waits.avg_queue, Duration::seconds(270));
+
+        let by_map = calculate_wait_times(&pool, &StatsFilter::default(), Some(StatsGroup::Map)).await?;
+        assert_eq!(by_map.len(), 2);
+        // One raid each way, the later spelling is shown
+        assert_eq!(by_map[0].map_name.as_deref(), Some("customs"));
+        assert_eq!(by_map[0].waits.avg_queue, Duration::minutes(4));
+        assert_eq!(by_map[0].waits.avg_deploying_committed, Duration::minutes(1));
+
+        let by_mode = calculate_wait_times(&pool, &StatsFilter::default(), Some(StatsGroup::GameMode)).await?;
+        assert_eq!(by_mode.iter().map(|g| g.waits.raid_count).collect::<Vec<_>>(), vec![2, 2]);
+
+        let comparison = compare_session_wait_times(&pool, today, None).await?;
+        assert_eq!(comparison.current.avg_queue, Duration::minutes(6));
+        assert!((comparison.queue_change.unwrap() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(comparison.maps.len(), 2);
+        let woods = comparison.maps.iter().find(|m| m.map_name == "Woods").unwrap();
+        assert_eq!(woods.all_time.avg_queue, Duration::minutes(5));
+        assert!((woods.queue_change.unwrap() - 0.2).abs() < 1e-9);
+
+        let pvp_only = compare_session_wait_times(&pool, today, Some(GameMode::PVP)).await?;
+        assert_eq!(pvp_only.current.raid_count, 1);
+        assert_eq!(pvp_only.all_time.avg_queue, Duration::minutes(4));
+
+        let empty = create_session(&pool, SessionType::Stream, None, None).await?;
+        let comparison = compare_session_wait_times(&pool, empty, None).await?;
+        assert_eq!(comparison.current.raid_count, 0);
+        assert_eq!(comparison.queue_change, None);
+        assert!(comparison.maps.is_empty());
+
+        pool.close().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streaks_and_records() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let base = OffsetDateTime::now_utc() - time::Duration::days(2);
+        let s1 = create_session(&pool, SessionType::Stream, None, Some(base)).await?;
+        let s2 = create_session(&pool, SessionType::Stream, None, Some(base + time::Duration::days(1))).await?;
+
+        // (session, outcome, kills, minutes long)
+        let raids = [
+            (s1, "survived", 1, 30),
+            (s1, "survived", 2, 20),
+            (s1, "died", 1, 40),
+            (s2, "survived", 0, 15),
+            (s2, "died", 3, 25),
+            (s2, "survived", 1, 10),
+        ];
+        let mut ids = Vec::new();
+        for (i, (session, outcome, kills, minutes)) in raids.into_iter().enumerate() {
+            let start = base + time::Duration::days(if session == s1 { 0 } else { 1 }) + time::Duration::hours(i as i64);
+            let raid = create_raid(&pool, session, "Customs", CharacterType::PMC, GameMode::PVP, Some(start)).await?;
+            for _ in 0..kills {
+                add_kill(&pool, raid, "pmc", None, None, Some(start + time::Duration::minutes(1))).await?;
+            }
+            log_forced_transition(&pool, raid, outcome, Some(start + time::Duration::minutes(minutes))).await?;
+            end_raid(&pool, raid, Some(start + time::Duration::minutes(minutes)), None).await?;
+            ids.push(raid);
+        }
+        // Still running, not part of anything
+        let running = create_raid(&pool, s2, "Woods", CharacterType::PMC, GameMode::PVP, None).await?;
+        add_kill(&pool, running, "pmc", None, None, None).await?;
+
+        let streaks = calculate_streaks(&pool, &StatsFilter::default()).await?;
+        assert_eq!(streaks.survival.current, Streak { length: 1, first_raid_id: Some(ids[5]), last_raid_id: Some(ids[5]) });
+        assert_eq!(streaks.survival.longest, Streak { length: 2, first_raid_id: Some(ids[0]), last_raid_id: Some(ids[1]) });
+        assert_eq!(streaks.death.current.length, 0);
+        assert_eq!(streaks.death.longest.last_raid_id, Some(ids[2]));
+        // Kills since the last death: 1 + 2 + the one before dying in the third raid
+        assert_eq!(streaks.kill.longest, Streak { length: 4, first_raid_id: Some(ids[0]), last_raid_id: Some(ids[2]) });
+        assert_eq!(streaks.kill.current.length, 1);
+
+        let records = calculate_records(&pool).await?;
+        let record = |kind: RecordKind| records.iter().find(|r| r.kind == kind).unwrap().clone();
+        assert_eq!(record(RecordKind::MostKills).raid_id, Some(ids[4]));
+        assert_eq!(record(RecordKind::MostKills).display, "3 kills");
+        assert_eq!(record(RecordKind::LongestRaid).raid_id, Some(ids[2]));
+        assert_eq!(record(RecordKind::LongestRaid).value, 2400.0);
+        assert_eq!(record(RecordKind::FastestExtract).raid_id, Some(ids[5]));
+        assert_eq!(record(RecordKind::FastestExtract).display, "10m 0s");
+        // Both sessions have 3 raids and 2 survived, the first one to get there holds them
+        let most_raids = record(RecordKind::MostRaidsInSession);
+        assert_eq!((most_raids.session_id, most_raids.raid_id), (s1, None));
+        assert_eq!(record(RecordKind::BestSessionSurvivalRate).session_id, s1);
+        assert_eq!(record(RecordKind::BestSessionSurvivalRate).display, "67% survived");
+
+        // One raid isn't enough for a survival rate record
+        let s3 = create_session(&pool, SessionType::Stream, None, None).await?;
+        let raid = create_raid(&pool, s3, "Factory", CharacterType::PMC, GameMode::PVP, None).await?;
+        log_forced_transition(&pool, raid, "survived", None).await?;
+        end_raid(&pool, raid, None, None).await?;
+        let after = calculate_records(&pool).await?;
+        assert_eq!(after.iter().find(|r| r.kind == RecordKind::BestSessionSurvivalRate).unwrap().session_id, s1);
+        assert!(find_new_records(&records, &after).is_empty());
+
+        let filtered = calculate_streaks(&pool, &StatsFilter { game_mode: Some(GameMode::PVE), ..Default::default() }).await?;
+        assert_eq!(filtered, Streaks::default());
+
+        pool.close().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streaks_carry_across_a_transfer() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(3);
+        let session = create_session(&pool, SessionType::Stream, None, Some(base)).await?;
+
+        let first = create_raid(&pool, session, "Customs", CharacterType::PMC, GameMode::PVP, Some(base)).await?;
+        add_kill(&pool, first, "pmc", None, None, Some(base + time::Duration::minutes(5))).await?;
+        log_forced_transition(&pool, first, "survived", Some(base + time::Duration::minutes(20))).await?;
+        end_raid(&pool, first, Some(base + time::Duration::minutes(20)), None).await?;
+
+        // Customs into Labs, extracting from Labs
+        let start = base + time::Duration::hours(1);
+        let head = create_raid(&pool, session, "Customs", CharacterType::PMC, GameMode::PVP, Some(start)).await?;
+        add_kill(&pool, head, "scav", None, None, Some(start + time::Duration::minutes(5))).await?;
+        log_forced_transition(&pool, head, "transfer", Some(start + time::Duration::minutes(10))).await?;
+        end_raid(&pool, head, Some(start + time::Duration::minutes(10)), None).await?;
+        let previous = get_raid_by_id(&pool, head).await?.unwrap();
+        let segment = create_raid_segment(&pool, &previous, "Labs", start + time::Duration::minutes(10)).await?;
+        add_kill(&pool, segment, "pmc", None, None, Some(start + time::Duration::minutes(15))).await?;
+        log_forced_transition(&pool, segment, "survived", Some(start + time::Duration::minutes(30))).await?;
+        end_raid(&pool, segment, Some(start + time::Duration::minutes(30)), None).await?;
+
+        // The transfer didn't break anything, and isn't a death
+        let streaks = calculate_streaks(&pool, &StatsFilter::default()).await?;
+        assert_eq!(streaks.survival.current, Streak { length: 2, first_raid_id: Some(first), last_raid_id: Some(segment) });
+        assert_eq!(streaks.death.longest.length, 0);
+        assert_eq!(streaks.kill.current, Streak { length: 3, first_raid_id: Some(first), last_raid_id: Some(segment) });
+
+        let stats = calculate_session_stats(&pool, session).await?;
+        assert_eq!((stats.total_raids, stats.survived_raids, stats.total_kills), (2, 2, 3));
+
+        pool.close().await;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_calculate_time_before_first_raid() -> Result<(), sqlx::Error> {
         let pool = setup_test_db().await?;
@@ -361,6 +1247,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(unused_assignments)]
     async fn test_calculate_time_single_state_visit() -> Result<(), sqlx::Error> {
         let pool = setup_test_db().await?;
 
@@ -496,7 +1383,7 @@ mod tests {
         add_kill(&pool, raid1, "pmc", None, None, Some(start1 + time::Duration::minutes(10))).await?;
 
         // End as Survived
-        log_state_transition(&pool, raid1, "survived", Some(start1 + time::Duration::minutes(30))).await?;
+        log_forced_transition(&pool, raid1, "survived", Some(start1 + time::Duration::minutes(30))).await?;
         end_raid(&pool, raid1, Some(start1 + time::Duration::minutes(30)), Some("Crossroads".into())).await?;
 
         // Raid 2: KIA (Died), 15 min, 1 kill
@@ -507,7 +1394,7 @@ mod tests {
         add_kill(&pool, raid2, "scav", None, None, Some(start2 + time::Duration::minutes(5))).await?;
 
         // End as KIA (State != survived)
-        log_state_transition(&pool, raid2, "kia", Some(start2 + time::Duration::minutes(15))).await?;
+        log_forced_transition(&pool, raid2, "kia", Some(start2 + time::Duration::minutes(15))).await?;
         end_raid(&pool, raid2, Some(start2 + time::Duration::minutes(15)), None).await?;
 
         // Calculate Stats
@@ -542,14 +1429,14 @@ mod tests {
         let pve_raid = create_raid(&pool, session, "Customs", CharacterType::PMC, GameMode::PVE, Some(base_time)).await?;
         add_kill(&pool, pve_raid, "scav", None, None, Some(base_time + time::Duration::minutes(5))).await?;
         add_kill(&pool, pve_raid, "scav", None, None, Some(base_time + time::Duration::minutes(10))).await?;
-        log_state_transition(&pool, pve_raid, "survived", Some(base_time + time::Duration::minutes(20))).await?;
+        log_forced_transition(&pool, pve_raid, "survived", Some(base_time + time::Duration::minutes(20))).await?;
         end_raid(&pool, pve_raid, Some(base_time + time::Duration::minutes(20)), None).await?;
 
         // Create PVP raid with 1 kill (died)
         let pvp_raid = create_raid(&pool, session, "Factory", CharacterType::PMC, GameMode::PVP, Some(base_time +
     time::Duration::minutes(30))).await?;
         add_kill(&pool, pvp_raid, "pmc", None, None, Some(base_time + time::Duration::minutes(35))).await?;
-        log_state_transition(&pool, pvp_raid, "died", Some(base_time + time::Duration::minutes(40))).await?;
+        log_forced_transition(&pool, pvp_raid, "died", Some(base_time + time::Duration::minutes(40))).await?;
         end_raid(&pool, pvp_raid, Some(base_time + time::Duration::minutes(40)), None).await?;
 
         // Test PVE filter
@@ -580,7 +1467,7 @@ mod tests {
        add_kill(&pool, raid1, "scav", None, None, Some(base_time + time::Duration::minutes(5))).await?;
        add_kill(&pool, raid1, "scav", None, None, Some(base_time + time::Duration::minutes(10))).await?;
        add_kill(&pool, raid1, "scav", None, None, Some(base_time + time::Duration::minutes(15))).await?;
-       log_state_transition(&pool, raid1, "survived", Some(base_time + time::Duration::minutes(20))).await?;
+       log_forced_transition(&pool, raid1, "survived", Some(base_time + time::Duration::minutes(20))).await?;
        end_raid(&pool, raid1, Some(base_time + time::Duration::minutes(20)), None).await?;
 
        // Raid 2: Survived with 2 kills
@@ -588,7 +1475,7 @@ mod tests {
            Some(base_time + time::Duration::minutes(30))).await?;
        add_kill(&pool, raid2, "scav", None, None, Some(base_time + time::Duration::minutes(35))).await?;
        add_kill(&pool, raid2, "scav", None, None, Some(base_time + time::Duration::minutes(40))).await?;
-       log_state_transition(&pool, raid2, "survived", Some(base_time + time::Duration::minutes(45))).await?;
+       log_forced_transition(&pool, raid2, "survived", Some(base_time + time::Duration::minutes(45))).await?;
        end_raid(&pool, raid2, Some(base_time + time::Duration::minutes(45)), None).await?;
 
        let stats = calculate_session_stats(&pool, session).await?;
@@ -612,7 +1499,7 @@ mod tests {
         let s1 = create_session(&pool, SessionType::Stream, None, Some(base_time)).await?;
         let r1 = create_raid(&pool, s1, "Customs", CharacterType::PMC, GameMode::PVP, Some(base_time)).await?;
         add_kill(&pool, r1, "scav", None, None, Some(base_time)).await?;
-        log_state_transition(&pool, r1, "survived", Some(base_time + Duration::minutes(10))).await?;
+        log_forced_transition(&pool, r1, "survived", Some(base_time + Duration::minutes(10))).await?;
         end_raid(&pool, r1, Some(base_time + Duration::minutes(10)), None).await?;
 
         // Session 2: Current session (1 raid, 5 kills, died)
@@ -621,7 +1508,7 @@ mod tests {
         for _ in 0..5 {
             add_kill(&pool, r2, "pmc", None, None, Some(base_time + Duration::hours(1))).await?;
         }
-        log_state_transition(&pool, r2, "died", Some(base_time + Duration::hours(1) + Duration::minutes(5))).await?;
+        log_forced_transition(&pool, r2, "died", Some(base_time + Duration::hours(1) + Duration::minutes(5))).await?;
         end_raid(&pool, r2, Some(base_time + Duration::hours(1) + Duration::minutes(5)), None).await?;
 
         let comparison = compare_session_to_mode_global(&pool, s2, None).await?;
@@ -765,11 +1652,11 @@ mod tests {
         time = time + time::Duration::minutes(2);
 
         // DISCONNECT - internet drops
-        log_state_transition(&pool, raid_id, "disconnected", Some(time)).await?;
+        log_forced_transition(&pool, raid_id, "disconnected", Some(time)).await?;
         time = time + time::Duration::minutes(5); // 5 min to reconnect
 
         // RECONNECT - back to raid_active
-        log_state_transition(&pool, raid_id, "raid_active", Some(time)).await?;
+        log_forced_transition(&pool, raid_id, "raid_active", Some(time)).await?;
         time = time + time::Duration::minutes(8);
 
         // Get 1 more kill after reconnect
@@ -819,10 +1706,54 @@ mod tests {
         let kills = get_kills_for_raid(&pool, raid_id).await?;
         assert_eq!(kills.len(), 3, "Should have 3 kills across disconnect");
 
+        // The disconnect only comes off the active duration once it's a non-play state
+        let stats = calculate_session_stats(&pool, session_id).await?;
+        assert_eq!(stats.avg_raid_duration, time::Duration::minutes(39));
+        assert_eq!(stats.avg_active_duration, time::Duration::minutes(39));
+
+        set_non_play_states(&pool, &["disconnected".into(), "reconnecting".into()]).await?;
+        let stats = calculate_session_stats(&pool, session_id).await?;
+        assert_eq!(stats.avg_raid_duration, time::Duration::minutes(39));
+        assert_eq!(stats.avg_active_duration, time::Duration::minutes(34));
+
         pool.close().await;
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_active_duration_skips_default_non_play_states() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(1);
+        let at = |minutes: i64| Some(base + time::Duration::minutes(minutes));
+        let session_id = create_session(&pool, SessionType::Stream, None, at(0)).await?;
+        assert_eq!(get_non_play_states(&pool).await?, vec!["error", "paused", "reconnecting"]);
+
+        // 3 minutes reconnecting mid raid
+        let r1 = create_raid(&pool, session_id, "Woods", CharacterType::PMC, GameMode::PVP, at(0)).await?;
+        log_forced_transition(&pool, r1, "raid_active", at(0)).await?;
+        log_state_transition(&pool, r1, "reconnecting", at(10)).await?;
+        log_state_transition(&pool, r1, "raid_active", at(13)).await?;
+        log_state_transition(&pool, r1, "died", at(20)).await?;
+        end_raid(&pool, r1, at(20), None).await?;
+
+        // Crashed out, the error runs until the raid was ended
+        let r2 = create_raid(&pool, session_id, "Customs", CharacterType::PMC, GameMode::PVP, at(30)).await?;
+        log_forced_transition(&pool, r2, "raid_active", at(30)).await?;
+        log_state_transition(&pool, r2, "error", at(35)).await?;
+        end_raid(&pool, r2, at(40), None).await?;
+
+        let stats = calculate_session_stats(&pool, session_id).await?;
+        assert_eq!(stats.avg_raid_duration, time::Duration::minutes(15));
+        assert_eq!(stats.avg_active_duration, time::Duration::minutes(11));
+
+        // A raid still reconnecting has no active time to report yet
+        let r3 = create_raid(&pool, session_id, "Woods", CharacterType::PMC, GameMode::PVP, at(45)).await?;
+        log_forced_transition(&pool, r3, "reconnecting", at(50)).await?;
+        let stats = calculate_session_stats(&pool, session_id).await?;
+        assert_eq!(stats.avg_active_duration, time::Duration::minutes(11));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_calculate_time_between_raids() -> Result<(), sqlx::Error> {
         let pool = setup_test_db().await?;
@@ -833,28 +1764,28 @@ mod tests {
 
         // Raid 1: 10 min duration, ends at base_time + 10min
         let raid1 = create_raid(&pool, session_id, "Customs", CharacterType::PMC, GameMode::PVP, Some(base_time)).await?;
-        log_state_transition(&pool, raid1, "survived", Some(base_time + time::Duration::minutes(10))).await?;
+        log_forced_transition(&pool, raid1, "survived", Some(base_time + time::Duration::minutes(10))).await?;
         end_raid(&pool, raid1, Some(base_time + time::Duration::minutes(10)), None).await?;
 
         // Gap 1: 5 minutes in stash (from 10min to 15min)
 
         // Raid 2: Starts at 15min, ends at 35min (20 min duration)
         let raid2 = create_raid(&pool, session_id, "Woods", CharacterType::PMC, GameMode::PVE, Some(base_time + time::Duration::minutes(15))).await?;
-        log_state_transition(&pool, raid2, "survived", Some(base_time + time::Duration::minutes(35))).await?;
+        log_forced_transition(&pool, raid2, "survived", Some(base_time + time::Duration::minutes(35))).await?;
         end_raid(&pool, raid2, Some(base_time + time::Duration::minutes(35)), None).await?;
 
         // Gap 2: 10 minutes in stash (from 35min to 45min)
 
         // Raid 3: Starts at 45min, ends at 60min (15 min duration)
         let raid3 = create_raid(&pool, session_id, "Factory", CharacterType::PMC, GameMode::PVP, Some(base_time + time::Duration::minutes(45))).await?;
-        log_state_transition(&pool, raid3, "survived", Some(base_time + time::Duration::minutes(60))).await?;
+        log_forced_transition(&pool, raid3, "survived", Some(base_time + time::Duration::minutes(60))).await?;
         end_raid(&pool, raid3, Some(base_time + time::Duration::minutes(60)), None).await?;
 
         // Gap 3: 15 minutes in stash (from 60min to 75min)
 
         // Raid 4: Starts at 75min, ends at 85min (10 min duration)
         let raid4 = create_raid(&pool, session_id, "Shoreline", CharacterType::PMC, GameMode::PVP, Some(base_time + time::Duration::minutes(75))).await?;
-        log_state_transition(&pool, raid4, "survived", Some(base_time + time::Duration::minutes(85))).await?;
+        log_forced_transition(&pool, raid4, "survived", Some(base_time + time::Duration::minutes(85))).await?;
         end_raid(&pool, raid4, Some(base_time + time::Duration::minutes(85)), None).await?;
 
         // Calculate between-raids time
@@ -1031,7 +1962,7 @@ mod tests {
         time = time + time::Duration::minutes(2);
 
         // Cancel before deploying - return to stash
-        log_state_transition(&pool, raid1, "cancelled", Some(time)).await?;
+        log_forced_transition(&pool, raid1, "cancelled", Some(time)).await?;
         time = time + time::Duration::minutes(1);
 
         // End the cancelled raid
@@ -1131,4 +2062,342 @@ mod tests {
         pool.close().await;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_stats_by_any_groups_and_filters() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let ids = crate::db::tests::seed_history(&pool).await?;
+        let stream = get_raid_by_id(&pool, ids[0]).await?.unwrap().session_id;
+
+        // Two groups at once, "customs" and "Customs" still one map
+        let rows = calculate_stats_by(&pool, &StatsFilter::default(), &[StatsGroup::GameMode, StatsGroup::Map]).await?;
+        let keys: Vec<_> = rows.iter()
+            .map(|r| (r.game_mode.clone().unwrap(), r.map_name.clone().unwrap().to_lowercase()))
+            .collect();
+        assert_eq!(keys, vec![
+            (GameMode::PVE, "customs".into()),
+            (GameMode::PVE, "shoreline".into()),
+            (GameMode::PVP, "customs".into()),
+            (GameMode::PVP, "woods".into()),
+        ]);
+        assert_eq!(rows[2].stats.total_raids, 2);
+        assert_eq!(rows[2].stats.total_kills, 5);
+        assert!(rows[2].session_type.is_none());
+
+        let filter = StatsFilter { game_mode: Some(GameMode::PVP), ..Default::default() };
+        let types = calculate_stats_by(&pool, &filter, &[StatsGroup::SessionType]).await?;
+        assert_eq!(types.iter().map(|t| (t.session_type.clone().unwrap(), t.stats.total_raids)).collect::<Vec<_>>(),
+            vec![(SessionType::Practice, 1), (SessionType::Stream, 2)]);
+
+        let filter = StatsFilter {
+            session_type: Some(SessionType::Practice),
+            map_name: Some("CUSTOMS".into()),
+            ..Default::default()
+        };
+        let practice = calculate_stats(&pool, &filter).await?;
+        assert_eq!((practice.total_raids, practice.total_kills), (1, 3));
+
+        let filter = StatsFilter { session_ids: vec![stream], ..Default::default() };
+        let sessions = calculate_stats_by(&pool, &filter, &[StatsGroup::Session]).await?;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].session_id, sessions[0].stats.total_raids), (Some(stream), 3));
+
+        let second_day = get_raid_by_id(&pool, ids[0]).await?.unwrap().started_at + time::Duration::days(1);
+        let later = calculate_stats(&pool, &StatsFilter { from: Some(second_day), ..Default::default() }).await?;
+        assert_eq!(later.total_raids, 2);
+        let earlier = calculate_stats(&pool, &StatsFilter { to: Some(second_day), ..Default::default() }).await?;
+        assert_eq!(earlier.total_raids, 3);
+
+        // Weekdays in UTC, same as the raids' own timestamps
+        let mut expected: BTreeMap<String, i64> = BTreeMap::new();
+        for id in &ids {
+            let started_at = get_raid_by_id(&pool, *id).await?.unwrap().started_at.to_offset(time::UtcOffset::UTC);
+            *expected.entry(started_at.weekday().to_string().to_lowercase()).or_default() += 1;
+        }
+        let days = calculate_stats_by(&pool, &StatsFilter::default(), &[StatsGroup::Weekday]).await?;
+        let days: BTreeMap<String, i64> = days.iter().map(|d| (d.weekday.clone().unwrap(), d.stats.total_raids)).collect();
+        assert_eq!(days, expected);
+
+        // The mode split is the same engine underneath
+        let modes = get_mode_stats_for_session(&pool, stream).await?;
+        assert_eq!((modes.pvp.total_raids, modes.pve.total_raids), (2, 1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grouped_stats() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(3);
+        let session = create_session(&pool, SessionType::Stream, None, Some(base)).await?;
+
+        let raids = [
+            ("Customs", CharacterType::PMC, GameMode::PVP, "survived", 2),
+            ("Customs", CharacterType::Scav, GameMode::PVE, "died", 1),
+            ("Woods", CharacterType::PMC, GameMode::PVP, "died", 0),
+        ];
+        for (i, (map, character, mode, outcome, kills)) in raids.into_iter().enumerate() {
+            let start = base + time::Duration::minutes(30 * i as i64);
+            let raid = create_raid(&pool, session, map, character, mode, Some(start)).await?;
+            for _ in 0..kills {
+                add_kill(&pool, raid, "scav", None, None, Some(start + time::Duration::minutes(5))).await?;
+            }
+            log_forced_transition(&pool, raid, outcome, Some(start + time::Duration::minutes(20))).await?;
+            end_raid(&pool, raid, Some(start + time::Duration::minutes(20)), None).await?;
+        }
+
+        let maps = calculate_grouped_stats(&pool, StatsGroup::Map, None).await?;
+        assert_eq!(maps.len(), 2);
+        assert_eq!(maps[0].map_name.as_deref(), Some("Customs"));
+        assert_eq!(maps[0].stats.total_raids, 2);
+        assert_eq!(maps[0].stats.total_kills, 3);
+        assert_eq!(maps[0].stats.kd_ratio, 3.0);
+        assert_eq!(maps[1].stats.survival_rate, 0.0);
+        assert!(maps[0].game_mode.is_none());
+
+        let characters = calculate_grouped_stats(&pool, StatsGroup::CharacterType, Some(GameMode::PVP)).await?;
+        assert_eq!(characters.len(), 1);
+        assert_eq!(characters[0].character_type, Some(CharacterType::PMC));
+        assert_eq!(characters[0].stats.total_raids, 2);
+        assert_eq!(characters[0].stats.avg_raid_duration, Duration::minutes(20));
+
+        pool.close().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_map_stats() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(5);
+        let session = create_session(&pool, SessionType::Stream, None, Some(base)).await?;
+
+        // (map, character, mode, queue minutes, outcome, extract)
+        let raids = [
+            ("Customs", CharacterType::PMC, GameMode::PVP, 2, "survived", Some("ZB-1011")),
+            ("customs", CharacterType::PMC, GameMode::PVP, 4, "survived", Some("Crossroads")),
+            ("customs", CharacterType::PMC, GameMode::PVP, 0, "survived", Some("crossroads")),
+            ("Lighthouse", CharacterType::Scav, GameMode::PVE, 6, "died", None),
+        ];
+        for (i, (map, character, mode, queue, outcome, extract)) in raids.into_iter().enumerate() {
+            let start = base + time::Duration::hours(i as i64);
+            let raid = create_raid(&pool, session, map, character, mode, Some(start)).await?;
+            let mut at = start + time::Duration::minutes(1);
+            log_state_transition(&pool, raid, "pre_raid_setup", Some(at)).await?;
+            if queue > 0 {
+                log_state_transition(&pool, raid, "queuing", Some(at)).await?;
+                at += time::Duration::minutes(queue);
+            }
+            log_forced_transition(&pool, raid, "deploying_committed", Some(at)).await?;
+            log_forced_transition(&pool, raid, outcome, Some(start + time::Duration::minutes(30))).await?;
+            end_raid(&pool, raid, Some(start + time::Duration::minutes(30)), extract.map(String::from)).await?;
+        }
+
+        let maps = calculate_map_stats(&pool, &StatsFilter::default()).await?;
+        assert_eq!(maps.len(), 2);
+        // Spelled that way most often
+        assert_eq!(maps[0].map_name, "customs");
+        assert_eq!(maps[0].stats.total_raids, 3);
+        assert_eq!(maps[0].stats.survival_rate, 1.0);
+        assert_eq!(maps[0].stats.avg_raid_duration, Duration::minutes(30));
+        // Only the two raids that queued count towards the average
+        assert_eq!(maps[0].avg_queue_time, Duration::minutes(3));
+        assert_eq!(maps[0].most_used_extract.as_deref(), Some("Crossroads"));
+
+        assert_eq!(maps[1].map_name, "Lighthouse");
+        assert_eq!(maps[1].avg_queue_time, Duration::minutes(6));
+        assert_eq!(maps[1].most_used_extract, None);
+
+        let filter = StatsFilter { character_type: Some(CharacterType::Scav), ..Default::default() };
+        let scav = calculate_map_stats(&pool, &filter).await?;
+        assert_eq!(scav.len(), 1);
+        assert_eq!(scav[0].map_name, "Lighthouse");
+
+        let filter = StatsFilter { session_ids: vec![session], game_mode: Some(GameMode::PVE), ..Default::default() };
+        assert_eq!(calculate_map_stats(&pool, &filter).await?.len(), 1);
+
+        // Ended while still queuing, the queue runs until the end like every other span
+        let start = base + time::Duration::hours(4);
+        let raid = create_raid(&pool, session, "Lighthouse", CharacterType::Scav, GameMode::PVE, Some(start)).await?;
+        log_forced_transition(&pool, raid, "queuing", Some(start)).await?;
+        end_raid(&pool, raid, Some(start + time::Duration::minutes(2)), None).await?;
+        let maps = calculate_map_stats(&pool, &StatsFilter::default()).await?;
+        assert_eq!(maps[1].avg_queue_time, Duration::minutes(4));
+
+        pool.close().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_weapon_stats() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let base = OffsetDateTime::now_utc() - time::Duration::hours(3);
+        let s1 = create_session(&pool, SessionType::Stream, None, Some(base)).await?;
+        let r1 = create_raid(&pool, s1, "Customs", CharacterType::PMC, GameMode::PVP, Some(base)).await?;
+
+        let kills = [
+            ("pmc", Some("M4A1"), Some(true)),
+            ("Scav", Some("m4-a1"), Some(false)),
+            ("scav", Some(" m4-a1 "), None),
+            ("pmc", Some("SKS"), Some(true)),
+            ("scav", None, Some(true)),
+        ];
+        for (enemy, weapon, headshot) in kills {
+            add_kill(&pool, r1, enemy, weapon.map(String::from), headshot, Some(base)).await?;
+        }
+
+        let s2 = create_session(&pool, SessionType::Stream, None, Some(base + time::Duration::hours(1))).await?;
+        let r2 = create_raid(&pool, s2, "Woods", CharacterType::Scav, GameMode::PVE, Some(base + time::Duration::hours(1))).await?;
+        for _ in 0..4 {
+            add_kill(&pool, r2, "scav", Some("SKS".into()), Some(false), Some(base + time::Duration::hours(1))).await?;
+        }
+
+        let session = calculate_weapon_stats(&pool, &StatsFilter { session_ids: vec![s1], ..Default::default() }).await?;
+        // The spelling with the most kills
+        assert_eq!(session.top_weapon.as_deref(), Some("m4-a1"));
+        assert_eq!(session.weapons.len(), 2);
+        let m4 = &session.weapons[0];
+        assert_eq!(m4.kills, 3);
+        assert_eq!(m4.headshots, 1);
+        // The kill without a headshot flag is left out of the rate
+        assert_eq!(m4.headshot_rate, 0.5);
+        assert_eq!(m4.enemy_types["scav"], 2);
+        assert_eq!(m4.enemy_types["pmc"], 1);
+        // The kill without a weapon still counts overall
+        assert_eq!(session.total_kills, 5);
+        assert_eq!(session.headshots, 3);
+        assert_eq!(session.headshot_rate, 0.75);
+
+        let all_time = calculate_weapon_stats(&pool, &StatsFilter::default()).await?;
+        assert_eq!(all_time.top_weapon.as_deref(), Some("SKS"));
+        assert_eq!(all_time.weapons[0].kills, 5);
+
+        let pvp = calculate_weapon_stats(&pool, &StatsFilter { game_mode: Some(GameMode::PVP), ..Default::default() }).await?;
+        assert_eq!(pvp.weapons[1].kills, 1);
+
+        let empty = calculate_weapon_stats(&pool, &StatsFilter { session_ids: vec![999], ..Default::default() }).await?;
+        assert!(empty.top_weapon.is_none());
+        assert_eq!(empty.headshot_rate, 0.0);
+
+        pool.close().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trend_over_sessions_and_raids() -> Result<(), sqlx::Error> {
+        let pool = setup_test_db().await?;
+        let base = OffsetDateTime::now_utc() - time::Duration::days(10);
+
+        // Four sessions a day apart, each one raid better than the last:
+        // session i has i+1 raids, i of them survived, one kill per raid
+        for i in 0..4i64 {
+            let start = base + time::Duration::days(i);
+            let session = create_session(&pool, SessionType::Stream, None, Some(start)).await?;
+            for j in 0..=i {
+                let raid_start = start + time::Duration::hours(j);
+                let raid = create_raid(&pool, session, "Customs", CharacterType::PMC, GameMode::PVP, Some(raid_start)).await?;
+                add_kill(&pool, raid, "pmc", None, None, Some(raid_start)).await?;
+                let outcome = if j < i { "survived" } else { "died" };
+                log_forced_transition(&pool, raid, outcome, Some(raid_start + time::Duration::minutes(20))).await?;
+                end_raid(&pool, raid, Some(raid_start + time::Duration::minutes(20)), None).await?;
+            }
+        }
+        // A session with no raids isn't a point
+        create_session(&pool, SessionType::Stream, None, Some(base + time::Duration::days(5))).await?;
+
+        let trend = calculate_trend(&pool, &StatsFilter::default(), TrendUnit::Sessions, 3, 2).await?;
+        assert_eq!(trend.points.len(), 3);
+        assert!(trend.points.windows(2).all(|p| p[0].started_at < p[1].started_at));
+        assert_eq!(trend.points[0].stats.survival_rate, 0.5);
+        assert_eq!(trend.points[2].stats.total_raids, 4);
+        // The first point's window reaches back to the session before it: 1 of 3 raids survived
+        assert!((trend.points[0].rolling.survival_rate - 1.0 / 3.0).abs() < 1e-9);
+        // Sessions 3 and 4 pooled: 5 survived of 7, 7 kills over 2 deaths
+        assert!((trend.points[2].rolling.survival_rate - 5.0 / 7.0).abs() < 1e-9);
+        assert_eq!(trend.points[2].rolling.kd_ratio, 3.5);
+
+        assert_eq!(trend.sparkline(TrendMetric::SurvivalRate, false), vec![0.5, 0.667, 0.75]);
+        assert_eq!(trend.sparkline(TrendMetric::Raids, true), vec![3.0, 5.0, 7.0]);
+
+        // Per raid, the last five: died, survived x3, died
+        let raids = calculate_trend(&pool, &StatsFilter::default(), TrendUnit::Raids, 5, 5).await?;
+        assert_eq!(raids.sparkline(TrendMetric::SurvivalRate, false), vec![0.0, 1.0, 1.0, 1.0, 0.0]);
+        assert_eq!(raids.points[4].rolling.survival_rate, 0.6);
+
+        let none = calculate_trend(&pool, &StatsFilter { game_mode: Some(GameMode::PVE), ..Default::default() },
+            TrendUnit::Sessions, 10, 3).await?;
+        assert!(none.points.is_empty());
+
+        pool.close().await;
+        Ok(())
+    }
+
+    // The old way: every raid in memory and one kill query per raid
+    async fn per_raid_stats(pool: &SqlitePool, game_mode: Option<GameMode>) -> Result<SessionStats, sqlx::Error> {
+        let raids: Vec<Raid> = get_all_raids(pool).await?
+            .into_iter()
+            .filter(|r| game_mode.as_ref().is_none_or(|m| &r.game_mode == m))
+            .collect();
+
+        let mut agg = RaidAggregate { raid_count: raids.len() as i64, ..Default::default() };
+        for raid in &raids {
+            if raid.state() == RaidState::Survived {
+                agg.survived_count += 1;
+            }
+            if let Some(ended_at) = raid.ended_at {
+                agg.duration_ms += (ended_at - raid.started_at).whole_milliseconds() as i64;
+                agg.active_duration_ms += (ended_at - raid.started_at).whole_milliseconds() as i64;
+                agg.ended_count += 1;
+            }
+            agg.kill_count += get_kills_for_raid(pool, raid.raid_id).await?.len() as i64;
+        }
+        Ok(stats_from_aggregate(Some(&agg)))
+    }
+
+    // Benchmark, run with `cargo test -- --ignored --nocapture bench`
+    #[tokio::test]
+    #[ignore]
+    async fn bench_all_time_stats_sql_vs_per_raid() -> Result<(), sqlx::Error> {
+        const RAIDS: i64 = 30_000;
+        let pool = setup_test_db().await?;
+        create_session(&pool, SessionType::Stream, None, Some(OffsetDateTime::now_utc() - time::Duration::days(1500))).await?;
+
+        // One raid an hour, 25 minutes each, a third survived, 0-2 kills
+        sqlx::query(r#"
+            WITH RECURSIVE seq(n) AS (SELECT 0 UNION ALL SELECT n + 1 FROM seq WHERE n + 1 < ?)
+            INSERT INTO raids (session_id, started_at, ended_at, map_name, character_type, game_mode, current_state)
+            SELECT 1,
+                strftime('%Y-%m-%dT%H:%M:%SZ', 1600000000 + n * 3600, 'unixepoch'),
+                strftime('%Y-%m-%dT%H:%M:%SZ', 1600000000 + n * 3600 + 1500, 'unixepoch'),
+                CASE n % 4 WHEN 0 THEN 'Customs' WHEN 1 THEN 'Woods' WHEN 2 THEN 'Shoreline' ELSE 'Interchange' END,
+                CASE n % 5 WHEN 0 THEN 'scav' ELSE 'pmc' END,
+                CASE n % 2 WHEN 0 THEN 'pvp' ELSE 'pve' END,
+                CASE n % 3 WHEN 0 THEN 'survived' ELSE 'died' END
+            FROM seq
+        "#).bind(RAIDS).execute(&pool).await?;
+        sqlx::query(r#"
+            INSERT INTO kills (raid_id, killed_at, enemy_type)
+            SELECT r.raid_id, r.started_at, 'scav' FROM raids r, (SELECT 1 AS k UNION ALL SELECT 2) ks
+            WHERE ks.k <= r.raid_id % 3
+        "#).execute(&pool).await?;
+
+        let timer = std::time::Instant::now();
+        let sql = calculate_global_stats(&pool, Some(GameMode::PVP)).await?;
+        let sql_time = timer.elapsed();
+
+        let timer = std::time::Instant::now();
+        let naive = per_raid_stats(&pool, Some(GameMode::PVP)).await?;
+        let naive_time = timer.elapsed();
+
+        println!("{} raids: grouped SQL {:?}, per-raid queries {:?}", RAIDS, sql_time, naive_time);
+
+        assert_eq!(sql.total_raids, RAIDS / 2);
+        assert_eq!(sql.total_raids, naive.total_raids);
+        assert_eq!(sql.survived_raids, naive.survived_raids);
+        assert_eq!(sql.total_kills, naive.total_kills);
+        assert_eq!(sql.avg_raid_duration, Duration::minutes(25));
+        assert_eq!(sql.avg_raid_duration, naive.avg_raid_duration);
+        assert!(sql_time < naive_time);
+
+        pool.close().await;
+        Ok(())
+    }
 }